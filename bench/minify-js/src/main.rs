@@ -1,6 +1,5 @@
-use minify_js::minify;
-use minify_js::Session;
-use minify_js::TopLevelMode;
+use minify_js::Minifier;
+use minify_js::MinifyOptions;
 use std::env;
 use std::fs::File;
 use std::io::Read;
@@ -18,10 +17,10 @@ fn main() {
   let mut output_len = 0;
   let mut output = Vec::new();
   let started = Instant::now();
-  let session = Session::new();
+  let mut minifier = Minifier::new(MinifyOptions::new());
   for _ in 0..iterations {
     output.clear();
-    minify(&session, TopLevelMode::Global, &code, &mut output).expect("minify");
+    minifier.minify(&code, &mut output).expect("minify");
     output_len = output.len();
   }
   let elapsed_ns = started.elapsed().as_nanos();
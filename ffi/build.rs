@@ -0,0 +1,11 @@
+fn main() {
+  let crate_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+  println!("cargo:rerun-if-changed=src/lib.rs");
+  println!("cargo:rerun-if-changed=cbindgen.toml");
+  cbindgen::generate_with_config(
+    &crate_dir,
+    cbindgen::Config::from_root_or_default(&crate_dir),
+  )
+  .expect("generate C header")
+  .write_to_file("minify_js.h");
+}
@@ -0,0 +1,76 @@
+/* C API for minify-js. Link against the minify_js_ffi library built from this crate. */
+
+#ifndef MINIFY_JS_H
+#define MINIFY_JS_H
+
+/* Generated by cbindgen from src/lib.rs when building this crate. Don't edit it by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// Values for the `mode` argument of [minify_js_minify].
+typedef enum MinifyJsTopLevelMode {
+  MINIFY_JS_TOP_LEVEL_MODE_GLOBAL = 0,
+  MINIFY_JS_TOP_LEVEL_MODE_MODULE = 1,
+} MinifyJsTopLevelMode;
+
+// The output or error from minifying, which owns its memory so it stays valid after the session is reused.
+typedef struct MinifyJsResult MinifyJsResult;
+
+// Reusable memory for minifying, like [Session]. It must only be used by one thread at a time.
+typedef struct MinifyJsSession MinifyJsSession;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Creates a session, which must be freed with [minify_js_session_free].
+struct MinifyJsSession *minify_js_session_new(void);
+
+// Frees a session.
+//
+// # Safety
+//
+// `session` must be from [minify_js_session_new] and not already freed, or null.
+void minify_js_session_free(struct MinifyJsSession *session);
+
+// Minifies `len` bytes of UTF-8 code. `mode` is a [MinifyJsTopLevelMode] value; any other value is an error. Memory from previous calls with the same session is reused. The returned result must be freed with [minify_js_result_free].
+//
+// # Safety
+//
+// `session` must be a live session, and `code` must point to `len` readable bytes.
+struct MinifyJsResult *minify_js_minify(struct MinifyJsSession *session,
+                                        const uint8_t *code,
+                                        size_t len,
+                                        uint32_t mode);
+
+// Returns the error message, or null if minifying succeeded. It's valid until the result is freed.
+//
+// # Safety
+//
+// `result` must be a live result.
+const char *minify_js_result_error(const struct MinifyJsResult *result);
+
+// Returns the minified code and writes its length in bytes to `len`. It's not NUL terminated, and is valid until the result is freed.
+//
+// # Safety
+//
+// `result` must be a live result, and `len` must be writable.
+const uint8_t *minify_js_result_output(const struct MinifyJsResult *result,
+                                       size_t *len);
+
+// Frees a result.
+//
+// # Safety
+//
+// `result` must be from [minify_js_minify] and not already freed, or null.
+void minify_js_result_free(struct MinifyJsResult *result);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif /* MINIFY_JS_H */
@@ -0,0 +1,140 @@
+//! C API for minify-js. The `minify_js.h` header is generated from this file by cbindgen when building the crate.
+
+use minify_js::Diagnostic;
+use minify_js::MinifyOptions;
+use minify_js::Session;
+use minify_js::TopLevelMode;
+use std::ffi::c_char;
+use std::ffi::CString;
+use std::panic::catch_unwind;
+use std::panic::AssertUnwindSafe;
+use std::ptr;
+use std::slice;
+
+/// Reusable memory for minifying, like [Session]. It must only be used by one thread at a time.
+pub struct MinifyJsSession(Session);
+
+/// The output or error from minifying, which owns its memory so it stays valid after the session is reused.
+pub struct MinifyJsResult {
+  output: Vec<u8>,
+  error: Option<CString>,
+}
+
+/// Values for the `mode` argument of [minify_js_minify].
+#[repr(C)]
+pub enum MinifyJsTopLevelMode {
+  Global = 0,
+  Module = 1,
+}
+
+/// Creates a session, which must be freed with [minify_js_session_free].
+#[no_mangle]
+pub extern "C" fn minify_js_session_new() -> *mut MinifyJsSession {
+  Box::into_raw(Box::new(MinifyJsSession(Session::new())))
+}
+
+/// Frees a session.
+///
+/// # Safety
+///
+/// `session` must be from [minify_js_session_new] and not already freed, or null.
+#[no_mangle]
+pub unsafe extern "C" fn minify_js_session_free(session: *mut MinifyJsSession) {
+  if !session.is_null() {
+    drop(Box::from_raw(session));
+  };
+}
+
+fn error_result(msg: &str) -> MinifyJsResult {
+  // Messages can quote the source code, which could contain NUL.
+  MinifyJsResult {
+    output: Vec::new(),
+    error: Some(CString::new(msg.replace('\0', "\\0")).unwrap()),
+  }
+}
+
+/// Minifies `len` bytes of UTF-8 code. `mode` is a [MinifyJsTopLevelMode] value; any other value is an error. Memory from previous calls with the same session is reused. The returned result must be freed with [minify_js_result_free].
+///
+/// # Safety
+///
+/// `session` must be a live session, and `code` must point to `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn minify_js_minify(
+  session: *mut MinifyJsSession,
+  code: *const u8,
+  len: usize,
+  mode: u32,
+) -> *mut MinifyJsResult {
+  let session = &mut (*session).0;
+  let code = if len == 0 {
+    &[]
+  } else {
+    slice::from_raw_parts(code, len)
+  };
+  // A Rust enum can't hold values that C can pass for it, so the mode is checked as an integer.
+  let mode = match mode {
+    m if m == MinifyJsTopLevelMode::Global as u32 => TopLevelMode::Global,
+    m if m == MinifyJsTopLevelMode::Module as u32 => TopLevelMode::Module,
+    m => {
+      return Box::into_raw(Box::new(error_result(&format!(
+        "invalid top-level mode {}",
+        m
+      ))))
+    }
+  };
+  let cfg = MinifyOptions::new().top_level_mode(mode);
+  // Unwinding across the C boundary is undefined behaviour, so a panic (which is a bug) is returned as an error.
+  let result = catch_unwind(AssertUnwindSafe(|| {
+    session.reset();
+    let mut output = Vec::new();
+    match minify_js::minify(session, code, &cfg, &mut output) {
+      Ok(_) => MinifyJsResult {
+        output,
+        error: None,
+      },
+      Err(err) => error_result(&Diagnostic::from_minify_error(code, &err).to_string()),
+    }
+  }))
+  .unwrap_or_else(|_| error_result("minify-js panicked, which is a bug"));
+  Box::into_raw(Box::new(result))
+}
+
+/// Returns the error message, or null if minifying succeeded. It's valid until the result is freed.
+///
+/// # Safety
+///
+/// `result` must be a live result.
+#[no_mangle]
+pub unsafe extern "C" fn minify_js_result_error(result: *const MinifyJsResult) -> *const c_char {
+  match &(*result).error {
+    Some(msg) => msg.as_ptr(),
+    None => ptr::null(),
+  }
+}
+
+/// Returns the minified code and writes its length in bytes to `len`. It's not NUL terminated, and is valid until the result is freed.
+///
+/// # Safety
+///
+/// `result` must be a live result, and `len` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn minify_js_result_output(
+  result: *const MinifyJsResult,
+  len: *mut usize,
+) -> *const u8 {
+  let output = &(*result).output;
+  *len = output.len();
+  output.as_ptr()
+}
+
+/// Frees a result.
+///
+/// # Safety
+///
+/// `result` must be from [minify_js_minify] and not already freed, or null.
+#[no_mangle]
+pub unsafe extern "C" fn minify_js_result_free(result: *mut MinifyJsResult) {
+  if !result.is_null() {
+    drop(Box::from_raw(result));
+  };
+}
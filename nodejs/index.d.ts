@@ -1,7 +1,68 @@
+export type MinifyOptions = {
+  /** Whether the code is a script or an ES module. Defaults to "global". */
+  mode?: "global" | "module";
+  /** The oldest ECMAScript version the output must run on. Defaults to "esnext". */
+  target?:
+    | "es5"
+    | "es2015"
+    | "es2016"
+    | "es2017"
+    | "es2018"
+    | "es2019"
+    | "es2020"
+    | "es2021"
+    | "es2022"
+    | "esnext";
+  /** Where to keep license comments. */
+  legalComments?: "none" | "eof" | "top";
+  /** Line ending to use in the output. */
+  newline?: "lf" | "crlf";
+  /** Break lines once they reach this length. */
+  maxLineLen?: number;
+  /** Text to put before the output. */
+  banner?: string;
+  /** Text to put after the output. */
+  footer?: string;
+  /** Indent and put statements on their own lines. */
+  beautify?: boolean;
+  /** Separate statements with line breaks instead of semicolons where automatic semicolon insertion allows. */
+  asi?: boolean;
+};
+
 /**
- * Minifies a Buffer containing UTF-8 JavaScript code.
+ * Error thrown for code that can't be minified.
+ */
+export interface MinifyError extends Error {
+  /** Stable identifier of the problem, such as "E_EXPECTED_SYNTAX". */
+  code: string;
+  /** 1-based line of the problem. */
+  line: number;
+  /** 1-based column of the problem. */
+  column: number;
+}
+
+/**
+ * Minifies JavaScript code, given as a string or a Buffer containing UTF-8.
+ *
+ * @param code - Source JS code
+ * @param options - Minification options
+ * @returns Minified JS code, of the same type as `code`
+ */
+export function minify(code: string, options?: MinifyOptions): string;
+export function minify(code: Buffer, options?: MinifyOptions): Buffer;
+
+/**
+ * Like `minify`, but minifies on the libuv thread pool without blocking the event loop.
  *
- * @param src - Source JS code
- * @returns Minified JS code
+ * @param code - Source JS code
+ * @param options - Minification options
+ * @returns Promise of the minified JS code, of the same type as `code`
  */
-export function minify(topLevelType: "global" | "module", src: Buffer): Buffer;
+export function minifyAsync(
+  code: string,
+  options?: MinifyOptions
+): Promise<string>;
+export function minifyAsync(
+  code: Buffer,
+  options?: MinifyOptions
+): Promise<Buffer>;
@@ -1,31 +1,165 @@
+use minify_js::Diagnostic;
+use minify_js::MinifyOptions;
 use minify_js::Session;
-use neon::prelude::*;
-use neon::types::buffer::TypedArray;
+use napi::bindgen_prelude::*;
+use napi_derive::napi;
 use std::str::FromStr;
 
-fn minify(mut cx: FunctionContext) -> JsResult<JsBuffer> {
-  let top_level_mode_raw = cx.argument::<JsString>(0).map(|v| v.value(&mut cx))?;
-  let top_level_mode = match minify_js::TopLevelMode::from_str(&top_level_mode_raw) {
-    Ok(m) => m,
-    Err(_) => return cx.throw_type_error("invalid top-level mode"),
-  };
-  let src = cx.argument::<JsBuffer>(1)?;
-  let mut out = Vec::new();
-  // TODO Allow reuse by creating a JS function that creates a native object.
+#[napi(object, js_name = "MinifyOptions")]
+#[derive(Default)]
+pub struct Options {
+  pub mode: Option<String>,
+  pub target: Option<String>,
+  pub legal_comments: Option<String>,
+  pub newline: Option<String>,
+  pub max_line_len: Option<u32>,
+  pub banner: Option<String>,
+  pub footer: Option<String>,
+  pub beautify: Option<bool>,
+  pub asi: Option<bool>,
+}
+
+fn parse_option<T: FromStr>(key: &str, raw: &Option<String>) -> Result<Option<T>> {
+  raw
+    .as_deref()
+    .map(|raw| {
+      T::from_str(raw)
+        .map_err(|_| Error::new(Status::InvalidArg, format!("invalid {} `{}`", key, raw)))
+    })
+    .transpose()
+}
+
+impl Options {
+  fn to_minify_options(&self) -> Result<MinifyOptions> {
+    let mut cfg = MinifyOptions::new()
+      .beautify(self.beautify.unwrap_or(false))
+      .asi(self.asi.unwrap_or(false));
+    if let Some(mode) = parse_option("mode", &self.mode)? {
+      cfg = cfg.top_level_mode(mode);
+    };
+    if let Some(target) = parse_option("target", &self.target)? {
+      cfg = cfg.target(target);
+    };
+    if let Some(legal_comments) = parse_option("legalComments", &self.legal_comments)? {
+      cfg = cfg.legal_comments(legal_comments);
+    };
+    if let Some(newline) = parse_option("newline", &self.newline)? {
+      cfg = cfg.newline(newline);
+    };
+    if let Some(max_line_len) = self.max_line_len {
+      cfg = cfg.max_line_len(max_line_len as usize);
+    };
+    if let Some(banner) = &self.banner {
+      cfg = cfg.banner(banner.clone());
+    };
+    if let Some(footer) = &self.footer {
+      cfg = cfg.footer(footer.clone());
+    };
+    Ok(cfg)
+  }
+}
+
+// Code given as a string is returned as a string, and code given as a Buffer is returned as a Buffer. Buffers are copied, as they can't be sent to the thread pool.
+pub enum Code {
+  String(String),
+  Buffer(Vec<u8>),
+}
+
+impl Code {
+  fn new(code: Either<String, Buffer>) -> Code {
+    match code {
+      Either::A(s) => Code::String(s),
+      Either::B(b) => Code::Buffer(b.to_vec()),
+    }
+  }
+
+  fn as_bytes(&self) -> &[u8] {
+    match self {
+      Code::String(s) => s.as_bytes(),
+      Code::Buffer(b) => b,
+    }
+  }
+
+  fn to_js(&self, out: Vec<u8>) -> Either<String, Buffer> {
+    match self {
+      Code::String(_) => Either::A(String::from_utf8(out).expect("minified code is UTF-8")),
+      Code::Buffer(_) => Either::B(out.into()),
+    }
+  }
+}
+
+fn minify_code(code: &Code, cfg: &MinifyOptions) -> std::result::Result<Vec<u8>, Diagnostic> {
   let session = Session::new();
-  let res = match minify_js::minify(&session, top_level_mode, src.as_slice(&mut cx), &mut out) {
-    Ok(()) => Ok(JsBuffer::external(&mut cx, out)),
-    // We can't call `cx.throw_error` here as `cx` is already borrowed, so we create the error string and then throw later.
-    Err(err) => Err(format!("{:?}", err)),
-  };
-  match res {
-    Ok(res) => Ok(res),
-    Err(msg) => cx.throw_error(msg),
+  let mut out = Vec::new();
+  match minify_js::minify(&session, code.as_bytes(), cfg, &mut out) {
+    Ok(_) => Ok(out),
+    Err(err) => Err(Diagnostic::from_minify_error(code.as_bytes(), &err)),
+  }
+}
+
+// Creates a JS error for a diagnostic, with its code and position as properties, like Node.js's own errors.
+fn diagnostic_error(env: Env, d: Diagnostic) -> Error {
+  let err = env.create_error(Error::new(
+    Status::GenericFailure,
+    format!("{} ({}:{})", d.message, d.line, d.column),
+  ));
+  let err = err.and_then(|mut err| {
+    err.set_named_property("code", env.create_string(d.code)?)?;
+    err.set_named_property("line", env.create_uint32(d.line as u32)?)?;
+    err.set_named_property("column", env.create_uint32(d.column as u32)?)?;
+    Ok(err)
+  });
+  match err {
+    Ok(err) => Error::from(err.into_unknown()),
+    Err(err) => err,
+  }
+}
+
+/// Minifies JavaScript code, given as a string or a Buffer containing UTF-8, returning the minified code as the same type.
+#[napi(ts_return_type = "typeof code")]
+pub fn minify(
+  env: Env,
+  code: Either<String, Buffer>,
+  options: Option<Options>,
+) -> Result<Either<String, Buffer>> {
+  let cfg = options.unwrap_or_default().to_minify_options()?;
+  let code = Code::new(code);
+  match minify_code(&code, &cfg) {
+    Ok(out) => Ok(code.to_js(out)),
+    Err(d) => Err(diagnostic_error(env, d)),
+  }
+}
+
+pub struct MinifyTask {
+  code: Code,
+  cfg: MinifyOptions,
+}
+
+impl Task for MinifyTask {
+  type Output = std::result::Result<Vec<u8>, Diagnostic>;
+  type JsValue = Either<String, Buffer>;
+
+  fn compute(&mut self) -> Result<Self::Output> {
+    Ok(minify_code(&self.code, &self.cfg))
+  }
+
+  fn resolve(&mut self, env: Env, output: Self::Output) -> Result<Self::JsValue> {
+    match output {
+      Ok(out) => Ok(self.code.to_js(out)),
+      Err(d) => Err(diagnostic_error(env, d)),
+    }
   }
 }
 
-#[neon::main]
-fn main(mut cx: ModuleContext) -> NeonResult<()> {
-  cx.export_function("minify", minify)?;
-  Ok(())
+/// Like `minify`, but minifies on the libuv thread pool, so that minifying large files doesn't block the event loop.
+#[napi(ts_return_type = "Promise<typeof code>")]
+pub fn minify_async(
+  code: Either<String, Buffer>,
+  options: Option<Options>,
+) -> Result<AsyncTask<MinifyTask>> {
+  let cfg = options.unwrap_or_default().to_minify_options()?;
+  Ok(AsyncTask::new(MinifyTask {
+    code: Code::new(code),
+    cfg,
+  }))
 }
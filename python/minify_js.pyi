@@ -0,0 +1,36 @@
+from typing import Literal, Optional
+
+class MinifyError(ValueError):
+    """Raised for code that can't be minified, with the `code`, `line`, and `column` of the problem."""
+
+    code: str
+    line: int
+    column: int
+
+def minify(
+    code: str,
+    *,
+    mode: Optional[Literal["global", "module"]] = None,
+    target: Optional[
+        Literal[
+            "es5",
+            "es2015",
+            "es2016",
+            "es2017",
+            "es2018",
+            "es2019",
+            "es2020",
+            "es2021",
+            "es2022",
+            "esnext",
+        ]
+    ] = None,
+    legal_comments: Optional[Literal["none", "eof", "top"]] = None,
+    newline: Optional[Literal["lf", "crlf"]] = None,
+    max_line_len: Optional[int] = None,
+    banner: Optional[str] = None,
+    footer: Optional[str] = None,
+    beautify: bool = False,
+    asi: bool = False,
+) -> str:
+    """Minifies JavaScript code. The GIL is released while minifying, so other Python threads can run, including ones minifying other code."""
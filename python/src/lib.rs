@@ -0,0 +1,105 @@
+use minify_js::Diagnostic;
+use minify_js::MinifyOptions;
+use minify_js::Session;
+use pyo3::create_exception;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use std::str::FromStr;
+
+create_exception!(
+  minify_js,
+  MinifyError,
+  PyValueError,
+  "Raised for code that can't be minified, with the `code`, `line`, and `column` of the problem."
+);
+
+fn parse_option<T: FromStr>(key: &str, raw: Option<String>) -> PyResult<Option<T>> {
+  raw
+    .map(|raw| {
+      T::from_str(&raw).map_err(|_| PyValueError::new_err(format!("invalid {} `{}`", key, raw)))
+    })
+    .transpose()
+}
+
+fn minify_code(code: &str, cfg: &MinifyOptions) -> Result<String, Diagnostic> {
+  let session = Session::new();
+  let mut out = Vec::new();
+  match minify_js::minify(&session, code.as_bytes(), cfg, &mut out) {
+    Ok(_) => Ok(String::from_utf8(out).expect("minified code is UTF-8")),
+    Err(err) => Err(Diagnostic::from_minify_error(code.as_bytes(), &err)),
+  }
+}
+
+fn diagnostic_error(py: Python<'_>, d: Diagnostic) -> PyResult<PyErr> {
+  let err = MinifyError::new_err(format!("{} ({}:{})", d.message, d.line, d.column));
+  let value = err.value(py);
+  value.setattr("code", d.code)?;
+  value.setattr("line", d.line)?;
+  value.setattr("column", d.column)?;
+  Ok(err)
+}
+
+/// Minifies JavaScript code. The GIL is released while minifying, so other Python threads can run, including ones minifying other code.
+#[pyfunction]
+#[pyo3(signature = (
+  code,
+  *,
+  mode = None,
+  target = None,
+  legal_comments = None,
+  newline = None,
+  max_line_len = None,
+  banner = None,
+  footer = None,
+  beautify = false,
+  asi = false,
+))]
+#[allow(clippy::too_many_arguments)]
+fn minify(
+  py: Python<'_>,
+  code: String,
+  mode: Option<String>,
+  target: Option<String>,
+  legal_comments: Option<String>,
+  newline: Option<String>,
+  max_line_len: Option<usize>,
+  banner: Option<String>,
+  footer: Option<String>,
+  beautify: bool,
+  asi: bool,
+) -> PyResult<String> {
+  let mut cfg = MinifyOptions::new().beautify(beautify).asi(asi);
+  if let Some(mode) = parse_option("mode", mode)? {
+    cfg = cfg.top_level_mode(mode);
+  };
+  if let Some(target) = parse_option("target", target)? {
+    cfg = cfg.target(target);
+  };
+  if let Some(legal_comments) = parse_option("legal_comments", legal_comments)? {
+    cfg = cfg.legal_comments(legal_comments);
+  };
+  if let Some(newline) = parse_option("newline", newline)? {
+    cfg = cfg.newline(newline);
+  };
+  if let Some(max_line_len) = max_line_len {
+    cfg = cfg.max_line_len(max_line_len);
+  };
+  if let Some(banner) = banner {
+    cfg = cfg.banner(banner);
+  };
+  if let Some(footer) = footer {
+    cfg = cfg.footer(footer);
+  };
+  match py.allow_threads(|| minify_code(&code, &cfg)) {
+    Ok(out) => Ok(out),
+    Err(d) => Err(diagnostic_error(py, d)?),
+  }
+}
+
+#[pymodule]
+#[pyo3(name = "minify_js")]
+fn minify_js_python(m: &Bound<'_, PyModule>) -> PyResult<()> {
+  m.add_function(wrap_pyfunction!(minify, m)?)?;
+  m.add("MinifyError", m.py().get_type::<MinifyError>())?;
+  Ok(())
+}
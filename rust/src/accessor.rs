@@ -0,0 +1,179 @@
+use parse_js::ast::ClassMember;
+use parse_js::ast::ClassOrObjectMemberKey;
+use parse_js::ast::ClassOrObjectMemberValue;
+use parse_js::ast::NodeData;
+use parse_js::ast::ObjectMemberType;
+use parse_js::ast::Syntax;
+use parse_js::session::Session;
+use parse_js::source::SourceRange;
+use parse_js::visit::JourneyControls;
+use parse_js::visit::Visitor;
+
+// Whether the last token in `gap`, the code between the end of whatever comes before a member and the start of its key, is `get` or `set`, which makes the member a getter or setter. Comments are skipped. A `[` before a computed key is ignored.
+fn accessor_keyword(gap: &[u8]) -> Option<Accessor> {
+  let is_word = |c: u8| c.is_ascii_alphanumeric() || c == b'_' || c == b'$' || c >= 0x80;
+  let mut last = None;
+  let mut i = 0;
+  while i < gap.len() {
+    if gap[i].is_ascii_whitespace() {
+      i += 1;
+    } else if gap[i..].starts_with(b"/*") {
+      i = gap[i + 2..]
+        .windows(2)
+        .position(|w| w == b"*/")
+        .map_or(gap.len(), |p| i + 2 + p + 2);
+    } else if gap[i..].starts_with(b"//") {
+      i = gap[i..]
+        .iter()
+        .position(|&c| c == b'\n')
+        .map_or(gap.len(), |p| i + p);
+    } else if is_word(gap[i]) {
+      let start = i;
+      while i < gap.len() && is_word(gap[i]) {
+        i += 1;
+      }
+      last = Some(&gap[start..i]);
+    } else {
+      if gap[i] != b'[' {
+        last = Some(&gap[i..i + 1]);
+      };
+      i += 1;
+    };
+  }
+  match last {
+    Some(b"get") => Some(Accessor::Getter),
+    Some(b"set") => Some(Accessor::Setter),
+    _ => None,
+  }
+}
+
+#[derive(Clone, Copy)]
+enum Accessor {
+  Getter,
+  Setter,
+}
+
+// Finds whether a member is a getter or setter from the code from the start of `before` to the start of its key, excluding `before` itself if `exclusive`.
+fn accessor_before<'a>(
+  before: SourceRange<'a>,
+  exclusive: bool,
+  key: &ClassOrObjectMemberKey<'a>,
+) -> Option<Accessor> {
+  let start = if exclusive { before.end() } else { before.start() };
+  let key_loc = match key {
+    ClassOrObjectMemberKey::Direct(name) => *name,
+    ClassOrObjectMemberKey::Computed(expr) => expr.loc,
+  };
+  // Trees can have nodes parsed from other code, such as when bundling.
+  let base = |loc: SourceRange<'_>| loc.as_slice().as_ptr() as usize - loc.start();
+  if base(before) != base(key_loc) || key_loc.start() < start {
+    return None;
+  };
+  let range = before + key_loc;
+  accessor_keyword(&range.as_slice()[start - range.start()..key_loc.start() - range.start()])
+}
+
+struct Accessors<'a> {
+  session: &'a Session,
+}
+
+impl<'a> Accessors<'a> {
+  // Turns a method into the getter or setter it was written as. A setter's parameter is its only parameter, or the parameter declaration if it has a default value.
+  fn fix(&self, accessor: Accessor, value: &mut ClassOrObjectMemberValue<'a>) {
+    let ClassOrObjectMemberValue::Method {
+      is_async: false,
+      generator: false,
+      signature,
+      body,
+    } = value
+    else {
+      return;
+    };
+    let Syntax::FunctionSignature { parameters } = &mut signature.stx else {
+      unreachable!();
+    };
+    *value = match (accessor, parameters.len()) {
+      (Accessor::Getter, 0) => ClassOrObjectMemberValue::Getter {
+        body: body.take(self.session),
+      },
+      (Accessor::Setter, 1) => {
+        let parameter = match &mut parameters[0].stx {
+          Syntax::ParamDecl {
+            rest: false,
+            pattern,
+            default_value: None,
+          } => pattern.take(self.session),
+          Syntax::ParamDecl { rest: false, .. } => parameters[0].take(self.session),
+          _ => return,
+        };
+        ClassOrObjectMemberValue::Setter {
+          parameter,
+          body: body.take(self.session),
+        }
+      }
+      _ => return,
+    };
+  }
+
+  fn fix_class(&self, start: SourceRange<'a>, exclusive: bool, members: &mut [ClassMember<'a>]) {
+    let mut before = (start, exclusive);
+    for member in members.iter_mut() {
+      if let Some(accessor) = accessor_before(before.0, before.1, &member.key) {
+        self.fix(accessor, &mut member.value);
+      };
+      let end = match &member.value {
+        ClassOrObjectMemberValue::Getter { body }
+        | ClassOrObjectMemberValue::Setter { body, .. }
+        | ClassOrObjectMemberValue::Method { body, .. } => body.loc,
+        ClassOrObjectMemberValue::Property {
+          initializer: Some(initializer),
+        } => initializer.loc,
+        ClassOrObjectMemberValue::Property { initializer: None } => match &member.key {
+          ClassOrObjectMemberKey::Direct(name) => *name,
+          ClassOrObjectMemberKey::Computed(expr) => expr.loc,
+        },
+      };
+      before = (end, true);
+    }
+  }
+}
+
+impl<'a> Visitor<'a> for Accessors<'a> {
+  fn on_syntax_down(&mut self, node: &mut NodeData<'a>, _ctl: &mut JourneyControls) {
+    match &mut node.stx {
+      Syntax::ObjectMember {
+        typ: ObjectMemberType::Valued { key, value },
+      } => {
+        if let Some(accessor) = accessor_before(node.loc, false, key) {
+          self.fix(accessor, value);
+        };
+      }
+      Syntax::ClassDecl {
+        name,
+        extends,
+        members,
+        ..
+      }
+      | Syntax::ClassExpr {
+        name,
+        extends,
+        members,
+        ..
+      } => {
+        // Only the code after the name or superclass is searched, as they could end with `get` or `set`.
+        let (start, exclusive) = match (extends, name) {
+          (Some(extends), _) => (extends.loc, true),
+          (None, Some(name)) => (name.loc, true),
+          (None, None) => (node.loc, false),
+        };
+        self.fix_class(start, exclusive, members);
+      }
+      _ => {}
+    };
+  }
+}
+
+// parse-js parses getters and setters (e.g. `get x() {}`) as methods, as it checks for a method's `(` before it checks whether `get` or `set` came before the key, so this turns them back into getters and setters in a parsed tree, using the code before each method's key.
+pub fn fix_accessors<'a>(session: &'a Session, node: &mut NodeData<'a>) {
+  Accessors { session }.visit(node);
+}
@@ -0,0 +1,93 @@
+use parse_js::char::ID_CONTINUE_CHARSTR;
+use parse_js::char::ID_START_CHARSTR;
+use std::str::FromStr;
+
+/// The characters that minified names are made of, in the order they're used: the first name is the first character that can start a name, and longer names are only used once all shorter ones are taken. Keywords and reserved words (e.g. `do` or `eval`) are always skipped. The default is `a`–`z`, `A`–`Z`, `_`, and `$`, with digits after the first character. It can also be parsed from a string of the characters.
+///
+/// # Examples
+///
+/// ```
+/// use minify_js::{MinifyOptions, NameAlphabet, Session, minify_str};
+///
+/// let alphabet = NameAlphabet::new("xyz").unwrap();
+/// let cfg = MinifyOptions::new().name_alphabet(alphabet);
+/// let session = Session::new();
+/// let out = minify_str(&session, "(() => { let first = 1, second = 2; f(first, second) })()", &cfg);
+/// assert_eq!(out.unwrap(), "(()=>{let x=1,y=2;f(x,y)})()");
+/// assert!(NameAlphabet::new("0123").is_err());
+/// assert!("abc-".parse::<NameAlphabet>().is_err());
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NameAlphabet {
+  chars: Vec<u8>,
+  by_frequency: bool,
+}
+
+impl Default for NameAlphabet {
+  fn default() -> NameAlphabet {
+    NameAlphabet {
+      chars: ID_CONTINUE_CHARSTR.to_vec(),
+      by_frequency: false,
+    }
+  }
+}
+
+impl NameAlphabet {
+  /// Returns an error if `chars` has characters other than ASCII letters, digits, `_`, and `$`, has duplicates, or has no character that can start a name (i.e. it's only digits).
+  pub fn new(chars: &str) -> Result<NameAlphabet, String> {
+    if let Some(c) = chars
+      .chars()
+      .find(|&c| !c.is_ascii() || !ID_CONTINUE_CHARSTR.contains(&(c as u8)))
+    {
+      return Err(format!("`{}` can't be used in minified names", c));
+    };
+    let chars = chars.as_bytes();
+    if let Some((_, &c)) = chars
+      .iter()
+      .enumerate()
+      .find(|(i, c)| chars[..*i].contains(c))
+    {
+      return Err(format!("`{}` is repeated", c as char));
+    };
+    if !chars.iter().any(|c| ID_START_CHARSTR.contains(c)) {
+      return Err("there must be a character that can start a name".to_string());
+    };
+    Ok(NameAlphabet {
+      chars: chars.to_vec(),
+      by_frequency: false,
+    })
+  }
+
+  /// Reorders the characters by how often they appear in the code being minified, most frequent first (keeping their order if equally frequent), so names reuse characters that are common in the rest of the output, which can help gzip and Brotli compress it.
+  pub fn by_frequency(mut self) -> NameAlphabet {
+    self.by_frequency = true;
+    self
+  }
+
+  // Returns the characters that can start a name, and all characters, in the order to use them when minifying `source`.
+  pub fn chars_for(&self, source: &[u8]) -> (Vec<u8>, Vec<u8>) {
+    let mut chars = self.chars.clone();
+    if self.by_frequency {
+      let mut counts = [0usize; 256];
+      for &c in source {
+        counts[c as usize] += 1;
+      }
+      // This is a stable sort.
+      chars.sort_by_key(|&c| std::cmp::Reverse(counts[c as usize]));
+    };
+    let start = chars
+      .iter()
+      .copied()
+      .filter(|c| ID_START_CHARSTR.contains(c))
+      .collect();
+    (start, chars)
+  }
+}
+
+impl FromStr for NameAlphabet {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    NameAlphabet::new(s)
+  }
+}
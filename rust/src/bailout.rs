@@ -0,0 +1,209 @@
+use crate::cfg::MinifyOptions;
+use crate::cfg::Target;
+use crate::diagnostic::Diagnostic;
+use crate::diagnostic::Severity;
+use crate::error::line_column;
+use crate::minify::purity::pattern_names;
+use crate::minify::purity::Purity;
+use parse_js::ast::NodeData;
+use parse_js::ast::Syntax;
+use parse_js::operator::OperatorName;
+use parse_js::symbol::ScopeFlag;
+use parse_js::symbol::ScopeType;
+use parse_js::symbol::Symbol;
+use parse_js::visit::JourneyControls;
+use parse_js::visit::Visitor;
+use std::collections::HashSet;
+
+// A function declaration that would become an arrow function unless it's used as a constructor.
+struct FnDecl {
+  sym: Symbol,
+  name: String,
+  start: usize,
+  end: usize,
+}
+
+struct Bailouts<'a> {
+  source: &'a [u8],
+  // Whether functions can become arrow functions at all.
+  arrows: bool,
+  // Purity without and with `assume_plain_objects`, to find statements only kept because they read properties.
+  purity: Purity,
+  plain_purity: Option<Purity>,
+  fn_decls: Vec<FnDecl>,
+  constructors: HashSet<Symbol>,
+  out: Vec<Diagnostic>,
+}
+
+impl<'a> Bailouts<'a> {
+  fn report(&mut self, code: &'static str, message: String, start: usize, end: usize) {
+    let (line, column, _) = line_column(self.source, start);
+    self.out.push(Diagnostic {
+      code,
+      message,
+      severity: Severity::Warning,
+      start,
+      end,
+      line,
+      column,
+    });
+  }
+
+  // Reports why a function can't become an arrow function if it uses `this` or `arguments`, and returns whether it did.
+  fn check_fn(&mut self, node: &NodeData<'a>, body: &NodeData<'a>) -> bool {
+    let flags = body.scope.flags();
+    let (code, reason) = if flags.has(ScopeFlag::UsesThis) {
+      ("B_USES_THIS", "uses `this`")
+    } else if flags.has(ScopeFlag::UsesArguments) {
+      ("B_USES_ARGUMENTS", "uses `arguments`")
+    } else {
+      return false;
+    };
+    self.report(
+      code,
+      format!(
+        "function isn't turned into an arrow function because it {}",
+        reason
+      ),
+      node.loc.start(),
+      node.loc.end(),
+    );
+    true
+  }
+
+  fn mark_constructor(&mut self, node: &NodeData<'a>, name: &NodeData<'a>) {
+    if let Syntax::IdentifierExpr { name } = name.stx {
+      if let Some(sym) = node.scope.find_symbol(name) {
+        self.constructors.insert(sym);
+      };
+    };
+  }
+}
+
+impl<'a> Visitor<'a> for Bailouts<'a> {
+  fn on_syntax_down(&mut self, node: &mut NodeData<'a>, _ctl: &mut JourneyControls) {
+    match &node.stx {
+      Syntax::FunctionExpr {
+        generator: false,
+        name: None,
+        body,
+        ..
+      } if self.arrows => {
+        self.check_fn(node, body);
+      }
+      Syntax::FunctionDecl {
+        export: false,
+        generator: false,
+        name: Some(name),
+        body,
+        ..
+      } if self.arrows => {
+        // Functions using `this` or `arguments` are already reported, so only check whether others are constructors.
+        let reported = self.check_fn(node, body);
+        if let (false, Some(sym)) = (reported, node.scope.find_symbol(name.loc)) {
+          self.fn_decls.push(FnDecl {
+            sym,
+            name: String::from_utf8_lossy(name.loc.as_slice()).into_owned(),
+            start: node.loc.start(),
+            end: node.loc.end(),
+          });
+        };
+      }
+      Syntax::UnaryExpr {
+        operator: OperatorName::New,
+        argument,
+        ..
+      } => match &argument.stx {
+        Syntax::CallExpr {
+          parenthesised: false,
+          callee,
+          ..
+        } => self.mark_constructor(node, callee),
+        _ => self.mark_constructor(node, argument),
+      },
+      Syntax::MemberExpr {
+        left,
+        right,
+        optional_chaining: false,
+        ..
+      } if right.as_slice() == b"prototype" => self.mark_constructor(node, left),
+      Syntax::ExpressionStmt { expression } => {
+        if let Some(plain_purity) = &self.plain_purity {
+          if !self.purity.is_pure(expression) && plain_purity.is_pure(expression) {
+            self.report(
+              "B_PROPERTY_READ",
+              "unused property read is kept because the property may have a getter; enable `assume_plain_objects` if it can't".to_string(),
+              expression.loc.start(),
+              expression.loc.end(),
+            );
+          };
+        };
+      }
+      _ => {}
+    };
+  }
+}
+
+// Finds code that prevents some minification, such as functions using `this` (which can't become arrow functions) and names of globals (which aren't renamed), so it can be refactored for better compression. The conditions mirror the minifier's, but are checked before minifying. `source` must be the source that `node` was parsed from.
+pub fn collect_bailouts<'a>(
+  source: &'a [u8],
+  node: &mut NodeData<'a>,
+  cfg: &MinifyOptions,
+) -> Vec<Diagnostic> {
+  let mut bailouts = Bailouts {
+    source,
+    arrows: cfg.target >= Target::Es2015,
+    purity: Purity::new(node.loc, false, cfg.environment),
+    plain_purity: (!cfg.assume_plain_objects).then(|| Purity::new(node.loc, true, cfg.environment)),
+    fn_decls: Vec::new(),
+    constructors: HashSet::new(),
+    out: Vec::new(),
+  };
+  // Global scopes don't have symbols, so find the names declared by top-level statements.
+  let mut globals = Vec::new();
+  if let (ScopeType::Global, Syntax::TopLevel { body }) = (node.scope.typ(), &node.stx) {
+    for stmt in body.iter() {
+      match &stmt.stx {
+        Syntax::VarDecl { declarators, .. } => {
+          for d in declarators.iter() {
+            pattern_names(d.pattern, &mut globals);
+          }
+        }
+        Syntax::ClassDecl {
+          name: Some(name), ..
+        }
+        | Syntax::FunctionDecl {
+          name: Some(name), ..
+        } => globals.push(name.loc),
+        _ => {}
+      };
+    }
+  };
+  for name in globals {
+    bailouts.report(
+      "B_GLOBAL_NAME",
+      format!(
+        "`{}` is a global variable, so it isn't renamed; minify the code as a module or use `enclose` to rename it",
+        String::from_utf8_lossy(name.as_slice())
+      ),
+      name.start(),
+      name.end(),
+    );
+  }
+  bailouts.visit(node);
+  for decl in std::mem::take(&mut bailouts.fn_decls) {
+    if bailouts.constructors.contains(&decl.sym) {
+      bailouts.report(
+        "B_CONSTRUCTOR",
+        format!(
+          "`{}` isn't turned into an arrow function because it's used with `new` or its `prototype` is used",
+          decl.name
+        ),
+        decl.start,
+        decl.end,
+      );
+    };
+  }
+  bailouts.out.sort_by_key(|d| d.start);
+  bailouts.out
+}
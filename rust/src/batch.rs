@@ -0,0 +1,255 @@
+use crate::define::Define;
+use crate::diagnostic::Diagnostic;
+use crate::files::FileId;
+use crate::files::SourceFiles;
+use crate::minify;
+use crate::position_map::utf16_len;
+use crate::MinifyOptions;
+use crate::MinifyStats;
+use crate::RenameDecision;
+use crate::RenameFilter;
+use crate::SourceMapOptions;
+use parse_js::session::Session;
+use parse_js::symbol::ScopeType;
+use rayon::prelude::*;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Arc;
+
+/// Minified names of top-level variables in modules, by original name, shared across the modules in a [minify_batch] so that the same variable gets the same name in every module. It can be kept between builds (e.g. with the `serialize` feature) so names stay stable across rebuilds, which helps long-term caching.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serialize", serde(transparent))]
+pub struct NameCache {
+  pub names: BTreeMap<String, String>,
+}
+
+impl NameCache {
+  pub fn new() -> NameCache {
+    NameCache::default()
+  }
+}
+
+/// A syntax error or warning from one of the modules in a [minify_batch].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ModuleDiagnostic {
+  /// Index of the module in the batch.
+  pub module: usize,
+  pub diagnostic: Diagnostic,
+}
+
+/// The result of a [minify_batch].
+#[derive(Clone, Debug, Default)]
+pub struct BatchOutput {
+  /// The output and stats of each module, in the same order as the modules, or `None` if the module couldn't be minified.
+  pub outputs: Vec<Option<(Vec<u8>, MinifyStats)>>,
+  /// Errors from modules that couldn't be minified, and warnings if [MinifyOptions::warnings] is enabled, ordered by module and then position. Warnings are moved here from the stats.
+  pub diagnostics: Vec<ModuleDiagnostic>,
+}
+
+impl BatchOutput {
+  pub fn has_errors(&self) -> bool {
+    self.outputs.iter().any(Option::is_none)
+  }
+}
+
+/// Minifies many modules with the same options on the [rayon] thread pool, such as all the chunks of a bundle. Use [`ThreadPool::install`](rayon::ThreadPool::install) to run it on a different pool. Each thread reuses its own [`Session`].
+///
+/// `defines` are replaced in every module, before any in [MinifyOptions::defines]. Top-level variables are renamed to the names in `name_cache` where possible, and new names are added to it once all modules are minified, in module order, so the output doesn't depend on which modules finish first. As with [RenameDecision::Rename], a cached name is only used if it doesn't clash, so names can still differ between modules. A [MinifyOptions::rename_filter] is still called first, and the cache is only used for variables it leaves to be minified.
+///
+/// # Examples
+///
+/// ```
+/// use minify_js::{minify_batch, Define, MinifyOptions, NameCache, TopLevelMode};
+///
+/// let cfg = MinifyOptions::new().top_level_mode(TopLevelMode::Module);
+/// let defines = ["DEBUG=false".parse::<Define>().unwrap()];
+/// let mut cache = NameCache::new();
+/// let out = minify_batch(
+///   &["let first = 1; if (DEBUG) f(first); export {first as x}", "let = ;"],
+///   &cfg,
+///   &defines,
+///   &mut cache,
+/// );
+/// assert_eq!(out.outputs[0].as_ref().unwrap().0, b"let a=1;!1&&f(a);export{a as x}");
+/// assert!(out.outputs[1].is_none());
+/// assert_eq!(out.diagnostics[0].module, 1);
+/// assert_eq!(cache.names["first"], "a");
+/// ```
+pub fn minify_batch<S: AsRef<[u8]> + Sync>(
+  modules: &[S],
+  cfg: &MinifyOptions,
+  defines: &[Define],
+  name_cache: &mut NameCache,
+) -> BatchOutput {
+  let mut cfg = cfg.clone();
+  cfg.defines.splice(0..0, defines.iter().cloned());
+  // The rename map is needed to update the cache.
+  let keep_renames = cfg.rename_map;
+  cfg.rename_map = true;
+  let cached = Arc::new(name_cache.names.clone());
+  let user_filter = cfg.rename_filter.take();
+  cfg.rename_filter = Some(RenameFilter::new(move |info| {
+    if let Some(filter) = &user_filter {
+      match (filter.0)(info) {
+        RenameDecision::Minify => {}
+        decision => return decision,
+      };
+    };
+    if !matches!(info.scope, ScopeType::Global | ScopeType::Module) {
+      return RenameDecision::Minify;
+    };
+    match cached.get(info.name) {
+      Some(name) => RenameDecision::Rename(name.clone()),
+      None => RenameDecision::Minify,
+    }
+  }));
+
+  let results = modules
+    .par_iter()
+    .map_init(Session::new, |session, source| {
+      let source = source.as_ref();
+      session.reset();
+      let mut output = Vec::new();
+      minify(session, source, &cfg, &mut output)
+        .map(|stats| (output, stats))
+        .map_err(|err| Diagnostic::from_minify_error(source, &err))
+    })
+    .collect::<Vec<_>>();
+
+  let mut batch = BatchOutput::default();
+  for (module, result) in results.into_iter().enumerate() {
+    match result {
+      Ok((output, mut stats)) => {
+        let top_level = stats
+          .renames
+          .as_ref()
+          .and_then(|renames| renames.scopes.first())
+          .filter(|scope| matches!(scope.typ, ScopeType::Global | ScopeType::Module));
+        for rename in top_level.into_iter().flat_map(|scope| scope.renames.iter()) {
+          if rename.minified != rename.original {
+            name_cache
+              .names
+              .entry(rename.original.clone())
+              .or_insert_with(|| rename.minified.clone());
+          };
+        }
+        if !keep_renames {
+          stats.renames = None;
+        };
+        batch.diagnostics.extend(
+          stats
+            .warnings
+            .drain(..)
+            .map(|diagnostic| ModuleDiagnostic { module, diagnostic }),
+        );
+        batch.outputs.push(Some((output, stats)));
+      }
+      Err(diagnostic) => {
+        batch
+          .diagnostics
+          .push(ModuleDiagnostic { module, diagnostic });
+        batch.outputs.push(None);
+      }
+    };
+  }
+  batch
+}
+
+/// The result of a [minify_concat].
+#[derive(Clone, Debug, Default)]
+pub struct ConcatOutput {
+  /// The minified files, joined in order.
+  pub code: Vec<u8>,
+  /// An [index source map](https://tc39.es/source-map/#index-map) of `code`, with a section for each file.
+  pub source_map: String,
+  /// The stats of each file, in order.
+  pub stats: Vec<MinifyStats>,
+  /// Warnings, if [MinifyOptions::warnings] is enabled, ordered by file and then position.
+  pub diagnostics: Vec<ModuleDiagnostic>,
+}
+
+/// Minifies files like [minify_batch] and joins them in order into one script, with a source map covering all of them, for build scripts that concatenate files. Each file is minified with [MinifyOptions::safe_concat], so joining them can't change how they're parsed (e.g. a file starting with `(` doesn't call the end of the previous file).
+///
+/// Top-level variables of global scripts aren't renamed, so files can still use each other's globals; use [MinifyOptions::enclose] to rename them within each file. In modules, top-level variables are renamed using `name_cache`, as with [minify_batch].
+///
+/// The source map is generated with `opts` for each file, except for [SourceMapOptions::input_map], which is ignored as it would only apply to one of the files.
+///
+/// Returns all diagnostics, ordered by file and then position, if any file couldn't be minified, as the joined code would be missing it.
+///
+/// # Examples
+///
+/// ```
+/// use minify_js::{minify_concat, MinifyOptions, NameCache, Session, SourceFiles, SourceMapOptions};
+///
+/// let session = Session::new();
+/// let mut files = SourceFiles::new(&session);
+/// files.add("a.js", b"var first = 1\nf(first)");
+/// files.add("b.js", b"(function () { g(first) })()");
+/// let out = minify_concat(&files, &MinifyOptions::new(), &[], &mut NameCache::new(), &SourceMapOptions::default())
+///   .unwrap();
+/// assert_eq!(out.code, b"var first=1;f(first);(()=>{g(first)})();");
+/// assert!(out.source_map.starts_with(r#"{"version":3,"sections":[{"offset":{"line":0,"column":0},"map":{"version":3,"sources":["a.js"]"#));
+/// assert!(out.source_map.contains(r#"{"offset":{"line":0,"column":20},"map":{"version":3,"sources":["b.js"]"#));
+/// ```
+pub fn minify_concat(
+  files: &SourceFiles,
+  cfg: &MinifyOptions,
+  defines: &[Define],
+  name_cache: &mut NameCache,
+  opts: &SourceMapOptions,
+) -> Result<ConcatOutput, Vec<ModuleDiagnostic>> {
+  let keep_positions = cfg.position_map;
+  let cfg = cfg.clone().safe_concat(true).position_map(true);
+  let sources = (0..files.len())
+    .map(|i| files.code(FileId(i)))
+    .collect::<Vec<_>>();
+  let batch = minify_batch(&sources, &cfg, defines, name_cache);
+  if batch.has_errors() {
+    return Err(batch.diagnostics);
+  };
+  let opts = SourceMapOptions {
+    input_map: None,
+    ..opts.clone()
+  };
+  let mut concat = ConcatOutput {
+    diagnostics: batch.diagnostics,
+    ..ConcatOutput::default()
+  };
+  let mut map = String::from("{\"version\":3,\"sections\":[");
+  // Line and UTF-16 column of the end of the joined code, where the next section starts.
+  let (mut line, mut column) = (0, 0);
+  for (i, (output, mut stats)) in batch.outputs.into_iter().flatten().enumerate() {
+    let positions = match keep_positions {
+      true => stats.positions.clone(),
+      false => stats.positions.take(),
+    }
+    .unwrap_or_default();
+    // Both sides of a join can have a `;` from `safe_concat`, but only one is needed. Dropping the file's first byte moves its mappings back a column.
+    let skip = usize::from(concat.code.ends_with(b";") && output.starts_with(b";"));
+    if i > 0 {
+      map.push(',');
+    };
+    write!(
+      map,
+      "{{\"offset\":{{\"line\":{},\"column\":{}}},\"map\":{}}}",
+      line,
+      column - skip,
+      files.source_map(FileId(i), &positions, &output, &opts)
+    )
+    .unwrap();
+    let joined = &output[skip..];
+    match memchr::memrchr(b'\n', joined) {
+      Some(last) => {
+        line += memchr::memchr_iter(b'\n', joined).count();
+        column = utf16_len(&joined[last + 1..]);
+      }
+      None => column += utf16_len(joined),
+    };
+    concat.code.extend_from_slice(joined);
+    concat.stats.push(stats);
+  }
+  map.push_str("]}");
+  concat.source_map = map;
+  Ok(concat)
+}
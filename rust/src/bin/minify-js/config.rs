@@ -0,0 +1,109 @@
+use crate::sourcemap::SourceMapKind;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+// Names of configuration files, in order of preference when both are in the same directory.
+const FILE_NAMES: &[&str] = &["minifyjs.toml", "minifyjs.json"];
+
+// Project-wide settings from a configuration file. Keys are the same as the long CLI flags (e.g. `max-line-len`), and CLI flags override them. Paths are relative to the file's directory.
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+  pub mode: Option<String>,
+  pub target: Option<String>,
+  pub out_dir: Option<PathBuf>,
+  pub mangle: Option<bool>,
+  // Map from global variables or property paths to expressions.
+  pub define: BTreeMap<String, String>,
+  pub drop_console: Option<bool>,
+  pub reserved: Vec<String>,
+  pub keep_names: Vec<String>,
+  pub legal_comments: Option<String>,
+  // Either a boolean or the kind of source map.
+  pub source_map: Option<SourceMapSetting>,
+  pub source_map_root: Option<String>,
+  pub sources_content: Option<bool>,
+  pub beautify: Option<bool>,
+  pub max_line_len: Option<usize>,
+  pub asi: Option<bool>,
+  pub safe_concat: Option<bool>,
+  pub assume_plain_objects: Option<bool>,
+  pub environment: Option<String>,
+  pub keep_fn_names: Option<bool>,
+  pub keep_source_url: Option<bool>,
+  pub keep_hashbang: Option<bool>,
+  pub name_alphabet: Option<String>,
+  pub name_alphabet_by_frequency: Option<bool>,
+  pub obfuscate: Option<bool>,
+  pub obfuscation_seed: Option<u64>,
+  pub banner: Option<String>,
+  pub footer: Option<String>,
+  pub enclose: Option<Vec<String>>,
+  pub newline: Option<String>,
+  pub warnings: Option<bool>,
+  pub bailouts: Option<bool>,
+  pub verify: Option<String>,
+  pub jobs: Option<usize>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum SourceMapSetting {
+  Enabled(bool),
+  Kind(String),
+}
+
+impl Config {
+  // Reads a configuration file, as TOML or JSON depending on its extension.
+  pub fn read(path: &Path) -> Result<Config, String> {
+    let raw =
+      fs::read_to_string(path).map_err(|err| format!("can't read {}: {}", path.display(), err))?;
+    let mut config: Config = if path.extension().is_some_and(|ext| ext == "json") {
+      serde_json::from_str(&raw).map_err(|err| err.to_string())
+    } else {
+      toml::from_str(&raw).map_err(|err| err.to_string())
+    }
+    .map_err(|err| format!("invalid {}: {}", path.display(), err))?;
+    if let Some(out_dir) = &config.out_dir {
+      config.out_dir = Some(path.parent().unwrap().join(out_dir));
+    };
+    Ok(config)
+  }
+
+  pub fn source_map_kind(&self) -> Result<Option<SourceMapKind>, String> {
+    match &self.source_map {
+      Some(SourceMapSetting::Enabled(true)) => Ok(Some(SourceMapKind::default())),
+      Some(SourceMapSetting::Enabled(false)) | None => Ok(None),
+      Some(SourceMapSetting::Kind(kind)) => parse_setting("source-map", &Some(kind.clone())),
+    }
+  }
+
+  // Finds the nearest configuration file in the current directory or its ancestors.
+  pub fn find() -> Option<PathBuf> {
+    let cwd = std::env::current_dir().ok()?;
+    cwd.ancestors().find_map(|dir| {
+      FILE_NAMES
+        .iter()
+        .map(|name| dir.join(name))
+        .find(|path| path.is_file())
+    })
+  }
+}
+
+// Parses a setting from a configuration file like its CLI flag.
+pub fn parse_setting<T: FromStr>(key: &str, value: &Option<String>) -> Result<Option<T>, String>
+where
+  T::Err: ToString,
+{
+  value
+    .as_ref()
+    .map(|v| {
+      v.parse()
+        .map_err(|err: T::Err| format!("invalid `{}` in configuration: {}", key, err.to_string()))
+    })
+    .transpose()
+}
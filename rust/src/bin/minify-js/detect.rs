@@ -0,0 +1,71 @@
+use minify_js::parse_js::ast::Syntax;
+use minify_js::parse_js::parse;
+use minify_js::Session;
+use minify_js::TopLevelMode;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+
+// Detects whether files are modules or global scripts the way Node.js does: `.mjs` files are modules, `.cjs` files are scripts, and other files follow the `type` field of the nearest package.json. Package types are cached by directory, as most files share one.
+#[derive(Default)]
+pub struct ModeDetector {
+  package_types: HashMap<PathBuf, Option<TopLevelMode>>,
+}
+
+impl ModeDetector {
+  // Returns `None` if the mode can't be detected, such as when the nearest package.json has no `type` field.
+  pub fn detect(&mut self, path: &Path) -> Option<TopLevelMode> {
+    match path.extension().and_then(|ext| ext.to_str()) {
+      Some("mjs") => Some(TopLevelMode::Module),
+      Some("cjs") => Some(TopLevelMode::Global),
+      _ => {
+        // The file itself may not exist, such as a name given for stdin.
+        let dir = match path.parent()? {
+          p if p.as_os_str().is_empty() => Path::new("."),
+          p => p,
+        };
+        self.package_type(&dir.canonicalize().ok()?)
+      }
+    }
+  }
+
+  fn package_type(&mut self, dir: &Path) -> Option<TopLevelMode> {
+    if let Some(mode) = self.package_types.get(dir) {
+      return *mode;
+    };
+    let mode = match fs::read(dir.join("package.json")) {
+      Ok(raw) => serde_json::from_slice::<serde_json::Value>(&raw)
+        .ok()
+        .and_then(|package| match package.get("type")?.as_str()? {
+          "module" => Some(TopLevelMode::Module),
+          "commonjs" => Some(TopLevelMode::Global),
+          _ => None,
+        }),
+      Err(_) => dir.parent().and_then(|parent| self.package_type(parent)),
+    };
+    self.package_types.insert(dir.to_path_buf(), mode);
+    mode
+  }
+}
+
+// Whether the code has top-level import or export statements, which are only allowed in modules. It's parsed as a global script, which allows them, so that it can be checked before knowing the mode.
+pub fn has_module_syntax(session: &Session, source: &[u8]) -> bool {
+  let Ok(node) = parse(session, source, TopLevelMode::Global) else {
+    return false;
+  };
+  let Syntax::TopLevel { body } = &node.stx else {
+    return false;
+  };
+  body.iter().any(|stmt| {
+    matches!(
+      stmt.stx,
+      Syntax::ImportStmt { .. }
+        | Syntax::ExportDefaultExprStmt { .. }
+        | Syntax::ExportListStmt { .. }
+        | Syntax::ClassDecl { export: true, .. }
+        | Syntax::FunctionDecl { export: true, .. }
+        | Syntax::VarDecl { export: true, .. }
+    )
+  })
+}
@@ -0,0 +1,83 @@
+use glob::glob;
+use glob::Pattern;
+use std::path::Component;
+use std::path::Path;
+use std::path::PathBuf;
+
+// Extensions of files to minify when a directory is given.
+const EXTENSIONS: &[&str] = &["js", "mjs", "cjs"];
+
+// A file to minify, and its path relative to the base of the input it came from, which is where it goes in the output directory.
+pub struct InputFile {
+  pub path: PathBuf,
+  pub relative: PathBuf,
+}
+
+fn is_glob(s: &str) -> bool {
+  s.contains(['*', '?', '['])
+}
+
+// Returns whether an input expands to any number of files, rather than being a single file.
+pub fn is_multiple(input: &str) -> bool {
+  is_glob(input) || Path::new(input).is_dir()
+}
+
+// Expands inputs, which can be files, directories, or glob patterns, into the files to minify. The base of a glob pattern is the directory before the first component with a wildcard (e.g. `src` for `src/**/*.js`), and the base of a file is its directory.
+pub fn expand_inputs(inputs: &[String]) -> Result<Vec<InputFile>, String> {
+  let mut files = Vec::new();
+  for input in inputs {
+    let path = Path::new(input);
+    let (pattern, base) = if is_glob(input) {
+      let base = path
+        .components()
+        .take_while(|c| !is_glob(&c.as_os_str().to_string_lossy()))
+        .filter(|c| *c != Component::CurDir)
+        .collect::<PathBuf>();
+      (input.clone(), base)
+    } else if path.is_dir() {
+      let pattern = format!("{}/**/*", Pattern::escape(input));
+      let base = path
+        .components()
+        .filter(|c| *c != Component::CurDir)
+        .collect::<PathBuf>();
+      (pattern, base)
+    } else {
+      files.push(InputFile {
+        path: path.to_path_buf(),
+        relative: PathBuf::from(
+          path
+            .file_name()
+            .ok_or(format!("`{}` isn't a file", input))?,
+        ),
+      });
+      continue;
+    };
+    let entries = glob(&pattern).map_err(|err| format!("invalid pattern `{}`: {}", input, err))?;
+    let mut found = false;
+    for entry in entries {
+      let path = entry.map_err(|err| err.to_string())?;
+      let wanted = path.is_file()
+        && (is_glob(input)
+          || path
+            .extension()
+            .is_some_and(|ext| EXTENSIONS.iter().any(|e| ext == *e)));
+      if !wanted {
+        continue;
+      };
+      found = true;
+      // Matches don't start with `./`, even if the pattern does.
+      let relative = path
+        .components()
+        .filter(|c| *c != Component::CurDir)
+        .collect::<PathBuf>()
+        .strip_prefix(&base)
+        .unwrap()
+        .to_path_buf();
+      files.push(InputFile { path, relative });
+    }
+    if !found {
+      return Err(format!("no files found for `{}`", input));
+    };
+  }
+  Ok(files)
+}
@@ -0,0 +1,691 @@
+use config::parse_setting;
+use config::Config;
+use detect::has_module_syntax;
+use detect::ModeDetector;
+use inputs::expand_inputs;
+use inputs::is_multiple;
+use inputs::InputFile;
+use minify_js::minify;
+use minify_js::regex::Regex;
+use minify_js::syntax_errors;
+use minify_js::Define;
+use minify_js::Diagnostic;
+use minify_js::Environment;
+use minify_js::LegalComments;
+use minify_js::MinifyError;
+use minify_js::MinifyOptions;
+use minify_js::NameAlphabet;
+use minify_js::Newline;
+use minify_js::Obfuscation;
+use minify_js::RenameDecision;
+use minify_js::Session;
+use minify_js::SourceMapOptions;
+use minify_js::SourceMappingUrl;
+use minify_js::Target;
+use minify_js::TopLevelMode;
+use minify_js::Verify;
+use outcome::Outcome;
+use outcome::ReportFormat;
+use sourcemap::data_url;
+use sourcemap::read_input_map;
+use sourcemap::relative_path;
+use sourcemap::SourceMapKind;
+use std::ffi::OsString;
+use std::fs;
+use std::io::stdin;
+use std::io::stdout;
+use std::io::Read;
+use std::io::Write;
+use std::num::NonZeroUsize;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::thread;
+use std::time::Instant;
+use structopt::StructOpt;
+
+mod config;
+mod detect;
+mod inputs;
+mod outcome;
+mod sourcemap;
+
+fn parse_mode(s: &str) -> Result<TopLevelMode, &'static str> {
+  match s {
+    "script" => Ok(TopLevelMode::Global),
+    s => s.parse(),
+  }
+}
+
+#[derive(StructOpt)]
+#[structopt(name = "minify-js", about = "Extremely fast JS minifier")]
+// WARNING: Keep descriptions in sync with MinifyOptions.
+struct Cli {
+  /// Files, directories, or glob patterns (e.g. `'src/**/*.js'`) to minify; omit for stdin. Directories include all .js, .mjs, and .cjs files in them. Use `--out-dir` for more than one file.
+  input: Vec<String>,
+
+  /// Configuration file with project-wide settings, using the long names of flags as keys, which flags override. By default, the nearest minifyjs.toml or minifyjs.json in the current directory or its ancestors is used.
+  #[structopt(long, parse(from_os_str))]
+  config: Option<PathBuf>,
+
+  /// Path to use for stdin in messages and source maps, and to detect its mode from. It doesn't need to exist.
+  #[structopt(long, parse(from_os_str))]
+  filename: Option<PathBuf>,
+
+  /// Output destination; omit for stdout.
+  #[structopt(short, long, parse(from_os_str), conflicts_with = "out-dir")]
+  output: Option<PathBuf>,
+
+  /// Directory to write outputs to, keeping each file's path relative to the directory before the first wildcard in its pattern, or to the directory given.
+  #[structopt(long, parse(from_os_str))]
+  out_dir: Option<PathBuf>,
+
+  /// Replace each input file with its minified output. The file is only replaced once it has been minified, by writing to a temporary file and renaming it.
+  #[structopt(short, long, conflicts_with_all = &["output", "out-dir"])]
+  in_place: bool,
+
+  /// Keep a copy of each file replaced by `--in-place`, named by appending this to its file name (e.g. `.bak`).
+  #[structopt(long, requires = "in-place")]
+  backup_ext: Option<String>,
+
+  /// Whether files are modules or global scripts, either module or global (or script). By default, .mjs files are modules, .cjs files are scripts, and other files follow the `type` field of the nearest package.json, otherwise being modules only if they have import or export statements.
+  #[structopt(short, long, parse(try_from_str = parse_mode), conflicts_with_all = &["module", "script"])]
+  mode: Option<TopLevelMode>,
+
+  /// Parse files as modules; the same as `--mode module`.
+  #[structopt(long, conflicts_with = "script")]
+  module: bool,
+
+  /// Parse files as global scripts; the same as `--mode global`.
+  #[structopt(long)]
+  script: bool,
+
+  /// Keep the original names of all variables instead of minifying them.
+  #[structopt(long)]
+  no_mangle: bool,
+
+  /// Drop calls to methods of the global `console`, such as `console.log(a)`, including their arguments.
+  #[structopt(long)]
+  drop_console: bool,
+
+  /// Replace a global variable or a property path of one with an expression, such as `DEBUG=false` or `process.env.NODE_ENV="production"`. Can be repeated.
+  #[structopt(long, number_of_values = 1)]
+  define: Vec<Define>,
+
+  /// Keep the original name of this variable, such as one accessed via `eval`. Can be repeated.
+  #[structopt(long, number_of_values = 1)]
+  reserved: Vec<String>,
+
+  /// Keep the original names of variables, functions, and classes matching this regex (e.g. `^on[A-Z]`), such as those looked up by name at runtime. Can be repeated.
+  #[structopt(long, number_of_values = 1)]
+  keep_names: Vec<Regex>,
+
+  /// Where to keep legal comments, either none, eof (the default), or top.
+  #[structopt(long)]
+  legal_comments: Option<LegalComments>,
+
+  /// Generate a source map for each output, either external (the default), inline, or both (e.g. `--source-map=inline`). An external source map is written next to the output file, named after it with `.map` appended, and requires `--output`, `--out-dir`, or `--in-place`. An inline one is put in the output as a data URL. If the input references its own source map, such as from a compiler, the generated one maps through it to the original sources.
+  #[structopt(long, require_equals = true)]
+  source_map: Option<Option<SourceMapKind>>,
+
+  /// Set the `sourceRoot` field of source maps, which tools prepend to the paths of sources.
+  #[structopt(long)]
+  source_map_root: Option<String>,
+
+  /// Include the source code in source maps, so they can be used without the source files.
+  #[structopt(long)]
+  sources_content: bool,
+
+  /// Output formatted code, with indentation, one statement per line, and spaces around operators, instead of minified code. Useful for debugging; names are still mangled.
+  #[structopt(long)]
+  beautify: bool,
+
+  /// Insert line breaks at safe points so that output lines are at most this many bytes long, as some tools struggle with very long lines.
+  #[structopt(long)]
+  max_line_len: Option<usize>,
+
+  /// The oldest ECMAScript version the output must run on, from es5 to es2022 or esnext (the default).
+  #[structopt(long)]
+  target: Option<Target>,
+
+  /// Separate statements with line breaks instead of semicolons wherever automatic semicolon insertion allows.
+  #[structopt(long)]
+  asi: bool,
+
+  /// Start and end the output with `;` where needed so it can be safely concatenated with other scripts.
+  #[structopt(long)]
+  safe_concat: bool,
+
+  /// Assume that reading properties has no side effects (i.e. no getters, proxies, or null objects), so unused property reads can be dropped.
+  #[structopt(long)]
+  assume_plain_objects: bool,
+
+  /// The environment the code runs in, either none (the default), browser, node, or worker. Its globals are assumed to exist and not be reassigned, so reading them can be dropped, and with `--warnings`, other globals are reported.
+  #[structopt(long)]
+  environment: Option<Environment>,
+
+  /// Keep the names of functions and classes, so their `name` property is unchanged.
+  #[structopt(long)]
+  keep_fn_names: bool,
+
+  /// Keep a `//# sourceURL=` comment at the end of the input, which devtools use to name code run with `eval`.
+  #[structopt(long)]
+  keep_source_url: bool,
+
+  /// Keep the hashbang comment at the start of the input (e.g. `#!/usr/bin/env node`), for scripts run directly.
+  #[structopt(long)]
+  keep_hashbang: bool,
+
+  /// Characters to make minified names from, in the order to use them (e.g. `abcdefghijklmnopqrstuvwxyz`). Defaults to ASCII letters, `_`, and `$`, with digits after the first character.
+  #[structopt(long)]
+  name_alphabet: Option<NameAlphabet>,
+
+  /// Use the characters of minified names in order of how often they appear in the code, which can help compression.
+  #[structopt(long)]
+  name_alphabet_by_frequency: bool,
+
+  /// Obfuscate the output to make it harder to reverse engineer, at the cost of size: strings are replaced with calls to a decoder function, property accesses become computed so their names are hidden too, and minified names are skipped randomly instead of used in order.
+  #[structopt(long)]
+  obfuscate: bool,
+
+  /// Seed for the random choices made by `--obfuscate`. The same seed always produces the same output.
+  #[structopt(long, requires = "obfuscate")]
+  obfuscation_seed: Option<u64>,
+
+  /// Text to emit as is at the start of the output, followed by a line break.
+  #[structopt(long)]
+  banner: Option<String>,
+
+  /// Text to emit as is on a new line at the end of the output.
+  #[structopt(long)]
+  footer: Option<String>,
+
+  /// Wrap a global script in an immediately invoked function, passing in these comma-separated globals (which can be empty), so that top-level declarations can be minified.
+  #[structopt(long)]
+  enclose: Option<String>,
+
+  /// Write a JSON map of the original and minified names of all variables to this file. Only available when minifying one file.
+  #[structopt(long, parse(from_os_str))]
+  rename_map: Option<PathBuf>,
+
+  /// Line terminator for line breaks added to the output, either lf (the default) or crlf.
+  #[structopt(long)]
+  newline: Option<Newline>,
+
+  /// Report suspicious but valid code, such as direct eval calls and duplicate object keys.
+  #[structopt(long)]
+  warnings: bool,
+
+  /// Report code that prevents some minification, such as functions using `this` and globals that aren't renamed, so it can be refactored for better compression.
+  #[structopt(long)]
+  bailouts: bool,
+
+  /// Check each output by parsing it again, either none (the default), reparse, or idempotent (which also checks that minifying it again doesn't change it). Failures are bugs in the minifier, so this is for testing it on real code.
+  #[structopt(long)]
+  verify: Option<Verify>,
+
+  /// Report syntax errors and warnings as a JSON array of diagnostics with stable codes, instead of human-readable messages.
+  #[structopt(long)]
+  json_diagnostics: bool,
+
+  /// Report each file as a line of JSON with its input and output sizes in bytes, the time taken in milliseconds, and its diagnostics, instead of human-readable messages.
+  #[structopt(long)]
+  stats_json: bool,
+
+  /// Number of files to minify at the same time, defaulting to one per available CPU.
+  #[structopt(short, long)]
+  jobs: Option<usize>,
+}
+
+// Exits for invalid usage, such as conflicting arguments.
+fn usage_error(message: &str) -> ! {
+  eprintln!("error: {}", message);
+  std::process::exit(2);
+}
+
+// A file to minify and where to write its output.
+struct Job {
+  // `None` for stdin.
+  input: Option<PathBuf>,
+  // Path that stdin is treated as, given with `--filename`.
+  stdin_name: Option<PathBuf>,
+  // `None` for stdout.
+  output: Option<PathBuf>,
+  // `None` if it couldn't be detected, in which case the file is a module only if it has import or export statements.
+  mode: Option<TopLevelMode>,
+}
+
+impl Job {
+  fn path(&self) -> Option<&Path> {
+    self.input.as_deref().or(self.stdin_name.as_deref())
+  }
+
+  fn file_name(&self) -> String {
+    self
+      .path()
+      .map_or("<stdin>".to_string(), |p| p.display().to_string())
+  }
+}
+
+// Fills in settings not given as flags from the configuration file.
+fn apply_config(args: &mut Cli, config: Config) -> Result<(), String> {
+  if args.source_map.is_none() {
+    args.source_map = config.source_map_kind()?.map(Some);
+  };
+  if args.mode.is_none() {
+    args.mode = config
+      .mode
+      .as_deref()
+      .map(parse_mode)
+      .transpose()
+      .map_err(|err| format!("invalid `mode` in configuration: {}", err))?;
+  };
+  args.target = args.target.or(parse_setting("target", &config.target)?);
+  args.newline = args.newline.or(parse_setting("newline", &config.newline)?);
+  args.legal_comments = args
+    .legal_comments
+    .or(parse_setting("legal-comments", &config.legal_comments)?);
+  if args.output.is_none() && !args.in_place {
+    args.out_dir = args.out_dir.take().or(config.out_dir);
+  };
+  args.no_mangle |= config.mangle == Some(false);
+  args.drop_console |= config.drop_console.unwrap_or(false);
+  args.source_map_root = args.source_map_root.take().or(config.source_map_root);
+  args.sources_content |= config.sources_content.unwrap_or(false);
+  args.beautify |= config.beautify.unwrap_or(false);
+  args.asi |= config.asi.unwrap_or(false);
+  args.safe_concat |= config.safe_concat.unwrap_or(false);
+  args.assume_plain_objects |= config.assume_plain_objects.unwrap_or(false);
+  args.environment = args
+    .environment
+    .or(parse_setting("environment", &config.environment)?);
+  args.keep_fn_names |= config.keep_fn_names.unwrap_or(false);
+  args.keep_source_url |= config.keep_source_url.unwrap_or(false);
+  args.keep_hashbang |= config.keep_hashbang.unwrap_or(false);
+  args.name_alphabet = args
+    .name_alphabet
+    .take()
+    .or(parse_setting("name-alphabet", &config.name_alphabet)?);
+  args.name_alphabet_by_frequency |= config.name_alphabet_by_frequency.unwrap_or(false);
+  args.obfuscate |= config.obfuscate.unwrap_or(false);
+  args.obfuscation_seed = args.obfuscation_seed.or(config.obfuscation_seed);
+  args.warnings |= config.warnings.unwrap_or(false);
+  args.bailouts |= config.bailouts.unwrap_or(false);
+  args.verify = args.verify.or(parse_setting("verify", &config.verify)?);
+  args.max_line_len = args.max_line_len.or(config.max_line_len);
+  args.jobs = args.jobs.or(config.jobs);
+  args.banner = args.banner.take().or(config.banner);
+  args.footer = args.footer.take().or(config.footer);
+  args.enclose = args
+    .enclose
+    .take()
+    .or(config.enclose.map(|globals| globals.join(",")));
+  // Defines from flags come first, so they take precedence.
+  for (name, value) in config.define {
+    let define = format!("{}={}", name, value)
+      .parse()
+      .map_err(|err| format!("invalid `define` in configuration: {}", err))?;
+    args.define.push(define);
+  }
+  args.reserved.extend(config.reserved);
+  for pattern in config.keep_names {
+    let pattern = Regex::new(&pattern)
+      .map_err(|err| format!("invalid `keep-names` in configuration: {}", err))?;
+    args.keep_names.push(pattern);
+  }
+  Ok(())
+}
+
+fn main() {
+  let mut args = Cli::from_args();
+  if args.module {
+    args.mode = Some(TopLevelMode::Module);
+  } else if args.script {
+    args.mode = Some(TopLevelMode::Global);
+  };
+  let config_path = args.config.clone().or_else(Config::find);
+  if let Some(path) = config_path {
+    let config = Config::read(&path).unwrap_or_else(|err| usage_error(&err));
+    apply_config(&mut args, config).unwrap_or_else(|err| usage_error(&err));
+  };
+  let jobs = if args.input.is_empty() {
+    if args.out_dir.is_some() {
+      usage_error("--out-dir can't be used with stdin");
+    };
+    if args.in_place {
+      usage_error("--in-place can't be used with stdin");
+    };
+    let mode = args.mode.or_else(|| {
+      let path = args.filename.as_ref()?;
+      ModeDetector::default().detect(path)
+    });
+    vec![Job {
+      input: None,
+      stdin_name: args.filename.clone(),
+      output: args.output.clone(),
+      mode,
+    }]
+  } else {
+    if args.filename.is_some() {
+      usage_error("--filename can only be used with stdin");
+    };
+    let files = expand_inputs(&args.input).unwrap_or_else(|err| usage_error(&err));
+    let mut detector = ModeDetector::default();
+    let mut mode = |path: &Path| args.mode.or_else(|| detector.detect(path));
+    match &args.out_dir {
+      _ if args.in_place => files
+        .into_iter()
+        .map(|InputFile { path, .. }| Job {
+          mode: mode(&path),
+          input: Some(path.clone()),
+          stdin_name: None,
+          output: Some(path),
+        })
+        .collect(),
+      Some(out_dir) => files
+        .into_iter()
+        .map(|InputFile { path, relative }| Job {
+          mode: mode(&path),
+          input: Some(path),
+          stdin_name: None,
+          output: Some(out_dir.join(relative)),
+        })
+        .collect(),
+      None if files.len() > 1 || args.input.iter().any(|i| is_multiple(i)) => {
+        usage_error("--out-dir is required to minify more than one file")
+      }
+      None => {
+        let path = files.into_iter().next().unwrap().path;
+        vec![Job {
+          mode: mode(&path),
+          input: Some(path),
+          stdin_name: None,
+          output: args.output.clone(),
+        }]
+      }
+    }
+  };
+  if args.rename_map.is_some() && (jobs.len() > 1 || args.out_dir.is_some()) {
+    usage_error("--rename-map can only be used when minifying one file");
+  };
+  let source_map = args.source_map.map(Option::unwrap_or_default);
+  if let Some(kind) = source_map {
+    if kind.writes_file() && jobs.iter().any(|j| j.output.is_none()) {
+      usage_error("--source-map requires --output, --out-dir, or --in-place unless it's inline");
+    };
+    // Positions are in the wrapped source, which we don't have.
+    if args.enclose.is_some() {
+      usage_error("--source-map can't be used with --enclose");
+    };
+  };
+
+  let mut cfg = MinifyOptions::new()
+    .beautify(args.beautify)
+    .target(args.target.unwrap_or_default())
+    .asi(args.asi)
+    .safe_concat(args.safe_concat)
+    .assume_plain_objects(args.assume_plain_objects)
+    .environment(args.environment.unwrap_or_default())
+    .keep_fn_names(args.keep_fn_names)
+    .keep_source_url(args.keep_source_url)
+    .keep_hashbang(args.keep_hashbang)
+    .mangle(!args.no_mangle)
+    .drop_console(args.drop_console)
+    .defines(args.define.clone())
+    .keep_names(args.keep_names.clone())
+    .obfuscation(if args.obfuscate {
+      Obfuscation::all(args.obfuscation_seed.unwrap_or(0))
+    } else {
+      Obfuscation::default()
+    })
+    .rename_map(args.rename_map.is_some())
+    .position_map(source_map.is_some())
+    .warnings(args.warnings)
+    .bailouts(args.bailouts)
+    .verify(args.verify.unwrap_or_default())
+    .newline(args.newline.unwrap_or_default())
+    .legal_comments(args.legal_comments.unwrap_or_default());
+  let name_alphabet = args.name_alphabet.clone().unwrap_or_default();
+  cfg = cfg.name_alphabet(if args.name_alphabet_by_frequency {
+    name_alphabet.by_frequency()
+  } else {
+    name_alphabet
+  });
+  cfg.max_line_len = args.max_line_len;
+  cfg.banner = args.banner.clone();
+  cfg.footer = args.footer.clone();
+  if let Some(enclose) = &args.enclose {
+    cfg = cfg.enclose(enclose.split(',').filter(|p| !p.is_empty()));
+  };
+  if !args.no_mangle && !args.reserved.is_empty() {
+    let reserved = args.reserved.clone();
+    cfg = cfg.rename_filter(move |sym| {
+      if reserved.iter().any(|r| r == sym.name) {
+        RenameDecision::Keep
+      } else {
+        RenameDecision::Minify
+      }
+    });
+  };
+
+  let format = if args.stats_json {
+    ReportFormat::StatsJson
+  } else if args.json_diagnostics {
+    ReportFormat::JsonDiagnostics
+  } else {
+    ReportFormat::Human
+  };
+  // Errors are reported per file, so that one bad file doesn't stop the rest from being minified.
+  let next = AtomicUsize::new(0);
+  let failed = AtomicBool::new(false);
+  let work = || {
+    let mut session = Session::new();
+    while let Some(job) = jobs.get(next.fetch_add(1, Ordering::Relaxed)) {
+      session.reset();
+      let started = Instant::now();
+      let mut outcome = Outcome::default();
+      minify_file(&args, &cfg, &session, job, &mut outcome);
+      if !outcome.is_ok() {
+        failed.store(true, Ordering::Relaxed);
+      };
+      let output_name = job.output.as_ref().map(|p| p.display().to_string());
+      eprint!(
+        "{}",
+        outcome.render(
+          format,
+          &job.file_name(),
+          output_name.as_deref(),
+          started.elapsed()
+        )
+      );
+    }
+  };
+  let threads = match args.jobs {
+    Some(0) => usage_error("--jobs must be at least 1"),
+    Some(n) => n,
+    None => thread::available_parallelism().map_or(1, NonZeroUsize::get),
+  }
+  .min(jobs.len());
+  if threads <= 1 {
+    work();
+  } else {
+    thread::scope(|s| {
+      for _ in 0..threads {
+        s.spawn(work);
+      }
+    });
+  };
+  if failed.into_inner() {
+    std::process::exit(1);
+  };
+}
+
+// Minifies a file, recording what happened in `outcome`.
+fn minify_file(
+  args: &Cli,
+  cfg: &MinifyOptions,
+  session: &Session,
+  job: &Job,
+  outcome: &mut Outcome,
+) {
+  let input = match &job.input {
+    Some(p) => fs::read(p).map_err(|err| format!("can't read file: {}", err)),
+    None => {
+      let mut input = Vec::new();
+      stdin()
+        .read_to_end(&mut input)
+        .map(|_| input)
+        .map_err(|err| format!("can't read stdin: {}", err))
+    }
+  };
+  let input = match input {
+    Ok(input) => input,
+    Err(err) => {
+      outcome.error = Some(err);
+      return;
+    }
+  };
+  if let Err(err) = minify_source(args, cfg, session, job, &input, outcome) {
+    outcome.error = Some(err);
+  };
+  outcome.input = Some(input);
+}
+
+// Minifies the source of a file and writes its outputs, returning an error message if it fails. Syntax errors are added to `outcome` instead.
+fn minify_source(
+  args: &Cli,
+  cfg: &MinifyOptions,
+  session: &Session,
+  job: &Job,
+  input: &[u8],
+  outcome: &mut Outcome,
+) -> Result<(), String> {
+  let source_map = args.source_map.map(Option::unwrap_or_default);
+  let map = job
+    .output
+    .as_ref()
+    .filter(|_| source_map.is_some_and(SourceMapKind::writes_file))
+    .map(|output| {
+      let mut path = output.clone().into_os_string();
+      path.push(".map");
+      PathBuf::from(path)
+    });
+  let mode = job.mode.unwrap_or_else(|| {
+    if has_module_syntax(session, input) {
+      TopLevelMode::Module
+    } else {
+      TopLevelMode::Global
+    }
+  });
+  let mut cfg = cfg.clone().top_level_mode(mode);
+  if let (Some(map), Some(SourceMapKind::External)) = (&map, source_map) {
+    cfg = cfg.source_mapping_url(SourceMappingUrl::Rewrite(
+      map.file_name().unwrap().to_string_lossy().into_owned(),
+    ));
+  };
+  let mut output = Vec::new();
+  let mut stats = match minify(session, input, &cfg, &mut output) {
+    Ok(stats) => stats,
+    Err(MinifyError::Syntax(_)) => {
+      // Report every error, not just the first, so they can all be fixed at once.
+      outcome.errors = syntax_errors(session, input, mode)
+        .iter()
+        .map(|err| Diagnostic::from_syntax_error(input, err))
+        .collect();
+      return Ok(());
+    }
+    Err(MinifyError::Internal(diagnostic)) => {
+      outcome.errors = vec![diagnostic];
+      return Ok(());
+    }
+    Err(MinifyError::Unsupported(err)) => {
+      outcome.errors = vec![Diagnostic::from_unsupported_syntax(input, &err)];
+      return Ok(());
+    }
+    Err(err) => return Err(err.to_string()),
+  };
+  outcome.warnings = std::mem::take(&mut stats.warnings);
+  outcome.warnings.append(&mut stats.bailouts);
+  outcome.warnings.sort_by_key(|d| d.start);
+  if let Some(kind) = source_map {
+    // Sources are relative to the source map, which is next to the output.
+    let map_dir = job
+      .output
+      .as_ref()
+      .map_or(Path::new(""), |p| p.parent().unwrap());
+    let input_map = match read_input_map(job.path(), input, map_dir) {
+      Ok(input_map) => input_map,
+      Err(err) => {
+        outcome.notes.push(err);
+        None
+      }
+    };
+    let source_name = match job.path() {
+      Some(p) => relative_path(map_dir, p),
+      None => job.file_name(),
+    };
+    let opts = SourceMapOptions {
+      source_root: args.source_map_root.clone(),
+      sources_content: args.sources_content,
+      input_map,
+    };
+    let json = stats
+      .positions
+      .unwrap()
+      .to_source_map_with(&output, input, &source_name, &opts);
+    if kind.is_inline() {
+      output.extend_from_slice(cfg.newline.as_bytes());
+      output.extend_from_slice(b"//# sourceMappingURL=");
+      output.extend_from_slice(data_url(&json).as_bytes());
+    };
+    if let Some(map) = map {
+      if let Some(dir) = map.parent().filter(|d| !d.as_os_str().is_empty()) {
+        fs::create_dir_all(dir).map_err(|err| format!("can't create output directory: {}", err))?;
+      };
+      fs::write(map, json).map_err(|err| format!("can't write source map: {}", err))?;
+    };
+  };
+  match &job.output {
+    Some(p) if args.in_place => replace_file(p, &output, args.backup_ext.as_deref())?,
+    Some(p) => {
+      if let Some(dir) = p.parent().filter(|d| !d.as_os_str().is_empty()) {
+        fs::create_dir_all(dir).map_err(|err| format!("can't create output directory: {}", err))?;
+      };
+      fs::write(p, &output).map_err(|err| format!("can't write output: {}", err))?;
+    }
+    None => stdout()
+      .lock()
+      .write_all(&output)
+      .map_err(|err| format!("can't write output: {}", err))?,
+  };
+  outcome.output_len = Some(output.len());
+  if let Some(p) = &args.rename_map {
+    fs::write(p, stats.renames.unwrap().to_json())
+      .map_err(|err| format!("can't write rename map: {}", err))?;
+  };
+  Ok(())
+}
+
+// Replaces a file's contents atomically, so that it's never left partially written, optionally keeping a copy of the original.
+fn replace_file(path: &Path, contents: &[u8], backup_ext: Option<&str>) -> Result<(), String> {
+  if let Some(ext) = backup_ext {
+    let mut backup = path.as_os_str().to_os_string();
+    backup.push(ext);
+    fs::copy(path, backup).map_err(|err| format!("can't write backup: {}", err))?;
+  };
+  // The temporary file must be in the same directory, as renaming across file systems isn't atomic.
+  let mut temp_name = OsString::from(".");
+  temp_name.push(path.file_name().unwrap());
+  temp_name.push(format!(".{}.tmp", std::process::id()));
+  let temp = path.with_file_name(temp_name);
+  let result = fs::write(&temp, contents)
+    .and_then(|_| fs::set_permissions(&temp, fs::metadata(path)?.permissions()))
+    .and_then(|_| fs::rename(&temp, path));
+  if let Err(err) = result {
+    let _ = fs::remove_file(&temp);
+    return Err(format!("can't write output: {}", err));
+  };
+  Ok(())
+}
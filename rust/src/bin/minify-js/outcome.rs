@@ -0,0 +1,89 @@
+use minify_js::diagnostics_to_json;
+use minify_js::render_diagnostic;
+use minify_js::Diagnostic;
+use serde::Serialize;
+use std::fmt::Write;
+use std::time::Duration;
+
+fn json<T: Serialize + ?Sized>(value: &T) -> String {
+  serde_json::to_string(value).unwrap()
+}
+
+// How to report the outcome of minifying each file.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+  // Messages with the code they refer to.
+  Human,
+  // Syntax errors and warnings as JSON arrays of diagnostics.
+  JsonDiagnostics,
+  // One JSON object per file with its sizes, timing, and diagnostics.
+  StatsJson,
+}
+
+// What happened when minifying a file. It's reported once the file is done, so that files minified in parallel don't interleave their messages.
+#[derive(Default)]
+pub struct Outcome {
+  // `None` if it couldn't be read.
+  pub input: Option<Vec<u8>>,
+  // `None` if no output was written.
+  pub output_len: Option<usize>,
+  pub errors: Vec<Diagnostic>,
+  pub warnings: Vec<Diagnostic>,
+  // Problems that aren't in the source code, such as an input source map that can't be read.
+  pub notes: Vec<String>,
+  // Why the file couldn't be minified, other than syntax errors.
+  pub error: Option<String>,
+}
+
+impl Outcome {
+  pub fn is_ok(&self) -> bool {
+    self.errors.is_empty() && self.error.is_none()
+  }
+
+  pub fn render(
+    &self,
+    format: ReportFormat,
+    file_name: &str,
+    output_name: Option<&str>,
+    duration: Duration,
+  ) -> String {
+    let mut out = String::new();
+    if format == ReportFormat::StatsJson {
+      writeln!(
+        out,
+        "{{\"file\":{},\"output\":{},\"ok\":{},\"input_len\":{},\"output_len\":{},\"duration_ms\":{},\"errors\":{},\"warnings\":{},\"notes\":{},\"error\":{}}}",
+        json(file_name),
+        json(&output_name),
+        self.is_ok(),
+        json(&self.input.as_ref().map(Vec::len)),
+        json(&self.output_len),
+        duration.as_micros() as f64 / 1000.0,
+        diagnostics_to_json(&self.errors),
+        diagnostics_to_json(&self.warnings),
+        json(&self.notes),
+        json(&self.error),
+      )
+      .unwrap();
+      return out;
+    };
+    let input = self.input.as_deref().unwrap_or_default();
+    for diagnostics in [&self.errors, &self.warnings] {
+      if format == ReportFormat::JsonDiagnostics {
+        if !diagnostics.is_empty() {
+          writeln!(out, "{}", diagnostics_to_json(diagnostics)).unwrap();
+        };
+      } else {
+        for d in diagnostics.iter() {
+          out.push_str(&render_diagnostic(input, file_name, d));
+        }
+      };
+    }
+    for note in self.notes.iter() {
+      writeln!(out, "warning: {}: {}", file_name, note).unwrap();
+    }
+    if let Some(err) = &self.error {
+      writeln!(out, "error: {}: {}", file_name, err).unwrap();
+    };
+    out
+  }
+}
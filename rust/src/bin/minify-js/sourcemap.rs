@@ -0,0 +1,171 @@
+use minify_js::find_source_mapping_url;
+use minify_js::SourceMap;
+use std::fs;
+use std::path::Component;
+use std::path::Path;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+const BASE64: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+// Where to put a generated source map.
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub enum SourceMapKind {
+  // A file next to the output, referenced by a `sourceMappingURL` comment.
+  #[default]
+  External,
+  // A `data:` URL in the `sourceMappingURL` comment.
+  Inline,
+  // A file next to the output, as well as inlined in the output.
+  Both,
+}
+
+impl SourceMapKind {
+  pub fn writes_file(self) -> bool {
+    self != SourceMapKind::Inline
+  }
+
+  pub fn is_inline(self) -> bool {
+    self != SourceMapKind::External
+  }
+}
+
+impl FromStr for SourceMapKind {
+  type Err = &'static str;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "external" => Ok(SourceMapKind::External),
+      "inline" => Ok(SourceMapKind::Inline),
+      "both" => Ok(SourceMapKind::Both),
+      _ => Err("invalid source map kind"),
+    }
+  }
+}
+
+// Returns a `data:` URL for a source map, for a `sourceMappingURL` comment.
+pub fn data_url(json: &str) -> String {
+  let mut url = String::from("data:application/json;charset=utf-8;base64,");
+  for chunk in json.as_bytes().chunks(3) {
+    let n = chunk
+      .iter()
+      .enumerate()
+      .fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - i * 8));
+    for i in 0..4 {
+      if i <= chunk.len() {
+        url.push(BASE64[(n >> (18 - i * 6)) as usize & 0b111111] as char);
+      } else {
+        url.push('=');
+      };
+    }
+  }
+  url
+}
+
+fn decode_base64(s: &str) -> Option<Vec<u8>> {
+  let digits = s
+    .bytes()
+    .filter(|&c| c != b'=')
+    .map(|c| BASE64.iter().position(|&d| d == c))
+    .collect::<Option<Vec<_>>>()?;
+  let mut bytes = Vec::new();
+  for chunk in digits.chunks(4) {
+    let n = chunk
+      .iter()
+      .enumerate()
+      .fold(0u32, |n, (i, &d)| n | (d as u32) << (18 - i * 6));
+    for i in 0..chunk.len().saturating_sub(1) {
+      bytes.push((n >> (16 - i * 8)) as u8);
+    }
+  }
+  Some(bytes)
+}
+
+// Reads the source map referenced by a `sourceMappingURL` comment at the end of the source, such as from a compiler, so the generated source map can map through it. Its sources are made relative to `map_dir`, where the generated source map will be.
+pub fn read_input_map(
+  input: Option<&Path>,
+  source: &[u8],
+  map_dir: &Path,
+) -> Result<Option<SourceMap>, String> {
+  let Some(url) = find_source_mapping_url(source) else {
+    return Ok(None);
+  };
+  let input_dir = input
+    .and_then(|p| p.parent())
+    .unwrap_or(Path::new(""))
+    .to_path_buf();
+  let (json, base) = match url.strip_prefix("data:") {
+    Some(data) => {
+      let json = data
+        .split_once(";base64,")
+        .and_then(|(_, data)| decode_base64(data))
+        .ok_or("unsupported data URL for input source map")?;
+      (json, input_dir)
+    }
+    None if url.contains("://") => {
+      return Err(format!("can't read input source map from URL `{}`", url))
+    }
+    None => {
+      let path = input_dir.join(url);
+      let json = fs::read(&path)
+        .map_err(|err| format!("can't read input source map {}: {}", path.display(), err))?;
+      (json, path.parent().unwrap().to_path_buf())
+    }
+  };
+  let mut map: SourceMap =
+    serde_json::from_slice(&json).map_err(|err| format!("invalid input source map: {}", err))?;
+  map.sources = map
+    .source_urls()
+    .into_iter()
+    .map(|s| {
+      if s.contains("://") {
+        s
+      } else {
+        relative_path(map_dir, &base.join(s))
+      }
+    })
+    .collect();
+  map.source_root = None;
+  Ok(Some(map))
+}
+
+// Makes a path absolute and removes `.` and `..` components, without touching the file system, as the path may not exist yet.
+fn absolute(p: &Path) -> PathBuf {
+  let mut absolute = PathBuf::new();
+  for c in std::env::current_dir()
+    .expect("get current dir")
+    .join(p)
+    .components()
+  {
+    match c {
+      Component::CurDir => {}
+      Component::ParentDir => {
+        absolute.pop();
+      }
+      c => absolute.push(c),
+    };
+  }
+  absolute
+}
+
+// Returns the path of `file` relative to `dir`, with forward slashes as in URLs, falling back to the absolute path of `file`.
+pub fn relative_path(dir: &Path, file: &Path) -> String {
+  let dir = absolute(dir);
+  let file = absolute(file);
+  let common = dir
+    .components()
+    .zip(file.components())
+    .take_while(|(a, b)| a == b)
+    .count();
+  if common == 0 {
+    return file.to_string_lossy().replace('\\', "/");
+  };
+  let mut parts = vec!["..".to_string(); dir.components().count() - common];
+  parts.extend(
+    file
+      .components()
+      .skip(common)
+      .map(|c| c.as_os_str().to_string_lossy().into_owned()),
+  );
+  parts.join("/")
+}
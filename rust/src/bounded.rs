@@ -0,0 +1,210 @@
+use crate::comment::PreservedComments;
+use crate::early_error::StatementEarlyErrors;
+use crate::emit::emit_js;
+use crate::emit::Emitter;
+use crate::minify::KeepTopLevel;
+use crate::minify_parsed;
+use crate::MinifyError;
+use crate::MinifyOptions;
+use crate::MinifyStats;
+use crate::RenameDecision;
+use crate::RenameFilter;
+use crate::WriteError;
+use parse_js::ast::new_node;
+use parse_js::ast::Syntax;
+use parse_js::error::SyntaxError;
+use parse_js::lex::Lexer;
+use parse_js::parse::pattern::ParsePatternRules;
+use parse_js::parse::toplevel::TopLevelMode;
+use parse_js::parse::ParseCtx;
+use parse_js::parse::Parser;
+use parse_js::session::Session;
+use parse_js::source::SourceRange;
+use parse_js::symbol::Scope;
+use parse_js::symbol::ScopeType;
+use parse_js::symbol::SymbolGenerator;
+use parse_js::token::TokenType;
+use std::io::Write;
+
+// Errors from a batch refer to the source with the lifetime of the batch's session, but have positions in the entire source, so they can refer to it instead.
+fn in_source<'a>(source: &'a [u8], err: SyntaxError<'_>) -> MinifyError<'a> {
+  MinifyError::Syntax(SyntaxError::new(
+    err.typ,
+    SourceRange::new(source, err.source.start(), err.source.end()),
+    err.actual_token,
+  ))
+}
+
+/// Minifies very large code, such as a 500 MB bundle, with bounded memory use. Top-level statements are parsed, minified, and written to `output` in batches of about `batch_len` bytes of source code, and each batch's memory is reused for the next, so the entire tree is never held at once. The source code itself must still be in memory, but can be memory-mapped.
+///
+/// As each batch is minified on its own, optimisations across top-level statements are lost: top-level variables in a module keep their names (unless a [MinifyOptions::rename_filter] renames them), top-level function declarations aren't turned into arrow functions, and legal comments are kept next to the batch they're in. A single statement larger than `batch_len` (e.g. a function wrapping all the code) is still one batch. [MinifyOptions::enclose] is ignored, and the stats only have the input and output lengths and renaming savings, as rename maps, position maps, warnings, syntax features, bail-outs, and reports aren't collected.
+///
+/// # Examples
+///
+/// ```
+/// use minify_js::{minify_bounded, MinifyOptions, TopLevelMode};
+///
+/// let code = b"f(); function f() { g() }\nlet first = 1; const second = first + 1;\n(first)";
+/// let cfg = MinifyOptions::new().top_level_mode(TopLevelMode::Module);
+/// let mut out = Vec::new();
+/// minify_bounded(code, &cfg, 16, &mut out).unwrap();
+/// assert_eq!(out, b"function f(){g()}f();let first=1;const second=first+ 1;first");
+/// ```
+pub fn minify_bounded<'a>(
+  source: &'a [u8],
+  cfg: &MinifyOptions,
+  batch_len: usize,
+  output: &mut impl Write,
+) -> Result<MinifyStats, WriteError<'a>> {
+  cfg.limits.check(source).map_err(MinifyError::from)?;
+  cfg.check_cancelled()?;
+  let mut cfg = cfg.clone();
+  cfg.enclose = None;
+  cfg.rename_map = false;
+  cfg.position_map = false;
+  cfg.warnings = false;
+  cfg.syntax_features = false;
+  cfg.bailouts = false;
+  cfg.report = false;
+  // Other batches can refer to top-level variables, so their names must be kept.
+  let user_filter = cfg.rename_filter.take();
+  cfg.rename_filter = Some(RenameFilter::new(move |info| {
+    if let Some(filter) = &user_filter {
+      match (filter.0)(info) {
+        RenameDecision::Minify => {}
+        decision => return decision,
+      };
+    };
+    match info.scope {
+      ScopeType::Global | ScopeType::Module => RenameDecision::Keep,
+      _ => RenameDecision::Minify,
+    }
+  }));
+  let banner = cfg.banner.take();
+  let footer = cfg.footer.take();
+  let source_mapping_url = std::mem::take(&mut cfg.source_mapping_url);
+  let safe_concat = cfg.safe_concat;
+  let scope_type = match cfg.top_level_mode {
+    TopLevelMode::Global => ScopeType::Global,
+    TopLevelMode::Module => ScopeType::Module,
+  };
+
+  let mut stats = MinifyStats {
+    input_len: source.len(),
+    ..MinifyStats::default()
+  };
+  let mut session = Session::new();
+  let mut buf = Vec::new();
+  // Where the next batch starts, including any whitespace and comments before its first statement.
+  let mut start = 0;
+  let mut checkpoint = None;
+  let mut early_errors = StatementEarlyErrors::default();
+  // Whether all statements so far are strings, so a batch's strings at its start are still directives.
+  let mut in_prologue = true;
+  loop {
+    session.reset();
+    let mut parser = Parser::new(Lexer::new(source));
+    if let Some(checkpoint) = checkpoint.take() {
+      parser.restore_checkpoint(checkpoint);
+    };
+    let ctx = ParseCtx {
+      scope: Scope::new(&session, SymbolGenerator::new(), None, scope_type),
+      session: &session,
+      rules: ParsePatternRules {
+        await_allowed: true,
+        yield_allowed: true,
+      },
+    };
+    let mut body = session.new_vec();
+    let mut end = start;
+    let last = loop {
+      let stmt_start = parser.checkpoint();
+      if parser
+        .consume_if(TokenType::EOF)
+        .map_err(|err| in_source(source, err))?
+        .is_match()
+      {
+        break true;
+      };
+      let stmt = parser
+        .parse_stmt(ctx)
+        .map_err(|err| in_source(source, err))?;
+      early_errors
+        .check(stmt)
+        .map_err(|err| in_source(source, err))?;
+      body.push(stmt);
+      end = parser.since_checkpoint(stmt_start).end();
+      if end - start >= batch_len {
+        let next = parser.checkpoint();
+        // Don't start an empty batch for any whitespace and comments at the end.
+        if parser
+          .consume_if(TokenType::EOF)
+          .map_err(|err| in_source(source, err))?
+          .is_match()
+        {
+          break true;
+        };
+        checkpoint = Some(next);
+        break false;
+      };
+    };
+    let end = if last { source.len() } else { end };
+    let after_code = !in_prologue;
+    in_prologue = in_prologue
+      && body.iter().all(|s| {
+        matches!(&s.stx, Syntax::ExpressionStmt { expression } if matches!(expression.stx, Syntax::LiteralStringExpr { .. }))
+      });
+    let chunk = &source[start..end];
+    let parsed = new_node(
+      &session,
+      ctx.scope,
+      SourceRange::new(source, start, end),
+      Syntax::TopLevel { body },
+    );
+
+    cfg.banner = if start == 0 { banner.clone() } else { None };
+    // Batches are already separated from each other.
+    cfg.safe_concat = safe_concat && (start == 0 || last);
+    if last {
+      cfg.footer = footer.clone();
+      cfg.source_mapping_url = source_mapping_url.clone();
+    };
+    let batch_stats =
+      minify_parsed(&session, parsed, &cfg, &mut None, KeepTopLevel {
+        functions: true,
+        after_code,
+        ..KeepTopLevel::default()
+      }).map_err(|err| match err {
+        MinifyError::Syntax(err) => in_source(source, err),
+        MinifyError::LimitExceeded(err) => MinifyError::LimitExceeded(err),
+        MinifyError::Cancelled => MinifyError::Cancelled,
+        MinifyError::Unsupported(err) => MinifyError::Unsupported(err),
+        MinifyError::Internal(err) => MinifyError::Internal(err),
+      })?;
+    stats.renaming_savings += batch_stats.renaming_savings;
+    buf.clear();
+    let comments = PreservedComments::collect(chunk, &cfg);
+    comments.emit_top(&cfg, &mut buf);
+    let code_start = buf.len();
+    emit_js(&mut Emitter::new(&mut buf, &cfg), parsed);
+    // Separate this batch's code from the next, which could otherwise continue it (e.g. if it starts with `(`).
+    if !last && buf.len() > code_start && !buf.ends_with(b";") {
+      buf.push(b';');
+      if cfg.beautify {
+        buf.extend_from_slice(cfg.newline.as_bytes());
+      };
+    };
+    let eof_start = buf.len();
+    comments.emit_eof(&cfg, &mut buf);
+    // Comments kept at the end could be single-line comments.
+    if !last && buf.len() > eof_start && !buf.ends_with(cfg.newline.as_bytes()) {
+      buf.extend_from_slice(cfg.newline.as_bytes());
+    };
+    output.write_all(&buf)?;
+    stats.output_len += buf.len();
+    if last {
+      return Ok(stats);
+    };
+    start = end;
+  }
+}
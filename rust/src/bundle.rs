@@ -0,0 +1,922 @@
+use crate::diagnostic::Diagnostic;
+use crate::early_error::check_early_errors;
+use crate::emit;
+use crate::files::FileProvider;
+use crate::files::SourceFiles;
+use crate::json::write_json_string;
+use crate::metadata::alias_name;
+use crate::metadata::module_metadata;
+use crate::metadata::pattern_names;
+use crate::minify;
+use crate::MinifyOptions;
+use crate::MinifyStats;
+use parse_js::ast::new_node;
+use parse_js::ast::ClassOrObjectMemberKey;
+use parse_js::ast::ClassOrObjectMemberValue;
+use parse_js::ast::ExportNames;
+use parse_js::ast::NodeData;
+use parse_js::ast::ObjectMemberType;
+use parse_js::ast::Syntax;
+use parse_js::ast::VarDeclMode;
+use parse_js::ast::VariableDeclarator;
+use parse_js::parse;
+use parse_js::parse::toplevel::TopLevelMode;
+use parse_js::session::Session;
+use parse_js::source::SourceRange;
+use parse_js::symbol::Scope;
+use parse_js::symbol::ScopeType;
+use parse_js::visit::JourneyControls;
+use parse_js::visit::Visitor;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Write;
+
+// Local name of an anonymous default export. It can't clash with a variable, as it isn't an identifier.
+const DEFAULT_LOCAL: &str = "*default*";
+
+/// Finds the modules for [bundle], which are then read with [FileProvider::read] using their IDs as names.
+pub trait ModuleResolver: FileProvider {
+  /// Returns the ID of the module that `specifier` refers to when imported by the module with ID `importer`, such as a normalised path. Only relative specifiers (starting with `./` or `../`) are resolved; imports of other modules are kept in the output.
+  fn resolve(&self, specifier: &str, importer: &str) -> Result<String, String>;
+}
+
+/// Error returned when bundling.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BundleError {
+  /// [ModuleResolver::resolve] failed.
+  Resolve {
+    importer: String,
+    specifier: String,
+    message: String,
+  },
+  /// [FileProvider::read] failed.
+  Load { module: String, message: String },
+  /// A module couldn't be parsed, or exceeds the [MinifyOptions::limits].
+  Syntax {
+    module: String,
+    diagnostic: Diagnostic,
+  },
+  /// A module imports or re-exports a name that the imported module doesn't export.
+  MissingExport {
+    importer: String,
+    module: String,
+    name: String,
+  },
+  /// A module imports or re-exports a name that the imported module gets from more than one `export *`, with different values.
+  AmbiguousExport {
+    importer: String,
+    module: String,
+    name: String,
+  },
+  /// A module uses CommonJS `require` calls or exports, which would break if it were hoisted into the bundle's scope.
+  CommonJs { module: String },
+  /// The bundled code couldn't be minified, such as because minification was cancelled.
+  Minify(Diagnostic),
+}
+
+impl Display for BundleError {
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    match self {
+      BundleError::Resolve {
+        importer,
+        specifier,
+        message,
+      } => write!(
+        f,
+        "can't resolve `{}` from `{}`: {}",
+        specifier, importer, message
+      ),
+      BundleError::Load { module, message } => {
+        write!(f, "can't load `{}`: {}", module, message)
+      }
+      BundleError::Syntax { module, diagnostic } => write!(f, "{}: {}", module, diagnostic),
+      BundleError::MissingExport {
+        importer,
+        module,
+        name,
+      } => write!(
+        f,
+        "`{}` imports `{}` from `{}`, which doesn't export it",
+        importer, name, module
+      ),
+      BundleError::AmbiguousExport {
+        importer,
+        module,
+        name,
+      } => write!(
+        f,
+        "`{}` imports `{}` from `{}`, which exports it from more than one `export *`",
+        importer, name, module
+      ),
+      BundleError::CommonJs { module } => write!(
+        f,
+        "`{}` is a CommonJS module, which can't be bundled",
+        module
+      ),
+      BundleError::Minify(diagnostic) => Display::fmt(diagnostic, f),
+    }
+  }
+}
+
+impl Error for BundleError {}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+enum Source {
+  // Index of a bundled module.
+  Internal(usize),
+  // Specifier of a module that's kept as an import.
+  External(String),
+}
+
+// Where the value of an imported or exported name comes from.
+#[derive(PartialEq, Eq)]
+enum Binding {
+  // A top-level variable of a bundled module.
+  Local(usize, String),
+  // The namespace object of a bundled module.
+  Namespace(usize),
+  // A name imported from an external module, which may be `*` or `default`.
+  External(String, String),
+}
+
+// An exported name that more than one `export *` provides with different bindings, which can't be imported by name, and is left out of namespace objects.
+struct Ambiguous;
+
+#[derive(Default)]
+struct Module<'a> {
+  id: String,
+  top: Option<&'a mut NodeData<'a>>,
+  // Source and imported name (`*`, `default`, or a name) of each import, by local name.
+  imports: HashMap<String, (Source, String)>,
+  // Local variable of each exported name, in source order.
+  exports: Vec<(String, String)>,
+  // Source and imported name of each name re-exported with `export ... from`.
+  reexports: Vec<(String, Source, String)>,
+  // Sources of `export * from`.
+  stars: Vec<Source>,
+  // Top-level declarations other than imports, in declaration order.
+  declared: Vec<String>,
+  // Every identifier in the module, including references to globals.
+  identifiers: HashSet<String>,
+}
+
+// Records the statements that import and export across modules, and removes them or turns them into plain declarations.
+struct ModuleSyntax {
+  imports: Vec<(String, String, String)>,
+  exports: Vec<(String, String)>,
+  reexports: Vec<(String, String, String)>,
+  stars: Vec<String>,
+  // Every specifier imported or re-exported from, in source order.
+  specifiers: Vec<String>,
+  has_default_local: bool,
+}
+
+fn ident<'a>(session: &'a Session, name: &str) -> SourceRange<'a> {
+  SourceRange::from_slice(session.get_allocator().alloc_slice_copy(name.as_bytes()))
+}
+
+fn class_or_function_name<'a>(
+  session: &'a Session,
+  scope: Scope<'a>,
+  loc: SourceRange<'a>,
+) -> &'a mut NodeData<'a> {
+  new_node(
+    session,
+    scope,
+    loc,
+    Syntax::ClassOrFunctionName {
+      name: ident(session, DEFAULT_LOCAL),
+    },
+  )
+}
+
+fn take_module_syntax<'a>(session: &'a Session, top: &mut NodeData<'a>) -> ModuleSyntax {
+  let mut out = ModuleSyntax {
+    imports: Vec::new(),
+    exports: Vec::new(),
+    reexports: Vec::new(),
+    stars: Vec::new(),
+    specifiers: Vec::new(),
+    has_default_local: false,
+  };
+  let Syntax::TopLevel { body } = &mut top.stx else {
+    unreachable!();
+  };
+  for stmt in body.iter_mut() {
+    let (scope, loc) = (stmt.scope, stmt.loc);
+    match &mut stmt.stx {
+      Syntax::ImportStmt {
+        default,
+        names,
+        module,
+      } => {
+        out.specifiers.push(module.to_string());
+        if let Some(default) = default {
+          out.imports.push((
+            alias_name(default),
+            module.to_string(),
+            "default".to_string(),
+          ));
+        };
+        match names {
+          Some(ExportNames::All(Some(alias))) => {
+            out
+              .imports
+              .push((alias_name(alias), module.to_string(), "*".to_string()));
+          }
+          Some(ExportNames::Specific(names)) => {
+            for n in names.iter() {
+              out.imports.push((
+                alias_name(n.alias),
+                module.to_string(),
+                n.target.as_str().to_string(),
+              ));
+            }
+          }
+          _ => {}
+        };
+        stmt.stx = Syntax::EmptyStmt {};
+      }
+      Syntax::ExportListStmt { names, from } => {
+        let from = *from;
+        match (names, from) {
+          (ExportNames::All(None), Some(from)) => out.stars.push(from.to_string()),
+          (ExportNames::All(Some(alias)), Some(from)) => {
+            out
+              .reexports
+              .push((alias_name(alias), from.to_string(), "*".to_string()));
+          }
+          (ExportNames::Specific(names), Some(from)) => {
+            for n in names.iter() {
+              out.reexports.push((
+                alias_name(n.alias),
+                from.to_string(),
+                n.target.as_str().to_string(),
+              ));
+            }
+          }
+          (ExportNames::Specific(names), None) => {
+            for n in names.iter() {
+              out
+                .exports
+                .push((alias_name(n.alias), n.target.as_str().to_string()));
+            }
+          }
+          (ExportNames::All(_), None) => unreachable!(),
+        };
+        if let Some(from) = from {
+          out.specifiers.push(from.to_string());
+        };
+        stmt.stx = Syntax::EmptyStmt {};
+      }
+      Syntax::ExportDefaultExprStmt { expression } => {
+        out
+          .exports
+          .push(("default".to_string(), DEFAULT_LOCAL.to_string()));
+        out.has_default_local = true;
+        let mut declarators = session.new_vec();
+        declarators.push(VariableDeclarator {
+          pattern: new_node(
+            session,
+            scope,
+            loc,
+            Syntax::IdentifierPattern {
+              name: ident(session, DEFAULT_LOCAL),
+            },
+          ),
+          initializer: Some(expression.take(session)),
+        });
+        stmt.stx = Syntax::VarDecl {
+          export: false,
+          mode: VarDeclMode::Const,
+          declarators,
+        };
+      }
+      Syntax::ClassDecl {
+        export,
+        export_default,
+        name,
+        ..
+      }
+      | Syntax::FunctionDecl {
+        export,
+        export_default,
+        name,
+        ..
+      } => {
+        if name.is_none() {
+          *name = Some(class_or_function_name(session, scope, loc));
+          out.has_default_local = true;
+        };
+        let local = match &name.as_ref().unwrap().stx {
+          Syntax::ClassOrFunctionName { name } => name.as_str().to_string(),
+          _ => unreachable!(),
+        };
+        if *export_default {
+          out.exports.push(("default".to_string(), local));
+        } else if *export {
+          out.exports.push((local.clone(), local));
+        };
+        *export = false;
+        *export_default = false;
+      }
+      Syntax::VarDecl {
+        export,
+        declarators,
+        ..
+      } if *export => {
+        let mut names = Vec::new();
+        for d in declarators.iter() {
+          pattern_names(d.pattern, &mut names);
+        }
+        out
+          .exports
+          .extend(names.into_iter().map(|n| (n.clone(), n)));
+        *export = false;
+      }
+      _ => {}
+    };
+  }
+  out
+}
+
+struct CollectIdentifiers<'m> {
+  out: &'m mut HashSet<String>,
+}
+
+impl<'a, 'm> Visitor<'a> for CollectIdentifiers<'m> {
+  fn on_syntax_down(&mut self, node: &mut NodeData<'a>, _ctl: &mut JourneyControls) {
+    match &node.stx {
+      Syntax::IdentifierExpr { name }
+      | Syntax::IdentifierPattern { name }
+      | Syntax::ClassOrFunctionName { name } => {
+        self.out.insert(name.as_str().to_string());
+      }
+      Syntax::ObjectMember {
+        typ: ObjectMemberType::Shorthand { identifier },
+      } => {
+        if let Syntax::IdentifierExpr { name } = &identifier.stx {
+          self.out.insert(name.as_str().to_string());
+        };
+      }
+      _ => {}
+    };
+  }
+}
+
+// Renames references to top-level variables and imports to their names in the bundle.
+struct RenameTopLevel<'a, 'n> {
+  session: &'a Session,
+  names: &'n HashMap<String, String>,
+}
+
+impl<'a, 'n> RenameTopLevel<'a, 'n> {
+  fn renamed(&self, scope: Scope<'a>, name: SourceRange<'a>) -> Option<SourceRange<'a>> {
+    if name.as_str() != DEFAULT_LOCAL {
+      let (scope, _) = scope.find_symbol_with_scope(name)?;
+      if scope.typ() != ScopeType::Module {
+        return None;
+      };
+    };
+    let new = self.names.get(name.as_str())?;
+    (new != name.as_str()).then(|| ident(self.session, new))
+  }
+}
+
+impl<'a, 'n> Visitor<'a> for RenameTopLevel<'a, 'n> {
+  fn on_syntax_down(&mut self, node: &mut NodeData<'a>, _ctl: &mut JourneyControls) {
+    let scope = node.scope;
+    match &mut node.stx {
+      Syntax::IdentifierExpr { name }
+      | Syntax::IdentifierPattern { name }
+      | Syntax::ClassOrFunctionName { name } => {
+        if let Some(new) = self.renamed(scope, *name) {
+          *name = new;
+        };
+      }
+      // The property name must stay the same, so these are expanded to `a: b`. The identifier is then visited as the value.
+      Syntax::ObjectMember { typ } => {
+        if let ObjectMemberType::Shorthand { identifier } = typ {
+          let Syntax::IdentifierExpr { name } = identifier.stx else {
+            unreachable!();
+          };
+          if self.renamed(identifier.scope, name).is_some() {
+            *typ = ObjectMemberType::Valued {
+              key: ClassOrObjectMemberKey::Direct(name),
+              value: ClassOrObjectMemberValue::Property {
+                initializer: Some(identifier.take(self.session)),
+              },
+            };
+          };
+        };
+      }
+      Syntax::ObjectPatternProperty {
+        target, shorthand, ..
+      } => {
+        if let Syntax::IdentifierPattern { name } = target.stx {
+          if *shorthand && self.renamed(target.scope, name).is_some() {
+            *shorthand = false;
+          };
+        };
+      }
+      _ => {}
+    };
+  }
+}
+
+struct Bundler<'a, 'r> {
+  session: &'a Session,
+  resolver: &'r dyn ModuleResolver,
+  cfg: &'r MinifyOptions,
+  // Sources of the modules, which have the same indices as `modules`.
+  files: SourceFiles<'a>,
+  modules: Vec<Module<'a>>,
+  // Modules in the order they're evaluated, with dependencies before the modules that import them.
+  order: Vec<usize>,
+  // Names taken by top-level variables in the bundle.
+  claimed: HashSet<String>,
+  // Names of the variables in the bundle for each module's top-level variables.
+  finals: Vec<HashMap<String, String>>,
+  // Variables holding the namespace objects of modules, in the order they're needed.
+  namespaces: Vec<(usize, String)>,
+  // Imports from external modules, in the order they're needed, with the variable each is bound to.
+  external_imports: Vec<(String, String, String)>,
+}
+
+fn is_relative(specifier: &str) -> bool {
+  specifier.starts_with("./") || specifier.starts_with("../")
+}
+
+impl<'a, 'r> Bundler<'a, 'r> {
+  // Loads a module and, recursively, the modules it imports, and returns its index.
+  fn load(&mut self, id: &str) -> Result<usize, BundleError> {
+    if let Some(i) = self.files.id(id) {
+      return Ok(i.0);
+    };
+    let file = self
+      .files
+      .load(self.resolver, id)
+      .map_err(|message| BundleError::Load {
+        module: id.to_string(),
+        message,
+      })?;
+    let source = self.files.code(file);
+    let syntax_error = |diagnostic| BundleError::Syntax {
+      module: id.to_string(),
+      diagnostic,
+    };
+    self
+      .cfg
+      .limits
+      .check(source)
+      .map_err(|err| syntax_error(Diagnostic::from_limit_exceeded(source, &err)))?;
+    let top = parse(self.session, source, TopLevelMode::Module)
+      .and_then(|top| {
+        check_early_errors(top)?;
+        Ok(top)
+      })
+      .map_err(|err| syntax_error(Diagnostic::from_syntax_error(source, &err)))?;
+    // CommonJS modules are boundaries that can't be hoisted, as `require`, `module`, and `exports` are provided by a wrapper function around each one.
+    let metadata = module_metadata(top);
+    if !metadata.requires.is_empty()
+      || !metadata.commonjs_exports.is_empty()
+      || metadata.assigns_module_exports
+    {
+      return Err(BundleError::CommonJs {
+        module: id.to_string(),
+      });
+    };
+    let mut identifiers = HashSet::new();
+    CollectIdentifiers {
+      out: &mut identifiers,
+    }
+    .visit(top);
+    let syntax = take_module_syntax(self.session, top);
+    let i = file.0;
+    self.modules.push(Module {
+      id: id.to_string(),
+      identifiers,
+      ..Module::default()
+    });
+
+    // Modules imported in a cycle are already being loaded, so they come before this one in the order.
+    let mut sources = HashMap::new();
+    for specifier in syntax.specifiers.iter() {
+      if sources.contains_key(specifier) {
+        continue;
+      };
+      let source = if is_relative(specifier) {
+        let dep_id =
+          self
+            .resolver
+            .resolve(specifier, id)
+            .map_err(|message| BundleError::Resolve {
+              importer: id.to_string(),
+              specifier: specifier.clone(),
+              message,
+            })?;
+        Source::Internal(self.load(&dep_id)?)
+      } else {
+        Source::External(specifier.clone())
+      };
+      sources.insert(specifier.clone(), source);
+    }
+
+    let import_locals = syntax
+      .imports
+      .iter()
+      .map(|(local, ..)| local.as_str())
+      .collect::<HashSet<_>>();
+    let mut declared = top
+      .scope
+      .symbol_names()
+      .iter()
+      .map(|n| n.as_str().to_string())
+      .filter(|n| !import_locals.contains(n.as_str()))
+      .collect::<Vec<_>>();
+    if syntax.has_default_local {
+      declared.push(DEFAULT_LOCAL.to_string());
+    };
+    let module = &mut self.modules[i];
+    module.top = Some(top);
+    module.declared = declared;
+    module.imports = syntax
+      .imports
+      .into_iter()
+      .map(|(local, specifier, imported)| (local, (sources[&specifier].clone(), imported)))
+      .collect();
+    module.exports = syntax.exports;
+    module.reexports = syntax
+      .reexports
+      .into_iter()
+      .map(|(name, specifier, imported)| (name, sources[&specifier].clone(), imported))
+      .collect();
+    module.stars = syntax.stars.iter().map(|s| sources[s].clone()).collect();
+    self.order.push(i);
+    Ok(i)
+  }
+
+  fn resolve_export(
+    &self,
+    m: usize,
+    name: &str,
+    seen: &mut HashSet<(usize, String)>,
+  ) -> Result<Option<Binding>, Ambiguous> {
+    if !seen.insert((m, name.to_string())) {
+      return Ok(None);
+    };
+    let module = &self.modules[m];
+    if let Some((_, local)) = module.exports.iter().find(|(n, _)| n == name) {
+      return match module.imports.get(local) {
+        Some((source, imported)) => self.resolve_import(source, imported, seen),
+        None => Ok(Some(Binding::Local(m, local.clone()))),
+      };
+    };
+    if let Some((_, source, imported)) = module.reexports.iter().find(|(n, ..)| n == name) {
+      return self.resolve_import(source, imported, seen);
+    };
+    if name == "default" {
+      return Ok(None);
+    };
+    // Names from external modules can't be known, so they're assumed to come from the first one, unless a bundled module exports them.
+    let mut found = None;
+    let mut external = None;
+    for source in module.stars.iter() {
+      match source {
+        Source::Internal(j) => {
+          if let Some(binding) = self.resolve_export(*j, name, seen)? {
+            if found.as_ref().is_some_and(|f| *f != binding) {
+              return Err(Ambiguous);
+            };
+            found = Some(binding);
+          };
+        }
+        Source::External(s) => {
+          external.get_or_insert_with(|| Binding::External(s.clone(), name.to_string()));
+        }
+      };
+    }
+    Ok(found.or(external))
+  }
+
+  fn resolve_import(
+    &self,
+    source: &Source,
+    imported: &str,
+    seen: &mut HashSet<(usize, String)>,
+  ) -> Result<Option<Binding>, Ambiguous> {
+    match source {
+      Source::Internal(j) if imported == "*" => Ok(Some(Binding::Namespace(*j))),
+      Source::Internal(j) => self.resolve_export(*j, imported, seen),
+      Source::External(s) => Ok(Some(Binding::External(s.clone(), imported.to_string()))),
+    }
+  }
+
+  // Names exported by a module, including through `export *` from other bundled modules.
+  fn export_names(&self, m: usize, seen: &mut HashSet<usize>, out: &mut Vec<String>) {
+    if !seen.insert(m) {
+      return;
+    };
+    let module = &self.modules[m];
+    let names = module
+      .exports
+      .iter()
+      .map(|(n, _)| n)
+      .chain(module.reexports.iter().map(|(n, ..)| n));
+    for n in names {
+      if !out.contains(n) {
+        out.push(n.clone());
+      };
+    }
+    for source in module.stars.iter() {
+      if let Source::Internal(j) = source {
+        let mut names = Vec::new();
+        self.export_names(*j, seen, &mut names);
+        out.extend(
+          names
+            .into_iter()
+            .filter(|n| n != "default")
+            .filter(|n| !out.contains(n))
+            .collect::<Vec<_>>(),
+        );
+      };
+    }
+  }
+
+  // Claims a name for a top-level variable in the bundle. The name is kept if no other module uses it, so it can't clash with or be shadowed by anything else.
+  fn claim(&mut self, m: Option<usize>, base: &str) -> String {
+    let used_elsewhere = |name: &str| {
+      name == "Object"
+        || self
+          .modules
+          .iter()
+          .enumerate()
+          .any(|(i, module)| Some(i) != m && module.identifiers.contains(name))
+    };
+    let name = if base != DEFAULT_LOCAL && !self.claimed.contains(base) && !used_elsewhere(base) {
+      base.to_string()
+    } else {
+      let base = if base == DEFAULT_LOCAL {
+        "_default"
+      } else {
+        base
+      };
+      (1..)
+        .map(|k| format!("{}${}", base, k))
+        .find(|n| {
+          !self.claimed.contains(n) && !self.modules.iter().any(|m| m.identifiers.contains(n))
+        })
+        .unwrap()
+    };
+    self.claimed.insert(name.clone());
+    name
+  }
+
+  fn binding_name(&mut self, binding: Binding, base: &str) -> String {
+    match binding {
+      // An exported variable that isn't declared is a global.
+      Binding::Local(j, local) => self.finals[j].get(&local).cloned().unwrap_or(local),
+      Binding::Namespace(j) => match self.namespaces.iter().find(|(k, _)| *k == j) {
+        Some((_, name)) => name.clone(),
+        None => {
+          let name = self.claim(None, base);
+          self.namespaces.push((j, name.clone()));
+          name
+        }
+      },
+      Binding::External(specifier, imported) => {
+        match self
+          .external_imports
+          .iter()
+          .find(|(s, i, _)| *s == specifier && *i == imported)
+        {
+          Some((.., name)) => name.clone(),
+          None => {
+            let name = self.claim(None, base);
+            self
+              .external_imports
+              .push((specifier, imported, name.clone()));
+            name
+          }
+        }
+      }
+    }
+  }
+
+  fn resolve_binding(
+    &self,
+    importer: usize,
+    source: &Source,
+    imported: &str,
+  ) -> Result<Binding, BundleError> {
+    let importer = self.modules[importer].id.clone();
+    let module = match source {
+      Source::Internal(j) => self.modules[*j].id.clone(),
+      Source::External(s) => s.clone(),
+    };
+    let name = imported.to_string();
+    match self.resolve_import(source, imported, &mut HashSet::new()) {
+      Ok(Some(binding)) => Ok(binding),
+      Ok(None) => Err(BundleError::MissingExport {
+        importer,
+        module,
+        name,
+      }),
+      Err(Ambiguous) => Err(BundleError::AmbiguousExport {
+        importer,
+        module,
+        name,
+      }),
+    }
+  }
+
+  // Resolves a name in the exports of a module, which are listed by `export_names`. Ambiguous names aren't exported, so they're skipped.
+  fn resolve_exported(&self, m: usize, name: &str) -> Result<Option<Binding>, BundleError> {
+    match self.resolve_export(m, name, &mut HashSet::new()) {
+      Ok(Some(binding)) => Ok(Some(binding)),
+      Ok(None) => Err(BundleError::MissingExport {
+        importer: self.modules[m].id.clone(),
+        module: self.modules[m].id.clone(),
+        name: name.to_string(),
+      }),
+      Err(Ambiguous) => Ok(None),
+    }
+  }
+
+  // Renames every module's top-level variables and imports to their names in the bundle.
+  fn rename(&mut self) -> Result<(), BundleError> {
+    self.finals = vec![HashMap::new(); self.modules.len()];
+    for i in self.order.clone() {
+      for name in self.modules[i].declared.clone() {
+        let new = self.claim(Some(i), &name);
+        self.finals[i].insert(name, new);
+      }
+    }
+    for i in self.order.clone() {
+      let mut names = self.finals[i].clone();
+      let mut imports = self.modules[i]
+        .imports
+        .iter()
+        .map(|(local, (source, imported))| (local.clone(), source.clone(), imported.clone()))
+        .collect::<Vec<_>>();
+      // Sort so that names are claimed in a deterministic order.
+      imports.sort_by(|a, b| a.0.cmp(&b.0));
+      for (local, source, imported) in imports {
+        let binding = self.resolve_binding(i, &source, &imported)?;
+        names.insert(local.clone(), self.binding_name(binding, &local));
+      }
+      for (_, source, imported) in self.modules[i].reexports.clone() {
+        self.resolve_binding(i, &source, &imported)?;
+      }
+      let session = self.session;
+      let top = self.modules[i].top.as_mut().unwrap();
+      RenameTopLevel {
+        session,
+        names: &names,
+      }
+      .visit(top);
+    }
+    Ok(())
+  }
+
+  fn write_namespace(&mut self, j: usize, name: &str, out: &mut String) -> Result<(), BundleError> {
+    let mut names = Vec::new();
+    self.export_names(j, &mut HashSet::new(), &mut names);
+    // Getters are defined with `Object.defineProperties` so the members stay live bindings.
+    write!(
+      out,
+      "const {}=Object.freeze(Object.defineProperties(Object.create(null),{{",
+      name
+    )
+    .unwrap();
+    let mut first = true;
+    for n in names.iter() {
+      let Some(binding) = self.resolve_exported(j, n)? else {
+        continue;
+      };
+      let value = self.binding_name(binding, n);
+      if !first {
+        out.push(',');
+      };
+      first = false;
+      write_json_string(out, n);
+      write!(out, ":{{get:()=>{},enumerable:true}}", value).unwrap();
+    }
+    out.push_str("}));\n");
+    Ok(())
+  }
+
+  // Joins the modules into the code of a single module, with the same exports as the entry module.
+  fn join(&mut self) -> Result<Vec<u8>, BundleError> {
+    let mut exports = String::new();
+    let mut names = Vec::new();
+    self.export_names(0, &mut HashSet::new(), &mut names);
+    for n in names.iter() {
+      let Some(binding) = self.resolve_exported(0, n)? else {
+        continue;
+      };
+      let local = self.binding_name(binding, n);
+      writeln!(exports, "export{{{} as {}}};", local, n).unwrap();
+    }
+    for source in self.modules[0].stars.iter() {
+      if let Source::External(s) = source {
+        exports.push_str("export*from");
+        write_json_string(&mut exports, s);
+        exports.push_str(";\n");
+      };
+    }
+    // Namespace objects can refer to other namespace objects and external imports, which are added as they're found.
+    let mut namespaces = String::new();
+    let mut written = 0;
+    while written < self.namespaces.len() {
+      let (j, name) = self.namespaces[written].clone();
+      self.write_namespace(j, &name, &mut namespaces)?;
+      written += 1;
+    }
+
+    let mut code = String::new();
+    for (specifier, imported, local) in self.external_imports.iter() {
+      match imported.as_str() {
+        "*" => write!(code, "import*as {} from", local).unwrap(),
+        imported => write!(code, "import{{{} as {}}}from", imported, local).unwrap(),
+      };
+      write_json_string(&mut code, specifier);
+      code.push_str(";\n");
+    }
+    code.push_str(&namespaces);
+    let mut out = code.into_bytes();
+    for &i in self.order.iter() {
+      emit(self.modules[i].top.take().unwrap(), &mut out);
+      out.extend_from_slice(b";\n");
+    }
+    out.extend_from_slice(exports.as_bytes());
+    Ok(out)
+  }
+}
+
+/// Bundles an ES module and the modules it imports into a single minified module, with the same exports as `entry`. This is opt-in with the `bundle` feature.
+///
+/// Relative imports (starting with `./` or `../`) are resolved and loaded with `resolver`, and their modules are concatenated in the order they'd be evaluated, with imports replaced by direct references to the exported variables (scope hoisting). Top-level variables with the same name in different modules are renamed apart before minifying, and imports of other modules (e.g. `"react"`) are moved to the top of the output. Dynamic `import()`, and `export *` from modules that aren't bundled, are kept as is.
+///
+/// Modules that import each other are supported, but as with other bundlers, their top-level code runs in a single order, so a module may see variables of a module it imports before they're initialised.
+///
+/// # Examples
+///
+/// ```
+/// use minify_js::{bundle, FileProvider, MinifyOptions, ModuleResolver, Session};
+/// use std::collections::HashMap;
+///
+/// struct Files(HashMap<String, &'static str>);
+///
+/// impl FileProvider for Files {
+///   fn read(&self, name: &str) -> Result<Vec<u8>, String> {
+///     self.0.read(name)
+///   }
+/// }
+///
+/// impl ModuleResolver for Files {
+///   fn resolve(&self, specifier: &str, _importer: &str) -> Result<String, String> {
+///     Ok(specifier.trim_start_matches("./").to_string())
+///   }
+/// }
+///
+/// let files = Files(HashMap::from([
+///   ("main.js".to_string(), "import { greet } from './greet.js'; export const main = () => greet('world');"),
+///   ("greet.js".to_string(), "export function greet(name) { return `Hello ${name}`; }"),
+/// ]));
+/// let session = Session::new();
+/// let mut out = Vec::new();
+/// bundle(&session, "main.js", &files, &MinifyOptions::new(), &mut out).unwrap();
+/// assert_eq!(out, b"var a=(a=>`Hello ${a}`);const b=()=>a(`world`);export{b as main}");
+/// ```
+pub fn bundle(
+  session: &Session,
+  entry: &str,
+  resolver: &dyn ModuleResolver,
+  cfg: &MinifyOptions,
+  output: &mut Vec<u8>,
+) -> Result<MinifyStats, BundleError> {
+  let mut bundler = Bundler {
+    session,
+    resolver,
+    cfg,
+    files: SourceFiles::new(session),
+    modules: Vec::new(),
+    order: Vec::new(),
+    claimed: HashSet::new(),
+    finals: Vec::new(),
+    namespaces: Vec::new(),
+    external_imports: Vec::new(),
+  };
+  bundler.load(entry)?;
+  bundler.rename()?;
+  let code = bundler.join()?;
+  let code = session.get_allocator().alloc_slice_copy(&code);
+  let cfg = cfg.clone().top_level_mode(TopLevelMode::Module);
+  minify(session, code, &cfg, output)
+    .map_err(|err| BundleError::Minify(Diagnostic::from_minify_error(code, &err)))
+}
@@ -0,0 +1,167 @@
+use crate::minify;
+use crate::MinifyError;
+use crate::MinifyOptions;
+use crate::MinifyStats;
+use parse_js::session::Session;
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+// FNV-1a, which is stable across platforms and compiler versions (unlike `DefaultHasher`), so keys can be used for files on disk. 128 bits make collisions negligible.
+fn fnv1a(hash: &mut u128, bytes: &[u8]) {
+  for &b in bytes {
+    *hash ^= b as u128;
+    *hash = hash.wrapping_mul(0x0000000001000000000000000000013B);
+  }
+}
+
+#[derive(Clone, Debug)]
+struct CacheEntry {
+  output: Vec<u8>,
+  renaming_savings: isize,
+}
+
+/// Cache of minified outputs, keyed by a hash of the source code and options, so that unchanged modules aren't minified again, such as on warm rebuilds in a bundler or dev server. Entries are kept in memory, and also in a directory if created with [MinifyCache::with_dir], so they last across processes.
+///
+/// Custom filters and transforms (e.g. [MinifyOptions::before_minify]) can't be hashed, so aren't part of the key; use a different cache (or directory) if they change. Options that add to the stats (rename maps, position maps, warnings, syntax features, bail-outs, and reports) can't be replayed from a cached output, so code minified with them is never cached. Whole modules are cached rather than each top-level function, as the output of a function depends on the names declared and used around it.
+///
+/// # Examples
+///
+/// ```
+/// use minify_js::{MinifyCache, MinifyOptions, Session};
+///
+/// let mut cache = MinifyCache::new();
+/// let session = Session::new();
+/// let cfg = MinifyOptions::new();
+/// for _ in 0..2 {
+///   let mut out = Vec::new();
+///   let stats = cache.minify(&session, b"let x = 1; f(x);", &cfg, &mut out).unwrap();
+///   assert_eq!(out, b"let x=1;f(x)");
+///   assert_eq!(stats.output_len, 12);
+/// }
+/// assert_eq!((cache.hits(), cache.misses()), (1, 1));
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct MinifyCache {
+  entries: HashMap<u128, CacheEntry>,
+  dir: Option<PathBuf>,
+  hits: usize,
+  misses: usize,
+}
+
+impl MinifyCache {
+  /// Creates a cache that's only kept in memory.
+  pub fn new() -> MinifyCache {
+    MinifyCache::default()
+  }
+
+  /// Creates a cache that's also kept in files in `dir`, which is created if it doesn't exist. Files that can't be read or written are treated as missing, as the cache is only an optimisation.
+  pub fn with_dir<P: Into<PathBuf>>(dir: P) -> MinifyCache {
+    MinifyCache {
+      dir: Some(dir.into()),
+      ..MinifyCache::default()
+    }
+  }
+
+  /// Number of calls to [MinifyCache::minify] that used a cached output.
+  pub fn hits(&self) -> usize {
+    self.hits
+  }
+
+  /// Number of calls to [MinifyCache::minify] that minified the code.
+  pub fn misses(&self) -> usize {
+    self.misses
+  }
+
+  /// Removes all entries from memory. Files in the cache's directory are kept.
+  pub fn clear(&mut self) {
+    self.entries.clear();
+  }
+
+  fn key(source: &[u8], cfg: &MinifyOptions) -> u128 {
+    // The cancellation flag changes, but doesn't change the output.
+    let mut cfg = cfg.clone();
+    cfg.cancel = None;
+    let mut hash = 0x6c62272e07bb014262b821756295c58d;
+    // Outputs could differ between versions of this crate.
+    fnv1a(&mut hash, env!("CARGO_PKG_VERSION").as_bytes());
+    fnv1a(&mut hash, format!("{:?}", cfg).as_bytes());
+    fnv1a(&mut hash, &(source.len() as u64).to_le_bytes());
+    fnv1a(&mut hash, source);
+    hash
+  }
+
+  fn path(&self, key: u128) -> Option<PathBuf> {
+    Some(self.dir.as_ref()?.join(format!("{:032x}.js", key)))
+  }
+
+  // Files have the renaming savings on the first line, followed by the output.
+  fn read(&self, key: u128) -> Option<CacheEntry> {
+    let file = fs::read(self.path(key)?).ok()?;
+    let nl = file.iter().position(|&c| c == b'\n')?;
+    let renaming_savings = std::str::from_utf8(&file[..nl]).ok()?.parse().ok()?;
+    Some(CacheEntry {
+      output: file[nl + 1..].to_vec(),
+      renaming_savings,
+    })
+  }
+
+  fn write(&self, key: u128, entry: &CacheEntry) {
+    let Some(path) = self.path(key) else {
+      return;
+    };
+    let mut file = format!("{}\n", entry.renaming_savings).into_bytes();
+    file.extend_from_slice(&entry.output);
+    // Write to a temporary file first, so other processes never read a partially written entry.
+    let tmp = path.with_extension(format!("{}.tmp", std::process::id()));
+    let _ = fs::create_dir_all(path.parent().unwrap())
+      .and_then(|_| fs::write(&tmp, file))
+      .and_then(|_| fs::rename(&tmp, &path));
+  }
+
+  /// Minifies `source` like [`minify`], appending the output to `output`, unless the same code was minified with the same options before, in which case the cached output is appended instead. The stats of a cached output only have the input and output lengths and renaming savings.
+  pub fn minify<'a>(
+    &mut self,
+    session: &'a Session,
+    source: &'a [u8],
+    cfg: &MinifyOptions,
+    output: &mut Vec<u8>,
+  ) -> Result<MinifyStats, MinifyError<'a>> {
+    if cfg.rename_map
+      || cfg.position_map
+      || cfg.warnings
+      || cfg.syntax_features
+      || cfg.bailouts
+      || cfg.report
+    {
+      self.misses += 1;
+      return minify(session, source, cfg, output);
+    };
+    let key = MinifyCache::key(source, cfg);
+    if !self.entries.contains_key(&key) {
+      if let Some(entry) = self.read(key) {
+        self.entries.insert(key, entry);
+      };
+    };
+    if let Some(entry) = self.entries.get(&key) {
+      self.hits += 1;
+      output.extend_from_slice(&entry.output);
+      return Ok(MinifyStats {
+        input_len: source.len(),
+        output_len: entry.output.len(),
+        renaming_savings: entry.renaming_savings,
+        ..MinifyStats::default()
+      });
+    };
+    self.misses += 1;
+    let output_start = output.len();
+    let stats = minify(session, source, cfg, output)?;
+    let entry = CacheEntry {
+      output: output[output_start..].to_vec(),
+      renaming_savings: stats.renaming_savings,
+    };
+    self.write(key, &entry);
+    self.entries.insert(key, entry);
+    Ok(stats)
+  }
+}
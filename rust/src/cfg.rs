@@ -0,0 +1,700 @@
+use crate::alphabet::NameAlphabet;
+use crate::define::Define;
+use crate::environment::Environment;
+use crate::error::MinifyError;
+use crate::limits::Limits;
+use crate::obfuscate::Obfuscation;
+use core::fmt;
+use core::fmt::Debug;
+use core::fmt::Formatter;
+use parse_js::ast::NodeData;
+use parse_js::parse::toplevel::TopLevelMode;
+use parse_js::session::Session;
+use parse_js::symbol::ScopeType;
+use regex::Regex;
+use std::str::FromStr;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+/// What to do with an existing `//# sourceMappingURL=` (or legacy `//@ sourceMappingURL=`) comment at the end of the input.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serialize", serde(rename_all = "lowercase"))]
+pub enum SourceMappingUrl {
+  /// Drop the comment. This is the default, as the referenced source map would no longer match the minified output.
+  #[default]
+  Strip,
+  /// Keep the comment as is at the end of the output.
+  Preserve,
+  /// Emit a `//# sourceMappingURL=` comment with the provided URL at the end of the output, replacing any existing one.
+  Rewrite(String),
+}
+
+/// Where to keep legal comments, which are comments starting with `/*!` or `//!`, or containing `@license`, `@preserve`, or `@copyright`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serialize", serde(rename_all = "lowercase"))]
+pub enum LegalComments {
+  /// Drop all legal comments.
+  None,
+  /// Keep all legal comments, in their original order, at the end of the output. This is the default, as stripping them violates many licenses.
+  #[default]
+  Eof,
+  /// Hoist legal comments to the top of the output, dropping duplicates.
+  Top,
+}
+
+impl FromStr for LegalComments {
+  type Err = &'static str;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "none" => Ok(LegalComments::None),
+      "eof" => Ok(LegalComments::Eof),
+      "top" => Ok(LegalComments::Top),
+      _ => Err("invalid legal comments"),
+    }
+  }
+}
+
+/// Line terminator to use for line breaks in the output.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serialize", serde(rename_all = "lowercase"))]
+pub enum Newline {
+  #[default]
+  Lf,
+  CrLf,
+}
+
+impl Newline {
+  pub fn as_bytes(self) -> &'static [u8] {
+    match self {
+      Newline::Lf => b"\n",
+      Newline::CrLf => b"\r\n",
+    }
+  }
+}
+
+impl FromStr for Newline {
+  type Err = &'static str;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "lf" => Ok(Newline::Lf),
+      "crlf" => Ok(Newline::CrLf),
+      _ => Err("invalid newline"),
+    }
+  }
+}
+
+/// How much memory to reserve in the output before emitting code into it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serialize", serde(rename_all = "lowercase"))]
+pub enum OutputCapacity {
+  /// Reserve the estimated size of the output, based on the size of the source code and how much renaming saved, so that large outputs aren't repeatedly reallocated and copied as they grow. The estimate is usually larger than the output, as it doesn't account for removed whitespace and comments; use [MinifyOptions::shrink_output] to free the excess.
+  #[default]
+  Estimate,
+  /// Reserve this many bytes, such as when the size of the output is known from a previous build.
+  Exact(usize),
+  /// Don't reserve anything up front; the output grows as needed.
+  Grow,
+}
+
+/// How to check the output once it's emitted, for [MinifyOptions::verify].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serialize", serde(rename_all = "lowercase"))]
+pub enum Verify {
+  /// Don't check the output.
+  #[default]
+  None,
+  /// Parse the output and check that it has the same syntax tree as the minified code, so that emitting it didn't change its meaning (e.g. by dropping needed parentheses).
+  Reparse,
+  /// Also minify the output again and check that it doesn't change other than in minified names, as minifying minified code should do nothing. Transforms run again too, so they must also be idempotent.
+  Idempotent,
+}
+
+impl FromStr for Verify {
+  type Err = &'static str;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "none" => Ok(Verify::None),
+      "reparse" => Ok(Verify::Reparse),
+      "idempotent" => Ok(Verify::Idempotent),
+      _ => Err("invalid verify"),
+    }
+  }
+}
+
+/// The kind of HTML `<script>` element that code is inlined into, for [minify_inline_script](crate::minify_inline_script).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScriptKind {
+  /// A classic `<script>`, parsed as a global script.
+  Classic,
+  /// A `<script type="module">`, parsed as a module.
+  Module,
+}
+
+impl ScriptKind {
+  pub fn top_level_mode(self) -> TopLevelMode {
+    match self {
+      ScriptKind::Classic => TopLevelMode::Global,
+      ScriptKind::Module => TopLevelMode::Module,
+    }
+  }
+}
+
+/// ECMAScript version that the output must be able to run on.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serialize", serde(rename_all = "lowercase"))]
+pub enum Target {
+  Es5,
+  Es2015,
+  Es2016,
+  Es2017,
+  Es2018,
+  Es2019,
+  Es2020,
+  Es2021,
+  Es2022,
+  #[default]
+  EsNext,
+}
+
+impl FromStr for Target {
+  type Err = &'static str;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "es5" => Ok(Target::Es5),
+      "es2015" => Ok(Target::Es2015),
+      "es2016" => Ok(Target::Es2016),
+      "es2017" => Ok(Target::Es2017),
+      "es2018" => Ok(Target::Es2018),
+      "es2019" => Ok(Target::Es2019),
+      "es2020" => Ok(Target::Es2020),
+      "es2021" => Ok(Target::Es2021),
+      "es2022" => Ok(Target::Es2022),
+      "esnext" => Ok(Target::EsNext),
+      _ => Err("invalid target"),
+    }
+  }
+}
+
+/// What to do with a comment, as decided by a [CommentFilter].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CommentAction {
+  /// Keep the comment at the end of the output.
+  Keep,
+  /// Drop the comment.
+  Drop,
+  /// Keep the comment and hoist it to the top of the output, dropping duplicates.
+  Hoist,
+}
+
+/// A callback that decides what to do with each comment in the source. It receives the entire comment, including its `//` or `/* */` delimiters.
+#[derive(Clone)]
+pub struct CommentFilter(pub Arc<dyn Fn(&str) -> CommentAction + Send + Sync>);
+
+impl CommentFilter {
+  pub fn new<F: Fn(&str) -> CommentAction + Send + Sync + 'static>(f: F) -> CommentFilter {
+    CommentFilter(Arc::new(f))
+  }
+}
+
+impl Debug for CommentFilter {
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    f.write_str("CommentFilter")
+  }
+}
+
+/// A variable about to be renamed, as passed to a [RenameFilter].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SymbolInfo<'a> {
+  pub name: &'a str,
+  /// Byte offset of the variable's first declaration in the source.
+  pub position: usize,
+  /// Type of the scope the variable is declared in.
+  pub scope: ScopeType,
+}
+
+/// What to name a variable, as decided by a [RenameFilter].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RenameDecision {
+  /// Give the variable the shortest available name, as usual.
+  Minify,
+  /// Keep the variable's original name.
+  Keep,
+  /// Rename the variable to this name, which must be a valid identifier.
+  Rename(String),
+}
+
+/// A callback that decides how to rename each variable. It's called once for every declared variable that would be minified.
+///
+/// Generated names never clash with kept or forced names. If a kept or forced name would shadow another variable used in the same scope, clash with another variable in the same scope, or is a keyword, the variable is minified as usual instead.
+#[derive(Clone)]
+pub struct RenameFilter(pub Arc<dyn Fn(&SymbolInfo) -> RenameDecision + Send + Sync>);
+
+impl RenameFilter {
+  pub fn new<F: Fn(&SymbolInfo) -> RenameDecision + Send + Sync + 'static>(f: F) -> RenameFilter {
+    RenameFilter(Arc::new(f))
+  }
+}
+
+impl Debug for RenameFilter {
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    f.write_str("RenameFilter")
+  }
+}
+
+/// A custom pass that transforms the parsed tree, such as a [Visitor](parse_js::visit::Visitor) that injects build metadata or rewrites feature flags. It receives the session, for allocating new nodes, and the top-level node.
+#[derive(Clone)]
+pub struct Transform(pub Arc<TransformFn>);
+
+type TransformFn = dyn for<'a> Fn(&'a Session, &mut NodeData<'a>) + Send + Sync;
+
+impl Transform {
+  pub fn new<F: for<'a> Fn(&'a Session, &mut NodeData<'a>) + Send + Sync + 'static>(
+    f: F,
+  ) -> Transform {
+    Transform(Arc::new(f))
+  }
+}
+
+impl Debug for Transform {
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    f.write_str("Transform")
+  }
+}
+
+// TopLevelMode is from parse-js, which doesn't derive serde traits for it.
+#[cfg(feature = "serialize")]
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(remote = "TopLevelMode", rename_all = "lowercase")]
+enum TopLevelModeDef {
+  Global,
+  Module,
+}
+
+// Regex doesn't implement serde traits, so patterns are (de)serialized as strings.
+#[cfg(feature = "serialize")]
+mod regexes_serde {
+  use regex::Regex;
+  use serde::de::Error;
+  use serde::Deserialize;
+  use serde::Deserializer;
+  use serde::Serializer;
+
+  pub fn serialize<S: Serializer>(regexes: &[Regex], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(regexes.iter().map(Regex::as_str))
+  }
+
+  pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Regex>, D::Error> {
+    Vec::<String>::deserialize(deserializer)?
+      .iter()
+      .map(|pattern| Regex::new(pattern).map_err(D::Error::custom))
+      .collect()
+  }
+}
+
+// Defines are (de)serialized as strings in the form `NAME=VALUE`, as they're parsed from them.
+#[cfg(feature = "serialize")]
+mod defines_serde {
+  use crate::Define;
+  use serde::de::Error;
+  use serde::Deserialize;
+  use serde::Deserializer;
+  use serde::Serializer;
+
+  pub fn serialize<S: Serializer>(defines: &[Define], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(defines.iter().map(Define::to_string))
+  }
+
+  pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Define>, D::Error> {
+    Vec::<String>::deserialize(deserializer)?
+      .iter()
+      .map(|define| define.parse().map_err(D::Error::custom))
+      .collect()
+  }
+}
+
+/// Options that can be adjusted and passed to a minification function to change its behaviour.
+///
+/// Start from [MinifyOptions::new], which has sensible defaults, and chain the builder methods to change options:
+///
+/// ```
+/// use minify_js::{MinifyOptions, Target, TopLevelMode};
+///
+/// let options = MinifyOptions::new()
+///   .top_level_mode(TopLevelMode::Module)
+///   .target(Target::Es2015)
+///   .max_line_len(32000);
+/// ```
+///
+/// New options may be added in minor releases, so this struct can't be constructed with a struct literal outside this crate.
+///
+/// With the `serialize` feature, options can be loaded from JSON or other formats supported by serde, with the same field names. Omitted fields have their default values, enum values are lowercase (e.g. `"es2015"` or `"crlf"`), and `comment_filter`, `rename_filter`, `name_alphabet`, `cancel`, and transforms can't be set.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serialize", serde(default))]
+#[non_exhaustive]
+pub struct MinifyOptions {
+  /// How to parse the provided code. Defaults to a global script, which is non-strict code unless it starts with a `"use strict"` directive, so it can use legacy syntax such as octal literals and escapes (e.g. `0755` and `"\07"`). Modules are always strict mode code.
+  #[cfg_attr(feature = "serialize", serde(with = "TopLevelModeDef"))]
+  pub top_level_mode: TopLevelMode,
+  /// How to handle an existing `sourceMappingURL` comment.
+  pub source_mapping_url: SourceMappingUrl,
+  /// Where to keep legal comments.
+  pub legal_comments: LegalComments,
+  /// If provided, decides what to do with every comment (except a trailing `sourceMappingURL` comment, and a trailing `sourceURL` comment if `keep_source_url` is set), instead of `legal_comments`.
+  #[cfg_attr(feature = "serialize", serde(skip))]
+  pub comment_filter: Option<CommentFilter>,
+  /// Global variables and property paths to replace with constant expressions (e.g. `DEBUG=false`), such as for build-time feature flags. They're replaced before `drop_console` and `before_minify`, like [replace_defines](crate::replace_defines). With the `serialize` feature, they're strings in the form `NAME=VALUE`.
+  #[cfg_attr(feature = "serialize", serde(with = "defines_serde"))]
+  pub defines: Vec<Define>,
+  /// Drop calls to methods of the global `console` (e.g. `console.log(a)`), including their arguments, such as to remove logging from production builds. A call whose value is used is replaced with `undefined`. Off by default.
+  pub drop_console: bool,
+  /// Custom passes to run, in order, on the parsed tree before minification. Variables are resolved during parsing, so new references to variables won't be renamed with their declarations, and new declarations won't be minified or checked for conflicts.
+  #[cfg_attr(feature = "serialize", serde(skip))]
+  pub before_minify: Vec<Transform>,
+  /// Custom passes to run, in order, on the minified tree before emitting it (and before lowering to ES5 with the `downlevel` feature). Variables have already been renamed.
+  #[cfg_attr(feature = "serialize", serde(skip))]
+  pub after_minify: Vec<Transform>,
+  /// Output formatted code, with indentation, one statement per line, and spaces around operators, instead of minified code. Useful for debugging; names are still mangled.
+  pub beautify: bool,
+  /// If provided, insert line breaks at safe points so that output lines are at most this many bytes long (e.g. 32000), as some tools struggle with very long lines. Lines can still be longer if there is no safe point to break at, such as in a long string.
+  pub max_line_len: Option<usize>,
+  /// The oldest ECMAScript version the output must run on. Minification won't introduce syntax newer than this (e.g. arrow functions or template literals when targeting ES5), but syntax already in the input is kept as is.
+  pub target: Target,
+  /// Separate statements with line breaks instead of semicolons wherever automatic semicolon insertion (ASI) allows, and omit semicolons at the end of lines when beautifying. This doesn't change the output size (unless using CRLF line breaks), but makes minified output easier to read and diff.
+  pub asi: bool,
+  /// Make the output safe to concatenate with other scripts, by starting it with `;` if it starts with a character that could continue a preceding script's last statement (e.g. `(`), and ending it with `;` if its last statement could be continued by a following script (e.g. one starting with `(`, `[`, or `` ` ``). If it ends with a single-line comment (e.g. a legal comment), it also ends with a line terminator. Nothing is added if it isn't needed.
+  pub safe_concat: bool,
+  /// Assume that reading properties doesn't run any code and doesn't throw, as if objects never have getters and are never `null` or `undefined`, so unused property reads (e.g. `a.b;`) and object spreads can be dropped like other code without side effects. Off by default, as reading properties of proxies, objects with getters, and `null` can have side effects.
+  pub assume_plain_objects: bool,
+  /// The environment that the code runs in, which decides which globals are assumed to exist and not be reassigned, for dropping unused reads of them, avoiding them as minified names, and warning about unknown globals. See [Environment]. Defaults to [Environment::None], which assumes nothing.
+  pub environment: Environment,
+  /// Keep the original names of functions and classes, including variables that anonymous functions and classes are assigned to (e.g. `f` in `let f = () => {}`), so their `name` property is unchanged. All of them are kept, as any function or class passed to other code could have its `name` read there.
+  pub keep_fn_names: bool,
+  /// Escape `</script` and `<!--` (in any case) wherever they would appear in the output, including in strings, templates, regexes, and preserved comments, so the output can be inlined in an HTML `<script>` element without ending it early. Strings and regexes still have the same values. The banner, footer, and a rewritten `sourceMappingURL` are emitted as is. See [minify_inline_script](crate::minify_inline_script).
+  pub inline_script: bool,
+  /// Keep a `//# sourceURL=` (or legacy `//@ sourceURL=`) comment at the end of the input, which devtools use to name code run with `eval` or `new Function`. It's emitted at the end of the output, before any `sourceMappingURL` comment.
+  pub keep_source_url: bool,
+  /// Keep the hashbang comment at the start of the input (e.g. `#!/usr/bin/env node`), which Node.js entry scripts need to be run directly. It's emitted as is at the very start of the output, before the banner. Otherwise it's dropped like other comments.
+  pub keep_hashbang: bool,
+  /// Return a map of the original and minified names of all variables in the stats, which is useful for deobfuscating stack traces and debugging renaming.
+  pub rename_map: bool,
+  /// Return a [PositionMap](crate::PositionMap) in the stats, which maps positions in the output back to the source code, such as for reporting where errors thrown by the minified code came from.
+  pub position_map: bool,
+  /// Return a [MinifyReport](crate::MinifyReport) in the stats with the wall time and memory used by each phase and pass, such as for deciding which optional work to disable to meet a latency budget.
+  pub report: bool,
+  /// Minify the names of variables, functions, and classes. If off, all names are kept, as if `rename_filter` kept every name. On by default.
+  pub mangle: bool,
+  /// If provided, decides how to rename each variable, such as to keep names that are looked up dynamically, or to force specific names. Use `rename_map` to get the final names.
+  #[cfg_attr(feature = "serialize", serde(skip))]
+  pub rename_filter: Option<RenameFilter>,
+  /// Keep the original names of variables, functions, and classes whose names match any of these patterns, such as those that frameworks look up by name at runtime. Patterns match anywhere in the name unless anchored with `^` and `$`. These names are kept even if `rename_filter` decides otherwise. With the `serialize` feature, they're strings.
+  #[cfg_attr(feature = "serialize", serde(with = "regexes_serde"))]
+  pub keep_names: Vec<Regex>,
+  /// The characters that minified names are made of, and the order they're used in. Defaults to ASCII letters, `_`, and `$`, with digits after the first character.
+  #[cfg_attr(feature = "serialize", serde(skip))]
+  pub name_alphabet: NameAlphabet,
+  /// Return warnings in the stats about suspicious but valid code, such as direct `eval` calls (which can't see renamed variables), duplicate object keys, and annotations like `/*#__PURE__*/` that are dropped from the output. Positions are offset by any `enclose` wrapper.
+  pub warnings: bool,
+  /// Return the syntax introduced after ES5 that the source uses in the stats, such as optional chaining and class fields, with the first use of each and the ECMAScript version that introduced it, to check that code is compatible with a target. They're found in the source before minification, which can introduce syntax (e.g. arrow functions). Positions are offset by any `enclose` wrapper. See [syntax_features](crate::syntax_features).
+  pub syntax_features: bool,
+  /// Return diagnostics in the stats about code that prevents some minification, with the reason, so it can be refactored for better compression: functions that can't become arrow functions because they use `this` or `arguments` or are constructors, unused property reads that are kept because they could run getters, and globals that aren't renamed. Their codes start with `B_`. Positions are offset by any `enclose` wrapper.
+  pub bailouts: bool,
+  /// How much memory to reserve in the output before emitting code into it. Only used by functions that emit into a `Vec` (e.g. [minify](crate::minify)), not writers.
+  pub output_capacity: OutputCapacity,
+  /// Shrink the capacity of the output to its length once done, to free memory reserved by [OutputCapacity::Estimate] beyond what was used. This reallocates and copies the output, so it's off by default.
+  pub shrink_output: bool,
+  /// Line terminator for line breaks added to the output, such as when beautifying, breaking long lines, and separating preserved comments. Line terminators within preserved comments and template literals are kept as is.
+  pub newline: Newline,
+  /// If provided, this text is emitted as is at the start of the output, followed by a line break. It isn't parsed or minified.
+  pub banner: Option<String>,
+  /// If provided, this text is emitted as is on a new line at the end of the output, before any `sourceMappingURL` comment. It isn't parsed or minified.
+  pub footer: Option<String>,
+  /// If provided, wrap a global script in a function that's immediately invoked with these globals as arguments (e.g. `window` and `document`). This allows top-level declarations, and references to these globals, to be minified, but top-level declarations will no longer create globals. Has no effect on modules. Syntax error and rename map positions will be offset by the added wrapper.
+  pub enclose: Option<Vec<String>>,
+  /// Limits on the source code, such as its size and nesting depth, for minifying untrusted code. Checked before parsing, and before any `enclose` wrapper is added.
+  pub limits: Limits,
+  /// Obfuscate the output, such as by encoding strings, to make it harder to reverse engineer at the cost of size. Off by default.
+  pub obfuscation: Obfuscation,
+  /// Check the output before returning it, and fail with [MinifyError::Internal] if it doesn't match the minified code, instead of returning broken code. This is for catching bugs in the minifier, such as when trying new passes or transforms, and roughly doubles the time taken (or triples it with [Verify::Idempotent]). Only functions that return the output in a `Vec` (e.g. [minify](crate::minify)) check it, not writers or [minify_bounded](crate::minify_bounded). Code with syntax that parse-js can't parse as emitted, such as dynamic imports, isn't checked, and a warning with the code `W_VERIFY_SKIPPED` is added to [MinifyStats::warnings](crate::MinifyStats::warnings) instead.
+  pub verify: Verify,
+  /// If provided, minification stops with [MinifyError::Cancelled] soon after this is set to `true`, such as from another thread when an editor or dev server no longer needs the result. It's checked before and after parsing and between minification passes, but not during parsing.
+  #[cfg_attr(feature = "serialize", serde(skip))]
+  pub cancel: Option<Arc<AtomicBool>>,
+}
+
+impl Default for MinifyOptions {
+  fn default() -> MinifyOptions {
+    MinifyOptions {
+      top_level_mode: TopLevelMode::Global,
+      source_mapping_url: SourceMappingUrl::default(),
+      legal_comments: LegalComments::default(),
+      comment_filter: None,
+      defines: Vec::new(),
+      drop_console: false,
+      before_minify: Vec::new(),
+      after_minify: Vec::new(),
+      beautify: false,
+      max_line_len: None,
+      target: Target::default(),
+      asi: false,
+      safe_concat: false,
+      assume_plain_objects: false,
+      environment: Environment::default(),
+      keep_fn_names: false,
+      inline_script: false,
+      keep_source_url: false,
+      keep_hashbang: false,
+      rename_map: false,
+      position_map: false,
+      report: false,
+      mangle: true,
+      rename_filter: None,
+      keep_names: Vec::new(),
+      name_alphabet: NameAlphabet::default(),
+      warnings: false,
+      syntax_features: false,
+      bailouts: false,
+      output_capacity: OutputCapacity::default(),
+      shrink_output: false,
+      newline: Newline::default(),
+      banner: None,
+      footer: None,
+      enclose: None,
+      limits: Limits::default(),
+      obfuscation: Obfuscation::default(),
+      verify: Verify::default(),
+      cancel: None,
+    }
+  }
+}
+
+impl MinifyOptions {
+  pub fn new() -> MinifyOptions {
+    MinifyOptions::default()
+  }
+
+  pub fn top_level_mode(mut self, top_level_mode: TopLevelMode) -> MinifyOptions {
+    self.top_level_mode = top_level_mode;
+    self
+  }
+
+  pub fn source_mapping_url(mut self, source_mapping_url: SourceMappingUrl) -> MinifyOptions {
+    self.source_mapping_url = source_mapping_url;
+    self
+  }
+
+  pub fn legal_comments(mut self, legal_comments: LegalComments) -> MinifyOptions {
+    self.legal_comments = legal_comments;
+    self
+  }
+
+  pub fn comment_filter<F: Fn(&str) -> CommentAction + Send + Sync + 'static>(
+    mut self,
+    f: F,
+  ) -> MinifyOptions {
+    self.comment_filter = Some(CommentFilter::new(f));
+    self
+  }
+
+  pub fn defines(mut self, defines: impl IntoIterator<Item = Define>) -> MinifyOptions {
+    self.defines = defines.into_iter().collect();
+    self
+  }
+
+  pub fn drop_console(mut self, drop_console: bool) -> MinifyOptions {
+    self.drop_console = drop_console;
+    self
+  }
+
+  pub fn before_minify<F: for<'a> Fn(&'a Session, &mut NodeData<'a>) + Send + Sync + 'static>(
+    mut self,
+    f: F,
+  ) -> MinifyOptions {
+    self.before_minify.push(Transform::new(f));
+    self
+  }
+
+  pub fn after_minify<F: for<'a> Fn(&'a Session, &mut NodeData<'a>) + Send + Sync + 'static>(
+    mut self,
+    f: F,
+  ) -> MinifyOptions {
+    self.after_minify.push(Transform::new(f));
+    self
+  }
+
+  pub fn beautify(mut self, beautify: bool) -> MinifyOptions {
+    self.beautify = beautify;
+    self
+  }
+
+  pub fn max_line_len(mut self, max_line_len: usize) -> MinifyOptions {
+    self.max_line_len = Some(max_line_len);
+    self
+  }
+
+  pub fn target(mut self, target: Target) -> MinifyOptions {
+    self.target = target;
+    self
+  }
+
+  pub fn asi(mut self, asi: bool) -> MinifyOptions {
+    self.asi = asi;
+    self
+  }
+
+  pub fn safe_concat(mut self, safe_concat: bool) -> MinifyOptions {
+    self.safe_concat = safe_concat;
+    self
+  }
+
+  pub fn assume_plain_objects(mut self, assume_plain_objects: bool) -> MinifyOptions {
+    self.assume_plain_objects = assume_plain_objects;
+    self
+  }
+
+  pub fn environment(mut self, environment: Environment) -> MinifyOptions {
+    self.environment = environment;
+    self
+  }
+
+  pub fn keep_fn_names(mut self, keep_fn_names: bool) -> MinifyOptions {
+    self.keep_fn_names = keep_fn_names;
+    self
+  }
+
+  pub fn inline_script(mut self, inline_script: bool) -> MinifyOptions {
+    self.inline_script = inline_script;
+    self
+  }
+
+  pub fn keep_source_url(mut self, keep_source_url: bool) -> MinifyOptions {
+    self.keep_source_url = keep_source_url;
+    self
+  }
+
+  pub fn keep_hashbang(mut self, keep_hashbang: bool) -> MinifyOptions {
+    self.keep_hashbang = keep_hashbang;
+    self
+  }
+
+  pub fn rename_map(mut self, rename_map: bool) -> MinifyOptions {
+    self.rename_map = rename_map;
+    self
+  }
+
+  pub fn position_map(mut self, position_map: bool) -> MinifyOptions {
+    self.position_map = position_map;
+    self
+  }
+
+  pub fn report(mut self, report: bool) -> MinifyOptions {
+    self.report = report;
+    self
+  }
+
+  pub fn mangle(mut self, mangle: bool) -> MinifyOptions {
+    self.mangle = mangle;
+    self
+  }
+
+  pub fn rename_filter<F: Fn(&SymbolInfo) -> RenameDecision + Send + Sync + 'static>(
+    mut self,
+    f: F,
+  ) -> MinifyOptions {
+    self.rename_filter = Some(RenameFilter::new(f));
+    self
+  }
+
+  pub fn keep_names(mut self, keep_names: impl IntoIterator<Item = Regex>) -> MinifyOptions {
+    self.keep_names = keep_names.into_iter().collect();
+    self
+  }
+
+  pub fn name_alphabet(mut self, name_alphabet: NameAlphabet) -> MinifyOptions {
+    self.name_alphabet = name_alphabet;
+    self
+  }
+
+  pub fn warnings(mut self, warnings: bool) -> MinifyOptions {
+    self.warnings = warnings;
+    self
+  }
+
+  pub fn syntax_features(mut self, syntax_features: bool) -> MinifyOptions {
+    self.syntax_features = syntax_features;
+    self
+  }
+
+  pub fn bailouts(mut self, bailouts: bool) -> MinifyOptions {
+    self.bailouts = bailouts;
+    self
+  }
+
+  pub fn output_capacity(mut self, output_capacity: OutputCapacity) -> MinifyOptions {
+    self.output_capacity = output_capacity;
+    self
+  }
+
+  pub fn shrink_output(mut self, shrink_output: bool) -> MinifyOptions {
+    self.shrink_output = shrink_output;
+    self
+  }
+
+  pub fn newline(mut self, newline: Newline) -> MinifyOptions {
+    self.newline = newline;
+    self
+  }
+
+  pub fn banner(mut self, banner: impl Into<String>) -> MinifyOptions {
+    self.banner = Some(banner.into());
+    self
+  }
+
+  pub fn footer(mut self, footer: impl Into<String>) -> MinifyOptions {
+    self.footer = Some(footer.into());
+    self
+  }
+
+  pub fn enclose<S: Into<String>>(mut self, params: impl IntoIterator<Item = S>) -> MinifyOptions {
+    self.enclose = Some(params.into_iter().map(Into::into).collect());
+    self
+  }
+
+  pub fn limits(mut self, limits: Limits) -> MinifyOptions {
+    self.limits = limits;
+    self
+  }
+
+  pub fn obfuscation(mut self, obfuscation: Obfuscation) -> MinifyOptions {
+    self.obfuscation = obfuscation;
+    self
+  }
+
+  pub fn verify(mut self, verify: Verify) -> MinifyOptions {
+    self.verify = verify;
+    self
+  }
+
+  pub fn cancel(mut self, cancel: Arc<AtomicBool>) -> MinifyOptions {
+    self.cancel = Some(cancel);
+    self
+  }
+
+  pub(crate) fn check_cancelled<'a>(&self) -> Result<(), MinifyError<'a>> {
+    match &self.cancel {
+      Some(cancel) if cancel.load(Ordering::Relaxed) => Err(MinifyError::Cancelled),
+      _ => Ok(()),
+    }
+  }
+}
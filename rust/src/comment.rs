@@ -0,0 +1,363 @@
+use crate::cfg::CommentAction;
+use crate::cfg::LegalComments;
+use crate::cfg::MinifyOptions;
+use crate::cfg::SourceMappingUrl;
+use crate::emit::starts_html_sequence;
+use crate::pattern::Patterns;
+use parse_js::lex::lex_next;
+use parse_js::lex::lex_template_string_continue;
+use parse_js::lex::LexMode;
+use parse_js::lex::Lexer;
+use parse_js::token::TokenType;
+use parse_js::token::UNRESERVED_KEYWORDS;
+
+// Used to quickly check whether the source could have any comments we're interested in, so we can avoid lexing again in the common case.
+static INTERESTING_COMMENT_PATTERNS: Patterns = Patterns::new(&[
+  b"/*!",
+  b"//!",
+  b"@license",
+  b"@preserve",
+  b"@copyright",
+  b"sourceMappingURL=",
+  b"sourceURL=",
+]);
+
+static LEGAL_COMMENT_PATTERNS: Patterns =
+  Patterns::new(&[b"@license", b"@preserve", b"@copyright"]);
+
+pub struct Comment<'a> {
+  // The entire comment, including the `//` or `/* */` delimiters but excluding any trailing line terminator.
+  pub raw: &'a [u8],
+  // Whether there are no more tokens after this comment.
+  pub trailing: bool,
+}
+
+// Whether a following `/` would be a division operator and not the start of a regex. This is the usual heuristic based on the previous token, as we don't have the parser's context.
+fn slash_is_division(prev: TokenType) -> bool {
+  match prev {
+    TokenType::BraceClose
+    | TokenType::BracketClose
+    | TokenType::HyphenHyphen
+    | TokenType::Identifier
+    | TokenType::KeywordSuper
+    | TokenType::KeywordThis
+    | TokenType::LiteralBigInt
+    | TokenType::LiteralFalse
+    | TokenType::LiteralNull
+    | TokenType::LiteralNumber
+    | TokenType::LiteralRegex
+    | TokenType::LiteralString
+    | TokenType::LiteralTemplatePartStringEnd
+    | TokenType::LiteralTrue
+    | TokenType::ParenthesisClose
+    | TokenType::PlusPlus
+    | TokenType::PrivateMember => true,
+    t => UNRESERVED_KEYWORDS.contains(&t),
+  }
+}
+
+fn collect_comments_in_gap<'a>(
+  source: &'a [u8],
+  gap_start: usize,
+  gap_end: usize,
+  trailing: bool,
+  out: &mut Vec<Comment<'a>>,
+) {
+  // A gap between two tokens only contains whitespace and comments.
+  let mut i = gap_start;
+  while i + 1 < gap_end {
+    match (source[i], source[i + 1]) {
+      (b'/', b'/') => {
+        let end = memchr::memchr(b'\n', &source[i..gap_end])
+          .map(|p| i + p)
+          .unwrap_or(gap_end);
+        let end = if end > i && source[end - 1] == b'\r' {
+          end - 1
+        } else {
+          end
+        };
+        out.push(Comment {
+          raw: &source[i..end],
+          trailing,
+        });
+        i = end;
+      }
+      (b'/', b'*') => {
+        let end = memchr::memmem::find(&source[i + 2..gap_end], b"*/")
+          .map(|p| i + 2 + p + 2)
+          .unwrap_or(gap_end);
+        out.push(Comment {
+          raw: &source[i..end],
+          trailing,
+        });
+        i = end;
+      }
+      _ => i += 1,
+    };
+  }
+}
+
+// Returns the hashbang comment (e.g. `#!/usr/bin/env node`) that the source starts with, if any, excluding its line terminator. It's only allowed at the very start of a script or module, and parse-js doesn't lex it, so it's rewritten to spaces before parsing (see `normalize_source`).
+pub fn hashbang(source: &[u8]) -> Option<&[u8]> {
+  if !source.starts_with(b"#!") {
+    return None;
+  };
+  let end = (0..source.len())
+    .find(|&i| {
+      matches!(source[i], b'\n' | b'\r')
+        || matches!(source.get(i..i + 3), Some(b"\xE2\x80\xA8" | b"\xE2\x80\xA9"))
+    })
+    .unwrap_or(source.len());
+  Some(&source[..end])
+}
+
+// The parser discards comments, so we lex the source again to find them. This is only done when some option requires comments, as it roughly doubles the lexing cost.
+// Any lexing error (e.g. JSX, which we don't track the context of) simply ends the scan early, as the source has already been successfully parsed and comments are best effort.
+pub fn collect_comments<'a>(source: &'a [u8]) -> Vec<Comment<'a>> {
+  let mut comments = Vec::new();
+  let mut lexer = Lexer::new(source);
+  // For each open brace, whether it was opened by a template substitution `${`.
+  let mut braces = Vec::<bool>::new();
+  let mut prev = TokenType::_Dummy;
+  let mut prev_end = 0;
+  loop {
+    let mode = if slash_is_division(prev) {
+      LexMode::Standard
+    } else {
+      LexMode::SlashIsRegex
+    };
+    let Ok(t) = lex_next(&mut lexer, mode) else {
+      break;
+    };
+    let start = t.loc.start();
+    collect_comments_in_gap(
+      source,
+      prev_end,
+      start,
+      t.typ == TokenType::EOF,
+      &mut comments,
+    );
+    let mut t = t;
+    match t.typ {
+      TokenType::EOF => break,
+      TokenType::BraceOpen => braces.push(false),
+      TokenType::BraceClose if braces.pop() == Some(true) => {
+        let Ok(part) = lex_template_string_continue(&mut lexer, false) else {
+          break;
+        };
+        t = part;
+      }
+      _ => {}
+    };
+    // A template string token's `loc` excludes its delimiters, so use the lexer position instead.
+    prev_end = lexer_position(&lexer);
+    if t.typ == TokenType::LiteralTemplatePartString {
+      braces.push(true);
+    };
+    prev = t.typ;
+  }
+  comments
+}
+
+fn lexer_position(lexer: &Lexer) -> usize {
+  lexer.since_checkpoint(lexer.checkpoint()).start()
+}
+
+fn is_legal_comment(raw: &[u8]) -> bool {
+  raw.starts_with(b"/*!") || raw.starts_with(b"//!") || LEGAL_COMMENT_PATTERNS.is_match(raw)
+}
+
+fn is_source_mapping_url_comment(raw: &[u8]) -> bool {
+  raw.starts_with(b"//# sourceMappingURL=") || raw.starts_with(b"//@ sourceMappingURL=")
+}
+
+fn is_source_url_comment(raw: &[u8]) -> bool {
+  raw.starts_with(b"//# sourceURL=") || raw.starts_with(b"//@ sourceURL=")
+}
+
+/// Returns the URL in the `//# sourceMappingURL=` (or legacy `//@ sourceMappingURL=`) comment at the end of the source code, if there is one, such as to find the source map of code generated by a compiler. The URL may be a `data:` URL with the source map inlined.
+///
+/// # Examples
+///
+/// ```
+/// use minify_js::find_source_mapping_url;
+///
+/// let code = b"f();\n//# sourceMappingURL=app.js.map\n";
+/// assert_eq!(find_source_mapping_url(code), Some("app.js.map"));
+/// assert_eq!(find_source_mapping_url(b"//# sourceMappingURL=app.js.map\nf();"), None);
+/// ```
+pub fn find_source_mapping_url(source: &[u8]) -> Option<&str> {
+  memchr::memmem::find(source, b"sourceMappingURL=")?;
+  let comment = collect_comments(source)
+    .into_iter()
+    .rfind(|c| c.trailing && is_source_mapping_url_comment(c.raw))?;
+  std::str::from_utf8(&comment.raw[b"//# sourceMappingURL=".len()..])
+    .ok()
+    .map(str::trim)
+}
+
+// If inlining into HTML, a backslash is inserted into `<!--` and `</script` so they don't break out of the `<script>` element. Comments have no value, so this is only visible to readers.
+fn emit_comment(cfg: &MinifyOptions, out: &mut Vec<u8>, raw: &[u8]) {
+  if !cfg.inline_script {
+    out.extend_from_slice(raw);
+    return;
+  };
+  let mut run_start = 0;
+  for i in 0..raw.len() {
+    if raw[i] == b'<' && starts_html_sequence(&raw[i..]) {
+      out.extend_from_slice(&raw[run_start..=i]);
+      out.push(b'\\');
+      run_start = i + 1;
+    };
+  }
+  out.extend_from_slice(&raw[run_start..]);
+}
+
+// Comments from the source that should be kept in the output, as decided by the MinifyOptions.
+#[derive(Default)]
+pub struct PreservedComments<'a> {
+  hashbang: Option<&'a [u8]>,
+  top: Vec<&'a [u8]>,
+  eof: Vec<&'a [u8]>,
+  source_mapping_url: Option<&'a [u8]>,
+  source_url: Option<&'a [u8]>,
+}
+
+impl<'a> PreservedComments<'a> {
+  pub fn collect(source: &'a [u8], cfg: &MinifyOptions) -> PreservedComments<'a> {
+    let mut preserved = PreservedComments::default();
+    let hashbang = hashbang(source);
+    if cfg.keep_hashbang {
+      preserved.hashbang = hashbang;
+    };
+    // The lexer can't lex the hashbang, so start after it.
+    let source = &source[hashbang.map_or(0, <[u8]>::len)..];
+    if cfg.comment_filter.is_none() {
+      let wants_comments = cfg.legal_comments != LegalComments::None
+        || cfg.source_mapping_url == SourceMappingUrl::Preserve
+        || cfg.keep_source_url;
+      if !wants_comments || !INTERESTING_COMMENT_PATTERNS.is_match(source) {
+        return preserved;
+      };
+    };
+    for c in collect_comments(source) {
+      if c.trailing && is_source_mapping_url_comment(c.raw) {
+        // Only the last one is effective.
+        preserved.source_mapping_url = Some(c.raw);
+        continue;
+      };
+      if cfg.keep_source_url && c.trailing && is_source_url_comment(c.raw) {
+        preserved.source_url = Some(c.raw);
+        continue;
+      };
+      let action = match &cfg.comment_filter {
+        Some(filter) => filter.0(&String::from_utf8_lossy(c.raw)),
+        None if !is_legal_comment(c.raw) => CommentAction::Drop,
+        None => match cfg.legal_comments {
+          LegalComments::None => CommentAction::Drop,
+          LegalComments::Eof => CommentAction::Keep,
+          LegalComments::Top => CommentAction::Hoist,
+        },
+      };
+      match action {
+        CommentAction::Keep => preserved.eof.push(c.raw),
+        CommentAction::Drop => {}
+        CommentAction::Hoist => {
+          if !preserved.top.contains(&c.raw) {
+            preserved.top.push(c.raw);
+          };
+        }
+      };
+    }
+    preserved
+  }
+
+  // The banner and footer aren't comments, but they're emitted here as they must surround preserved comments. The hashbang must come before everything, as it's only allowed at the very start.
+  pub fn emit_top(&self, cfg: &MinifyOptions, out: &mut Vec<u8>) {
+    if let Some(hashbang) = self.hashbang {
+      out.extend_from_slice(hashbang);
+      out.extend_from_slice(cfg.newline.as_bytes());
+    };
+    if let Some(banner) = &cfg.banner {
+      out.extend_from_slice(banner.as_bytes());
+      out.extend_from_slice(cfg.newline.as_bytes());
+    };
+    for c in self.top.iter() {
+      emit_comment(cfg, out, c);
+      out.extend_from_slice(cfg.newline.as_bytes());
+    }
+  }
+
+  pub fn emit_eof(&self, cfg: &MinifyOptions, out: &mut Vec<u8>) {
+    // Whether the output ends with a single-line comment, which would comment out the start of any code concatenated after it.
+    let mut ends_with_line_comment = false;
+    for c in self.eof.iter() {
+      out.extend_from_slice(cfg.newline.as_bytes());
+      emit_comment(cfg, out, c);
+      ends_with_line_comment = c.starts_with(b"//");
+    }
+    if let Some(footer) = &cfg.footer {
+      out.extend_from_slice(cfg.newline.as_bytes());
+      out.extend_from_slice(footer.as_bytes());
+      ends_with_line_comment = footer
+        .rsplit('\n')
+        .next()
+        .is_some_and(|line| line.trim_start().starts_with("//"));
+    };
+    if let Some(c) = self.source_url {
+      out.extend_from_slice(cfg.newline.as_bytes());
+      emit_comment(cfg, out, c);
+      ends_with_line_comment = true;
+    };
+    // This must always be last, apart from a line terminator for safe concatenation.
+    match &cfg.source_mapping_url {
+      SourceMappingUrl::Strip => {}
+      SourceMappingUrl::Preserve => {
+        if let Some(c) = self.source_mapping_url {
+          out.extend_from_slice(cfg.newline.as_bytes());
+          emit_comment(cfg, out, c);
+          ends_with_line_comment = c.starts_with(b"//");
+        };
+      }
+      SourceMappingUrl::Rewrite(url) => {
+        out.extend_from_slice(cfg.newline.as_bytes());
+        out.extend_from_slice(b"//# sourceMappingURL=");
+        out.extend_from_slice(url.as_bytes());
+        ends_with_line_comment = true;
+      }
+    };
+    if cfg.safe_concat && ends_with_line_comment {
+      out.extend_from_slice(cfg.newline.as_bytes());
+    };
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::collect_comments;
+
+  fn check(source: &str, expected: &[(&str, bool)]) {
+    let comments = collect_comments(source.as_bytes());
+    let actual = comments
+      .iter()
+      .map(|c| (std::str::from_utf8(c.raw).unwrap(), c.trailing))
+      .collect::<Vec<_>>();
+    assert_eq!(actual, expected);
+  }
+
+  #[test]
+  fn test_collect_comments() {
+    check(
+      "a /* b */ + c // d\n",
+      &[("/* b */", false), ("// d", true)],
+    );
+    check("let a = '//'; /*! x */", &[("/*! x */", true)]);
+    check("a = /\\/*x/ // y", &[("// y", true)]);
+    check("a = b / 2 /* c */ / d", &[("/* c */", false)]);
+    check(
+      "`${ {} /* a */ }// ${1}` // b",
+      &[("/* a */", false), ("// b", true)],
+    );
+    check("a\r\n// b\r\n", &[("// b", true)]);
+  }
+}
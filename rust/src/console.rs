@@ -0,0 +1,71 @@
+use parse_js::ast::new_node;
+use parse_js::ast::NodeData;
+use parse_js::ast::Syntax;
+use parse_js::num::JsNumber;
+use parse_js::operator::OperatorName;
+use parse_js::session::Session;
+use parse_js::visit::JourneyControls;
+use parse_js::visit::Visitor;
+
+struct DropConsole<'a> {
+  session: &'a Session,
+}
+
+// Whether the node is a method of the global `console` (e.g. `console.log`). A variable declared in the code named `console` isn't the global.
+fn is_console_method(node: &NodeData) -> bool {
+  let (Syntax::MemberExpr {
+    optional_chaining: false,
+    left,
+    ..
+  }
+  | Syntax::ComputedMemberExpr {
+    optional_chaining: false,
+    object: left,
+    ..
+  }) = &node.stx
+  else {
+    return false;
+  };
+  match &left.stx {
+    Syntax::IdentifierExpr { name } => {
+      name.as_slice() == b"console" && left.scope.find_symbol(*name).is_none()
+    }
+    _ => false,
+  }
+}
+
+impl<'a> Visitor<'a> for DropConsole<'a> {
+  fn on_syntax_down(&mut self, node: &mut NodeData<'a>, ctl: &mut JourneyControls) {
+    let Syntax::CallExpr {
+      optional_chaining: false,
+      parenthesised,
+      callee,
+      ..
+    } = &node.stx
+    else {
+      return;
+    };
+    if !is_console_method(callee) {
+      return;
+    };
+    let argument = new_node(
+      self.session,
+      node.scope,
+      node.loc,
+      Syntax::LiteralNumberExpr {
+        value: JsNumber(0.0),
+      },
+    );
+    node.stx = Syntax::UnaryExpr {
+      parenthesised: *parenthesised,
+      operator: OperatorName::Void,
+      argument,
+    };
+    ctl.skip();
+  }
+}
+
+// Replaces calls to methods of the global `console` (e.g. `console.log(a)`), including their arguments, with `void 0`, which is then dropped like other code without side effects where its value isn't used. See `MinifyOptions::drop_console`.
+pub fn drop_console<'a>(session: &'a Session, node: &mut NodeData<'a>) {
+  DropConsole { session }.visit(node);
+}
@@ -0,0 +1,422 @@
+use crate::lossless::Extent;
+use crate::lossless::RangeTree;
+use parse_js::ast::NodeData;
+use parse_js::ast::Syntax;
+use parse_js::lex::lex_next;
+use parse_js::lex::LexMode;
+use parse_js::lex::Lexer;
+use parse_js::source::SourceRange;
+use parse_js::token::TokenType;
+use parse_js::visit::Visitor;
+
+// Name of a node's syntax variant.
+pub(crate) fn syntax_kind(stx: &Syntax<'_>) -> &'static str {
+  match stx {
+    Syntax::IdentifierPattern { .. } => "IdentifierPattern",
+    Syntax::ArrayPattern { .. } => "ArrayPattern",
+    Syntax::ObjectPattern { .. } => "ObjectPattern",
+    Syntax::ClassOrFunctionName { .. } => "ClassOrFunctionName",
+    Syntax::FunctionSignature { .. } => "FunctionSignature",
+    Syntax::ClassDecl { .. } => "ClassDecl",
+    Syntax::FunctionDecl { .. } => "FunctionDecl",
+    Syntax::ParamDecl { .. } => "ParamDecl",
+    Syntax::VarDecl { .. } => "VarDecl",
+    Syntax::ArrowFunctionExpr { .. } => "ArrowFunctionExpr",
+    Syntax::BinaryExpr { .. } => "BinaryExpr",
+    Syntax::CallExpr { .. } => "CallExpr",
+    Syntax::ClassExpr { .. } => "ClassExpr",
+    Syntax::ConditionalExpr { .. } => "ConditionalExpr",
+    Syntax::ComputedMemberExpr { .. } => "ComputedMemberExpr",
+    Syntax::FunctionExpr { .. } => "FunctionExpr",
+    Syntax::IdentifierExpr { .. } => "IdentifierExpr",
+    Syntax::ImportExpr { .. } => "ImportExpr",
+    Syntax::ImportMeta { .. } => "ImportMeta",
+    Syntax::JsxAttribute { .. } => "JsxAttribute",
+    Syntax::JsxElement { .. } => "JsxElement",
+    Syntax::JsxExpressionContainer { .. } => "JsxExpressionContainer",
+    Syntax::JsxMemberExpression { .. } => "JsxMemberExpression",
+    Syntax::JsxName { .. } => "JsxName",
+    Syntax::JsxSpreadAttribute { .. } => "JsxSpreadAttribute",
+    Syntax::JsxText { .. } => "JsxText",
+    Syntax::LiteralArrayExpr { .. } => "LiteralArrayExpr",
+    Syntax::LiteralBigIntExpr { .. } => "LiteralBigIntExpr",
+    Syntax::LiteralBooleanExpr { .. } => "LiteralBooleanExpr",
+    Syntax::LiteralNull { .. } => "LiteralNull",
+    Syntax::LiteralNumberExpr { .. } => "LiteralNumberExpr",
+    Syntax::LiteralObjectExpr { .. } => "LiteralObjectExpr",
+    Syntax::LiteralRegexExpr { .. } => "LiteralRegexExpr",
+    Syntax::LiteralStringExpr { .. } => "LiteralStringExpr",
+    Syntax::LiteralTemplateExpr { .. } => "LiteralTemplateExpr",
+    Syntax::MemberExpr { .. } => "MemberExpr",
+    Syntax::SuperExpr { .. } => "SuperExpr",
+    Syntax::ThisExpr { .. } => "ThisExpr",
+    Syntax::UnaryExpr { .. } => "UnaryExpr",
+    Syntax::UnaryPostfixExpr { .. } => "UnaryPostfixExpr",
+    Syntax::BlockStmt { .. } => "BlockStmt",
+    Syntax::BreakStmt { .. } => "BreakStmt",
+    Syntax::ContinueStmt { .. } => "ContinueStmt",
+    Syntax::DebuggerStmt { .. } => "DebuggerStmt",
+    Syntax::DoWhileStmt { .. } => "DoWhileStmt",
+    Syntax::EmptyStmt { .. } => "EmptyStmt",
+    Syntax::ExportDefaultExprStmt { .. } => "ExportDefaultExprStmt",
+    Syntax::ExportListStmt { .. } => "ExportListStmt",
+    Syntax::ExpressionStmt { .. } => "ExpressionStmt",
+    Syntax::IfStmt { .. } => "IfStmt",
+    Syntax::ImportStmt { .. } => "ImportStmt",
+    Syntax::ForStmt { .. } => "ForStmt",
+    Syntax::LabelStmt { .. } => "LabelStmt",
+    Syntax::ReturnStmt { .. } => "ReturnStmt",
+    Syntax::SwitchStmt { .. } => "SwitchStmt",
+    Syntax::ThrowStmt { .. } => "ThrowStmt",
+    Syntax::TryStmt { .. } => "TryStmt",
+    Syntax::WhileStmt { .. } => "WhileStmt",
+    Syntax::TopLevel { .. } => "TopLevel",
+    Syntax::CallArg { .. } => "CallArg",
+    Syntax::CatchBlock { .. } => "CatchBlock",
+    Syntax::ObjectMember { .. } => "ObjectMember",
+    Syntax::ObjectPatternProperty { .. } => "ObjectPatternProperty",
+    Syntax::SwitchBranch { .. } => "SwitchBranch",
+    Syntax::_TakenNode { .. } => "_TakenNode",
+  }
+}
+
+/// What a [CstElement] is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CstKind {
+  /// A node of the syntax tree, named after its [Syntax] variant (e.g. `"BinaryExpr"`). The root is always a `"TopLevel"` node.
+  Node(&'static str),
+  /// A keyword, punctuator, name, or literal, such as `return`, `(`, or `"a"`.
+  Token,
+  /// Spaces, tabs, and line terminators.
+  Whitespace,
+  /// A comment, including its `//` or `/* */` delimiters.
+  Comment,
+}
+
+/// The index of an element in a [ConcreteTree].
+pub type CstId = usize;
+
+/// An element of a [ConcreteTree]. Nodes have children; tokens, whitespace, and comments don't.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CstElement {
+  pub kind: CstKind,
+  /// Byte offset of the start of the element in the source.
+  pub start: usize,
+  /// Byte offset of the end of the element in the source.
+  pub end: usize,
+  /// The node containing this element, or `None` for the root.
+  pub parent: Option<CstId>,
+  /// The elements of a node, in source order. Together, they cover exactly the node's source.
+  pub children: Vec<CstId>,
+}
+
+/// A concrete syntax tree, which has every token, whitespace, and comment in the source, with links between parents and children. Unlike the syntax tree, which drops parentheses, comments, and formatting, printing it reproduces the source exactly, so tools can make formatting-preserving edits (e.g. codemods) by replacing the text of some elements. Create one with [concrete_tree].
+#[derive(Clone, Debug)]
+pub struct ConcreteTree<'a> {
+  source: &'a [u8],
+  elements: Vec<CstElement>,
+}
+
+impl<'a> ConcreteTree<'a> {
+  /// The root `TopLevel` node, which covers the entire source, including leading and trailing whitespace and comments.
+  pub fn root(&self) -> CstId {
+    0
+  }
+
+  pub fn get(&self, id: CstId) -> &CstElement {
+    &self.elements[id]
+  }
+
+  /// All elements, in pre-order (i.e. each node before its children), so [CstId]s can be used as indices.
+  pub fn elements(&self) -> &[CstElement] {
+    &self.elements
+  }
+
+  /// The source text of an element.
+  pub fn text(&self, id: CstId) -> &'a [u8] {
+    let e = &self.elements[id];
+    &self.source[e.start..e.end]
+  }
+
+  /// Elements that aren't nodes (tokens, whitespace, and comments), in source order.
+  pub fn leaves(&self) -> impl Iterator<Item = CstId> + '_ {
+    // Elements are in pre-order, which is source order for leaves.
+    (0..self.elements.len()).filter(|&id| !matches!(self.elements[id].kind, CstKind::Node(_)))
+  }
+
+  /// Writes the source, with the text of each element in `edits` replaced. If elements in `edits` overlap (e.g. a node and one of its tokens), the outermost one is used. With no edits, this reproduces the source exactly.
+  pub fn print_with(&self, edits: &[(CstId, &[u8])], output: &mut Vec<u8>) {
+    self.print_element(self.root(), edits, output);
+  }
+
+  fn print_element(&self, id: CstId, edits: &[(CstId, &[u8])], output: &mut Vec<u8>) {
+    if let Some((_, text)) = edits.iter().find(|(e, _)| *e == id) {
+      output.extend_from_slice(text);
+    } else if !matches!(self.elements[id].kind, CstKind::Node(_)) {
+      output.extend_from_slice(self.text(id));
+    } else {
+      for &c in self.elements[id].children.iter() {
+        self.print_element(c, edits, output);
+      }
+    };
+  }
+}
+
+struct Builder<'a> {
+  source: &'a [u8],
+  extents: Vec<Extent<'a>>,
+  elements: Vec<CstElement>,
+  // Position in `source` up to which we've built elements.
+  cursor: usize,
+}
+
+impl<'a> Builder<'a> {
+  fn push(&mut self, kind: CstKind, start: usize, end: usize, parent: Option<CstId>) -> CstId {
+    let id = self.elements.len();
+    self.elements.push(CstElement {
+      kind,
+      start,
+      end,
+      parent,
+      children: Vec::new(),
+    });
+    if let Some(parent) = parent {
+      self.elements[parent].children.push(id);
+    };
+    id
+  }
+
+  // Splits whitespace and comments, which only contain whitespace and comments, into elements.
+  fn push_trivia(&mut self, start: usize, end: usize, parent: CstId) {
+    let mut i = start;
+    while i < end {
+      let rest = &self.source[i..end];
+      let len = if rest.starts_with(b"//") {
+        memchr::memchr2(b'\n', b'\r', rest).unwrap_or(rest.len())
+      } else if rest.starts_with(b"/*") {
+        memchr::memmem::find(&rest[2..], b"*/").map_or(rest.len(), |p| p + 4)
+      } else {
+        // Whitespace runs until the next comment. Anything else (e.g. a `#!` line) is kept as a token, which shouldn't happen.
+        let len = rest
+          .windows(2)
+          .position(|w| w == b"//" || w == b"/*")
+          .unwrap_or(rest.len());
+        let kind = if std::str::from_utf8(&rest[..len]).is_ok_and(|s| s.trim().is_empty()) {
+          CstKind::Whitespace
+        } else {
+          CstKind::Token
+        };
+        self.push(kind, i, i + len, Some(parent));
+        i += len;
+        continue;
+      };
+      self.push(CstKind::Comment, i, i + len, Some(parent));
+      i += len;
+    }
+  }
+
+  // Splits source text that isn't covered by a node's children into tokens, whitespace, and comments.
+  fn push_gap(&mut self, start: usize, end: usize, mode: LexMode, parent: CstId) {
+    let mut lexer = Lexer::new(&self.source[start..end]);
+    let mut prev_end = start;
+    while let Ok(t) = lex_next(&mut lexer, mode) {
+      let (t_start, t_end) = (start + t.loc.start(), start + t.loc.end());
+      self.push_trivia(prev_end, t_start, parent);
+      prev_end = t_start;
+      if t.typ == TokenType::EOF {
+        break;
+      };
+      self.push(CstKind::Token, t_start, t_end, Some(parent));
+      prev_end = t_end;
+    }
+    // If the text can't be lexed on its own, keep the rest as one token.
+    if prev_end < end {
+      self.push(CstKind::Token, prev_end, end, Some(parent));
+    };
+  }
+
+  // Splits the text of a node not covered by its children. Template literals' strings are between their substitutions, so they're kept as single tokens instead of being lexed.
+  fn push_gap_until(&mut self, kind: &'static str, end: usize, parent: CstId) {
+    if end > self.cursor {
+      if kind == "LiteralTemplateExpr" {
+        self.push(CstKind::Token, self.cursor, end, Some(parent));
+      } else {
+        self.push_gap(self.cursor, end, LexMode::Standard, parent);
+      };
+      self.cursor = end;
+    };
+  }
+
+  fn build(&mut self, ext: usize, parent: Option<CstId>) -> Result<(), SourceRange<'a>> {
+    let Extent {
+      loc,
+      kind,
+      start,
+      end,
+      ref mut children,
+    } = self.extents[ext];
+    let mut children = core::mem::take(children);
+    if start < self.cursor {
+      return Err(loc);
+    };
+    // The root covers the entire source, including whitespace and comments before and after its first and last tokens.
+    let (start, end) = match parent {
+      None => (0, self.source.len()),
+      Some(_) => (start, end),
+    };
+    let id = self.push(CstKind::Node(kind), start, end, parent);
+    self.cursor = start;
+    if children.is_empty() {
+      // Leaves of the syntax tree, such as literals and identifiers, can only start with `/` if they're regexes. JSX text isn't code.
+      if kind == "JsxText" {
+        self.push(CstKind::Token, start, end, Some(id));
+      } else {
+        self.push_gap(start, end, LexMode::SlashIsRegex, id);
+      };
+      self.cursor = end;
+      return Ok(());
+    };
+    // The visitor visits children in evaluation order (e.g. the iterated value of a `for-of` loop before its declaration), so sort them into source order.
+    children.sort_by_key(|&c| self.extents[c].start);
+    for c in children {
+      self.push_gap_until(kind, self.extents[c].start, id);
+      self.build(c, Some(id))?;
+    }
+    self.push_gap_until(kind, end, id);
+    Ok(())
+  }
+}
+
+/// Builds a [ConcreteTree] of a parsed tree and the source it was parsed from, for formatting-preserving transformations. The tree must not have been changed since it was parsed.
+///
+/// Returns the source range of the first node that isn't from `source` or that overlaps its previous sibling, like [print_lossless](crate::print_lossless).
+///
+/// # Examples
+///
+/// ```
+/// use minify_js::{concrete_tree, CstKind, Session, TopLevelMode};
+/// use minify_js::parse_js::parse;
+///
+/// let source = b"let a = 1; // one\n";
+/// let session = Session::new();
+/// let node = parse(&session, source, TopLevelMode::Global).unwrap();
+/// let tree = concrete_tree(source, node).unwrap();
+/// let leaves = tree
+///   .leaves()
+///   .map(|id| (tree.get(id).kind, tree.text(id)))
+///   .collect::<Vec<_>>();
+/// assert_eq!(leaves[0], (CstKind::Token, &b"let"[..]));
+/// assert_eq!(leaves[leaves.len() - 2], (CstKind::Comment, &b"// one"[..]));
+///
+/// // Replace the `1` literal.
+/// let one = tree.leaves().find(|&id| tree.text(id) == b"1").unwrap();
+/// let mut out = Vec::new();
+/// tree.print_with(&[(one, b"2")], &mut out);
+/// assert_eq!(out, b"let a = 2; // one\n");
+/// ```
+pub fn concrete_tree<'a>(
+  source: &'a [u8],
+  node: &mut NodeData<'a>,
+) -> Result<ConcreteTree<'a>, SourceRange<'a>> {
+  let mut tree = RangeTree::new(source);
+  tree.visit(node);
+  if let Some(loc) = tree.foreign {
+    return Err(loc);
+  };
+  let mut builder = Builder {
+    source,
+    extents: tree.nodes,
+    elements: Vec::new(),
+    cursor: 0,
+  };
+  builder.build(0, None)?;
+  Ok(ConcreteTree {
+    source,
+    elements: builder.elements,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::concrete_tree;
+  use super::CstKind;
+  use crate::Session;
+  use crate::TopLevelMode;
+  use parse_js::parse;
+
+  fn check(source: &str) {
+    let session = Session::new();
+    let node = parse(&session, source.as_bytes(), TopLevelMode::Module).unwrap();
+    let tree = concrete_tree(source.as_bytes(), node).unwrap();
+    let mut out = Vec::new();
+    tree.print_with(&[], &mut out);
+    assert_eq!(std::str::from_utf8(&out).unwrap(), source);
+    // Leaves cover the source in order, and children are within their parents.
+    let mut pos = 0;
+    for id in tree.leaves() {
+      assert_eq!(tree.get(id).start, pos);
+      pos = tree.get(id).end;
+    }
+    assert_eq!(pos, source.len());
+    for (id, e) in tree.elements().iter().enumerate() {
+      for &c in e.children.iter() {
+        assert_eq!(tree.get(c).parent, Some(id));
+        assert!(e.start <= tree.get(c).start && tree.get(c).end <= e.end);
+      }
+    }
+  }
+
+  #[test]
+  fn test_concrete_tree() {
+    check("");
+    check("  // leading\nlet a = 1 /* b */ + 2;\n\n");
+    check(
+      "import {x as y} from 'z';\nexport default function f(a, b = 2, ...c) { return a ? b : c }",
+    );
+    check("class A extends B { static #x = 1; get y() { return this.#x } [z](q) {} }");
+    check("for (const [a, {b, c: d}] of e) if (a) { continue } else label: while (1) break label;");
+    check("const o = { a, b: 1, [c]: 2, ...d, e() {}, async *f() { yield* g; await h } };");
+    check("x = `a${b}c${`d${e}`}`; y = a?.b?.[c]?.(d); z = new F(...g) ?? /re/g;");
+    check("try { throw a } catch ({ b }) {} finally { switch (c) { case 1: default: } }");
+    check("let a = <div className=\"x\" {...b}>hi // not a comment\n{c}<br /></div>;");
+  }
+
+  #[test]
+  fn test_concrete_tree_trivia() {
+    let source = b"f( /* a */ (x) ) // b\n";
+    let session = Session::new();
+    let node = parse(&session, source, TopLevelMode::Global).unwrap();
+    let tree = concrete_tree(source, node).unwrap();
+    let leaves = tree
+      .leaves()
+      .map(|id| (tree.get(id).kind, std::str::from_utf8(tree.text(id)).unwrap()))
+      .collect::<Vec<_>>();
+    assert_eq!(leaves, vec![
+      (CstKind::Token, "f"),
+      (CstKind::Token, "("),
+      (CstKind::Whitespace, " "),
+      (CstKind::Comment, "/* a */"),
+      (CstKind::Whitespace, " "),
+      (CstKind::Token, "("),
+      (CstKind::Token, "x"),
+      (CstKind::Token, ")"),
+      (CstKind::Whitespace, " "),
+      (CstKind::Token, ")"),
+      (CstKind::Whitespace, " "),
+      (CstKind::Comment, "// b"),
+      (CstKind::Whitespace, "\n"),
+    ]);
+    // The syntax tree doesn't record parentheses, so they're tokens of the enclosing node.
+    let x = tree.leaves().find(|&id| tree.text(id) == b"x").unwrap();
+    let arg = tree.get(tree.get(x).parent.unwrap()).parent.unwrap();
+    assert_eq!(tree.get(arg).kind, CstKind::Node("CallArg"));
+    assert_eq!(tree.text(arg), b"x");
+    let call = tree.get(arg).parent.unwrap();
+    assert_eq!(tree.get(call).kind, CstKind::Node("CallExpr"));
+    assert_eq!(tree.text(call), b"f( /* a */ (x) )");
+
+    let mut out = Vec::new();
+    tree.print_with(&[(arg, b"y")], &mut out);
+    assert_eq!(out, b"f( /* a */ (y) ) // b\n");
+  }
+}
@@ -0,0 +1,143 @@
+use crate::cfg::MinifyOptions;
+use crate::diagnostic::Diagnostic;
+use crate::diagnostic::Severity;
+use crate::limits::scan_tokens;
+use crate::minify;
+use crate::stats::MinifyStats;
+use parse_js::session::Session;
+use parse_js::token::TokenType;
+use std::convert::Infallible;
+use std::thread;
+
+// Stack used by each level of the estimated depth when parsing, minifying, and emitting, with some headroom. Measured in debug builds, which use the most, with deeply nested brackets, functions, and chains of operators; `!!!a` uses the most at about 13 KiB per level.
+const STACK_PER_LEVEL: usize = 16 * 1024;
+// Stack for everything other than the levels of the tree.
+const BASE_STACK: usize = 1024 * 1024;
+// Code estimated to be nested less deeply than this is minified on the calling thread, whose stack is usually at least 2 MiB (the default for threads spawned by Rust).
+const MAX_DEPTH_ON_CALLER: usize = 64;
+
+// Whether a token can't be within an expression continuing from the previous token, so the depth of the tree resets (e.g. `;`, or `return` after an expression without a semicolon).
+fn ends_expression_chain(typ: TokenType) -> bool {
+  matches!(
+    typ,
+    TokenType::Semicolon
+      | TokenType::Comma
+      | TokenType::KeywordBreak
+      | TokenType::KeywordCase
+      | TokenType::KeywordConst
+      | TokenType::KeywordContinue
+      | TokenType::KeywordDefault
+      | TokenType::KeywordDo
+      | TokenType::KeywordFor
+      | TokenType::KeywordIf
+      | TokenType::KeywordLet
+      | TokenType::KeywordReturn
+      | TokenType::KeywordSwitch
+      | TokenType::KeywordThrow
+      | TokenType::KeywordTry
+      | TokenType::KeywordVar
+      | TokenType::KeywordWhile
+  )
+}
+
+// Estimates an upper bound of how deeply nested the parsed tree will be, without parsing. Each bracket is a level, and so is each token of an expression, as chains of operators (e.g. `a + b + c`, `a ? b : c ? d : e`, `!!!a`, or `a.b.c`) nest one node per operator.
+pub(crate) fn estimate_depth(source: &[u8]) -> usize {
+  // Tokens in the current expression at each level of brackets.
+  let mut levels: Vec<usize> = vec![0];
+  let mut tokens = 0;
+  let mut max = 0;
+  let _ = scan_tokens::<Infallible>(source, |typ, _, depth| {
+    while levels.len() <= depth {
+      levels.push(0);
+    }
+    while levels.len() > depth + 1 {
+      tokens -= levels.pop().unwrap();
+    }
+    let current = levels.last_mut().unwrap();
+    if ends_expression_chain(typ) {
+      tokens -= *current;
+      *current = 0;
+    } else {
+      *current += 1;
+      tokens += 1;
+    };
+    max = max.max(depth + tokens);
+    Ok(())
+  });
+  max
+}
+
+/// Minifies code like [minify], but never overflows the stack, however deeply the code is nested. Parsing, minifying, and emitting are recursive, so deeply nested code (such as thousands of nested parentheses or chained ternaries, which are common in generated code and fuzzer inputs) can overflow the stack of the calling thread and abort the process. This estimates how deeply the code is nested before parsing it, and if it's deep, minifies it on a new thread with a large enough stack, so the depth is only bounded by memory.
+///
+/// A new [Session] is used, and errors are returned as a [Diagnostic] as they can't borrow from it. If there isn't enough memory for the stack, the error has a code of `E_NESTED_TOO_DEEPLY`. Use [MinifyOptions::limits] to reject code that would use too much memory instead.
+///
+/// # Examples
+///
+/// ```
+/// use minify_js::{MinifyOptions, minify_deep};
+///
+/// let code = format!("let x = {}1{};", "(".repeat(5_000), ")".repeat(5_000));
+/// let mut out = Vec::new();
+/// minify_deep(code.as_bytes(), &MinifyOptions::new(), &mut out).unwrap();
+/// assert_eq!(out.as_slice(), b"let x=1");
+/// ```
+pub fn minify_deep(
+  source: &[u8],
+  cfg: &MinifyOptions,
+  output: &mut Vec<u8>,
+) -> Result<MinifyStats, Diagnostic> {
+  let run = |output: &mut Vec<u8>| {
+    let session = Session::new();
+    minify(&session, source, cfg, output).map_err(|err| Diagnostic::from_minify_error(source, &err))
+  };
+  let depth = estimate_depth(source);
+  if depth < MAX_DEPTH_ON_CALLER {
+    return run(output);
+  };
+  let stack_size = depth
+    .checked_mul(STACK_PER_LEVEL)
+    .and_then(|s| s.checked_add(BASE_STACK));
+  thread::scope(|scope| {
+    let spawned = stack_size.and_then(|stack_size| {
+      thread::Builder::new()
+        .name("minify-js-deep".to_string())
+        .stack_size(stack_size)
+        .spawn_scoped(scope, || run(output))
+        .ok()
+    });
+    match spawned {
+      Some(handle) => handle
+        .join()
+        .unwrap_or_else(|panic| std::panic::resume_unwind(panic)),
+      None => Err(Diagnostic {
+        code: "E_NESTED_TOO_DEEPLY",
+        message: format!(
+          "source code is nested about {} levels deep, which needs more memory for the stack than is available",
+          depth
+        ),
+        severity: Severity::Error,
+        start: 0,
+        end: 0,
+        line: 1,
+        column: 1,
+      }),
+    }
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::estimate_depth;
+
+  #[test]
+  fn test_estimate_depth() {
+    assert_eq!(estimate_depth(b""), 0);
+    // Statements and arguments don't nest.
+    assert_eq!(estimate_depth(b"a; b; c; d"), 1);
+    assert_eq!(estimate_depth(b"f(a, b, c)"), 4);
+    assert!(estimate_depth("(".repeat(100).as_bytes()) >= 100);
+    assert!(estimate_depth("a?b:".repeat(100).as_bytes()) >= 100);
+    assert!(estimate_depth("!".repeat(100).as_bytes()) >= 100);
+    assert!(estimate_depth(format!("a{}", ".b".repeat(100)).as_bytes()) >= 100);
+  }
+}
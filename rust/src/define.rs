@@ -0,0 +1,180 @@
+use crate::Transform;
+use parse_js::ast::ArrayElement;
+use parse_js::ast::NodeData;
+use parse_js::ast::Syntax;
+use parse_js::parse;
+use parse_js::parse::toplevel::TopLevelMode;
+use parse_js::session::Session;
+use parse_js::symbol::Scope;
+use parse_js::visit::JourneyControls;
+use parse_js::visit::Visitor;
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::str::FromStr;
+
+/// A global variable, or a property of one (e.g. `process.env.NODE_ENV`), to replace with a constant expression, such as for build-time feature flags. It can also be parsed from a string in the form `NAME=VALUE`.
+///
+/// # Examples
+///
+/// ```
+/// use minify_js::Define;
+///
+/// let define = Define::new("process.env.NODE_ENV", "\"production\"").unwrap();
+/// assert!(Define::new("process.env.NODE_ENV", "() => 1").is_err());
+/// assert!("DEBUG=false".parse::<Define>().is_ok());
+/// ```
+#[derive(Clone, Debug)]
+pub struct Define {
+  path: Vec<String>,
+  value: String,
+}
+
+impl Define {
+  /// Returns an error if `name` isn't an identifier or property path, or `value` isn't an expression without functions or classes.
+  pub fn new(name: &str, value: &str) -> Result<Define, String> {
+    let path = name.split('.').map(str::to_string).collect::<Vec<_>>();
+    if !path.iter().all(|p| is_identifier(p)) {
+      return Err(format!("`{}` isn't an identifier or property path", name));
+    };
+    if parse_value(&Session::new(), value).is_none() {
+      return Err(format!(
+        "`{}` isn't an expression without functions or classes",
+        value
+      ));
+    };
+    Ok(Define {
+      path,
+      value: value.to_string(),
+    })
+  }
+}
+
+impl Display for Define {
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    write!(f, "{}={}", self.path.join("."), self.value)
+  }
+}
+
+impl FromStr for Define {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let (name, value) = s
+      .split_once('=')
+      .ok_or_else(|| format!("expected NAME=VALUE but found `{}`", s))?;
+    Define::new(name, value)
+  }
+}
+
+fn is_identifier(s: &str) -> bool {
+  let mut chars = s.chars();
+  chars
+    .next()
+    .is_some_and(|c| c.is_alphabetic() || c == '_' || c == '$')
+    && chars.all(|c| c.is_alphanumeric() || c == '_' || c == '$')
+}
+
+// Parses the value of a define in the session, so it can be inserted into the tree. It's parsed as an array element so that object literals aren't parsed as blocks. Functions and classes aren't allowed, as their scopes and declarations wouldn't belong to the tree.
+fn parse_value<'a>(session: &'a Session, value: &str) -> Option<Syntax<'a>> {
+  let source = session
+    .get_allocator()
+    .alloc_slice_copy(format!("[{}\n]", value).as_bytes());
+  let node = parse(session, source, TopLevelMode::Global).ok()?;
+  let Syntax::TopLevel { body } = &mut node.stx else {
+    return None;
+  };
+  let [stmt] = body.as_mut_slice() else {
+    return None;
+  };
+  let Syntax::ExpressionStmt { expression } = &mut stmt.stx else {
+    return None;
+  };
+  let Syntax::LiteralArrayExpr { elements } = &mut expression.stx else {
+    return None;
+  };
+  let [ArrayElement::Single(value)] = elements.as_mut_slice() else {
+    return None;
+  };
+  let mut check = CheckValue { valid: true };
+  check.visit(value);
+  check
+    .valid
+    .then(|| std::mem::replace(&mut value.stx, Syntax::LiteralNull {}))
+}
+
+struct CheckValue {
+  valid: bool,
+}
+
+impl<'a> Visitor<'a> for CheckValue {
+  fn on_syntax_down(&mut self, node: &mut NodeData<'a>, ctl: &mut JourneyControls) {
+    if matches!(
+      node.stx,
+      Syntax::ArrowFunctionExpr { .. } | Syntax::FunctionExpr { .. } | Syntax::ClassExpr { .. }
+    ) {
+      self.valid = false;
+      ctl.skip();
+    };
+  }
+}
+
+// Moves nodes inserted from a define into the scope they replace a node in.
+struct SetScope<'a> {
+  scope: Scope<'a>,
+}
+
+impl<'a> Visitor<'a> for SetScope<'a> {
+  fn on_syntax_down(&mut self, node: &mut NodeData<'a>, _ctl: &mut JourneyControls) {
+    node.scope = self.scope;
+  }
+}
+
+struct ReplaceDefines<'a, 'd> {
+  session: &'a Session,
+  defines: &'d [Define],
+}
+
+// Whether the node is the global variable or property path. Variables declared in the code aren't replaced.
+fn matches_path(node: &NodeData, path: &[String]) -> bool {
+  let Some((last, rest)) = path.split_last() else {
+    return false;
+  };
+  match &node.stx {
+    Syntax::IdentifierExpr { name } => {
+      rest.is_empty()
+        && name.as_slice() == last.as_bytes()
+        && node.scope.find_symbol(*name).is_none()
+    }
+    Syntax::MemberExpr {
+      optional_chaining: false,
+      assignment_target: false,
+      left,
+      right,
+      ..
+    } => right.as_slice() == last.as_bytes() && matches_path(left, rest),
+    _ => false,
+  }
+}
+
+impl<'a, 'd> Visitor<'a> for ReplaceDefines<'a, 'd> {
+  fn on_syntax_down(&mut self, node: &mut NodeData<'a>, ctl: &mut JourneyControls) {
+    // Visiting top-down means the longest path is replaced, e.g. `a.b` rather than `a`.
+    let Some(define) = self.defines.iter().find(|d| matches_path(node, &d.path)) else {
+      return;
+    };
+    node.stx = parse_value(self.session, &define.value).unwrap();
+    SetScope { scope: node.scope }.visit(node);
+    ctl.skip();
+  }
+}
+
+// Replaces all defines in a parsed tree. See `replace_defines`.
+pub(crate) fn replace_defines_in<'a>(session: &'a Session, node: &mut NodeData<'a>, defines: &[Define]) {
+  ReplaceDefines { session, defines }.visit(node)
+}
+
+/// Returns a transform that replaces all defines, to add to [MinifyOptions::before_minify](crate::MinifyOptions::before_minify). Variables declared in the code with the same name aren't replaced, and if defines overlap, the longest path is replaced (e.g. `a.b` rather than `a`). [MinifyOptions::defines](crate::MinifyOptions::defines) does the same before any other transform.
+pub fn replace_defines(defines: Vec<Define>) -> Transform {
+  Transform::new(move |session, node| replace_defines_in(session, node, &defines))
+}
@@ -0,0 +1,154 @@
+use crate::metadata::is_undeclared;
+use crate::metadata::string_arg;
+use parse_js::ast::LiteralTemplatePart;
+use parse_js::ast::NodeData;
+use parse_js::ast::Syntax;
+use parse_js::error::SyntaxError;
+use parse_js::parse;
+use parse_js::parse::toplevel::TopLevelMode;
+use parse_js::session::Session;
+use parse_js::visit::JourneyControls;
+use parse_js::visit::Visitor;
+
+/// How a module is depended on, as returned by [module_dependencies].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DependencyKind {
+  /// An import statement, e.g. `import a from "x"`.
+  Import,
+  /// A re-export, e.g. `export * from "x"` or `export { a } from "x"`.
+  Reexport,
+  /// A dynamic import, e.g. `import("x")`.
+  DynamicImport,
+  /// A CommonJS `require("x")` call, where `require` isn't declared in the code.
+  Require,
+}
+
+/// A module specifier found by [module_dependencies].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Dependency {
+  /// The module specifier, with escapes resolved.
+  pub specifier: String,
+  pub kind: DependencyKind,
+}
+
+struct DependencyVisitor {
+  // Each dependency with the start of its node, as children aren't always visited in source order (e.g. call arguments before the callee).
+  out: Vec<(usize, Dependency)>,
+}
+
+impl DependencyVisitor {
+  fn push(&mut self, node: &NodeData, specifier: &str, kind: DependencyKind) {
+    self.out.push((node.loc.start(), Dependency {
+      specifier: specifier.to_string(),
+      kind,
+    }));
+  }
+}
+
+impl<'a> Visitor<'a> for DependencyVisitor {
+  fn on_syntax_down(&mut self, node: &mut NodeData<'a>, ctl: &mut JourneyControls) {
+    match &node.stx {
+      Syntax::ImportStmt { module, .. } => {
+        self.push(node, module, DependencyKind::Import);
+        ctl.skip();
+      }
+      Syntax::ExportListStmt { from, .. } => {
+        if let Some(from) = from {
+          self.push(node, from, DependencyKind::Reexport);
+        };
+        ctl.skip();
+      }
+      Syntax::ImportExpr { module } => match &module.stx {
+        Syntax::LiteralStringExpr { value } => {
+          self.push(node, value, DependencyKind::DynamicImport);
+        }
+        Syntax::LiteralTemplateExpr { parts } => {
+          if let [LiteralTemplatePart::String(value)] = parts.as_slice() {
+            self.push(node, value, DependencyKind::DynamicImport);
+          };
+        }
+        _ => {}
+      },
+      Syntax::CallExpr {
+        callee, arguments, ..
+      } if is_undeclared(callee, "require") => {
+        if let [arg] = arguments.as_slice() {
+          if let Some(specifier) = string_arg(arg) {
+            self.push(node, specifier, DependencyKind::Require);
+          };
+        };
+      }
+      _ => {}
+    };
+  }
+}
+
+/// Parses code and returns the modules it depends on, in source order and including duplicates, without minifying it, so bundlers can build a module graph with the same parser. This finds import statements, re-exports, dynamic imports, and `require` calls. Dynamic imports and `require` calls are only included if their argument is a string literal (or a template without substitutions for dynamic imports), as others can't be resolved without running the code. parse-js can't parse dynamic imports yet, so code using them fails with a syntax error.
+///
+/// Use [module_metadata](crate::module_metadata) on an already parsed tree to also find the imported and exported names.
+///
+/// # Examples
+///
+/// ```
+/// use minify_js::{module_dependencies, Dependency, DependencyKind, Session, TopLevelMode};
+///
+/// let code = b"import a from './a.js'; export * from './b.js'; require('c'); require(d);";
+/// let session = Session::new();
+/// let dependencies = module_dependencies(&session, code, TopLevelMode::Module).unwrap();
+/// assert_eq!(dependencies[0], Dependency {
+///   specifier: "./a.js".to_string(),
+///   kind: DependencyKind::Import,
+/// });
+/// assert_eq!(dependencies.len(), 3);
+/// assert_eq!(dependencies[2].kind, DependencyKind::Require);
+/// ```
+pub fn module_dependencies<'a>(
+  session: &'a Session,
+  source: &'a [u8],
+  top_level_mode: TopLevelMode,
+) -> Result<Vec<Dependency>, SyntaxError<'a>> {
+  let parsed = parse(session, source, top_level_mode)?;
+  let mut visitor = DependencyVisitor { out: Vec::new() };
+  visitor.visit(parsed);
+  visitor.out.sort_by_key(|(start, _)| *start);
+  Ok(visitor.out.into_iter().map(|(_, d)| d).collect())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::module_dependencies;
+  use super::DependencyKind;
+  use crate::Session;
+  use crate::TopLevelMode;
+
+  #[test]
+  fn test_module_dependencies() {
+    let source = r#"
+      import * as a from "a";
+      import { b } from 'b';
+      export { c } from "c";
+      export { b };
+      require("h");
+      require("i", 1);
+      function j(require) { require("k"); }
+      a.require("l");
+    "#;
+    let session = Session::new();
+    let dependencies = module_dependencies(&session, source.as_bytes(), TopLevelMode::Module)
+      .unwrap()
+      .into_iter()
+      .map(|d| (d.specifier, d.kind))
+      .collect::<Vec<_>>();
+    let dependency = |specifier: &str, kind| (specifier.to_string(), kind);
+    assert_eq!(
+      dependencies,
+      [
+        dependency("a", DependencyKind::Import),
+        dependency("b", DependencyKind::Import),
+        dependency("c", DependencyKind::Reexport),
+        dependency("h", DependencyKind::Require),
+      ]
+    );
+    assert!(module_dependencies(&session, b"import a from", TopLevelMode::Module).is_err());
+  }
+}
@@ -0,0 +1,266 @@
+use crate::error::line_column;
+use crate::error::MinifyError;
+use crate::features::UnsupportedSyntax;
+use crate::identifier::decode_unicode_escape;
+use crate::identifier::RESERVED_WORDS;
+use crate::json::write_json_string;
+use crate::limits::Limit;
+use crate::limits::LimitExceeded;
+use parse_js::error::SyntaxError;
+use parse_js::error::SyntaxErrorType;
+use std::error::Error;
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Write;
+
+/// How serious a [Diagnostic] is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+  /// The code is invalid and couldn't be minified.
+  Error,
+  /// The code is valid, but something about it is suspicious or prevents some minification.
+  Warning,
+}
+
+impl Severity {
+  pub fn as_str(self) -> &'static str {
+    match self {
+      Severity::Error => "error",
+      Severity::Warning => "warning",
+    }
+  }
+}
+
+/// A problem found in the source code, in a form suitable for editors and CI tools.
+///
+/// Unlike [SyntaxError], which borrows the source code and can't be sent to another thread, this owns all its data and is `Send + Sync`, so it can be used as an error in multithreaded code.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+  /// Stable identifier of the kind of problem, such as `E_LINE_TERMINATOR_IN_STRING`. Codes won't change between versions, unlike messages.
+  pub code: &'static str,
+  pub message: String,
+  pub severity: Severity,
+  /// Byte offset of the start of the problem in the source.
+  pub start: usize,
+  /// Byte offset of the end of the problem in the source.
+  pub end: usize,
+  /// 1-based line of `start`.
+  pub line: usize,
+  /// 1-based column of `start`, in characters.
+  pub column: usize,
+}
+
+/// Returns the stable code of a syntax error type, such as `E_LINE_TERMINATOR_IN_STRING`.
+pub fn syntax_error_code(typ: SyntaxErrorType) -> &'static str {
+  match typ {
+    SyntaxErrorType::ExpectedNotFound => "E_EXPECTED_NOT_FOUND",
+    SyntaxErrorType::ExpectedSyntax(_) => "E_EXPECTED_SYNTAX",
+    SyntaxErrorType::ForLoopHeaderHasInvalidLhs => "E_FOR_LOOP_HEADER_HAS_INVALID_LHS",
+    SyntaxErrorType::ForLoopHeaderHasMultipleDeclarators => {
+      "E_FOR_LOOP_HEADER_HAS_MULTIPLE_DECLARATORS"
+    }
+    SyntaxErrorType::ForLoopHeaderHasNoLhs => "E_FOR_LOOP_HEADER_HAS_NO_LHS",
+    SyntaxErrorType::InvalidAssigmentTarget => "E_INVALID_ASSIGNMENT_TARGET",
+    SyntaxErrorType::InvalidCharacterEscape => "E_INVALID_CHARACTER_ESCAPE",
+    SyntaxErrorType::LineTerminatorAfterArrowFunctionParameters => {
+      "E_LINE_TERMINATOR_AFTER_ARROW_FUNCTION_PARAMETERS"
+    }
+    SyntaxErrorType::LineTerminatorAfterThrow => "E_LINE_TERMINATOR_AFTER_THROW",
+    SyntaxErrorType::LineTerminatorAfterYield => "E_LINE_TERMINATOR_AFTER_YIELD",
+    SyntaxErrorType::LineTerminatorInRegex => "E_LINE_TERMINATOR_IN_REGEX",
+    SyntaxErrorType::LineTerminatorInString => "E_LINE_TERMINATOR_IN_STRING",
+    SyntaxErrorType::MalformedLiteralNumber => "E_MALFORMED_LITERAL_NUMBER",
+    SyntaxErrorType::JsxClosingTagMismatch => "E_JSX_CLOSING_TAG_MISMATCH",
+    SyntaxErrorType::RequiredTokenNotFound(_) => "E_REQUIRED_TOKEN_NOT_FOUND",
+    SyntaxErrorType::TryStatementHasNoCatchOrFinally => "E_TRY_STATEMENT_HAS_NO_CATCH_OR_FINALLY",
+    SyntaxErrorType::UnexpectedEnd => "E_UNEXPECTED_END",
+  }
+}
+
+/// Returns a human-readable description of a syntax error, such as `expected expression operand but found Semicolon`.
+pub fn syntax_error_message(err: &SyntaxError) -> String {
+  let mut message = match err.typ {
+    SyntaxErrorType::ExpectedNotFound => "unexpected character".to_string(),
+    SyntaxErrorType::ExpectedSyntax(s) => format!("expected {s}"),
+    SyntaxErrorType::ForLoopHeaderHasInvalidLhs => "invalid left side of for loop".to_string(),
+    SyntaxErrorType::ForLoopHeaderHasMultipleDeclarators => {
+      "more than one variable declared in for-in or for-of loop".to_string()
+    }
+    SyntaxErrorType::ForLoopHeaderHasNoLhs => "missing left side of for loop".to_string(),
+    SyntaxErrorType::InvalidAssigmentTarget => "invalid assignment target".to_string(),
+    SyntaxErrorType::InvalidCharacterEscape => "invalid character escape".to_string(),
+    SyntaxErrorType::LineTerminatorAfterArrowFunctionParameters => {
+      "line break after arrow function parameters".to_string()
+    }
+    SyntaxErrorType::LineTerminatorAfterThrow => "line break after `throw`".to_string(),
+    SyntaxErrorType::LineTerminatorAfterYield => "line break after `yield`".to_string(),
+    SyntaxErrorType::LineTerminatorInRegex => "line break in regular expression".to_string(),
+    SyntaxErrorType::LineTerminatorInString => "line break in string".to_string(),
+    SyntaxErrorType::MalformedLiteralNumber => "malformed number".to_string(),
+    SyntaxErrorType::JsxClosingTagMismatch => {
+      "JSX closing tag doesn't match opening tag".to_string()
+    }
+    SyntaxErrorType::RequiredTokenNotFound(t) => format!("expected {t:?}"),
+    SyntaxErrorType::TryStatementHasNoCatchOrFinally => {
+      "try statement without catch or finally".to_string()
+    }
+    SyntaxErrorType::UnexpectedEnd => "unexpected end of code".to_string(),
+  };
+  if let Some(t) = err.actual_token {
+    write!(message, " but found {t:?}").unwrap();
+  };
+  message
+}
+
+// If the code at `pos` continues an identifier with a Unicode escape (e.g. `\u0069f`), returns what the whole identifier decodes to. Valid escapes are decoded before parsing (see `decode_identifier_escapes`), so those left are escaped reserved words or escapes for characters that can't be in an identifier.
+fn escaped_identifier(source: &[u8], pos: usize) -> Option<String> {
+  let start = source[..pos]
+    .iter()
+    .rposition(|&c| !(c.is_ascii_alphanumeric() || c == b'_' || c == b'$'))
+    .map_or(0, |p| p + 1);
+  let s = &source[start..];
+  let mut name = String::new();
+  let mut escaped = false;
+  let mut i = 0;
+  loop {
+    if let Some((c, len)) = decode_unicode_escape(&s[i..]) {
+      name.push(c);
+      escaped = true;
+      i += len;
+      continue;
+    };
+    let Some(c) = String::from_utf8_lossy(&s[i..s.len().min(i + 4)])
+      .chars()
+      .next()
+      .filter(|&c| c.is_alphanumeric() || c == '_' || c == '$')
+    else {
+      break;
+    };
+    name.push(c);
+    i += c.len_utf8();
+  }
+  escaped.then_some(name)
+}
+
+impl Diagnostic {
+  /// Creates an error diagnostic from a syntax error returned from parsing `source`.
+  ///
+  /// An escaped reserved word (e.g. `\u0069f`) is neither that keyword nor an identifier, except as a property name, so its message says so.
+  pub fn from_syntax_error(source: &[u8], err: &SyntaxError) -> Diagnostic {
+    let (line, column, _) = line_column(source, err.source.start());
+    let escaped = match err.typ {
+      SyntaxErrorType::ExpectedNotFound => escaped_identifier(source, err.source.start()),
+      _ => None,
+    };
+    let message = match escaped {
+      Some(name) if RESERVED_WORDS.contains(&name.as_str()) => format!(
+        "escaped `{name}` is a reserved word, so can't be used as a keyword or identifier"
+      ),
+      Some(name) => {
+        format!("identifier with a Unicode escape for a character that can't be in one (`{name}`)")
+      }
+      None => syntax_error_message(err),
+    };
+    Diagnostic {
+      code: syntax_error_code(err.typ),
+      message,
+      severity: Severity::Error,
+      start: err.source.start(),
+      end: err.source.end(),
+      line,
+      column,
+    }
+  }
+
+  /// Creates an error diagnostic from a limit exceeded by `source`. It has a code of `E_INPUT_TOO_LONG`, `E_TOO_MANY_TOKENS`, or `E_NESTED_TOO_DEEPLY`.
+  pub fn from_limit_exceeded(source: &[u8], err: &LimitExceeded) -> Diagnostic {
+    let (line, column, _) = line_column(source, err.position);
+    Diagnostic {
+      code: match err.limit {
+        Limit::InputLen => "E_INPUT_TOO_LONG",
+        Limit::Tokens => "E_TOO_MANY_TOKENS",
+        Limit::NestingDepth => "E_NESTED_TOO_DEEPLY",
+      },
+      message: err.to_string(),
+      severity: Severity::Error,
+      start: err.position,
+      end: err.position,
+      line,
+      column,
+    }
+  }
+
+  /// Creates an error diagnostic from syntax in `source` that can't be lowered to ES5. It has a code of `E_UNSUPPORTED_SYNTAX`.
+  pub fn from_unsupported_syntax(source: &[u8], err: &UnsupportedSyntax) -> Diagnostic {
+    let (line, column, _) = line_column(source, err.position);
+    Diagnostic {
+      code: "E_UNSUPPORTED_SYNTAX",
+      message: err.to_string(),
+      severity: Severity::Error,
+      start: err.position,
+      end: err.position,
+      line,
+      column,
+    }
+  }
+
+  /// Creates an error diagnostic from an error returned from minifying `source`. Cancellation has a code of `E_CANCELLED` and is at the start of the source. Internal errors are already diagnostics.
+  pub fn from_minify_error(source: &[u8], err: &MinifyError) -> Diagnostic {
+    match err {
+      MinifyError::Syntax(err) => Diagnostic::from_syntax_error(source, err),
+      MinifyError::LimitExceeded(err) => Diagnostic::from_limit_exceeded(source, err),
+      MinifyError::Unsupported(err) => Diagnostic::from_unsupported_syntax(source, err),
+      MinifyError::Cancelled => Diagnostic {
+        code: "E_CANCELLED",
+        message: err.to_string(),
+        severity: Severity::Error,
+        start: 0,
+        end: 0,
+        line: 1,
+        column: 1,
+      },
+      MinifyError::Internal(diagnostic) => diagnostic.clone(),
+    }
+  }
+}
+
+impl Display for Diagnostic {
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    write!(
+      f,
+      "{}[{}] at {}:{}: {}",
+      self.severity.as_str(),
+      self.code,
+      self.line,
+      self.column,
+      self.message
+    )
+  }
+}
+
+impl Error for Diagnostic {}
+
+/// Serialises diagnostics as a JSON array, in the form `[{"code":"E_UNEXPECTED_END","message":"unexpected end of code","severity":"error","span":{"start":8,"end":8,"line":1,"column":9}}]`.
+pub fn diagnostics_to_json(diagnostics: &[Diagnostic]) -> String {
+  let mut out = String::from("[");
+  for (i, d) in diagnostics.iter().enumerate() {
+    if i > 0 {
+      out.push(',');
+    };
+    write!(out, "{{\"code\":\"{}\",\"message\":", d.code).unwrap();
+    write_json_string(&mut out, &d.message);
+    write!(
+      out,
+      ",\"severity\":\"{}\",\"span\":{{\"start\":{},\"end\":{},\"line\":{},\"column\":{}}}}}",
+      d.severity.as_str(),
+      d.start,
+      d.end,
+      d.line,
+      d.column
+    )
+    .unwrap();
+  }
+  out.push(']');
+  out
+}
@@ -0,0 +1,188 @@
+use crate::early_error::check_early_errors;
+use crate::early_error::StatementEarlyErrors;
+use crate::Diagnostic;
+use parse_js::ast::Node;
+use parse_js::error::SyntaxResult;
+use parse_js::lex::Lexer;
+use parse_js::parse;
+use parse_js::parse::pattern::ParsePatternRules;
+use parse_js::parse::toplevel::TopLevelMode;
+use parse_js::parse::ParseCtx;
+use parse_js::parse::Parser;
+use parse_js::session::Session;
+use parse_js::symbol::Scope;
+use parse_js::symbol::ScopeType;
+use parse_js::symbol::SymbolGenerator;
+use parse_js::token::TokenType;
+use std::ops::Range;
+
+// Parses top-level statements like parse_js::parse, and returns where each one starts, which is right after the previous statement, so includes any whitespace and comments before it.
+fn statement_starts(
+  session: &Session,
+  source: &[u8],
+  top_level_mode: TopLevelMode,
+) -> Result<Vec<usize>, Diagnostic> {
+  let scope_type = match top_level_mode {
+    TopLevelMode::Global => ScopeType::Global,
+    TopLevelMode::Module => ScopeType::Module,
+  };
+  let ctx = ParseCtx {
+    scope: Scope::new(session, SymbolGenerator::new(), None, scope_type),
+    session,
+    rules: ParsePatternRules {
+      await_allowed: true,
+      yield_allowed: true,
+    },
+  };
+  let mut parser = Parser::new(Lexer::new(source));
+  let mut starts = Vec::new();
+  let mut early_errors = StatementEarlyErrors::default();
+  let mut parse_all = || -> SyntaxResult<()> {
+    loop {
+      let start = parser.checkpoint();
+      if parser.consume_if(TokenType::EOF)?.is_match() {
+        return Ok(());
+      };
+      early_errors.check(parser.parse_stmt(ctx)?)?;
+      starts.push(parser.since_checkpoint(start).start());
+    }
+  };
+  parse_all().map_err(|err| Diagnostic::from_syntax_error(source, &err))?;
+  Ok(starts)
+}
+
+/// Source code being edited, such as a file open in an editor, that's reparsed incrementally as it changes, to keep its syntax error and top-level statements up to date on every keystroke in large files.
+///
+/// An edit only reparses the top-level statements it touches and the statements either side of them, as long as the code is valid and the edit doesn't change where the statements after it start (e.g. by opening a comment or template). Otherwise, all the code is reparsed.
+///
+/// Parsed trees are allocated in a [Session] and have scopes built while parsing, so they can't be patched; use [Document::parse] to get a tree of the current code when needed.
+///
+/// # Examples
+///
+/// ```
+/// use minify_js::{Document, TopLevelMode};
+///
+/// let mut doc = Document::new(b"let a = 1;\nlet b = 2;\nlet c = 3;\nlet d = 4;\n", TopLevelMode::Global);
+/// assert_eq!(doc.statements(), [0..10, 10..21, 21..32, 32..44]);
+/// // Only the first three statements are reparsed.
+/// assert_eq!(doc.edit(15..16, b"bb"), 0..33);
+/// assert_eq!(doc.statements(), [0..10, 10..22, 22..33, 33..45]);
+/// doc.edit(5..5, b"=");
+/// assert_eq!(doc.error().unwrap().code, "E_EXPECTED_SYNTAX");
+/// assert!(doc.statements().is_empty());
+/// ```
+pub struct Document {
+  source: Vec<u8>,
+  top_level_mode: TopLevelMode,
+  // Start of each top-level statement, or empty if there's an error.
+  starts: Vec<usize>,
+  error: Option<Diagnostic>,
+  // Reused for every reparse.
+  session: Session,
+}
+
+impl Document {
+  pub fn new(source: &[u8], top_level_mode: TopLevelMode) -> Document {
+    let mut doc = Document {
+      source: source.to_vec(),
+      top_level_mode,
+      starts: Vec::new(),
+      error: None,
+      session: Session::new(),
+    };
+    doc.reparse_all();
+    doc
+  }
+
+  pub fn source(&self) -> &[u8] {
+    &self.source
+  }
+
+  /// The first syntax error in the code, if any.
+  pub fn error(&self) -> Option<&Diagnostic> {
+    self.error.as_ref()
+  }
+
+  /// Byte ranges of the top-level statements, including any whitespace and comments before each one, and after the last one, so they cover all the code. Empty if there's a syntax error.
+  pub fn statements(&self) -> Vec<Range<usize>> {
+    self
+      .starts
+      .iter()
+      .enumerate()
+      .map(|(i, &start)| start..self.statement_end(i))
+      .collect()
+  }
+
+  fn statement_end(&self, i: usize) -> usize {
+    self.starts.get(i + 1).copied().unwrap_or(self.source.len())
+  }
+
+  /// Parses all the code into a tree.
+  pub fn parse<'a>(&'a self, session: &'a Session) -> SyntaxResult<'a, Node<'a>> {
+    let node = parse(session, &self.source, self.top_level_mode)?;
+    check_early_errors(node)?;
+    Ok(node)
+  }
+
+  fn reparse_all(&mut self) -> Range<usize> {
+    self.session.reset();
+    match statement_starts(&self.session, &self.source, self.top_level_mode) {
+      Ok(starts) => {
+        self.starts = starts;
+        self.error = None;
+      }
+      Err(err) => {
+        self.starts.clear();
+        self.error = Some(err);
+      }
+    };
+    0..self.source.len()
+  }
+
+  /// Replaces the code in `range` with `text`, and reparses the affected code, whose byte range in the new code is returned.
+  ///
+  /// # Panics
+  ///
+  /// Panics if `range` is out of bounds.
+  pub fn edit(&mut self, range: Range<usize>, text: &[u8]) -> Range<usize> {
+    let old_len = self.source.len();
+    self.source.splice(range.clone(), text.iter().copied());
+    if self.starts.is_empty() {
+      return self.reparse_all();
+    };
+    let shift = |pos: usize| pos + text.len() - range.len();
+    // Statements whose ranges touch the edit. An edit at the boundary of two statements touches both, as it could join them.
+    let first = self
+      .starts
+      .partition_point(|&s| s < range.start)
+      .saturating_sub(1);
+    let last = self.starts.partition_point(|&s| s <= range.end) - 1;
+    // Also reparse the statements either side, as an edit can change where the statement before it ends, or join the statement after it.
+    let lo = first.saturating_sub(1);
+    let hi = (last + 2).min(self.starts.len());
+    let start = self.starts[lo];
+    let end = shift(self.starts.get(hi).copied().unwrap_or(old_len));
+
+    self.session.reset();
+    let Ok(new_starts) =
+      statement_starts(&self.session, &self.source[start..end], self.top_level_mode)
+    else {
+      // Statements outside the reparsed code could be affected, such as by an unterminated comment, so the error is found by reparsing everything.
+      return self.reparse_all();
+    };
+    // The last reparsed statement is unchanged by the edit, so if it still starts in the same place, the statements after it do too.
+    if hi < self.starts.len() {
+      let unchanged = shift(self.starts[hi - 1]) - start;
+      if new_starts.binary_search(&unchanged).is_err() {
+        return self.reparse_all();
+      };
+    };
+    for s in self.starts[hi..].iter_mut() {
+      *s = shift(*s);
+    }
+    self
+      .starts
+      .splice(lo..hi, new_starts.into_iter().map(|s| start + s));
+    start..end
+  }
+}
@@ -0,0 +1,499 @@
+use crate::cfg::Target;
+use crate::features::first_newer_than;
+use crate::features::SyntaxFeature;
+use crate::features::UnsupportedSyntax;
+use crate::minify::purity::closure_of;
+use crate::minify::purity::pattern_names;
+use parse_js::ast::new_node;
+use parse_js::ast::ClassOrObjectMemberKey;
+use parse_js::ast::ClassOrObjectMemberValue;
+use parse_js::ast::LiteralTemplatePart;
+use parse_js::ast::Node;
+use parse_js::ast::NodeData;
+use parse_js::ast::ObjectMemberType;
+use parse_js::ast::Syntax;
+use parse_js::ast::VarDeclMode;
+use parse_js::ast::VariableDeclarator;
+use parse_js::num::JsNumber;
+use parse_js::operator::OperatorName;
+use parse_js::session::Session;
+use parse_js::session::SessionVec;
+use parse_js::source::SourceRange;
+use parse_js::symbol::Scope;
+use parse_js::symbol::ScopeType;
+use parse_js::symbol::Symbol;
+use parse_js::visit::JourneyControls;
+use parse_js::visit::Visitor;
+use std::collections::HashSet;
+
+// Finds usages of `this`, `arguments`, and `super` that belong to the nearest non-arrow function, so we know whether an arrow function or method can be turned into a plain function expression.
+#[derive(Default)]
+struct FunctionContextUsage {
+  this: bool,
+  arguments: bool,
+  sup: bool,
+}
+
+impl<'a> Visitor<'a> for FunctionContextUsage {
+  fn on_syntax_down(&mut self, node: &mut NodeData<'a>, ctl: &mut JourneyControls) {
+    match &node.stx {
+      Syntax::FunctionDecl { .. } | Syntax::FunctionExpr { .. } => ctl.skip(),
+      // Class bodies can use the outer `this` in places (e.g. computed keys), so just assume it's used.
+      Syntax::ClassDecl { .. } | Syntax::ClassExpr { .. } => {
+        self.this = true;
+        ctl.skip();
+      }
+      Syntax::ThisExpr {} => self.this = true,
+      Syntax::SuperExpr {} => self.sup = true,
+      Syntax::IdentifierExpr { name } if name.as_slice() == b"arguments" => self.arguments = true,
+      _ => {}
+    };
+  }
+}
+
+fn function_context_usage<'a>(node: &mut NodeData<'a>) -> FunctionContextUsage {
+  let mut usage = FunctionContextUsage::default();
+  usage.visit(node);
+  usage
+}
+
+// Finds `let` and `const` declarations in loops that a function within the loop refers to. Each iteration has its own copy of them, which the function keeps, but there'd only be one if they became `var` declarations (e.g. in `for (let i = 0; i < 3; i++) fs.push(() => i)`).
+#[derive(Default)]
+struct LoopCaptures<'a> {
+  // The closure of each loop being visited.
+  loops: Vec<Option<Scope<'a>>>,
+  // Variables declared in a loop, with the position of their name.
+  in_loops: Vec<(Symbol, usize)>,
+  // Variables referred to from a closure other than the one they're declared in.
+  captured: HashSet<Symbol>,
+}
+
+impl<'a> Visitor<'a> for LoopCaptures<'a> {
+  fn on_syntax_down(&mut self, node: &mut NodeData<'a>, _ctl: &mut JourneyControls) {
+    match &node.stx {
+      Syntax::ForStmt { .. } | Syntax::WhileStmt { .. } | Syntax::DoWhileStmt { .. } => {
+        self.loops.push(closure_of(node.scope));
+      }
+      Syntax::VarDecl {
+        mode: VarDeclMode::Let | VarDeclMode::Const,
+        declarators,
+        ..
+      } if self.loops.contains(&closure_of(node.scope)) => {
+        let mut names = Vec::new();
+        for decl in declarators.iter() {
+          pattern_names(decl.pattern, &mut names);
+        }
+        for name in names {
+          if let Some(sym) = node.scope.find_symbol(name) {
+            self.in_loops.push((sym, name.start()));
+          };
+        }
+      }
+      Syntax::IdentifierExpr { name } | Syntax::IdentifierPattern { name } => {
+        if let Some((decl_scope, sym)) = node.scope.find_symbol_with_scope(*name) {
+          if closure_of(node.scope) != closure_of(decl_scope) {
+            self.captured.insert(sym);
+          };
+        };
+      }
+      _ => {}
+    };
+  }
+
+  fn on_syntax_up(&mut self, node: &mut NodeData<'a>) {
+    if let Syntax::ForStmt { .. } | Syntax::WhileStmt { .. } | Syntax::DoWhileStmt { .. } =
+      node.stx
+    {
+      self.loops.pop();
+    };
+  }
+}
+
+// Collects the names of all variables, so a new one can be picked that doesn't shadow any of them.
+#[derive(Default)]
+struct Names {
+  names: HashSet<Vec<u8>>,
+}
+
+impl<'a> Visitor<'a> for Names {
+  fn on_syntax_down(&mut self, node: &mut NodeData<'a>, _ctl: &mut JourneyControls) {
+    if let Syntax::IdentifierExpr { name }
+    | Syntax::IdentifierPattern { name }
+    | Syntax::ClassOrFunctionName { name } = node.stx
+    {
+      self.names.insert(name.as_slice().to_vec());
+    };
+  }
+}
+
+// Replaces `arguments` in an arrow function with the variable it's aliased to in the enclosing function. Functions within it have their own `arguments`, and arrow functions within it have already been lowered.
+struct AliasArguments<'a> {
+  alias: SourceRange<'a>,
+}
+
+impl<'a> Visitor<'a> for AliasArguments<'a> {
+  fn on_syntax_down(&mut self, node: &mut NodeData<'a>, ctl: &mut JourneyControls) {
+    match &mut node.stx {
+      Syntax::FunctionDecl { .. }
+      | Syntax::FunctionExpr { .. }
+      | Syntax::ClassDecl { .. }
+      | Syntax::ClassExpr { .. } => ctl.skip(),
+      Syntax::IdentifierExpr { name } | Syntax::IdentifierPattern { name }
+        if name.as_slice() == b"arguments" && node.scope.find_symbol(*name).is_none() =>
+      {
+        *name = self.alias;
+      }
+      _ => {}
+    };
+  }
+}
+
+// Lowers ES2015+ syntax to ES5 equivalents. This should be run after `minify_js`, as that transforms code assuming the original syntax (e.g. block scoping), and `minify_names` must have been run with `unique_in_closure` so that `let` and `const` can become `var`.
+// Arrow functions using `arguments` use a variable set to the `arguments` of the enclosing function instead. Not everything can be lowered, and `downlevel_js` returns an error for anything left:
+// - Arrow functions using `super`, or `arguments` outside of any function, and methods using `super`.
+// - Default values of destructuring parameters.
+// - Shorthand `__proto__` properties, as there's no ES5 object literal syntax that defines a property with that name.
+// - `let` and `const` declarations in loops that functions in the loop refer to (see `LoopCaptures`).
+// - Syntax that isn't lowered at all, such as classes, destructuring, and generators.
+struct Downlevel<'a> {
+  session: &'a Session,
+  // Scope and location of the node currently being transformed, which we use for all new nodes.
+  scope: Scope<'a>,
+  loc: SourceRange<'a>,
+  // The name of the variables that alias `arguments`, which doesn't shadow any other variable.
+  arguments_alias: SourceRange<'a>,
+  // Scopes of functions with an arrow function that uses their `arguments`, so that need to declare the alias.
+  aliased: HashSet<Scope<'a>>,
+}
+
+impl<'a> Downlevel<'a> {
+  fn node(&self, stx: Syntax<'a>) -> Node<'a> {
+    new_node(self.session, self.scope, self.loc, stx)
+  }
+
+  // Moves default values of simple parameters into the start of the function body as `if (a === void 0) a = 1;`.
+  fn lower_default_params(&self, signature: &mut NodeData<'a>, body: &mut NodeData<'a>) {
+    let Syntax::FunctionSignature { parameters } = &mut signature.stx else {
+      unreachable!();
+    };
+    let Syntax::BlockStmt { body: statements } = &mut body.stx else {
+      unreachable!();
+    };
+    let mut prologue = Vec::new();
+    for p in parameters.iter_mut() {
+      let Syntax::ParamDecl {
+        rest: false,
+        pattern,
+        default_value,
+      } = &mut p.stx
+      else {
+        continue;
+      };
+      let Syntax::IdentifierPattern { name } = pattern.stx else {
+        continue;
+      };
+      let Some(default_value) = default_value.take() else {
+        continue;
+      };
+      let test = self.node(Syntax::BinaryExpr {
+        parenthesised: false,
+        operator: OperatorName::StrictEquality,
+        left: self.node(Syntax::IdentifierExpr { name }),
+        right: self.node(Syntax::UnaryExpr {
+          parenthesised: false,
+          operator: OperatorName::Void,
+          argument: self.node(Syntax::LiteralNumberExpr {
+            value: JsNumber(0.0),
+          }),
+        }),
+      });
+      let assignment = self.node(Syntax::BinaryExpr {
+        parenthesised: false,
+        operator: OperatorName::Assignment,
+        // The parser uses patterns for assignment targets.
+        left: self.node(Syntax::IdentifierPattern { name }),
+        right: default_value,
+      });
+      prologue.push(self.node(Syntax::IfStmt {
+        test,
+        consequent: self.node(Syntax::ExpressionStmt {
+          expression: assignment,
+        }),
+        alternate: None,
+      }));
+    }
+    for (i, stmt) in prologue.into_iter().enumerate() {
+      statements.insert(i, stmt);
+    }
+  }
+
+  // Declares the alias of `arguments` at the start of a function's body if an arrow function in it uses it, after any directives (e.g. `"use strict"`).
+  fn declare_arguments_alias(&mut self, body: &mut NodeData<'a>) {
+    if !self.aliased.remove(&body.scope) {
+      return;
+    };
+    let Syntax::BlockStmt { body: statements } = &mut body.stx else {
+      unreachable!();
+    };
+    let mut declarators = self.session.new_vec();
+    declarators.push(VariableDeclarator {
+      pattern: self.node(Syntax::IdentifierPattern {
+        name: self.arguments_alias,
+      }),
+      initializer: Some(self.node(Syntax::IdentifierExpr {
+        name: SourceRange::from_slice(b"arguments"),
+      })),
+    });
+    let decl = self.node(Syntax::VarDecl {
+      export: false,
+      mode: VarDeclMode::Var,
+      declarators,
+    });
+    let directives = statements
+      .iter()
+      .take_while(|s| {
+        matches!(&s.stx, Syntax::ExpressionStmt { expression } if matches!(expression.stx, Syntax::LiteralStringExpr { .. }))
+      })
+      .count();
+    statements.insert(directives, decl);
+  }
+
+  fn function_expr(&self, signature: Node<'a>, body: Node<'a>) -> Syntax<'a> {
+    self.lower_default_params(signature, body);
+    Syntax::FunctionExpr {
+      // We need parentheses in case this is at the start of a statement or is followed by `.bind`.
+      parenthesised: true,
+      is_async: false,
+      generator: false,
+      name: None,
+      signature,
+      body,
+    }
+  }
+
+  fn lower_arrow_function(&mut self, node: &mut NodeData<'a>) -> Option<Syntax<'a>> {
+    let Syntax::ArrowFunctionExpr {
+      is_async: false,
+      signature,
+      body,
+      ..
+    } = &mut node.stx
+    else {
+      return None;
+    };
+    let usage = function_context_usage(body);
+    if usage.sup {
+      return None;
+    };
+    if usage.arguments {
+      let function = node
+        .scope
+        .find_self_or_ancestor(|t| t == ScopeType::NonArrowFunction)?;
+      let mut alias = AliasArguments {
+        alias: self.arguments_alias,
+      };
+      alias.visit(signature);
+      alias.visit(body);
+      self.aliased.insert(function);
+    };
+    let signature = signature.take(self.session);
+    let body = if let Syntax::BlockStmt { .. } = body.stx {
+      body.take(self.session)
+    } else {
+      let value = body.take(self.session);
+      // The body has the function's scope, like the body of a function declared that way.
+      let scope = value.scope;
+      let mut statements = self.session.new_vec();
+      statements.push(self.node(Syntax::ReturnStmt { value: Some(value) }));
+      new_node(
+        self.session,
+        scope,
+        self.loc,
+        Syntax::BlockStmt { body: statements },
+      )
+    };
+    let func = self.function_expr(signature, body);
+    if !usage.this {
+      return Some(func);
+    };
+    let mut arguments = self.session.new_vec();
+    arguments.push(self.node(Syntax::CallArg {
+      spread: false,
+      value: self.node(Syntax::ThisExpr {}),
+    }));
+    Some(Syntax::CallExpr {
+      optional_chaining: false,
+      parenthesised: false,
+      callee: self.node(Syntax::MemberExpr {
+        parenthesised: false,
+        optional_chaining: false,
+        assignment_target: false,
+        left: self.node(func),
+        right: SourceRange::from_slice(b"bind"),
+      }),
+      arguments,
+    })
+  }
+
+  // `a${b}c` becomes `"a".concat(b,"c")`, which converts values to strings in the same way.
+  fn lower_template(&self, node: &mut NodeData<'a>) -> Option<Syntax<'a>> {
+    let Syntax::LiteralTemplateExpr { parts } = &mut node.stx else {
+      return None;
+    };
+    let mut strings = Vec::new();
+    let mut arguments: SessionVec<'a, Node<'a>> = self.session.new_vec();
+    let mut head = None;
+    for p in parts.iter_mut() {
+      match p {
+        LiteralTemplatePart::String(s) => {
+          if head.is_none() {
+            strings.push(*s);
+          } else if !s.is_empty() {
+            arguments.push(self.node(Syntax::CallArg {
+              spread: false,
+              value: self.node(Syntax::LiteralStringExpr { value: s }),
+            }));
+          };
+        }
+        LiteralTemplatePart::Substitution(sub) => {
+          head.get_or_insert_with(|| strings.concat());
+          let value = sub.take(self.session);
+          arguments.push(self.node(Syntax::CallArg {
+            spread: false,
+            value,
+          }));
+        }
+      };
+    }
+    let head = head.unwrap_or_else(|| strings.concat());
+    let head = Syntax::LiteralStringExpr {
+      value: self.session.get_allocator().alloc_str(&head),
+    };
+    if arguments.is_empty() {
+      return Some(head);
+    };
+    Some(Syntax::CallExpr {
+      optional_chaining: false,
+      parenthesised: false,
+      callee: self.node(Syntax::MemberExpr {
+        parenthesised: false,
+        optional_chaining: false,
+        assignment_target: false,
+        left: self.node(head),
+        right: SourceRange::from_slice(b"concat"),
+      }),
+      arguments,
+    })
+  }
+}
+
+impl<'a> Visitor<'a> for Downlevel<'a> {
+  fn on_syntax_up(&mut self, node: &mut NodeData<'a>) {
+    self.scope = node.scope;
+    self.loc = node.loc;
+    let new_stx = match &mut node.stx {
+      Syntax::ArrowFunctionExpr { .. } => self.lower_arrow_function(node),
+      Syntax::LiteralTemplateExpr { .. } => self.lower_template(node),
+      Syntax::VarDecl { mode, .. } => {
+        *mode = VarDeclMode::Var;
+        None
+      }
+      Syntax::FunctionDecl {
+        signature, body, ..
+      }
+      | Syntax::FunctionExpr {
+        signature, body, ..
+      } => {
+        self.declare_arguments_alias(body);
+        self.lower_default_params(signature, body);
+        None
+      }
+      Syntax::ObjectMember { typ } => match typ {
+        ObjectMemberType::Shorthand { identifier } => {
+          let Syntax::IdentifierExpr { name } = identifier.stx else {
+            unreachable!();
+          };
+          // `{ __proto__: __proto__ }` would set the prototype instead.
+          if name.as_slice() == b"__proto__" {
+            return;
+          };
+          Some(Syntax::ObjectMember {
+            typ: ObjectMemberType::Valued {
+              key: ClassOrObjectMemberKey::Direct(name),
+              value: ClassOrObjectMemberValue::Property {
+                initializer: Some(identifier.take(self.session)),
+              },
+            },
+          })
+        }
+        ObjectMemberType::Valued { value, .. } => {
+          if let ClassOrObjectMemberValue::Method { body, .. } = value {
+            self.declare_arguments_alias(body);
+          };
+          if let ClassOrObjectMemberValue::Method {
+            is_async: false,
+            generator: false,
+            signature,
+            body,
+          } = value
+          {
+            if !function_context_usage(body).sup {
+              let func = self.function_expr(signature.take(self.session), body.take(self.session));
+              *value = ClassOrObjectMemberValue::Property {
+                initializer: Some(self.node(func)),
+              };
+            };
+          };
+          None
+        }
+        ObjectMemberType::Rest { .. } => None,
+      },
+      _ => None,
+    };
+    if let Some(new_stx) = new_stx {
+      node.stx = new_stx;
+    };
+  }
+}
+
+// Returns an error for the first syntax that can't be lowered.
+pub fn downlevel_js<'a>(
+  session: &'a Session,
+  top_level_node: &mut NodeData<'a>,
+) -> Result<(), UnsupportedSyntax> {
+  let mut captures = LoopCaptures::default();
+  captures.visit(top_level_node);
+  if let Some(&(_, position)) = captures
+    .in_loops
+    .iter()
+    .filter(|(sym, _)| captures.captured.contains(sym))
+    .min_by_key(|(_, position)| *position)
+  {
+    return Err(UnsupportedSyntax {
+      feature: SyntaxFeature::LetConst,
+      position,
+    });
+  };
+  let mut names = Names::default();
+  names.visit(top_level_node);
+  let alias = (1..)
+    .map(|i| match i {
+      1 => "_arguments".to_string(),
+      i => format!("_arguments{i}"),
+    })
+    .find(|alias| !names.names.contains(alias.as_bytes()))
+    .unwrap();
+  let alias = session.get_allocator().alloc_str(&alias);
+  Downlevel {
+    session,
+    scope: top_level_node.scope,
+    loc: top_level_node.loc,
+    arguments_alias: SourceRange::from_slice(alias.as_bytes()),
+    aliased: HashSet::new(),
+  }
+  .visit(top_level_node);
+  match first_newer_than(top_level_node, Target::Es5) {
+    Some(err) => Err(err),
+    None => Ok(()),
+  }
+}
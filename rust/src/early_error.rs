@@ -0,0 +1,278 @@
+use crate::identifier::is_identifier_char;
+use crate::legacy_octal::has_leading_zero;
+use crate::legacy_octal::has_octal_escape;
+use crate::property_order::direct_key;
+use parse_js::ast::ClassOrObjectMemberKey;
+use parse_js::ast::ClassOrObjectMemberValue;
+use parse_js::ast::NodeData;
+use parse_js::ast::ObjectMemberType;
+use parse_js::ast::Syntax;
+use parse_js::error::SyntaxError;
+use parse_js::error::SyntaxErrorType;
+use parse_js::error::SyntaxResult;
+use parse_js::source::SourceRange;
+use parse_js::symbol::ScopeType;
+use parse_js::visit::JourneyControls;
+use parse_js::visit::Visitor;
+
+// Finds the first early error that parse-js doesn't detect itself. These are syntax errors in code that otherwise parses fine:
+// - An object literal with more than one `__proto__: value` property, which would set the prototype twice. Shorthand, computed, and method `__proto__` properties don't count, as they define a property instead. Destructuring patterns aren't object literals, so they can repeat it.
+// - `await` used as an identifier (a variable, parameter, function or class name, or label) in a module, and `yield` used as one in strict mode code. parse-js lexes both as keywords but accepts them as identifiers anywhere other than async functions and generators respectively, which is only right for non-strict scripts. Property names can still be either.
+// - Identifiers with non-ASCII characters that can't be in identifiers (e.g. `a😀`). parse-js lexes any non-ASCII character as part of an identifier, but only those with the XID_Start Unicode property can start one, and those with XID_Continue (or ZWNJ and ZWJ) can follow. Unicode whitespace, which it would also lex as part of one, is rewritten to spaces before parsing (see `normalize_whitespace`).
+// - BigInt literals with a leading zero (e.g. `01n`), which parse-js lexes like legacy octal numbers, but which aren't allowed even in non-strict scripts.
+// - Legacy octal number literals and numbers with a leading zero (e.g. `0755` and `089`), and legacy octal escapes in strings (e.g. `"\07"`, and `"\8"`), in strict mode code. parse-js accepts them everywhere; in non-strict scripts, their values are fixed after parsing (see `fix_legacy_octal`).
+// - `return` outside a function, which parse-js accepts anywhere. When a script is wrapped in a function by `enclose`, that function is the top level.
+// Strict mode code is modules, classes, and scripts and functions starting with a `"use strict"` directive.
+#[derive(Default)]
+struct EarlyErrors<'a> {
+  error: Option<SyntaxError<'a>>,
+  // Whether the top level and each function and class being visited is strict mode code, innermost last.
+  strict: Vec<bool>,
+  // Whether the code has been wrapped in a function (see `MinifyOptions::enclose`).
+  enclosed: bool,
+}
+
+// If `stmt` could be in a directive prologue (i.e. is a string literal on its own), returns the directive.
+fn directive<'a>(stmt: &NodeData<'a>) -> Option<&'a str> {
+  match &stmt.stx {
+    Syntax::ExpressionStmt { expression } => match &expression.stx {
+      Syntax::LiteralStringExpr { value } => Some(*value),
+      _ => None,
+    },
+    _ => None,
+  }
+}
+
+// Whether a script or function body starts with a `"use strict"` directive.
+fn has_use_strict(body: &[&mut NodeData<'_>]) -> bool {
+  body
+    .iter()
+    .map_while(|stmt| directive(stmt))
+    .any(|directive| directive == "use strict")
+}
+
+// Whether a name lexed as an identifier (or keyword, or private name) only has characters allowed in one.
+fn is_valid_identifier(name: &[u8]) -> bool {
+  let name = name.strip_prefix(b"#").unwrap_or(name);
+  if name.is_ascii() {
+    return true;
+  };
+  let Ok(name) = std::str::from_utf8(name) else {
+    return false;
+  };
+  name
+    .chars()
+    .enumerate()
+    .all(|(i, c)| c.is_ascii() || is_identifier_char(c, i == 0))
+}
+
+impl<'a> EarlyErrors<'a> {
+  fn check_identifier(&mut self, name: SourceRange<'a>) {
+    if self.error.is_none() && !is_valid_identifier(name.as_slice()) {
+      self.error = Some(SyntaxError::new(
+        SyntaxErrorType::ExpectedSyntax("identifier with only identifier characters"),
+        name,
+        None,
+      ));
+    };
+  }
+
+  // Checks a class member or property name, which can be a string or number instead, or a private name starting with `#`.
+  fn check_key(&mut self, key: &ClassOrObjectMemberKey<'a>) {
+    if let ClassOrObjectMemberKey::Direct(key) = key {
+      if !matches!(key.as_slice().first(), Some(b'"' | b'\'' | b'0'..=b'9' | b'.')) {
+        self.check_identifier(*key);
+      };
+    };
+  }
+
+  // If `node` starts strict or non-strict code, returns which.
+  fn strictness(&self, node: &NodeData<'a>, strict: bool) -> Option<bool> {
+    match &node.stx {
+      Syntax::TopLevel { body } => Some(strict || has_use_strict(body)),
+      Syntax::ArrowFunctionExpr { body, .. }
+      | Syntax::FunctionDecl { body, .. }
+      | Syntax::FunctionExpr { body, .. } => match &body.stx {
+        Syntax::BlockStmt { body } => Some(strict || has_use_strict(body)),
+        _ => Some(strict),
+      },
+      Syntax::ClassDecl { .. } | Syntax::ClassExpr { .. } => Some(true),
+      _ => None,
+    }
+  }
+}
+
+impl<'a> Visitor<'a> for EarlyErrors<'a> {
+  fn on_syntax_down(&mut self, node: &mut NodeData<'a>, ctl: &mut JourneyControls) {
+    if self.error.is_some() {
+      ctl.skip();
+      return;
+    };
+    let module = node
+      .scope
+      .find_self_or_ancestor(|t| t == ScopeType::Module)
+      .is_some();
+    let strict = self.strict.last().copied().unwrap_or(module);
+    if let Some(strict) = self.strictness(node, strict) {
+      self.strict.push(strict);
+    };
+    let strict = self.strict.last().copied().unwrap_or(module);
+    match &node.stx {
+      Syntax::IdentifierExpr { name }
+      | Syntax::IdentifierPattern { name }
+      | Syntax::ClassOrFunctionName { name }
+      | Syntax::LabelStmt { name, .. } => {
+        self.check_identifier(*name);
+        let expected = match name.as_slice() {
+          b"await" if module => Some("identifier other than `await` in a module"),
+          b"yield" if strict => Some("identifier other than `yield` in strict mode code"),
+          _ => None,
+        };
+        if let Some(expected) = expected {
+          self.error = Some(SyntaxError::new(
+            SyntaxErrorType::ExpectedSyntax(expected),
+            *name,
+            None,
+          ));
+        };
+      }
+      Syntax::MemberExpr { right, .. } => self.check_identifier(*right),
+      Syntax::ReturnStmt { .. } => {
+        let is_function = |t| matches!(t, ScopeType::ArrowFunction | ScopeType::NonArrowFunction);
+        let function = node.scope.find_self_or_ancestor(is_function);
+        let outside = match function {
+          None => true,
+          Some(f) if self.enclosed => f
+            .parent()
+            .and_then(|p| p.find_self_or_ancestor(is_function))
+            .is_none(),
+          Some(_) => false,
+        };
+        if outside {
+          self.error = Some(SyntaxError::new(
+            SyntaxErrorType::ExpectedSyntax("`return` only inside a function"),
+            node.loc,
+            None,
+          ));
+        };
+      }
+      Syntax::LiteralNumberExpr { .. } if strict && has_leading_zero(node.loc.as_slice()) => {
+        self.error = Some(SyntaxError::new(
+          SyntaxErrorType::ExpectedSyntax("number without a leading zero in strict mode code"),
+          node.loc,
+          None,
+        ));
+      }
+      Syntax::LiteralStringExpr { .. } if strict && has_octal_escape(node.loc.as_slice()) => {
+        self.error = Some(SyntaxError::new(
+          SyntaxErrorType::ExpectedSyntax("string without octal escapes in strict mode code"),
+          node.loc,
+          None,
+        ));
+      }
+      Syntax::LiteralBigIntExpr { .. }
+        if node.loc.len() > 2 && node.loc.as_slice()[0] == b'0' && node.loc.as_slice()[1].is_ascii_digit() =>
+      {
+        self.error = Some(SyntaxError::new(
+          SyntaxErrorType::MalformedLiteralNumber,
+          node.loc,
+          None,
+        ));
+      }
+      Syntax::ObjectMember {
+        typ: ObjectMemberType::Valued { key, .. },
+      }
+      | Syntax::ObjectPatternProperty { key, .. } => self.check_key(key),
+      // The visitor doesn't visit the identifier of a shorthand property.
+      Syntax::ObjectMember {
+        typ: ObjectMemberType::Shorthand { identifier },
+      } => self.check_identifier(identifier.loc),
+      Syntax::ClassDecl { members, .. } | Syntax::ClassExpr { members, .. } => {
+        for member in members.iter() {
+          self.check_key(&member.key);
+        }
+      }
+      Syntax::BreakStmt { label: Some(label) } | Syntax::ContinueStmt { label: Some(label) } => {
+        self.check_identifier(*label)
+      }
+      _ => {}
+    };
+    if let Syntax::LiteralObjectExpr { members } = &node.stx {
+      let mut protos = members.iter().filter_map(|m| match &m.stx {
+        Syntax::ObjectMember {
+          typ:
+            ObjectMemberType::Valued {
+              key: ClassOrObjectMemberKey::Direct(key),
+              value: ClassOrObjectMemberValue::Property { .. },
+            },
+        } if direct_key(*key) == "__proto__" => Some(*key),
+        _ => None,
+      });
+      if let Some(dupe) = protos.nth(1) {
+        self.error = Some(SyntaxError::new(
+          SyntaxErrorType::ExpectedSyntax("at most one `__proto__` property"),
+          dupe,
+          None,
+        ));
+      };
+    };
+  }
+
+  fn on_syntax_up(&mut self, node: &mut NodeData<'a>) {
+    if self.strictness(node, false).is_some() {
+      self.strict.pop();
+    };
+  }
+}
+
+// Returns the first early error in a parsed tree (see `EarlyErrors`), which should be run on all parsed code so invalid code is rejected like other syntax errors.
+pub fn check_early_errors<'a>(node: &mut NodeData<'a>) -> SyntaxResult<'a, ()> {
+  check_early_errors_in(node, false)
+}
+
+// Like `check_early_errors`, but for a script that `enclose` has wrapped in a function, which is treated as the top level.
+pub fn check_enclosed_early_errors<'a>(node: &mut NodeData<'a>) -> SyntaxResult<'a, ()> {
+  check_early_errors_in(node, true)
+}
+
+fn check_early_errors_in<'a>(node: &mut NodeData<'a>, enclosed: bool) -> SyntaxResult<'a, ()> {
+  let mut errors = EarlyErrors {
+    enclosed,
+    ..EarlyErrors::default()
+  };
+  errors.visit(node);
+  match errors.error {
+    Some(err) => Err(err),
+    None => Ok(()),
+  }
+}
+
+// Checks the top-level statements of some code one at a time as they're parsed, for when there's no tree of all of them. A `"use strict"` directive at the start makes the statements after it strict mode code.
+#[derive(Default)]
+pub struct StatementEarlyErrors {
+  // Whether a statement that isn't a directive has been checked.
+  prologue_ended: bool,
+  strict: bool,
+}
+
+impl StatementEarlyErrors {
+  // Call with each top-level statement in order. Returns the first early error in it, if any.
+  pub fn check<'a>(&mut self, stmt: &mut NodeData<'a>) -> SyntaxResult<'a, ()> {
+    if !self.prologue_ended {
+      match directive(stmt) {
+        Some(directive) => self.strict |= directive == "use strict",
+        None => self.prologue_ended = true,
+      };
+    };
+    let mut errors = EarlyErrors {
+      error: None,
+      strict: if self.strict { vec![true] } else { Vec::new() },
+      enclosed: false,
+    };
+    errors.visit(stmt);
+    match errors.error {
+      Some(err) => Err(err),
+      None => Ok(()),
+    }
+  }
+}
@@ -0,0 +1,66 @@
+use super::emitter::Emitter;
+
+// Converts the digits of a BigInt literal (without any prefix, separators, or `n` suffix) in `radix` to decimal digits.
+fn to_decimal(digits: &[u8], radix: u32) -> Vec<u8> {
+  // Little-endian limbs of the value, each below BASE.
+  const BASE: u64 = 1_000_000_000;
+  let mut limbs: Vec<u64> = vec![0];
+  for &d in digits {
+    let mut carry = u64::from((d as char).to_digit(radix).unwrap());
+    for limb in limbs.iter_mut() {
+      let v = *limb * u64::from(radix) + carry;
+      *limb = v % BASE;
+      carry = v / BASE;
+    }
+    if carry > 0 {
+      limbs.push(carry);
+    };
+  }
+  let mut out = limbs.last().unwrap().to_string().into_bytes();
+  for limb in limbs.iter().rev().skip(1) {
+    out.extend_from_slice(format!("{:09}", limb).as_bytes());
+  }
+  out
+}
+
+// Emits a BigInt literal from its source (e.g. `0xFFn`) in its shortest form, which is decimal if that's shorter (e.g. `255n`), and otherwise as it was. The value is never converted to a number, as that could lose precision, and would change its type.
+pub fn emit_bigint(out: &mut Emitter, raw: &[u8]) {
+  let radix = match raw.get(..2) {
+    Some(b"0x" | b"0X") => 16,
+    Some(b"0o" | b"0O") => 8,
+    Some(b"0b" | b"0B") => 2,
+    _ => 10,
+  };
+  let digits = raw
+    .get(if radix == 10 { 0 } else { 2 }..raw.len() - 1)
+    .unwrap_or_default();
+  if radix == 10 || digits.is_empty() {
+    out.extend_from_slice(raw);
+    return;
+  };
+  let mut decimal = to_decimal(digits, radix);
+  decimal.push(b'n');
+  out.extend_from_slice(if decimal.len() < raw.len() {
+    &decimal
+  } else {
+    raw
+  });
+}
+
+#[cfg(test)]
+mod tests {
+  use super::to_decimal;
+
+  #[test]
+  fn test_to_decimal() {
+    assert_eq!(to_decimal(b"0", 16), b"0");
+    assert_eq!(to_decimal(b"FF", 16), b"255");
+    assert_eq!(to_decimal(b"101", 2), b"5");
+    assert_eq!(to_decimal(b"17", 8), b"15");
+    assert_eq!(
+      to_decimal(b"FFFFFFFFFFFFFFFFFFFFFFFF", 16),
+      b"79228162514264337593543950335"
+    );
+    assert_eq!(to_decimal(b"3B9ACA00", 16), b"1000000000");
+  }
+}
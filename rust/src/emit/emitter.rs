@@ -0,0 +1,318 @@
+use crate::cfg::MinifyOptions;
+use crate::cfg::Target;
+use crate::position_map::Mapping;
+use crate::position_map::PositionMap;
+use std::io;
+use std::io::Write;
+use std::ops::Range;
+
+// Once the buffer of an Emitter with a sink reaches this size, it's flushed to the sink.
+const SINK_FLUSH_THRESHOLD: usize = 64 * 1024;
+
+// The end of the output that's kept in the buffer when flushing to the sink, so `extend_from_slice` can still see whether the next slice continues `</script`.
+const HTML_SEQUENCE_TAIL: usize = b"</script".len() - 1;
+
+// Destination for emitted code. All code should be written through this so that formatting options (e.g. beautify) are applied consistently.
+// In minified mode, the formatting methods are no-ops.
+pub struct Emitter<'o> {
+  out: &'o mut Vec<u8>,
+  // If set, `out` is only a buffer whose contents are periodically moved to this, so the entire output never has to be held in memory.
+  sink: Option<&'o mut dyn Write>,
+  // The first error from writing to `sink`, after which we stop writing to it.
+  sink_error: Option<io::Error>,
+  // Number of bytes that have been moved from `out` to `sink`.
+  flushed: usize,
+  beautify: bool,
+  max_line_len: Option<usize>,
+  target: Target,
+  asi: bool,
+  safe_concat: bool,
+  inline_script: bool,
+  newline: &'static [u8],
+  // Whether a statement separator is pending, which is emitted once we know what the next statement starts with.
+  pending_separator: bool,
+  // Whether a line terminator has been emitted since the pending separator was requested.
+  pending_separator_has_newline: bool,
+  indent_level: usize,
+  // Whether an `in` operator must be parenthesised, as it would otherwise be ambiguous. This is the case in the init of a `for(;;)`, outside any brackets, where it would make it a `for-in`.
+  in_disallowed: bool,
+  // Position in the output (including anything flushed to `sink`) where our output starts, so we don't emit a leading newline.
+  start: usize,
+  // Position in `out` where the current line starts.
+  line_start: usize,
+  // Position in `out` of the last point on the current line where a line break can be safely inserted.
+  break_pos: Option<usize>,
+  // If recording positions, the output and source range of each node emitted so far. Output positions exclude line breaks inserted for `max_line_len`, as they're inserted after the fact.
+  mappings: Option<Vec<Mapping>>,
+  // Output positions, excluding other inserted line breaks, where line breaks have been inserted for `max_line_len`, if recording positions.
+  inserted_breaks: Vec<usize>,
+}
+
+impl<'o> Emitter<'o> {
+  pub fn new(out: &'o mut Vec<u8>, cfg: &MinifyOptions) -> Emitter<'o> {
+    let start = out.len();
+    Emitter {
+      out,
+      sink: None,
+      sink_error: None,
+      flushed: 0,
+      beautify: cfg.beautify,
+      max_line_len: cfg.max_line_len,
+      target: cfg.target,
+      asi: cfg.asi,
+      safe_concat: cfg.safe_concat,
+      inline_script: cfg.inline_script,
+      newline: cfg.newline.as_bytes(),
+      // If safe to concatenate, the output is separated from any preceding script like from a preceding statement, but never by a line break, as that's only valid after a statement of our own.
+      pending_separator: cfg.safe_concat,
+      pending_separator_has_newline: cfg.safe_concat,
+      indent_level: 0,
+      in_disallowed: false,
+      start,
+      line_start: start,
+      break_pos: None,
+      mappings: cfg.position_map.then(Vec::new),
+      inserted_breaks: Vec::new(),
+    }
+  }
+
+  // Creates an Emitter that uses `buf` as a buffer and writes output to `sink`. `finish` must be called once done to write any remaining output.
+  pub fn with_sink(
+    buf: &'o mut Vec<u8>,
+    sink: &'o mut dyn Write,
+    cfg: &MinifyOptions,
+  ) -> Emitter<'o> {
+    let mut emitter = Emitter::new(buf, cfg);
+    emitter.sink = Some(sink);
+    emitter
+  }
+
+  // Writes any buffered output to the sink, if any, and returns the number of bytes written to it, or the first error encountered while writing to it.
+  pub fn finish(mut self) -> io::Result<usize> {
+    self.flush_to_sink(self.out.len());
+    match self.sink_error {
+      Some(err) => Err(err),
+      None => Ok(self.flushed),
+    }
+  }
+
+  // Moves the first `len` bytes of the buffer to the sink.
+  fn flush_to_sink(&mut self, len: usize) {
+    let Some(sink) = self.sink.as_mut() else {
+      return;
+    };
+    if len == 0 {
+      return;
+    };
+    if self.sink_error.is_none() {
+      if let Err(err) = sink.write_all(&self.out[..len]) {
+        self.sink_error = Some(err);
+      };
+    };
+    self.out.drain(..len);
+    self.flushed += len;
+    // These are only past `len` if we're finishing, in which case they no longer matter.
+    self.line_start = self.line_start.saturating_sub(len);
+    self.break_pos = self.break_pos.and_then(|p| p.checked_sub(len));
+  }
+
+  pub fn beautify(&self) -> bool {
+    self.beautify
+  }
+
+  pub fn target(&self) -> Target {
+    self.target
+  }
+
+  pub fn asi(&self) -> bool {
+    self.asi
+  }
+
+  pub fn safe_concat(&self) -> bool {
+    self.safe_concat
+  }
+
+  pub fn inline_script(&self) -> bool {
+    self.inline_script
+  }
+
+  // Whether appending `s` would join it with the end of the output to form `<!--`, which starts a comment in a global script (e.g. `a<!--b`), or `</script` if inlining into HTML (e.g. `a</script/`). Strings, regexes, and comments are escaped before this, so the two are always separate tokens, and a space can go between them.
+  fn joins_html_sequence(&self, s: &[u8]) -> bool {
+    let tail = if self.inline_script {
+      HTML_SEQUENCE_TAIL
+    } else {
+      b"<!--".len() - 1
+    };
+    let start = self.out.len().saturating_sub(tail);
+    (start..self.out.len()).any(|i| {
+      self.out[i] == b'<' && {
+        let joined = || self.out[i..].iter().chain(s.iter());
+        let is = |seq: &[u8]| {
+          self.out.len() - i + s.len() >= seq.len()
+            && joined().zip(seq).all(|(a, b)| a.eq_ignore_ascii_case(b))
+        };
+        is(b"<!--") || (self.inline_script && is(b"</script"))
+      }
+    })
+  }
+
+  // Separates two statements. If relying on ASI, this is a line break unless the next statement starts with a character that would continue the previous statement.
+  pub fn statement_separator(&mut self) {
+    if self.asi {
+      self.pending_separator = true;
+      self.pending_separator_has_newline = false;
+    } else {
+      self.extend_from_slice(b";");
+    };
+  }
+
+  // Emits the pending separator if `s` starts the next statement. It may be preceded by whitespace from `newline`, after which a separator is still valid.
+  fn emit_pending_separator(&mut self, s: &[u8]) {
+    let Some(&c) = s.first() else {
+      return;
+    };
+    self.pending_separator = false;
+    match c {
+      b' ' | b'\r' | b'\n' => {
+        self.pending_separator = true;
+        self.pending_separator_has_newline |= s.contains(&b'\n');
+      }
+      // These would continue the previous statement. `<` could start JSX.
+      b'(' | b'[' | b'`' | b'+' | b'-' | b'/' | b'<' => self.extend_from_slice(b";"),
+      _ if !self.pending_separator_has_newline => self.extend_from_slice(self.newline),
+      _ => {}
+    };
+  }
+
+  pub fn extend_from_slice(&mut self, s: &[u8]) {
+    if self.pending_separator {
+      self.emit_pending_separator(s);
+    };
+    if self.joins_html_sequence(s) {
+      self.out.push(b' ');
+    };
+    self.out.extend_from_slice(s);
+    let Some(max_line_len) = self.max_line_len else {
+      return;
+    };
+    // Strings and templates can contain line terminators.
+    if let Some(p) = memchr::memrchr(b'\n', s) {
+      self.line_start = self.out.len() - s.len() + p + 1;
+      self.break_pos = None;
+    };
+    if self.out.len() - self.line_start > max_line_len {
+      if let Some(p) = self.break_pos.take() {
+        if self.mappings.is_some() {
+          let pos = self.flushed + p - self.inserted_breaks.len() * self.newline.len();
+          self.inserted_breaks.push(pos);
+        };
+        self.out.splice(p..p, self.newline.iter().copied());
+        self.line_start = p + self.newline.len();
+      };
+    };
+    if self.sink.is_some() && self.out.len() >= SINK_FLUSH_THRESHOLD {
+      // Keep the current line in the buffer, as we may still need to insert a line break into it.
+      let len = if self.max_line_len.is_some() {
+        self.line_start
+      } else {
+        self.out.len()
+      };
+      self.flush_to_sink(len.min(self.out.len() - HTML_SEQUENCE_TAIL));
+    };
+  }
+
+  // Marks the current position as a safe point to insert a line break if the line gets too long.
+  // This must not be called where a line terminator would change the meaning of the code, such as after `return` or before `=>`.
+  pub fn break_point(&mut self) {
+    if self.max_line_len.is_some() && self.out.len() > self.line_start {
+      self.break_pos = Some(self.out.len());
+    };
+  }
+
+  // Emits a space if beautifying.
+  pub fn space(&mut self) {
+    if self.beautify {
+      self.out.push(b' ');
+    };
+  }
+
+  // Starts a new line at the current indentation if beautifying.
+  pub fn newline(&mut self) {
+    if self.beautify && self.flushed + self.out.len() > self.start {
+      self.extend_from_slice(self.newline);
+      for _ in 0..self.indent_level {
+        self.extend_from_slice(b"  ");
+      }
+    };
+  }
+
+  // Output position, excluding line breaks inserted for `max_line_len`.
+  fn mapping_pos(&self) -> usize {
+    self.flushed + self.out.len() - self.inserted_breaks.len() * self.newline.len()
+  }
+
+  // Records that a node from `source` starts being emitted, if recording positions. Returns an ID to pass to `end_mapping` once it's been emitted.
+  pub fn start_mapping(&mut self, source: Range<usize>) -> usize {
+    if self.mappings.is_none() {
+      return 0;
+    };
+    let pos = self.mapping_pos();
+    let mappings = self.mappings.as_mut().unwrap();
+    mappings.push(Mapping {
+      output: pos..pos,
+      source,
+    });
+    mappings.len() - 1
+  }
+
+  pub fn end_mapping(&mut self, id: usize) {
+    if self.mappings.is_none() {
+      return;
+    };
+    let pos = self.mapping_pos();
+    self.mappings.as_mut().unwrap()[id].output.end = pos;
+  }
+
+  // Returns the recorded positions, if recording, with output positions relative to `base` (a position in `out` before any output was flushed).
+  pub fn take_position_map(&mut self, base: usize) -> Option<PositionMap> {
+    let mut mappings = self.mappings.take()?;
+    let breaks = &self.inserted_breaks;
+    let newline_len = self.newline.len();
+    // A node starting where a line break was inserted starts after it, but one ending there doesn't include it.
+    let start = |pos: usize| pos + breaks.partition_point(|&b| b <= pos) * newline_len - base;
+    let end = |pos: usize| pos + breaks.partition_point(|&b| b < pos) * newline_len - base;
+    mappings.retain(|m| !m.output.is_empty());
+    for m in mappings.iter_mut() {
+      m.output = start(m.output.start)..end(m.output.end);
+    }
+    Some(PositionMap { mappings })
+  }
+
+  pub fn indent(&mut self) {
+    self.indent_level += 1;
+  }
+
+  pub fn dedent(&mut self) {
+    self.indent_level -= 1;
+  }
+
+  pub fn in_disallowed(&self) -> bool {
+    self.in_disallowed
+  }
+
+  // Returns the previous value, to restore once done.
+  pub fn set_in_disallowed(&mut self, in_disallowed: bool) -> bool {
+    std::mem::replace(&mut self.in_disallowed, in_disallowed)
+  }
+}
+
+impl<'o> Write for Emitter<'o> {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    self.extend_from_slice(buf);
+    Ok(buf.len())
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    Ok(())
+  }
+}
@@ -1,5 +1,8 @@
-use aho_corasick::AhoCorasick;
-use lazy_static::lazy_static;
+use bigint::emit_bigint;
+use crate::cfg::MinifyOptions;
+use crate::cfg::Target;
+use crate::stats::MinifyStats;
+pub use emitter::Emitter;
 use parse_js::ast::ArrayElement;
 use parse_js::ast::ClassMember;
 use parse_js::ast::ClassOrObjectMemberKey;
@@ -16,97 +19,104 @@ use parse_js::ast::VarDeclMode;
 use parse_js::operator::OperatorName;
 use parse_js::operator::OPERATORS;
 use parse_js::session::SessionVec;
-use std::collections::HashMap;
 use std::io::Write;
+use string::emit_raw_literal;
+use string::emit_string_contents;
+pub use string::starts_html_sequence;
 
+mod bigint;
+mod emitter;
+mod string;
 #[cfg(test)]
 mod tests;
 
-lazy_static! {
-  pub static ref BINARY_OPERATOR_SYNTAX: HashMap<OperatorName, &'static str> = {
-      let mut map = HashMap::<OperatorName, &'static str>::new();
-      // Excluded: Call, Conditional.
-      map.insert(OperatorName::Addition, "+");
-      map.insert(OperatorName::Assignment, "=");
-      map.insert(OperatorName::AssignmentAddition, "+=");
-      map.insert(OperatorName::AssignmentBitwiseAnd, "&=");
-      map.insert(OperatorName::AssignmentBitwiseLeftShift, "<<=");
-      map.insert(OperatorName::AssignmentBitwiseOr, "|=");
-      map.insert(OperatorName::AssignmentBitwiseRightShift, ">>=");
-      map.insert(OperatorName::AssignmentBitwiseUnsignedRightShift, ">>>=");
-      map.insert(OperatorName::AssignmentBitwiseXor, "^=");
-      map.insert(OperatorName::AssignmentDivision, "/=");
-      map.insert(OperatorName::AssignmentExponentiation, "**=");
-      map.insert(OperatorName::AssignmentLogicalAnd, "&&=");
-      map.insert(OperatorName::AssignmentLogicalOr, "||=");
-      map.insert(OperatorName::AssignmentMultiplication, "*=");
-      map.insert(OperatorName::AssignmentNullishCoalescing, "??=");
-      map.insert(OperatorName::AssignmentRemainder, "%=");
-      map.insert(OperatorName::AssignmentSubtraction, "-=");
-      map.insert(OperatorName::BitwiseAnd, "&");
-      map.insert(OperatorName::BitwiseLeftShift, "<<");
-      map.insert(OperatorName::BitwiseOr, "|");
-      map.insert(OperatorName::BitwiseRightShift, ">>");
-      map.insert(OperatorName::BitwiseUnsignedRightShift, ">>>");
-      map.insert(OperatorName::BitwiseXor, "^");
-      map.insert(OperatorName::Comma, ",");
-      map.insert(OperatorName::Division, "/");
-      map.insert(OperatorName::Equality, "==");
-      map.insert(OperatorName::Exponentiation, "**");
-      map.insert(OperatorName::GreaterThan, ">");
-      map.insert(OperatorName::GreaterThanOrEqual, ">=");
-      map.insert(OperatorName::In, " in ");
-      map.insert(OperatorName::Inequality, "!=");
-      map.insert(OperatorName::Instanceof, " instanceof ");
-      map.insert(OperatorName::LessThan, "<");
-      map.insert(OperatorName::LessThanOrEqual, "<=");
-      map.insert(OperatorName::LogicalAnd, "&&");
-      map.insert(OperatorName::LogicalOr, "||");
-      map.insert(OperatorName::MemberAccess, ".");
-      map.insert(OperatorName::Multiplication, "*");
-      map.insert(OperatorName::NullishCoalescing, "??");
-      map.insert(OperatorName::OptionalChainingMemberAccess, "?.");
-      map.insert(OperatorName::OptionalChainingComputedMemberAccess, "?.[");
-      map.insert(OperatorName::OptionalChainingCall, "?.(");
-      map.insert(OperatorName::Remainder, "%");
-      map.insert(OperatorName::StrictEquality, "===");
-      map.insert(OperatorName::StrictInequality, "!==");
-      map.insert(OperatorName::Subtraction, "-");
-      map.insert(OperatorName::Typeof, " typeof ");
-      map
-  };
-
-  pub static ref UNARY_OPERATOR_SYNTAX: HashMap<OperatorName, &'static str> = {
-      let mut map = HashMap::<OperatorName, &'static str>::new();
-      // Excluded: Postfix{Increment,Decrement}.
-      map.insert(OperatorName::Await, "await ");
-      map.insert(OperatorName::BitwiseNot, "~");
-      map.insert(OperatorName::Delete, "delete ");
-      map.insert(OperatorName::LogicalNot, "!");
-      map.insert(OperatorName::New, "new ");
-      map.insert(OperatorName::PrefixDecrement, "--");
-      map.insert(OperatorName::PrefixIncrement, "++");
-      map.insert(OperatorName::Typeof, "typeof ");
-      map.insert(OperatorName::UnaryNegation, "-");
-      map.insert(OperatorName::UnaryPlus, "+");
-      map.insert(OperatorName::Void, "void ");
-      map.insert(OperatorName::Yield, "yield ");
-      map.insert(OperatorName::YieldDelegated, "yield*");
-      map
-  };
+fn binary_operator_syntax(name: OperatorName) -> Option<&'static str> {
+  Some(match name {
+    // Excluded: Call, Conditional.
+    OperatorName::Addition => "+",
+    OperatorName::Assignment => "=",
+    OperatorName::AssignmentAddition => "+=",
+    OperatorName::AssignmentBitwiseAnd => "&=",
+    OperatorName::AssignmentBitwiseLeftShift => "<<=",
+    OperatorName::AssignmentBitwiseOr => "|=",
+    OperatorName::AssignmentBitwiseRightShift => ">>=",
+    OperatorName::AssignmentBitwiseUnsignedRightShift => ">>>=",
+    OperatorName::AssignmentBitwiseXor => "^=",
+    OperatorName::AssignmentDivision => "/=",
+    OperatorName::AssignmentExponentiation => "**=",
+    OperatorName::AssignmentLogicalAnd => "&&=",
+    OperatorName::AssignmentLogicalOr => "||=",
+    OperatorName::AssignmentMultiplication => "*=",
+    OperatorName::AssignmentNullishCoalescing => "??=",
+    OperatorName::AssignmentRemainder => "%=",
+    OperatorName::AssignmentSubtraction => "-=",
+    OperatorName::BitwiseAnd => "&",
+    OperatorName::BitwiseLeftShift => "<<",
+    OperatorName::BitwiseOr => "|",
+    OperatorName::BitwiseRightShift => ">>",
+    OperatorName::BitwiseUnsignedRightShift => ">>>",
+    OperatorName::BitwiseXor => "^",
+    OperatorName::Comma => ",",
+    OperatorName::Division => "/",
+    OperatorName::Equality => "==",
+    OperatorName::Exponentiation => "**",
+    OperatorName::GreaterThan => ">",
+    OperatorName::GreaterThanOrEqual => ">=",
+    OperatorName::In => " in ",
+    OperatorName::Inequality => "!=",
+    OperatorName::Instanceof => " instanceof ",
+    OperatorName::LessThan => "<",
+    OperatorName::LessThanOrEqual => "<=",
+    OperatorName::LogicalAnd => "&&",
+    OperatorName::LogicalOr => "||",
+    OperatorName::MemberAccess => ".",
+    OperatorName::Multiplication => "*",
+    OperatorName::NullishCoalescing => "??",
+    OperatorName::OptionalChainingMemberAccess => "?.",
+    OperatorName::OptionalChainingComputedMemberAccess => "?.[",
+    OperatorName::OptionalChainingCall => "?.(",
+    OperatorName::Remainder => "%",
+    OperatorName::StrictEquality => "===",
+    OperatorName::StrictInequality => "!==",
+    OperatorName::Subtraction => "-",
+    OperatorName::Typeof => " typeof ",
+    _ => return None,
+  })
+}
 
-  static ref TEMPLATE_LITERAL_ESCAPE_MAT: AhoCorasick = AhoCorasick::new(&[
-    b"\\",
-    b"`",
-    b"$",
-  ]);
+fn unary_operator_syntax(name: OperatorName) -> Option<&'static str> {
+  Some(match name {
+    // Excluded: Postfix{Increment,Decrement}.
+    OperatorName::Await => "await ",
+    OperatorName::BitwiseNot => "~",
+    OperatorName::Delete => "delete ",
+    OperatorName::LogicalNot => "!",
+    OperatorName::New => "new ",
+    OperatorName::PrefixDecrement => "--",
+    OperatorName::PrefixIncrement => "++",
+    OperatorName::Typeof => "typeof ",
+    OperatorName::UnaryNegation => "-",
+    OperatorName::UnaryPlus => "+",
+    OperatorName::Void => "void ",
+    OperatorName::Yield => "yield ",
+    OperatorName::YieldDelegated => "yield*",
+    _ => return None,
+  })
 }
 
-const TEMPLATE_LITERAL_ESCAPE_REP: &[&[u8]] = &[b"\\\\", b"\\`", b"\\$"];
+// Estimates the size of the output for the minified tree, to reserve memory for it up front. Minification removes whitespace and comments and shortens names, and rarely makes code longer (e.g. expanding shorthand properties), so the size of the source minus what renaming saved is an upper bound for most code. Beautifying adds indentation and spaces, so allow for some more.
+pub fn estimate_output_len(stats: &MinifyStats, cfg: &MinifyOptions) -> usize {
+  let code = stats
+    .input_len
+    .saturating_sub(stats.renaming_savings.max(0) as usize);
+  let code = if cfg.beautify { code + code / 4 } else { code };
+  let wrapper = |text: &Option<String>| text.as_ref().map_or(0, |t| t.len() + 2);
+  code + wrapper(&cfg.banner) + wrapper(&cfg.footer)
+}
 
 // Returns whether or not the value is a property.
 fn emit_class_or_object_member<'a>(
-  out: &mut Vec<u8>,
+  out: &mut Emitter,
   key: &'a ClassOrObjectMemberKey,
   value: &'a ClassOrObjectMemberValue,
   value_delimiter: &'static [u8],
@@ -146,17 +156,18 @@ fn emit_class_or_object_member<'a>(
   };
   match key {
     ClassOrObjectMemberKey::Direct(name) => {
-      out.extend_from_slice(name.as_slice());
+      emit_raw_literal(out, name.as_slice());
     }
     ClassOrObjectMemberKey::Computed(expr) => {
       out.extend_from_slice(b"[");
-      emit_js(out, *expr);
+      emit_without_comma(out, *expr);
       out.extend_from_slice(b"]");
     }
   };
   match value {
     ClassOrObjectMemberValue::Getter { body } => {
       out.extend_from_slice(b"()");
+      out.space();
       emit_js(out, *body);
     }
     ClassOrObjectMemberValue::Method {
@@ -165,25 +176,24 @@ fn emit_class_or_object_member<'a>(
       out.extend_from_slice(b"(");
       emit_js(out, *signature);
       out.extend_from_slice(b")");
+      out.space();
       emit_js(out, *body);
     }
     ClassOrObjectMemberValue::Property { initializer } => {
       if let Some(v) = initializer {
-        out.extend_from_slice(value_delimiter);
-        let is_comma = is_comma_expression(&v.stx);
-        if is_comma {
-          out.extend_from_slice(b"(");
-        };
-        emit_js(out, *v);
-        if is_comma {
-          out.extend_from_slice(b")");
+        if value_delimiter == b"=" {
+          out.space();
         };
+        out.extend_from_slice(value_delimiter);
+        out.space();
+        emit_without_comma(out, *v);
       };
     }
     ClassOrObjectMemberValue::Setter { body, parameter } => {
       out.extend_from_slice(b"(");
       emit_js(out, *parameter);
       out.extend_from_slice(b")");
+      out.space();
       emit_js(out, *body);
     }
   };
@@ -195,7 +205,7 @@ fn emit_class_or_object_member<'a>(
 }
 
 fn emit_class<'a>(
-  out: &mut Vec<u8>,
+  out: &mut Emitter,
   name: &Option<&mut NodeData<'a>>,
   extends: &Option<&mut NodeData<'a>>,
   members: &SessionVec<'a, ClassMember<'a>>,
@@ -209,29 +219,42 @@ fn emit_class<'a>(
     out.extend_from_slice(b" extends ");
     emit_js(out, s);
   }
+  out.space();
   out.extend_from_slice(b"{");
+  out.indent();
   let mut last_member_was_property = false;
   for (i, m) in members.iter().enumerate() {
     if i > 0 && last_member_was_property {
       out.extend_from_slice(b";");
     }
+    out.break_point();
+    out.newline();
     if m.statik {
       out.extend_from_slice(b"static ");
     }
     last_member_was_property = emit_class_or_object_member(out, &m.key, &m.value, b"=");
   }
+  if last_member_was_property && out.beautify() {
+    out.extend_from_slice(b";");
+  };
+  out.dedent();
+  if !members.is_empty() {
+    out.newline();
+  };
   out.extend_from_slice(b"}");
 }
 
 fn emit_import_or_export_statement_trailer<'a>(
-  out: &mut Vec<u8>,
+  out: &mut Emitter,
   names: Option<&ExportNames<'a>>,
   from: Option<&'a str>,
 ) -> () {
   match names {
     Some(ExportNames::All(alias)) => {
+      out.space();
       out.extend_from_slice(b"*");
       if let Some(alias) = alias {
+        out.space();
         out.extend_from_slice(b"as ");
         emit_js(out, *alias);
         if from.is_some() {
@@ -240,32 +263,45 @@ fn emit_import_or_export_statement_trailer<'a>(
       };
     }
     Some(ExportNames::Specific(names)) => {
+      out.space();
       out.extend_from_slice(b"{");
+      out.space();
       for (i, e) in names.iter().enumerate() {
         if i > 0 {
           out.extend_from_slice(b",");
+          out.break_point();
+          out.space();
         }
-        out.extend_from_slice(e.target.as_slice());
+        emit_raw_literal(out, e.target.as_slice());
         // TODO Omit if identical to `target`.
         out.extend_from_slice(b" as ");
         emit_js(out, e.alias);
       }
+      out.space();
       out.extend_from_slice(b"}");
     }
     None => {}
   };
   if let Some(from) = from {
-    out.extend_from_slice(b"from\"");
-    // TODO Escape?
-    out.extend_from_slice(from.as_bytes());
+    // `* as alias` is already followed by a space.
+    if let Some(ExportNames::Specific(_) | ExportNames::All(None)) = names {
+      out.space();
+    };
+    out.extend_from_slice(b"from");
+    out.space();
+    out.extend_from_slice(b"\"");
+    emit_string_contents(out, from.as_bytes(), b'"');
     out.extend_from_slice(b"\"");
   };
 }
 
-// NOTE: We no longer support outputting to a generic Write, as that incurs significant performance overhead (even with a BufWriter<Vec<u8>>) and our parser is not streaming anyway.
+// NOTE: We don't emit directly to a generic Write, as that incurs significant performance overhead (even with a BufWriter<Vec<u8>>). Instead, Emitter writes into a Vec<u8> and can move its contents to a Write in large chunks.
 // WARNING: We use this function for testing minification passes (it's easier than trying to write up and then match/compare trees), so all emit logic should be deterministic and not alter/deviate from the tree in any way (i.e. it's a genuine exact unopinionated/objective unmodified reflection of the tree).
-pub fn emit_js<'a>(out: &mut Vec<u8>, n: &NodeData<'a>) -> () {
+pub fn emit_js<'a>(out: &mut Emitter, n: &NodeData<'a>) -> () {
+  // Nodes emitted on their own are within brackets or start a statement, where `in` is allowed again.
+  let in_disallowed = out.set_in_disallowed(false);
   emit_js_under_operator(out, n, None);
+  out.set_in_disallowed(in_disallowed);
 }
 
 #[derive(Clone, Copy, PartialEq, Eq)]
@@ -298,7 +334,8 @@ fn get_leaf_node_type<'a>(n: &NodeData<'a>) -> LeafNodeType {
 // It's important to use this function:
 // - Omit semicolons where possible.
 // - Insert semicolon after last statement if its leaf is a `if`, `for`, `while`, or `with` statement with an empty statement as its body e.g. `if (x) label: for (;;) while (x)` but not `if (x) for (;;) label: while (x) {}` or `if (x) for (;;) label: while (x) return`.
-fn emit_statements<'a>(out: &mut Vec<u8>, statements: &[&mut NodeData<'a>]) -> () {
+// Returns whether any statement was emitted.
+fn emit_statements<'a>(out: &mut Emitter, statements: &[&mut NodeData<'a>]) -> bool {
   // Since we skip over some statements, the last actual statement may not be the last in the list.
   let mut last_statement: Option<&NodeData<'a>> = None;
   for n in statements {
@@ -306,24 +343,71 @@ fn emit_statements<'a>(out: &mut Vec<u8>, statements: &[&mut NodeData<'a>]) -> (
       continue;
     };
     if let Some(n) = last_statement {
-      match &n.stx {
-        Syntax::BlockStmt { .. }
-        | Syntax::ClassDecl { .. }
-        | Syntax::EmptyStmt {}
-        | Syntax::FunctionDecl { .. }
-        | Syntax::SwitchStmt { .. }
-        | Syntax::TryStmt { .. } => {}
-        _ => out.extend_from_slice(b";"),
-      }
+      if needs_semicolon_separator(out, n) {
+        out.statement_separator();
+      };
     }
+    out.break_point();
+    out.newline();
     emit_js(out, *n);
     last_statement = Some(*n);
   }
   if let Some(n) = last_statement {
-    if get_leaf_node_type(n) == LeafNodeType::EmptyStmt {
+    if get_leaf_node_type(n) == LeafNodeType::EmptyStmt
+      || ends_with_uninitialised_declaration(n)
+      || out.beautify() && !out.asi() && needs_semicolon_separator(out, n)
+    {
       out.extend_from_slice(b";");
     }
   }
+  last_statement.is_some()
+}
+
+// Whether the statement ends with a declaration whose last variable has no initializer (e.g. `if (x) var a, b`). That's valid before a `}`, but parse-js requires a `;` after it, so it couldn't parse our own output.
+fn ends_with_uninitialised_declaration(n: &NodeData<'_>) -> bool {
+  match &n.stx {
+    Syntax::VarDecl { declarators, .. } => declarators
+      .last()
+      .is_some_and(|d| d.initializer.is_none()),
+    Syntax::WhileStmt { body, .. } | Syntax::ForStmt { body, .. } => {
+      ends_with_uninitialised_declaration(body)
+    }
+    Syntax::LabelStmt { statement, .. } => ends_with_uninitialised_declaration(statement),
+    Syntax::IfStmt {
+      consequent,
+      alternate,
+      ..
+    } => ends_with_uninitialised_declaration(alternate.as_ref().unwrap_or(consequent)),
+    _ => false,
+  }
+}
+
+fn needs_semicolon_separator<'a>(out: &Emitter, n: &NodeData<'a>) -> bool {
+  // When beautifying, also omit the semicolon after e.g. `if (x) {}` for readability.
+  if out.beautify() && get_leaf_node_type(n) == LeafNodeType::Block {
+    return false;
+  };
+  match &n.stx {
+    Syntax::BlockStmt { .. }
+    | Syntax::ClassDecl { .. }
+    | Syntax::EmptyStmt {}
+    | Syntax::FunctionDecl { .. }
+    | Syntax::SwitchStmt { .. }
+    | Syntax::TryStmt { .. } => false,
+    _ => true,
+  }
+}
+
+// Emits statements wrapped in braces, on their own indented lines if beautifying.
+fn emit_block<'a>(out: &mut Emitter, statements: &[&mut NodeData<'a>]) {
+  out.extend_from_slice(b"{");
+  out.indent();
+  let emitted = emit_statements(out, statements);
+  out.dedent();
+  if emitted {
+    out.newline();
+  };
+  out.extend_from_slice(b"}");
 }
 
 fn is_comma_expression<'a>(stx: &Syntax<'a>) -> bool {
@@ -333,16 +417,211 @@ fn is_comma_expression<'a>(stx: &Syntax<'a>) -> bool {
   }
 }
 
+// Emits an expression where a comma would end it, such as an argument, an array element, or a default value, so a comma expression must be parenthesised (e.g. `f(a,(b,c))`). Parse trees don't need the parentheses, but trees from minifying do.
+fn emit_without_comma<'a>(out: &mut Emitter, n: &NodeData<'a>) {
+  let is_comma = is_comma_expression(&n.stx);
+  if is_comma {
+    out.extend_from_slice(b"(");
+  };
+  emit_js(out, n);
+  if is_comma {
+    out.extend_from_slice(b")");
+  };
+}
+
 fn leftmost_expression<'a, 'b>(stx: &'b Syntax<'a>) -> &'b Syntax<'a> {
   match stx {
     Syntax::ComputedMemberExpr { object, .. } => leftmost_expression(&object.stx),
     Syntax::MemberExpr { left, .. } | Syntax::BinaryExpr { left, .. } => {
       leftmost_expression(&left.stx)
     }
+    Syntax::CallExpr { callee, .. } => leftmost_expression(&callee.stx),
+    Syntax::ConditionalExpr { test, .. } => leftmost_expression(&test.stx),
+    Syntax::UnaryPostfixExpr { argument, .. } => leftmost_expression(&argument.stx),
     _ => stx,
   }
 }
 
+// Member accesses, calls, and `new` all have this precedence, so whether they need parentheses within each other depends on more than precedence; see `emit_chain_object` and `emit_new_callee`.
+fn chain_precedence() -> u8 {
+  OPERATORS[&OperatorName::MemberAccess].precedence
+}
+
+// Whether `n` is a member access or call that's part of an optional chain (e.g. `a?.b.c`), which the `?.` short-circuits. Parentheses end a chain, so `(a?.b).c` isn't one; computed member accesses don't record parentheses, so are always assumed to continue one.
+fn is_optional_chain<'a>(n: &NodeData<'a>) -> bool {
+  let (optional_chaining, object) = match &n.stx {
+    Syntax::MemberExpr {
+      optional_chaining,
+      left,
+      ..
+    } => (*optional_chaining, left),
+    Syntax::ComputedMemberExpr {
+      optional_chaining,
+      object,
+      ..
+    } => (*optional_chaining, object),
+    Syntax::CallExpr {
+      optional_chaining,
+      callee,
+      ..
+    } => (*optional_chaining, callee),
+    _ => return false,
+  };
+  optional_chaining
+    || (!matches!(
+      object.stx,
+      Syntax::MemberExpr {
+        parenthesised: true,
+        ..
+      } | Syntax::CallExpr {
+        parenthesised: true,
+        ..
+      }
+    ) && is_optional_chain(object))
+}
+
+// The parser reads as much of a member access and call chain as it can as the argument of `new`, so `new a.b().c` is `new` of `a.b().c`. The `new` takes the arguments of the innermost call in the chain that isn't parenthesised, if any, so that's `(new a.b()).c`; this returns that call. Otherwise, it's a `new` without arguments of the whole argument, e.g. `new a.b` or `new (f())`.
+pub(crate) fn new_call<'a, 'b>(argument: &'b NodeData<'a>) -> Option<&'b NodeData<'a>> {
+  let mut call = None;
+  let mut n = argument;
+  loop {
+    n = match &n.stx {
+      Syntax::MemberExpr {
+        parenthesised: false,
+        left,
+        ..
+      } => left,
+      Syntax::ComputedMemberExpr { object, .. } => object,
+      Syntax::CallExpr {
+        parenthesised: false,
+        callee,
+        ..
+      } => {
+        call = Some(n);
+        callee
+      }
+      _ => return call,
+    };
+  }
+}
+
+// Emits a member access or call, using `emit_object` to emit its object or callee.
+fn emit_chain_link<'a>(
+  out: &mut Emitter,
+  n: &NodeData<'a>,
+  emit_object: impl FnOnce(&mut Emitter, &NodeData<'a>),
+) {
+  match &n.stx {
+    Syntax::MemberExpr {
+      optional_chaining,
+      left,
+      right,
+      ..
+    } => {
+      emit_object(out, *left);
+      out.extend_from_slice(if *optional_chaining { b"?." } else { b"." });
+      out.extend_from_slice(right.as_slice());
+    }
+    Syntax::ComputedMemberExpr {
+      optional_chaining,
+      object,
+      member,
+      ..
+    } => {
+      emit_object(out, *object);
+      if *optional_chaining {
+        out.extend_from_slice(b"?.");
+      };
+      out.extend_from_slice(b"[");
+      emit_js(out, *member);
+      out.extend_from_slice(b"]");
+    }
+    Syntax::CallExpr {
+      optional_chaining,
+      callee,
+      arguments,
+      ..
+    } => {
+      emit_object(out, *callee);
+      if *optional_chaining {
+        out.extend_from_slice(b"?.");
+      }
+      out.extend_from_slice(b"(");
+      for (i, a) in arguments.iter().enumerate() {
+        if i > 0 {
+          out.extend_from_slice(b",");
+          out.break_point();
+          out.space();
+        }
+        emit_js(out, *a);
+      }
+      out.extend_from_slice(b")");
+    }
+    _ => unreachable!(),
+  };
+}
+
+// Emits the object of a member access or the callee of a call. Parentheses are only needed around a `new` without arguments, which would otherwise take the access or call (e.g. `(new a).b`), and to end an optional chain (e.g. `(a?.b).c`); any others are dropped.
+fn emit_chain_object<'a>(out: &mut Emitter, object: &NodeData<'a>) {
+  let parenthesise = match &object.stx {
+    Syntax::UnaryExpr {
+      operator: OperatorName::New,
+      argument,
+      ..
+    } => new_call(argument).is_none(),
+    Syntax::MemberExpr {
+      parenthesised: true,
+      ..
+    }
+    | Syntax::CallExpr {
+      parenthesised: true,
+      ..
+    } => is_optional_chain(object),
+    _ => false,
+  };
+  if parenthesise {
+    out.extend_from_slice(b"(");
+  };
+  emit_js_under_operator(out, object, Some(chain_precedence()));
+  if parenthesise {
+    out.extend_from_slice(b")");
+  };
+}
+
+// Emits what a `new` constructs, which must be a chain of member accesses without calls or optional chaining, so anything else is parenthesised (e.g. `new (f())()` or `new (a?.b)`). `followed` is whether arguments or a member access follow it, which a `new` without arguments within it would otherwise take (e.g. `new (new a)()`).
+fn emit_new_callee<'a>(out: &mut Emitter, callee: &NodeData<'a>, followed: bool) {
+  let parenthesise = match &callee.stx {
+    Syntax::CallExpr { .. } => true,
+    Syntax::MemberExpr { .. } | Syntax::ComputedMemberExpr { .. } => is_optional_chain(callee),
+    Syntax::UnaryExpr {
+      operator: OperatorName::New,
+      argument,
+      ..
+    } => followed && new_call(argument).is_none(),
+    _ => false,
+  };
+  if parenthesise {
+    out.extend_from_slice(b"(");
+    emit_js_under_operator(out, callee, Some(chain_precedence()));
+    out.extend_from_slice(b")");
+  } else if let Syntax::MemberExpr { .. } | Syntax::ComputedMemberExpr { .. } = callee.stx {
+    emit_chain_link(out, callee, |out, object| {
+      emit_new_callee(out, object, true)
+    });
+  } else {
+    emit_js_under_operator(out, callee, Some(chain_precedence()));
+  };
+}
+
+// Emits the argument of a `new` that has arguments: the chain down to `call` (see `new_call`), whose callee is what's constructed.
+fn emit_new_chain<'a>(out: &mut Emitter, n: &NodeData<'a>, call: &NodeData<'a>) {
+  if std::ptr::eq(n, call) {
+    emit_chain_link(out, n, |out, callee| emit_new_callee(out, callee, true));
+  } else {
+    emit_chain_link(out, n, |out, object| emit_new_chain(out, object, call));
+  };
+}
+
 /*
 For `do <stmt> while (...)` and `if <stmt> else (...)`, when does a semicolon need to be inserted after `<stmt>`?
 
@@ -360,19 +639,20 @@ For `do <stmt> while (...)` and `if <stmt> else (...)`, when does a semicolon ne
 */
 
 fn emit_js_under_operator<'a>(
-  out: &mut Vec<u8>,
+  out: &mut Emitter,
   node: &NodeData<'a>,
   parent_operator_precedence: Option<u8>,
 ) -> () {
+  let mapping = out.start_mapping(node.loc.start()..node.loc.end());
   match &node.stx {
     Syntax::EmptyStmt {} => {}
     Syntax::LiteralBigIntExpr { .. } => {
       // TODO This is invalid as `loc` may not be valid (e.g. newly created node during transform).
-      out.extend_from_slice(node.loc.as_slice());
+      emit_bigint(out, node.loc.as_slice());
     }
     Syntax::LiteralRegexExpr { .. } => {
       // TODO This is invalid as `loc` may not be valid (e.g. newly created node during transform).
-      out.extend_from_slice(node.loc.as_slice());
+      emit_raw_literal(out, node.loc.as_slice());
     }
     Syntax::LiteralBooleanExpr { value } => {
       match *value {
@@ -386,11 +666,14 @@ fn emit_js_under_operator<'a>(
     }
     Syntax::LiteralStringExpr { value } => {
       // TODO Possibly not optimal, could use `'` or `"` instead.
-      out.extend_from_slice(b"`");
-      TEMPLATE_LITERAL_ESCAPE_MAT
-        .stream_replace_all(value.as_bytes(), &mut *out, TEMPLATE_LITERAL_ESCAPE_REP)
-        .unwrap();
-      out.extend_from_slice(b"`");
+      let quote = if out.target() >= Target::Es2015 {
+        b'`'
+      } else {
+        b'"'
+      };
+      out.extend_from_slice(&[quote]);
+      emit_string_contents(out, value.as_bytes(), quote);
+      out.extend_from_slice(&[quote]);
     }
     Syntax::LiteralTemplateExpr { parts } => {
       out.extend_from_slice(b"`");
@@ -402,8 +685,7 @@ fn emit_js_under_operator<'a>(
             out.extend_from_slice(b"}");
           }
           LiteralTemplatePart::String(str) => {
-            // TODO Escape.
-            out.extend_from_slice(str.as_bytes());
+            emit_string_contents(out, str.as_bytes(), b'`');
           }
         }
       }
@@ -422,10 +704,14 @@ fn emit_js_under_operator<'a>(
       for (i, decl) in declarators.iter().enumerate() {
         if i > 0 {
           out.extend_from_slice(b",");
+          out.break_point();
+          out.space();
         }
         emit_js(out, decl.pattern);
         if let Some(expr) = &decl.initializer {
+          out.space();
           out.extend_from_slice(b"=");
+          out.space();
           // This is only really done for the Comma operator, which is the only operator below Assignment.
           let operator = &OPERATORS[&OperatorName::Assignment];
           emit_js_under_operator(out, *expr, Some(operator.precedence));
@@ -440,18 +726,24 @@ fn emit_js_under_operator<'a>(
       for (i, e) in elements.iter().enumerate() {
         if i > 0 {
           out.extend_from_slice(b",");
+          out.break_point();
+          out.space();
         }
         if let Some(e) = e {
           emit_js(out, e.target);
           if let Some(v) = &e.default_value {
+            out.space();
             out.extend_from_slice(b"=");
-            emit_js(out, *v);
+            out.space();
+            emit_without_comma(out, *v);
           }
         };
       }
       if let Some(r) = rest {
         if !elements.is_empty() {
           out.extend_from_slice(b",");
+          out.break_point();
+          out.space();
         }
         out.extend_from_slice(b"...");
         emit_js(out, *r);
@@ -460,19 +752,25 @@ fn emit_js_under_operator<'a>(
     }
     Syntax::ObjectPattern { properties, rest } => {
       out.extend_from_slice(b"{");
+      out.space();
       for (i, e) in properties.iter().enumerate() {
         if i > 0 {
           out.extend_from_slice(b",");
+          out.break_point();
+          out.space();
         }
         emit_js(out, *e);
       }
       if let Some(r) = rest {
         if !properties.is_empty() {
           out.extend_from_slice(b",");
+          out.break_point();
+          out.space();
         }
         out.extend_from_slice(b"...");
         emit_js(out, *r);
       };
+      out.space();
       out.extend_from_slice(b"}");
     }
     Syntax::ClassOrFunctionName { name } => {
@@ -482,6 +780,8 @@ fn emit_js_under_operator<'a>(
       for (i, p) in parameters.iter().enumerate() {
         if i > 0 {
           out.extend_from_slice(b",");
+          out.break_point();
+          out.space();
         };
         emit_js(out, *p);
       }
@@ -531,6 +831,7 @@ fn emit_js_under_operator<'a>(
       out.extend_from_slice(b"(");
       emit_js(out, *signature);
       out.extend_from_slice(b")");
+      out.space();
       emit_js(out, *body);
     }
     Syntax::ParamDecl {
@@ -543,8 +844,10 @@ fn emit_js_under_operator<'a>(
       };
       emit_js(out, *pattern);
       if let Some(v) = default_value {
+        out.space();
         out.extend_from_slice(b"=");
-        emit_js(out, *v);
+        out.space();
+        emit_without_comma(out, *v);
       }
     }
     Syntax::ArrowFunctionExpr {
@@ -555,7 +858,9 @@ fn emit_js_under_operator<'a>(
     } => {
       // See FunctionExpr.
       // TODO Omit parentheses if possible.
+      let in_disallowed = out.in_disallowed();
       if *parenthesised {
+        out.set_in_disallowed(false);
         out.extend_from_slice(b"(");
       }
       if *is_async {
@@ -589,7 +894,9 @@ fn emit_js_under_operator<'a>(
       if !can_omit_parentheses {
         out.extend_from_slice(b")");
       };
+      out.space();
       out.extend_from_slice(b"=>");
+      out.space();
       let must_parenthesise_body = match &body.stx {
         expr if is_comma_expression(expr) => true,
         // `{a: b}.b`, `{a: b} + 1`, etc. need to be wrapped.
@@ -601,14 +908,16 @@ fn emit_js_under_operator<'a>(
       };
       if must_parenthesise_body {
         out.extend_from_slice(b"(");
-      };
-      emit_js(out, *body);
-      if must_parenthesise_body {
+        emit_js(out, *body);
         out.extend_from_slice(b")");
+      } else {
+        // A body that's an expression is still within any `for(;;)` init.
+        emit_js_under_operator(out, body, None);
       };
       // TODO Omit parentheses if possible.
       if *parenthesised {
         out.extend_from_slice(b")");
+        out.set_in_disallowed(in_disallowed);
       };
     }
     Syntax::BinaryExpr {
@@ -625,19 +934,28 @@ fn emit_js_under_operator<'a>(
         // TODO Omit when possible.
         None if *operator_name == OperatorName::Assignment => *parenthesised,
         _ => false,
-      };
+      } || (*operator_name == OperatorName::In && out.in_disallowed());
+      let in_disallowed = out.in_disallowed();
       if must_parenthesise {
+        out.set_in_disallowed(false);
         out.extend_from_slice(b"(");
       };
       emit_js_under_operator(out, *left, Some(operator.precedence));
-      out.extend_from_slice(
-        BINARY_OPERATOR_SYNTAX
-          .get(operator_name)
-          .unwrap()
-          .as_bytes(),
-      );
+      let syntax = binary_operator_syntax(*operator_name).unwrap();
+      if out.beautify() {
+        if *operator_name != OperatorName::Comma {
+          out.space();
+        };
+        out.extend_from_slice(syntax.trim().as_bytes());
+        out.space();
+      } else {
+        out.extend_from_slice(syntax.as_bytes());
+      };
+      if *operator_name == OperatorName::Comma {
+        out.break_point();
+      };
       match operator_name {
-        OperatorName::Addition | OperatorName::Subtraction => {
+        OperatorName::Addition | OperatorName::Subtraction if !out.beautify() => {
           // Prevent potential confict with following unary operator e.g. `a+ +b` => `a++b`.
           // TODO Omit when possible.
           out.extend_from_slice(b" ");
@@ -647,38 +965,23 @@ fn emit_js_under_operator<'a>(
       emit_js_under_operator(out, *right, Some(operator.precedence));
       if must_parenthesise {
         out.extend_from_slice(b")");
+        out.set_in_disallowed(in_disallowed);
       };
     }
-    Syntax::CallExpr {
-      optional_chaining,
-      parenthesised,
-      callee,
-      arguments,
-    } => {
+    Syntax::CallExpr { parenthesised, .. } => {
       let operator = &OPERATORS[&OperatorName::Call];
       let must_parenthesise = match parent_operator_precedence {
         Some(po) if po > operator.precedence => true,
-        Some(po) if po == operator.precedence => *parenthesised,
         // We need to keep parentheses to prevent function expressions from being misinterpreted as a function declaration, which cannot be part of an expression e.g. IIFE.
         // TODO Omit parentheses if possible.
         None => *parenthesised,
+        // Within other member accesses, calls, and `new`, the parent decides.
         _ => false,
       };
       if must_parenthesise {
         out.extend_from_slice(b"(");
       }
-      emit_js_under_operator(out, *callee, Some(operator.precedence));
-      if *optional_chaining {
-        out.extend_from_slice(b"?.");
-      }
-      out.extend_from_slice(b"(");
-      for (i, a) in arguments.iter().enumerate() {
-        if i > 0 {
-          out.extend_from_slice(b",");
-        }
-        emit_js(out, *a);
-      }
-      out.extend_from_slice(b")");
+      emit_chain_link(out, node, emit_chain_object);
       // TODO Omit parentheses if possible.
       if must_parenthesise {
         out.extend_from_slice(b")");
@@ -699,10 +1002,19 @@ fn emit_js_under_operator<'a>(
       if must_parenthesise {
         out.extend_from_slice(b"(");
       };
-      emit_js_under_operator(out, *test, Some(operator.precedence));
+      // The conditional operator is right-associative, so a conditional as the test needs parentheses even if it wasn't written with them (e.g. when minified from an `if`), or it would take the rest (e.g. `(a?b:c)?d:e`).
+      let test_precedence = match test.stx {
+        Syntax::ConditionalExpr { .. } => operator.precedence + 1,
+        _ => operator.precedence,
+      };
+      emit_js_under_operator(out, *test, Some(test_precedence));
+      out.space();
       out.extend_from_slice(b"?");
+      out.space();
       emit_js_under_operator(out, *consequent, Some(operator.precedence));
+      out.space();
       out.extend_from_slice(b":");
+      out.space();
       emit_js_under_operator(out, *alternate, Some(operator.precedence));
       if must_parenthesise {
         out.extend_from_slice(b")");
@@ -737,6 +1049,7 @@ fn emit_js_under_operator<'a>(
       out.extend_from_slice(b"(");
       emit_js(out, *signature);
       out.extend_from_slice(b")");
+      out.space();
       emit_js(out, *body);
       // TODO Omit parentheses if possible.
       if *parenthesised {
@@ -748,7 +1061,7 @@ fn emit_js_under_operator<'a>(
     }
     Syntax::ImportExpr { module } => {
       out.extend_from_slice(b"import(");
-      emit_js(out, *module);
+      emit_without_comma(out, *module);
       out.extend_from_slice(b")");
     }
     Syntax::ImportMeta {} => {
@@ -809,7 +1122,7 @@ fn emit_js_under_operator<'a>(
     }
     Syntax::JsxSpreadAttribute { value } => {
       out.extend_from_slice(b"{...");
-      emit_js(out, *value);
+      emit_without_comma(out, *value);
       out.extend_from_slice(b"}");
     }
     Syntax::JsxText { value } => {
@@ -820,14 +1133,16 @@ fn emit_js_under_operator<'a>(
       for (i, e) in elements.iter().enumerate() {
         if i > 0 {
           out.extend_from_slice(b",");
+          out.break_point();
+          out.space();
         };
         match e {
           ArrayElement::Single(expr) => {
-            emit_js(out, *expr);
+            emit_without_comma(out, *expr);
           }
           ArrayElement::Rest(expr) => {
             out.extend_from_slice(b"...");
-            emit_js(out, *expr);
+            emit_without_comma(out, *expr);
           }
           ArrayElement::Empty => {}
         };
@@ -836,17 +1151,37 @@ fn emit_js_under_operator<'a>(
     }
     Syntax::LiteralObjectExpr { members } => {
       out.extend_from_slice(b"{");
+      if !members.is_empty() {
+        out.space();
+      };
       for (i, e) in members.iter().enumerate() {
         if i > 0 {
           out.extend_from_slice(b",");
+          out.break_point();
+          out.space();
         }
         emit_js(out, *e);
       }
+      if !members.is_empty() {
+        out.space();
+      };
       out.extend_from_slice(b"}");
     }
     Syntax::LiteralNull {} => {
       out.extend_from_slice(b"null");
     }
+    Syntax::UnaryExpr {
+      operator: OperatorName::New,
+      argument,
+      ..
+    } => {
+      // Nothing has higher precedence, and within other member accesses, calls, and `new`, the parent decides.
+      out.extend_from_slice(b"new ");
+      match new_call(argument) {
+        Some(call) => emit_new_chain(out, argument, call),
+        None => emit_new_callee(out, argument, false),
+      };
+    }
     Syntax::UnaryExpr {
       parenthesised,
       operator: operator_name,
@@ -861,7 +1196,7 @@ fn emit_js_under_operator<'a>(
       if must_parenthesise {
         out.extend_from_slice(b"(");
       };
-      out.extend_from_slice(UNARY_OPERATOR_SYNTAX.get(operator_name).unwrap().as_bytes());
+      out.extend_from_slice(unary_operator_syntax(*operator_name).unwrap().as_bytes());
       emit_js_under_operator(out, *argument, Some(operator.precedence));
       if must_parenthesise {
         out.extend_from_slice(b")");
@@ -892,9 +1227,7 @@ fn emit_js_under_operator<'a>(
       };
     }
     Syntax::BlockStmt { body } => {
-      out.extend_from_slice(b"{");
-      emit_statements(out, &body);
-      out.extend_from_slice(b"}");
+      emit_block(out, &body);
     }
     Syntax::BreakStmt { label } => {
       out.extend_from_slice(b"break");
@@ -913,23 +1246,8 @@ fn emit_js_under_operator<'a>(
     Syntax::DebuggerStmt {} => {
       out.extend_from_slice(b"debugger");
     }
-    Syntax::ComputedMemberExpr {
-      optional_chaining,
-      object,
-      member,
-      ..
-    } => {
-      emit_js_under_operator(
-        out,
-        *object,
-        Some(OPERATORS[&OperatorName::ComputedMemberAccess].precedence),
-      );
-      if *optional_chaining {
-        out.extend_from_slice(b"?.");
-      };
-      out.extend_from_slice(b"[");
-      emit_js(out, *member);
-      out.extend_from_slice(b"]");
+    Syntax::ComputedMemberExpr { .. } => {
+      emit_chain_link(out, node, emit_chain_object);
     }
     Syntax::ExportDefaultExprStmt { expression } => {
       out.extend_from_slice(b"export default ");
@@ -939,17 +1257,49 @@ fn emit_js_under_operator<'a>(
       out.extend_from_slice(b"export");
       emit_import_or_export_statement_trailer(out, Some(names), *from);
     }
-    Syntax::ExpressionStmt { expression } => {
-      emit_js(out, *expression);
-    }
+    Syntax::ExpressionStmt { expression } => match &expression.stx {
+      // A string on its own could be a directive (e.g. `"use strict"`), which must stay a string literal, as a template isn't a directive.
+      Syntax::LiteralStringExpr { value } => {
+        let mapping = out.start_mapping(expression.loc.start()..expression.loc.end());
+        out.extend_from_slice(b"\"");
+        emit_string_contents(out, value.as_bytes(), b'"');
+        out.extend_from_slice(b"\"");
+        out.end_mapping(mapping);
+      }
+      // A statement starting with `{`, `function`, or `class` would be a block or declaration, which minifying can cause (e.g. `({}).a` is parsed as a member access of an object without parentheses).
+      expr => {
+        let must_parenthesise = matches!(
+          leftmost_expression(expr),
+          Syntax::LiteralObjectExpr { .. }
+            | Syntax::FunctionExpr {
+              parenthesised: false,
+              ..
+            }
+            | Syntax::ClassExpr {
+              parenthesised: false,
+              ..
+            }
+        );
+        if must_parenthesise {
+          out.extend_from_slice(b"(");
+        };
+        emit_js(out, *expression);
+        if must_parenthesise {
+          out.extend_from_slice(b")");
+        };
+      }
+    },
     Syntax::IfStmt {
       test,
       consequent,
       alternate,
     } => {
-      out.extend_from_slice(b"if(");
+      out.extend_from_slice(b"if");
+      out.space();
+      out.extend_from_slice(b"(");
       emit_js(out, *test);
       out.extend_from_slice(b")");
+      out.space();
       emit_js(out, *consequent);
       if let Some(alternate) = alternate {
         if get_leaf_node_type(*consequent) == LeafNodeType::Block {
@@ -957,9 +1307,10 @@ fn emit_js_under_operator<'a>(
         } else {
           out.extend_from_slice(b";");
         };
+        out.space();
         out.extend_from_slice(b"else");
         if let Syntax::BlockStmt { .. } = &alternate.stx {
-          // Do nothing.
+          out.space();
         } else {
           out.extend_from_slice(b" ");
         };
@@ -974,17 +1325,24 @@ fn emit_js_under_operator<'a>(
           condition,
           post,
         } => {
+          out.space();
           out.extend_from_slice(b"(");
           match init {
             ForThreeInit::None => {}
-            ForThreeInit::Expression(n) | ForThreeInit::Declaration(n) => emit_js(out, *n),
+            ForThreeInit::Expression(n) | ForThreeInit::Declaration(n) => {
+              out.set_in_disallowed(true);
+              emit_js_under_operator(out, n, None);
+              out.set_in_disallowed(false);
+            }
           };
           out.extend_from_slice(b";");
           if let Some(n) = condition {
+            out.space();
             emit_js(out, *n);
           };
           out.extend_from_slice(b";");
           if let Some(n) = post {
+            out.space();
             emit_js(out, *n);
           };
         }
@@ -997,6 +1355,7 @@ fn emit_js_under_operator<'a>(
           if *await_ {
             out.extend_from_slice(b" await");
           }
+          out.space();
           out.extend_from_slice(b"(");
           match lhs {
             ForInOfStmtHeaderLhs::Declaration(n) | ForInOfStmtHeaderLhs::Pattern(n) => {
@@ -1005,13 +1364,17 @@ fn emit_js_under_operator<'a>(
           };
           if *of {
             out.extend_from_slice(b" of ");
+            // Only a `for-in` can have a comma expression on the right.
+            let operator = &OPERATORS[&OperatorName::Assignment];
+            emit_js_under_operator(out, rhs, Some(operator.precedence));
           } else {
             out.extend_from_slice(b" in ");
+            emit_js(out, *rhs);
           }
-          emit_js(out, *rhs);
         }
       };
       out.extend_from_slice(b")");
+      out.space();
       emit_js(out, *body);
     }
     Syntax::ImportStmt {
@@ -1048,6 +1411,16 @@ fn emit_js_under_operator<'a>(
     }
     Syntax::TopLevel { body } => {
       emit_statements(out, &body);
+      // Make sure a following script can't continue the last statement, unless it already ends with `;` (as always when beautifying without ASI) or a block.
+      let last = body
+        .iter()
+        .rev()
+        .find(|n| !matches!(n.stx, Syntax::EmptyStmt {}));
+      if let Some(n) = last.filter(|_| out.safe_concat() && (out.asi() || !out.beautify())) {
+        if get_leaf_node_type(n) == LeafNodeType::Other && needs_semicolon_separator(out, n) {
+          out.extend_from_slice(b";");
+        };
+      };
     }
     Syntax::TryStmt {
       wrapped,
@@ -1055,25 +1428,32 @@ fn emit_js_under_operator<'a>(
       finally,
     } => {
       out.extend_from_slice(b"try");
+      out.space();
       emit_js(out, *wrapped);
       if let Some(c) = catch {
+        out.space();
         emit_js(out, *c);
       }
       if let Some(f) = finally {
+        out.space();
         out.extend_from_slice(b"finally");
+        out.space();
         emit_js(out, *f);
       };
     }
     Syntax::WhileStmt { condition, body } => {
-      out.extend_from_slice(b"while(");
+      out.extend_from_slice(b"while");
+      out.space();
+      out.extend_from_slice(b"(");
       emit_js(out, *condition);
       out.extend_from_slice(b")");
+      out.space();
       emit_js(out, *body);
     }
     Syntax::DoWhileStmt { condition, body } => {
       out.extend_from_slice(b"do");
       if let Syntax::BlockStmt { .. } = &body.stx {
-        // Do nothing.
+        out.space();
       } else {
         out.extend_from_slice(b" ");
       };
@@ -1083,28 +1463,44 @@ fn emit_js_under_operator<'a>(
       } else {
         out.extend_from_slice(b";");
       };
-      out.extend_from_slice(b"while(");
+      out.space();
+      out.extend_from_slice(b"while");
+      out.space();
+      out.extend_from_slice(b"(");
       emit_js(out, *condition);
       out.extend_from_slice(b")");
     }
     Syntax::SwitchStmt { test, branches } => {
-      out.extend_from_slice(b"switch(");
+      out.extend_from_slice(b"switch");
+      out.space();
+      out.extend_from_slice(b"(");
       emit_js(out, *test);
-      out.extend_from_slice(b"){");
+      out.extend_from_slice(b")");
+      out.space();
+      out.extend_from_slice(b"{");
+      out.indent();
       for (i, b) in branches.iter().enumerate() {
-        if i > 0 {
-          out.extend_from_slice(b";");
+        // When beautifying, every statement is already terminated, or is on its own line.
+        if i > 0 && !out.beautify() {
+          out.statement_separator();
         };
+        out.newline();
         emit_js(out, *b);
       }
+      out.dedent();
+      if !branches.is_empty() {
+        out.newline();
+      };
       out.extend_from_slice(b"}");
     }
     Syntax::CatchBlock { parameter, body } => {
       out.extend_from_slice(b"catch");
+      out.space();
       if let Some(p) = parameter {
         out.extend_from_slice(b"(");
         emit_js(out, *p);
         out.extend_from_slice(b")");
+        out.space();
       }
       emit_js(out, *body);
     }
@@ -1120,7 +1516,9 @@ fn emit_js_under_operator<'a>(
           out.extend_from_slice(b"default:");
         }
       }
+      out.indent();
       emit_statements(out, &body);
+      out.dedent();
     }
     Syntax::ObjectPatternProperty {
       key,
@@ -1130,21 +1528,24 @@ fn emit_js_under_operator<'a>(
     } => {
       match key {
         ClassOrObjectMemberKey::Direct(name) => {
-          out.extend_from_slice(name.as_slice());
+          emit_raw_literal(out, name.as_slice());
         }
         ClassOrObjectMemberKey::Computed(expr) => {
           out.extend_from_slice(b"[");
-          emit_js(out, *expr);
+          emit_without_comma(out, *expr);
           out.extend_from_slice(b"]");
         }
       };
       if !*shorthand {
         out.extend_from_slice(b":");
+        out.space();
         emit_js(out, *target);
       };
       if let Some(v) = default_value {
+        out.space();
         out.extend_from_slice(b"=");
-        emit_js(out, *v);
+        out.space();
+        emit_without_comma(out, *v);
       };
     }
     Syntax::ObjectMember { typ } => {
@@ -1157,42 +1558,13 @@ fn emit_js_under_operator<'a>(
         }
         ObjectMemberType::Rest { value } => {
           out.extend_from_slice(b"...");
-          emit_js(out, *value);
+          emit_without_comma(out, *value);
         }
       };
     }
-    Syntax::MemberExpr {
-      parenthesised,
-      optional_chaining,
-      left,
-      right,
-      ..
-    } => {
-      let operator_name = &if *optional_chaining {
-        OperatorName::OptionalChainingMemberAccess
-      } else {
-        OperatorName::MemberAccess
-      };
-      let operator = &OPERATORS[operator_name];
-      let must_parenthesise = match parent_operator_precedence {
-        Some(po) if po > operator.precedence => true,
-        Some(po) if po == operator.precedence => *parenthesised,
-        _ => false,
-      };
-      if must_parenthesise {
-        out.extend_from_slice(b"(");
-      };
-      emit_js_under_operator(out, *left, Some(operator.precedence));
-      out.extend_from_slice(
-        BINARY_OPERATOR_SYNTAX
-          .get(operator_name)
-          .unwrap()
-          .as_bytes(),
-      );
-      out.extend_from_slice(right.as_slice());
-      if must_parenthesise {
-        out.extend_from_slice(b")");
-      };
+    Syntax::MemberExpr { .. } => {
+      // Nothing has higher precedence, and within other member accesses, calls, and `new`, the parent decides.
+      emit_chain_link(out, node, emit_chain_object);
     }
     Syntax::ClassExpr {
       parenthesised,
@@ -1214,17 +1586,19 @@ fn emit_js_under_operator<'a>(
     Syntax::LabelStmt { name, statement } => {
       out.extend_from_slice(name.as_slice());
       out.extend_from_slice(b":");
+      out.space();
       emit_js(out, *statement);
     }
     Syntax::CallArg { spread, value } => {
       if *spread {
         out.extend_from_slice(b"...");
       }
-      emit_js(out, *value);
+      emit_without_comma(out, *value);
     }
     Syntax::SuperExpr {} => {
       out.extend_from_slice(b"super");
     }
     Syntax::_TakenNode {} => unreachable!(),
   };
+  out.end_mapping(mapping);
 }
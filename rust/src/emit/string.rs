@@ -0,0 +1,110 @@
+use super::emitter::Emitter;
+use std::io::Write;
+
+// Emits the contents of a string literal delimited by `quote`, which must be one of `"`, `'`, or `` ` ``. The value is the decoded (cooked) string value, not the raw source.
+// Besides the usual escapes, this always escapes:
+// - U+2028 and U+2029, which terminate lines in older engines and some tools.
+// - Lone surrogates, which can only arrive as WTF-8 bytes from input that isn't valid UTF-8, and would otherwise be written out as invalid UTF-8.
+// - CR in template literals, as a line terminator in a template (including CRLF) is read as LF. A CR in the value of a template can only come from a `\r` escape, as CRLF line terminators are read as LF when parsing (see `fix_template_line_terminators`).
+// - `<` starting `<!--` or `</script`, if inlining into HTML.
+pub fn emit_string_contents(out: &mut Emitter, value: &[u8], quote: u8) {
+  let mut run_start = 0;
+  let mut i = 0;
+  while i < value.len() {
+    let c = value[i];
+    let (len, escaped): (usize, &[u8]) = match c {
+      b'\\' => (1, b"\\\\"),
+      b'\n' if quote != b'`' => (1, b"\\n"),
+      b'\r' => (1, b"\\r"),
+      b'"' if quote == b'"' => (1, b"\\\""),
+      b'\'' if quote == b'\'' => (1, b"\\'"),
+      b'`' if quote == b'`' => (1, b"\\`"),
+      b'$' if quote == b'`' && value.get(i + 1) == Some(&b'{') => (1, b"\\$"),
+      b'<' if out.inline_script() && starts_html_sequence(&value[i..]) => (1, b"\\x3C"),
+      0xe2 if value.get(i + 1) == Some(&0x80) => match value.get(i + 2) {
+        Some(0xa8) => (3, b"\\u2028"),
+        Some(0xa9) => (3, b"\\u2029"),
+        _ => (0, b""),
+      },
+      0xed if matches!(value.get(i + 1), Some(0xa0..=0xbf)) && i + 2 < value.len() => {
+        out.extend_from_slice(&value[run_start..i]);
+        let cp = 0xd000 | (u32::from(value[i + 1] & 0x3f) << 6) | u32::from(value[i + 2] & 0x3f);
+        write!(out, "\\u{:x}", cp).unwrap();
+        i += 3;
+        run_start = i;
+        continue;
+      }
+      _ => (0, b""),
+    };
+    if len == 0 {
+      i += 1;
+      continue;
+    };
+    out.extend_from_slice(&value[run_start..i]);
+    out.extend_from_slice(escaped);
+    i += len;
+    run_start = i;
+  }
+  out.extend_from_slice(&value[run_start..]);
+}
+
+// Whether `s` starts with `<!--` or `</script` (in any case), which would break out of a `<script>` element that the output is inlined into.
+pub fn starts_html_sequence(s: &[u8]) -> bool {
+  let starts_with = |seq: &[u8]| s.len() >= seq.len() && s[..seq.len()].eq_ignore_ascii_case(seq);
+  starts_with(b"<!--") || starts_with(b"</script")
+}
+
+// Emits a literal copied as is from the source, such as a regex or quoted property name. If inlining into HTML, `<` is escaped as in `emit_string_contents`, which works in regexes too. A `<` that's already escaped (`\<`) only needs the `x3C` added.
+pub fn emit_raw_literal(out: &mut Emitter, raw: &[u8]) {
+  if !out.inline_script() {
+    out.extend_from_slice(raw);
+    return;
+  };
+  let mut run_start = 0;
+  for i in 0..raw.len() {
+    if raw[i] == b'<' && starts_html_sequence(&raw[i..]) {
+      out.extend_from_slice(&raw[run_start..i]);
+      let backslashes = raw[..i].iter().rev().take_while(|&&c| c == b'\\').count();
+      out.extend_from_slice(if backslashes % 2 == 1 {
+        b"x3C"
+      } else {
+        b"\\x3C"
+      });
+      run_start = i + 1;
+    };
+  }
+  out.extend_from_slice(&raw[run_start..]);
+}
+
+#[cfg(test)]
+mod tests {
+  use super::emit_string_contents;
+  use crate::emit::emitter::Emitter;
+  use crate::MinifyOptions;
+
+  fn check(value: &[u8], quote: u8, expected: &str) {
+    let mut out = Vec::new();
+    emit_string_contents(
+      &mut Emitter::new(&mut out, &MinifyOptions::new()),
+      value,
+      quote,
+    );
+    assert_eq!(std::str::from_utf8(&out).unwrap(), expected);
+  }
+
+  #[test]
+  fn test_emit_string_contents() {
+    check(b"a\\b`c$d${e}", b'`', "a\\\\b\\`c$d\\${e}");
+    check(b"a\nb\rc", b'`', "a\nb\\rc");
+    check(b"a\nb\"c'd`", b'"', "a\\nb\\\"c'd`");
+    check(b"'", b'\'', "\\'");
+    check(
+      "a\u{2028}b\u{2029}c\u{2027}".as_bytes(),
+      b'`',
+      "a\\u2028b\\u2029c\u{2027}",
+    );
+    check("\u{1F600}\u{e000}".as_bytes(), b'`', "\u{1F600}\u{e000}");
+    // WTF-8 encoded lone surrogates U+D800 and U+DFFF.
+    check(b"a\xed\xa0\x80b\xed\xbf\xbf", b'`', "a\\ud800b\\udfff");
+  }
+}
@@ -1,5 +1,8 @@
 use crate::emit::emit_js;
+use crate::emit::Emitter;
 use crate::minify::minify_js;
+use crate::minify::KeepTopLevel;
+use crate::MinifyOptions;
 use crate::TopLevelMode;
 use parse_js::lex::Lexer;
 use parse_js::parse::Parser;
@@ -7,14 +10,23 @@ use parse_js::session::Session;
 use parse_js::symbol::SymbolGenerator;
 
 fn check(top_level_mode: TopLevelMode, src: &str, expected: &str) -> () {
+  check_with_cfg(&MinifyOptions::new(), top_level_mode, src, expected);
+}
+
+fn check_with_cfg(cfg: &MinifyOptions, top_level_mode: TopLevelMode, src: &str, expected: &str) {
   let session = Session::new();
   let mut parser = Parser::new(Lexer::new(src.as_bytes()));
   let node = parser
     .parse_top_level(&session, SymbolGenerator::new(), top_level_mode)
     .unwrap();
   let mut out = Vec::new();
-  minify_js(&session, node);
-  emit_js(&mut out, node);
+  // Statements without side effects are kept, so tests can emit expressions on their own.
+  let keep = KeepTopLevel {
+    pure_statements: true,
+    ..KeepTopLevel::default()
+  };
+  minify_js(&session, node, cfg, &mut None, keep).unwrap();
+  emit_js(&mut Emitter::new(&mut out, cfg), node);
   assert_eq!(
     unsafe { std::str::from_utf8_unchecked(out.as_slice()) },
     expected
@@ -301,3 +313,253 @@ fn test_advanced_if_minification() {
     r#"var foo=(a=>{var d,c;if(!a)return d=3,d;var b=1;if(cond)return c=2,c;return b})"#,
   );
 }
+
+#[test]
+fn test_emit_string_escapes() {
+  check(
+    TopLevelMode::Module,
+    "import a from 'a\"b\u{2028}';a('\u{2028}\\r${x}', `\u{2029}\\r\\`${a}\\${`);",
+    "import a from\"a\\\"b\\u2028\";a(`\\u2028\\r\\${x}`,`\\u2029\\r\\`${a}\\${`)",
+  );
+}
+
+#[test]
+fn test_emit_beautify() {
+  let cfg = MinifyOptions {
+    beautify: true,
+    ..MinifyOptions::new()
+  };
+  check_with_cfg(
+    &cfg,
+    TopLevelMode::Global,
+    r#"
+      function f(a, b = 1) {
+        if (a) {
+          console.log(a + b, {x: 1, y: a ? b : 2});
+        } else return;
+        for (let i = 0; i < 2; i++) {}
+        switch (b) {
+          case 1:
+            a();
+          default:
+        }
+        try { a() } catch (e) { b() } finally {}
+        class C extends D { x = 1; y() { return 2 } }
+        do a(); while (b);
+        return [1, 2].map(x => x * 2);
+      }
+    "#,
+    "\
+var f = ((a, b = 1) => {
+  if (!a) return undefined;
+  console.log(a + b, { x: 1, y: a ? b : 2 });
+  for (let a = 0; a < 2; a++) {}
+  switch (b) {
+    case 1:
+      a();
+    default:
+  }
+  try {
+    a();
+  } catch (a) {
+    b();
+  } finally {}
+  class c extends D {
+    x = 1;
+    y() {
+      return 2;
+    }
+  }
+  do a(); while (b);
+  return [1, 2].map(a => a * 2);
+});",
+  );
+}
+
+#[test]
+fn test_emit_asi() {
+  let src = r#"
+    let a = b;
+    (function () { c() })();
+    [1, 2].forEach(d);
+    a++;
+    -a;
+    `x`;
+    /y/.test(a);
+    switch (a) { case 1: c(); case 2: d() }
+    if (a) { c(); d() }
+  "#;
+  check_with_cfg(
+    &MinifyOptions {
+      asi: true,
+      ..MinifyOptions::new()
+    },
+    TopLevelMode::Global,
+    src,
+    "let a=b;(()=>{c()})();[1,2].forEach(d)\na++;-a;`x`;/y/.test(a)\nswitch(a){case 1:c()\ncase 2:d()}a&&(c(),d())",
+  );
+  check_with_cfg(
+    &MinifyOptions {
+      asi: true,
+      beautify: true,
+      ..MinifyOptions::new()
+    },
+    TopLevelMode::Global,
+    src,
+    "\
+let a = b
+;(() => {
+  c()
+})()
+;[1, 2].forEach(d)
+a++
+;-a
+;`x`
+;/y/.test(a)
+switch (a) {
+  case 1:
+    c()
+  case 2:
+    d()
+}
+a && (c(), d())",
+  );
+}
+
+// Emits `src` without minifying it, and checks that emitting the output again doesn't change it.
+fn check_emit(src: &str, expected: &str) {
+  let emit = |src: &str| {
+    let session = Session::new();
+    let mut parser = Parser::new(Lexer::new(src.as_bytes()));
+    let node = parser
+      .parse_top_level(&session, SymbolGenerator::new(), TopLevelMode::Global)
+      .unwrap();
+    let mut out = Vec::new();
+    emit_js(&mut Emitter::new(&mut out, &MinifyOptions::new()), node);
+    String::from_utf8(out).unwrap()
+  };
+  let out = emit(src);
+  assert_eq!(out, expected, "{}", src);
+  assert_eq!(emit(&out), expected, "{}", src);
+}
+
+#[test]
+fn test_emit_new_and_call_chains() {
+  for (src, expected) in [
+    // A `new` takes the arguments of the innermost call in its argument that isn't parenthesised.
+    ("new a", "new a"),
+    ("new a()", "new a()"),
+    ("new a.b()", "new a.b()"),
+    ("new a.b().c", "new a.b().c"),
+    ("new a().b()", "new a().b()"),
+    ("new a()()", "new a()()"),
+    ("new a()?.b", "new a()?.b"),
+    ("new a[b()]()", "new a[b()]()"),
+    ("(new a()).b", "new a().b"),
+    ("(new a())()", "new a()()"),
+    // What's constructed can't contain calls or optional chaining.
+    ("new (a.b())", "new (a.b())"),
+    ("new (f())", "new (f())"),
+    ("new (f())()", "new (f())()"),
+    ("new (a().b)", "new (a()).b"),
+    ("new (a().b)()", "new (a()).b()"),
+    ("new (a().b).c", "new (a()).b.c"),
+    ("new (a?.b)", "new (a?.b)"),
+    ("new (a?.b)()", "new (a?.b)()"),
+    ("new (a?.b.c)", "new (a?.b.c)"),
+    ("new (a?.b).c", "new (a?.b).c"),
+    ("new (a?.[b])", "new (a?.[b])"),
+    ("new (a.b)", "new a.b"),
+    ("new (a.b)()", "new a.b()"),
+    ("new (a[b()])", "new a[b()]"),
+    // A `new` without arguments would take any that follow it.
+    ("(new a).b", "(new a).b"),
+    ("(new a)()", "(new a)()"),
+    ("(new a)[b]", "(new a)[b]"),
+    ("new (new a)", "new new a"),
+    ("new (new a)()", "new (new a)()"),
+    ("new (new a).b", "new (new a).b"),
+    ("new (new a())()", "new new a()()"),
+    ("new (new a().b)", "new new a().b"),
+    ("new new a()()", "new new a()()"),
+    // Operators with lower precedence.
+    ("new (a, b)", "new (a,b)"),
+    ("new (a ? b : c)()", "new (a?b:c)()"),
+    ("new (a = b)", "new (a=b)"),
+    ("(a = b).c", "(a=b).c"),
+    ("(-a).b", "(-a).b"),
+    ("new (-a)", "new (-a)"),
+    // Parentheses end an optional chain.
+    ("(a?.b).c", "(a?.b).c"),
+    ("(a?.b)()", "(a?.b)()"),
+    ("(a?.())()", "(a?.())()"),
+    ("(a?.b.c).d", "(a?.b.c).d"),
+    ("a?.b.c()", "a?.b.c()"),
+    ("(a.b?.c)?.d", "(a.b?.c)?.d"),
+    // Other parentheses within a chain aren't needed.
+    ("(a.b)()", "a.b()"),
+    ("(a())()", "a()()"),
+    ("(a.b).c", "a.b.c"),
+    ("((a)().b)[c]", "a().b[c]"),
+    ("(a?.b.c)", "a?.b.c"),
+  ] {
+    check_emit(&format!("x = {}", src), &format!("x={}", expected));
+  }
+  // Calling a function expression must stay parenthesised at the start of a statement.
+  check_emit("(function(){})()", "(function(){})()");
+  check_emit("new function(){}", "new function(){}");
+  check_emit("new class{}()", "new class{}()");
+}
+
+#[test]
+fn test_emit_in_within_for_init() {
+  for (src, expected) in [
+    // An `in` outside brackets would make it a `for-in`.
+    ("for (var i = (a in b); i;);", "for(var i=(a in b);i;);"),
+    (
+      "for (var i = 1, j = (a in b); ;);",
+      "for(var i=1,j=(a in b);;);",
+    ),
+    (
+      "for (let i = (a in b) ? c : d; ;);",
+      "for(let i=(a in b)?c:d;;);",
+    ),
+    ("for ((a in b); ;);", "for((a in b);;);"),
+    ("for (x = (a in b) || c; ;);", "for(x=(a in b)||c;;);"),
+    ("for (x = !(a in b); ;);", "for(x=!(a in b);;);"),
+    ("for (x = (a in b).c; ;);", "for(x=(a in b).c;;);"),
+    (
+      "for (var f = (a) => (a in b); ;);",
+      "for(var f=a=>(a in b);;);",
+    ),
+    ("for (x = (a, (b in c)); ;);", "for(x=(a,b in c);;);"),
+    // Within brackets or a function body, it's unambiguous.
+    ("for (var i = [a in b]; ;);", "for(var i=[a in b];;);"),
+    (
+      "for (var i = { c: (a in b) }; ;);",
+      "for(var i={c:a in b};;);",
+    ),
+    ("for (var i = f((a in b)); ;);", "for(var i=f(a in b);;);"),
+    ("for (var i = c[(a in b)]; ;);", "for(var i=c[a in b];;);"),
+    (
+      "for (var i = `${(a in b)}`; ;);",
+      "for(var i=`${a in b}`;;);",
+    ),
+    (
+      "for (var f = function () { return (a in b) }; ;);",
+      "for(var f=function(){return a in b};;);",
+    ),
+    (
+      "for (var f = () => { (a in b) }; ;);",
+      "for(var f=()=>{a in b};;);",
+    ),
+    // Elsewhere in the head, it's also unambiguous.
+    ("for (; (a in b); (c in d));", "for(;a in b;c in d);"),
+    ("for (var x in (a in b));", "for(var x in a in b);"),
+    // Only a `for-in` can have a comma expression on the right.
+    ("for (var x in (a, b));", "for(var x in a,b);"),
+    ("for (var x of (a, b));", "for(var x of (a,b));"),
+  ] {
+    check_emit(src, expected);
+  }
+}
@@ -0,0 +1,256 @@
+use std::str::FromStr;
+
+// Globals defined by ECMAScript, so they exist in every environment.
+const STANDARD_GLOBALS: &[&str] = &[
+  "AggregateError",
+  "Array",
+  "ArrayBuffer",
+  "Atomics",
+  "BigInt",
+  "BigInt64Array",
+  "BigUint64Array",
+  "Boolean",
+  "DataView",
+  "Date",
+  "Error",
+  "EvalError",
+  "FinalizationRegistry",
+  "Float32Array",
+  "Float64Array",
+  "Function",
+  "Infinity",
+  "Int16Array",
+  "Int32Array",
+  "Int8Array",
+  "Intl",
+  "JSON",
+  "Map",
+  "Math",
+  "NaN",
+  "Number",
+  "Object",
+  "Promise",
+  "Proxy",
+  "RangeError",
+  "ReferenceError",
+  "Reflect",
+  "RegExp",
+  "Set",
+  "SharedArrayBuffer",
+  "String",
+  "Symbol",
+  "SyntaxError",
+  "TypeError",
+  "URIError",
+  "Uint16Array",
+  "Uint32Array",
+  "Uint8Array",
+  "Uint8ClampedArray",
+  "WeakMap",
+  "WeakRef",
+  "WeakSet",
+  "decodeURI",
+  "decodeURIComponent",
+  "encodeURI",
+  "encodeURIComponent",
+  "escape",
+  "eval",
+  "globalThis",
+  "isFinite",
+  "isNaN",
+  "parseFloat",
+  "parseInt",
+  "undefined",
+  "unescape",
+];
+
+// Web APIs that browsers, workers, and Node.js all have.
+const WEB_GLOBALS: &[&str] = &[
+  "AbortController",
+  "AbortSignal",
+  "Blob",
+  "BroadcastChannel",
+  "Event",
+  "EventTarget",
+  "FormData",
+  "Headers",
+  "MessageChannel",
+  "MessagePort",
+  "ReadableStream",
+  "Request",
+  "Response",
+  "TextDecoder",
+  "TextEncoder",
+  "TransformStream",
+  "URL",
+  "URLSearchParams",
+  "WebAssembly",
+  "WritableStream",
+  "atob",
+  "btoa",
+  "clearInterval",
+  "clearTimeout",
+  "console",
+  "crypto",
+  "fetch",
+  "performance",
+  "queueMicrotask",
+  "setInterval",
+  "setTimeout",
+  "structuredClone",
+];
+
+const BROWSER_GLOBALS: &[&str] = &[
+  "CustomEvent",
+  "Document",
+  "Element",
+  "HTMLElement",
+  "Image",
+  "MutationObserver",
+  "Node",
+  "WebSocket",
+  "Worker",
+  "XMLHttpRequest",
+  "addEventListener",
+  "alert",
+  "cancelAnimationFrame",
+  "confirm",
+  "customElements",
+  "devicePixelRatio",
+  "dispatchEvent",
+  "document",
+  "getComputedStyle",
+  "history",
+  "innerHeight",
+  "innerWidth",
+  "localStorage",
+  "location",
+  "matchMedia",
+  "navigator",
+  "postMessage",
+  "prompt",
+  "removeEventListener",
+  "requestAnimationFrame",
+  "screen",
+  "self",
+  "sessionStorage",
+  "window",
+];
+
+const WORKER_GLOBALS: &[&str] = &[
+  "WebSocket",
+  "XMLHttpRequest",
+  "addEventListener",
+  "close",
+  "dispatchEvent",
+  "importScripts",
+  "location",
+  "navigator",
+  "postMessage",
+  "removeEventListener",
+  "self",
+];
+
+// Includes the variables that Node.js passes to CommonJS modules, as they're in scope for any script it runs.
+const NODE_GLOBALS: &[&str] = &[
+  "Buffer",
+  "__dirname",
+  "__filename",
+  "clearImmediate",
+  "exports",
+  "global",
+  "module",
+  "process",
+  "require",
+  "setImmediate",
+];
+
+/// The environment that the code runs in, which decides which globals can be assumed to exist and not be reassigned (e.g. `window` and `document` in browsers, or `process` in Node.js). With a preset other than [Environment::None]:
+///
+/// - reading a known global is assumed to have no side effects, so statements that only read them (e.g. `document;`) can be dropped;
+/// - minified names never shadow known globals, so code evaluated in the same scope (such as with `eval`) can still use them;
+/// - reading or calling a global that isn't known, which is often a typo or code meant for another environment, is reported as a `W_UNKNOWN_GLOBAL` warning (see [MinifyOptions::warnings](crate::MinifyOptions::warnings)), as is assigning to a known one.
+///
+/// It can also be parsed from its lowercase name.
+///
+/// # Examples
+///
+/// ```
+/// use minify_js::{Environment, MinifyOptions, Session, minify_str};
+///
+/// let session = Session::new();
+/// let cfg = MinifyOptions::new().environment(Environment::Browser);
+/// let out = minify_str(&session, "(() => { document; f() })()", &cfg);
+/// assert_eq!(out.unwrap(), "(()=>{f()})()");
+/// assert!(Environment::Node.globals().any(|g| g == "process"));
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serialize", serde(rename_all = "lowercase"))]
+pub enum Environment {
+  /// Make no assumptions about globals, not even the ones defined by ECMAScript, as they could have been deleted or replaced.
+  #[default]
+  None,
+  /// A browser window, such as a `<script>` element.
+  Browser,
+  /// Node.js, including the variables it passes to CommonJS modules (e.g. `require` and `__dirname`).
+  Node,
+  /// A web worker or service worker.
+  Worker,
+}
+
+impl Environment {
+  fn lists(self) -> &'static [&'static [&'static str]] {
+    match self {
+      Environment::None => &[],
+      Environment::Browser => &[STANDARD_GLOBALS, WEB_GLOBALS, BROWSER_GLOBALS],
+      Environment::Node => &[STANDARD_GLOBALS, WEB_GLOBALS, NODE_GLOBALS],
+      Environment::Worker => &[STANDARD_GLOBALS, WEB_GLOBALS, WORKER_GLOBALS],
+    }
+  }
+
+  /// The globals that can be assumed to exist and not be reassigned.
+  pub fn globals(self) -> impl Iterator<Item = &'static str> {
+    self.lists().iter().flat_map(|l| l.iter().copied())
+  }
+
+  /// Whether `name` is one of [Environment::globals].
+  pub fn has_global(self, name: &[u8]) -> bool {
+    self
+      .lists()
+      .iter()
+      .any(|l| l.binary_search_by(|g| g.as_bytes().cmp(name)).is_ok())
+  }
+}
+
+impl FromStr for Environment {
+  type Err = &'static str;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "none" => Ok(Environment::None),
+      "browser" => Ok(Environment::Browser),
+      "node" => Ok(Environment::Node),
+      "worker" => Ok(Environment::Worker),
+      _ => Err("invalid environment"),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_globals_sorted() {
+    for list in [
+      STANDARD_GLOBALS,
+      WEB_GLOBALS,
+      BROWSER_GLOBALS,
+      WORKER_GLOBALS,
+      NODE_GLOBALS,
+    ] {
+      assert!(list.windows(2).all(|w| w[0] < w[1]), "{:?}", list);
+    }
+  }
+}
@@ -0,0 +1,223 @@
+use crate::diagnostic::Diagnostic;
+use crate::features::UnsupportedSyntax;
+use crate::limits::LimitExceeded;
+use parse_js::error::SyntaxError;
+use std::error::Error;
+use std::fmt;
+use std::fmt::Debug;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::io;
+use std::ops::Range;
+
+/// Error returned when minifying.
+#[derive(Clone, PartialEq)]
+pub enum MinifyError<'a> {
+  /// The source code could not be parsed.
+  Syntax(SyntaxError<'a>),
+  /// The source code exceeds one of the configured [Limits](crate::Limits).
+  LimitExceeded(LimitExceeded),
+  /// Minification was cancelled using [MinifyOptions::cancel](crate::MinifyOptions::cancel).
+  Cancelled,
+  /// The code uses syntax that can't be lowered to [Target::Es5](crate::Target::Es5) with the `downlevel` feature.
+  Unsupported(UnsupportedSyntax),
+  /// The output didn't pass the checks enabled by [MinifyOptions::verify](crate::MinifyOptions::verify), which is a bug in the minifier. The diagnostic describes the problem, and has a code of `E_INTERNAL_INVALID_OUTPUT`, `E_INTERNAL_MISMATCH`, or `E_INTERNAL_NOT_IDEMPOTENT`.
+  Internal(Diagnostic),
+}
+
+impl<'a> Debug for MinifyError<'a> {
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    match self {
+      MinifyError::Syntax(err) => Debug::fmt(err, f),
+      MinifyError::LimitExceeded(err) => Debug::fmt(err, f),
+      MinifyError::Cancelled => f.write_str("Cancelled"),
+      MinifyError::Unsupported(err) => Debug::fmt(err, f),
+      MinifyError::Internal(err) => Debug::fmt(err, f),
+    }
+  }
+}
+
+impl<'a> Display for MinifyError<'a> {
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    match self {
+      MinifyError::Syntax(err) => Display::fmt(err, f),
+      MinifyError::LimitExceeded(err) => Display::fmt(err, f),
+      MinifyError::Cancelled => f.write_str("minification was cancelled"),
+      MinifyError::Unsupported(err) => Display::fmt(err, f),
+      MinifyError::Internal(err) => Display::fmt(err, f),
+    }
+  }
+}
+
+impl<'a> Error for MinifyError<'a> {}
+
+impl<'a> From<SyntaxError<'a>> for MinifyError<'a> {
+  fn from(err: SyntaxError<'a>) -> Self {
+    MinifyError::Syntax(err)
+  }
+}
+
+impl<'a> From<LimitExceeded> for MinifyError<'a> {
+  fn from(err: LimitExceeded) -> Self {
+    MinifyError::LimitExceeded(err)
+  }
+}
+
+/// Error returned when minifying to an `io::Write`.
+pub enum WriteError<'a> {
+  /// The source code could not be parsed.
+  Syntax(SyntaxError<'a>),
+  /// The source code exceeds one of the configured [Limits](crate::Limits).
+  LimitExceeded(LimitExceeded),
+  /// Minification was cancelled using [MinifyOptions::cancel](crate::MinifyOptions::cancel).
+  Cancelled,
+  /// The code uses syntax that can't be lowered to [Target::Es5](crate::Target::Es5) with the `downlevel` feature.
+  Unsupported(UnsupportedSyntax),
+  /// The output didn't pass the checks enabled by [MinifyOptions::verify](crate::MinifyOptions::verify).
+  Internal(Diagnostic),
+  /// Writing the output failed.
+  Io(io::Error),
+}
+
+impl<'a> Debug for WriteError<'a> {
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    match self {
+      WriteError::Syntax(err) => Debug::fmt(err, f),
+      WriteError::LimitExceeded(err) => Debug::fmt(err, f),
+      WriteError::Cancelled => f.write_str("Cancelled"),
+      WriteError::Unsupported(err) => Debug::fmt(err, f),
+      WriteError::Internal(err) => Debug::fmt(err, f),
+      WriteError::Io(err) => Debug::fmt(err, f),
+    }
+  }
+}
+
+impl<'a> Display for WriteError<'a> {
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    match self {
+      WriteError::Syntax(err) => Display::fmt(err, f),
+      WriteError::LimitExceeded(err) => Display::fmt(err, f),
+      WriteError::Cancelled => f.write_str("minification was cancelled"),
+      WriteError::Unsupported(err) => Display::fmt(err, f),
+      WriteError::Internal(err) => Display::fmt(err, f),
+      WriteError::Io(err) => Display::fmt(err, f),
+    }
+  }
+}
+
+impl<'a> Error for WriteError<'a> {}
+
+impl<'a> From<MinifyError<'a>> for WriteError<'a> {
+  fn from(err: MinifyError<'a>) -> Self {
+    match err {
+      MinifyError::Syntax(err) => WriteError::Syntax(err),
+      MinifyError::LimitExceeded(err) => WriteError::LimitExceeded(err),
+      MinifyError::Cancelled => WriteError::Cancelled,
+      MinifyError::Unsupported(err) => WriteError::Unsupported(err),
+      MinifyError::Internal(err) => WriteError::Internal(err),
+    }
+  }
+}
+
+impl<'a> From<io::Error> for WriteError<'a> {
+  fn from(err: io::Error) -> Self {
+    WriteError::Io(err)
+  }
+}
+
+// Returns the 1-based line and column (in characters) of a byte position, and the range of the line containing it, excluding its line terminator.
+pub fn line_column(source: &[u8], pos: usize) -> (usize, usize, Range<usize>) {
+  let pos = pos.min(source.len());
+  let line_start = source[..pos]
+    .iter()
+    .rposition(|&c| c == b'\n')
+    .map_or(0, |i| i + 1);
+  let mut line_end = source[pos..]
+    .iter()
+    .position(|&c| c == b'\n')
+    .map_or(source.len(), |i| pos + i);
+  if line_end > line_start && source[line_end - 1] == b'\r' {
+    line_end -= 1;
+  };
+  let line = source[..line_start].iter().filter(|&&c| c == b'\n').count() + 1;
+  let column = String::from_utf8_lossy(&source[line_start..pos])
+    .chars()
+    .count()
+    + 1;
+  (line, column, line_start..line_end)
+}
+
+/// Renders a diagnostic as a human-readable message with its severity, code, and message, the file name, line, and column of the problem, and the offending line of code with the problem underlined, like this:
+///
+/// ```text
+/// error[E_EXPECTED_SYNTAX]: expected expression operand but found Semicolon
+///  --> src.js:2:9
+///   |
+/// 2 | let b = ;
+///   |         ^
+/// ```
+///
+/// # Arguments
+///
+/// * `source` - The source code that the diagnostic is about.
+/// * `file_name` - Name of the source file to show, such as its path.
+/// * `diagnostic` - The diagnostic to render.
+pub fn render_diagnostic(source: &[u8], file_name: &str, diagnostic: &Diagnostic) -> String {
+  let Diagnostic { line, column, .. } = *diagnostic;
+  let (_, _, line_range) = line_column(source, diagnostic.start);
+  let code = String::from_utf8_lossy(&source[line_range.clone()]);
+  // Underline the problem's source on this line, or at least one character.
+  let start = diagnostic.start.min(line_range.end);
+  let end = diagnostic.end.clamp(start, line_range.end);
+  let indent = String::from_utf8_lossy(&source[line_range.start..start])
+    .chars()
+    .map(|c| if c == '\t' { '\t' } else { ' ' })
+    .collect::<String>();
+  let carets = String::from_utf8_lossy(&source[start..end])
+    .chars()
+    .count()
+    .max(1);
+  let gutter = " ".repeat(line.to_string().len());
+  format!(
+    "{}[{}]: {}\n{gutter}--> {file_name}:{line}:{column}\n{gutter} |\n{line} | {code}\n{gutter} | {indent}{}\n",
+    diagnostic.severity.as_str(),
+    diagnostic.code,
+    diagnostic.message,
+    "^".repeat(carets),
+  )
+}
+
+/// Renders a syntax error returned from parsing `source` like [render_diagnostic].
+pub fn render_syntax_error(source: &[u8], file_name: &str, err: &SyntaxError) -> String {
+  render_diagnostic(
+    source,
+    file_name,
+    &Diagnostic::from_syntax_error(source, err),
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::render_syntax_error;
+  use crate::Session;
+  use crate::TopLevelMode;
+  use parse_js::parse;
+
+  #[test]
+  fn test_render_syntax_error() {
+    let source = "let a = 1;\r\n\tlet b = ;\r\n";
+    let session = Session::new();
+    let err = parse(&session, source.as_bytes(), TopLevelMode::Global).unwrap_err();
+    assert_eq!(
+      render_syntax_error(source.as_bytes(), "src.js", &err),
+      [
+        "error[E_EXPECTED_SYNTAX]: expected expression operand but found Semicolon\n",
+        " --> src.js:2:10\n",
+        "  |\n",
+        "2 | \tlet b = ;\n",
+        "  | \t        ^\n",
+      ]
+      .concat()
+    );
+  }
+}
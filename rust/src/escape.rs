@@ -0,0 +1,87 @@
+fn hex(digits: &[u8]) -> Option<u32> {
+  u32::from_str_radix(std::str::from_utf8(digits).ok()?, 16).ok()
+}
+
+// Decodes the contents of a string literal (without its quotes), or returns `None` if it's invalid or has a lone surrogate, which can't be in a `str`. Legacy octal escapes have up to three digits, as long as the value is at most `\377`.
+pub fn decode_string(mut raw: &[u8]) -> Option<String> {
+  let mut out = Vec::with_capacity(raw.len());
+  while let Some(pos) = raw.iter().position(|&c| c == b'\\') {
+    out.extend_from_slice(&raw[..pos]);
+    raw = &raw[pos + 1..];
+    let mut tmp = [0u8; 4];
+    let (skip, add): (usize, &[u8]) = match *raw.first()? {
+      b'\n' => (1, b""),
+      b'\r' => (if raw.get(1) == Some(&b'\n') { 2 } else { 1 }, b""),
+      b'b' => (1, b"\x08"),
+      b'f' => (1, b"\x0c"),
+      b'n' => (1, b"\n"),
+      b'r' => (1, b"\r"),
+      b't' => (1, b"\t"),
+      b'v' => (1, b"\x0b"),
+      c @ b'0'..=b'7' => {
+        let max = if c <= b'3' { 3 } else { 2 };
+        let len = raw
+          .iter()
+          .take(max)
+          .take_while(|c| (b'0'..=b'7').contains(*c))
+          .count();
+        let value = raw[..len]
+          .iter()
+          .fold(0, |v, &d| v * 8 + u32::from(d - b'0'));
+        (len, char::from_u32(value)?.encode_utf8(&mut tmp).as_bytes())
+      }
+      b'x' => {
+        let value = hex(raw.get(1..3)?)?;
+        (3, char::from_u32(value)?.encode_utf8(&mut tmp).as_bytes())
+      }
+      b'u' if raw.get(1) == Some(&b'{') => {
+        let end = raw.iter().position(|&c| c == b'}')?;
+        let value = hex(&raw[2..end])?;
+        (end + 1, char::from_u32(value)?.encode_utf8(&mut tmp).as_bytes())
+      }
+      b'u' => {
+        let mut value = hex(raw.get(1..5)?)?;
+        let mut len = 5;
+        if (0xD800..0xDC00).contains(&value) && raw.get(5..7) == Some(b"\\u") {
+          if let Some(low @ 0xDC00..=0xDFFF) = raw.get(7..11).and_then(hex) {
+            value = 0x10000 + ((value - 0xD800) << 10) + (low - 0xDC00);
+            len = 11;
+          };
+        };
+        (len, char::from_u32(value)?.encode_utf8(&mut tmp).as_bytes())
+      }
+      // Any other character, including `\8` and `\9`, is itself. If it's not ASCII, the rest of its bytes are copied as is.
+      c => (1, {
+        tmp[0] = c;
+        &tmp[..1]
+      }),
+    };
+    out.extend_from_slice(add);
+    raw = &raw[skip..];
+  }
+  out.extend_from_slice(raw);
+  String::from_utf8(out).ok()
+}
+
+// Decodes the contents of a string literal or a part of a template literal between substitutions, after `normalize_whitespace`. It only leaves `\r` before `\n`, which is read as `\n` in templates (and isn't allowed in strings), or after a `\` `\n` line continuation it rewrote from `\` `\r` `\n`, so any `\r` is dropped; only `\r` escapes are `\r` in the value.
+pub fn decode_normalized(raw: &[u8]) -> Option<String> {
+  let raw: Vec<u8> = raw.iter().copied().filter(|&c| c != b'\r').collect();
+  decode_string(&raw)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::decode_string;
+  use super::decode_normalized;
+
+  #[test]
+  fn test_decode() {
+    assert_eq!(decode_string(br"\0\07a\101\377\400\8").unwrap(), "\0\x07aA\u{ff} 08");
+    assert_eq!(decode_string(r"\1\x41B\u{43}\'\é".as_bytes()).unwrap(), "\x01ABC'é");
+    assert_eq!(decode_string(r"\1😀".as_bytes()).unwrap(), "\x01😀");
+    assert_eq!(decode_string(br"a\
+b\1").unwrap(), "ab\x01");
+    assert_eq!(decode_string(br"\1\ud83d"), None);
+    assert_eq!(decode_normalized(b"a\r\nb\\r\\\n\rc").unwrap(), "a\nb\rc");
+  }
+}
@@ -0,0 +1,98 @@
+use crate::early_error::check_early_errors;
+use crate::emit::emit_js;
+use crate::emit::Emitter;
+use crate::minify::KeepTopLevel;
+use crate::minify_parsed;
+use crate::MinifyError;
+use crate::MinifyOptions;
+use crate::MinifyStats;
+use parse_js::ast::Syntax;
+use parse_js::lex::Lexer;
+use parse_js::parse::pattern::ParsePatternRules;
+use parse_js::parse::toplevel::TopLevelMode;
+use parse_js::parse::ParseCtx;
+use parse_js::parse::Parser;
+use parse_js::session::Session;
+use parse_js::symbol::Scope;
+use parse_js::symbol::ScopeType;
+use parse_js::symbol::SymbolGenerator;
+use parse_js::token::TokenType;
+
+/// Minifies a single JavaScript expression, such as an expression in a template or the value of an HTML attribute, instead of a script or module. The source must be exactly one expression (which can be a comma-separated sequence), and the output is a minified expression without a trailing semicolon, which can be put wherever the original could.
+///
+/// Variables in the expression refer to the surrounding code, so they're never renamed, but variables declared inside it (e.g. function parameters) are. Options that only apply to entire scripts are ignored: comments aren't kept, the banner, footer, and `enclose` aren't added, `safe_concat` has no effect, and strings aren't encoded by [Obfuscation::encode_strings](crate::Obfuscation::encode_strings), as that needs a decoder function declaration. If a custom transform replaces the expression with other statements, they're emitted as is.
+///
+/// # Examples
+///
+/// ```
+/// use minify_js::{MinifyOptions, Session, minify_expression};
+///
+/// let session = Session::new();
+/// let mut out = Vec::new();
+/// let code = b"items.map(function (item) { return item.price * 2; })";
+/// minify_expression(&session, code, &MinifyOptions::new(), &mut out).unwrap();
+/// assert_eq!(out.as_slice(), b"items.map((a=>a.price*2))");
+/// assert!(minify_expression(&session, b"a; b", &MinifyOptions::new(), &mut Vec::new()).is_err());
+/// ```
+pub fn minify_expression<'a>(
+  session: &'a Session,
+  source: &'a [u8],
+  cfg: &MinifyOptions,
+  output: &mut Vec<u8>,
+) -> Result<MinifyStats, MinifyError<'a>> {
+  cfg.limits.check(source)?;
+  cfg.check_cancelled()?;
+  let mut cfg = cfg.clone();
+  cfg.safe_concat = false;
+  cfg.obfuscation.encode_strings = false;
+  let mut parser = Parser::new(Lexer::new(source));
+  let ctx = ParseCtx {
+    scope: Scope::new(
+      session,
+      SymbolGenerator::new(),
+      None,
+      match cfg.top_level_mode {
+        TopLevelMode::Global => ScopeType::Global,
+        TopLevelMode::Module => ScopeType::Module,
+      },
+    ),
+    session,
+    rules: ParsePatternRules {
+      await_allowed: true,
+      yield_allowed: true,
+    },
+  };
+  let expression = parser.parse_expr(ctx, TokenType::EOF)?;
+  parser.require(TokenType::EOF)?;
+  // Minification works on statements, so the expression is minified as the only statement of a script. A top-level expression statement is never dropped, even if it has no side effects.
+  let mut body = session.new_vec();
+  body.push(ctx.create_node(expression.loc, Syntax::ExpressionStmt { expression }));
+  let parsed = ctx.create_node(parser.source_range(), Syntax::TopLevel { body });
+  check_early_errors(parsed)?;
+  cfg.check_cancelled()?;
+  let mut stats = MinifyStats {
+    input_len: source.len(),
+    ..minify_parsed(
+      session,
+      parsed,
+      &cfg,
+      &mut None,
+      KeepTopLevel {
+        statements: true,
+        ..KeepTopLevel::default()
+      },
+    )?
+  };
+  let output_start = output.len();
+  let mut emitter = Emitter::new(output, &cfg);
+  match &mut parsed.stx {
+    Syntax::TopLevel { body } if body.len() == 1 => match &mut body[0].stx {
+      Syntax::ExpressionStmt { expression } => emit_js(&mut emitter, expression),
+      _ => emit_js(&mut emitter, parsed),
+    },
+    _ => emit_js(&mut emitter, parsed),
+  };
+  stats.positions = emitter.take_position_map(output_start);
+  stats.output_len = output.len() - output_start;
+  Ok(stats)
+}
@@ -0,0 +1,420 @@
+use crate::cfg::Target;
+use crate::error::line_column;
+use parse_js::ast::ArrayElement;
+use parse_js::ast::ClassMember;
+use parse_js::ast::ClassOrObjectMemberKey;
+use parse_js::ast::ClassOrObjectMemberValue;
+use parse_js::ast::ForStmtHeader;
+use parse_js::ast::NodeData;
+use parse_js::ast::ObjectMemberType;
+use parse_js::ast::Syntax;
+use parse_js::ast::VarDeclMode;
+use parse_js::operator::OperatorName;
+use parse_js::visit::JourneyControls;
+use parse_js::visit::Visitor;
+use std::error::Error;
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+
+/// Syntax introduced after ES5, as found by [syntax_features].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SyntaxFeature {
+  /// `() => {}`.
+  ArrowFunction,
+  /// Class declarations and expressions.
+  Class,
+  /// `let` and `const` declarations.
+  LetConst,
+  /// Template literals, e.g. `` `a${b}` ``.
+  TemplateLiteral,
+  /// Array and object patterns, e.g. `let [a, { b }] = c`.
+  Destructuring,
+  /// Default parameter values, e.g. `function f(a = 1) {}`.
+  DefaultParameter,
+  /// Rest parameters and elements, and spread arguments and elements, e.g. `f(...a)`, but not in object literals and patterns.
+  RestSpread,
+  /// Shorthand properties and methods in object literals, e.g. `{ a, b() {} }`.
+  ShorthandProperty,
+  /// Computed keys in object literals and classes, e.g. `{ [a]: 1 }`.
+  ComputedProperty,
+  /// `function* () {}`.
+  Generator,
+  /// `for (a of b)`.
+  ForOf,
+  /// Import and export statements.
+  Module,
+  /// `a ** b` and `a **= b`.
+  Exponentiation,
+  /// `async function () {}` and `async () => {}`.
+  AsyncFunction,
+  /// `for await (a of b)` and async generators.
+  AsyncIteration,
+  /// Rest and spread in object literals and patterns, e.g. `{ ...a }`.
+  ObjectRestSpread,
+  /// `try {} catch {}` without a parameter.
+  OptionalCatchBinding,
+  /// `a?.b`, `a?.[b]`, and `a?.()`.
+  OptionalChaining,
+  /// `a ?? b`.
+  NullishCoalescing,
+  /// BigInt literals, e.g. `1n`.
+  BigInt,
+  /// `import("a")`.
+  DynamicImport,
+  /// `import.meta`.
+  ImportMeta,
+  /// `a ||= b`, `a &&= b`, and `a ??= b`.
+  LogicalAssignment,
+  /// Public and private class fields, e.g. `class A { a = 1 }`.
+  ClassField,
+  /// Private class members, e.g. `this.#a`.
+  PrivateName,
+}
+
+impl SyntaxFeature {
+  /// The ECMAScript version that introduced the feature, which is the oldest [Target] it runs on.
+  pub fn target(self) -> Target {
+    match self {
+      SyntaxFeature::ArrowFunction
+      | SyntaxFeature::Class
+      | SyntaxFeature::LetConst
+      | SyntaxFeature::TemplateLiteral
+      | SyntaxFeature::Destructuring
+      | SyntaxFeature::DefaultParameter
+      | SyntaxFeature::RestSpread
+      | SyntaxFeature::ShorthandProperty
+      | SyntaxFeature::ComputedProperty
+      | SyntaxFeature::Generator
+      | SyntaxFeature::ForOf
+      | SyntaxFeature::Module => Target::Es2015,
+      SyntaxFeature::Exponentiation => Target::Es2016,
+      SyntaxFeature::AsyncFunction => Target::Es2017,
+      SyntaxFeature::AsyncIteration | SyntaxFeature::ObjectRestSpread => Target::Es2018,
+      SyntaxFeature::OptionalCatchBinding => Target::Es2019,
+      SyntaxFeature::OptionalChaining
+      | SyntaxFeature::NullishCoalescing
+      | SyntaxFeature::BigInt
+      | SyntaxFeature::DynamicImport
+      | SyntaxFeature::ImportMeta => Target::Es2020,
+      SyntaxFeature::LogicalAssignment => Target::Es2021,
+      SyntaxFeature::ClassField | SyntaxFeature::PrivateName => Target::Es2022,
+    }
+  }
+
+  /// A short lowercase name for the feature, such as `optional-chaining`.
+  pub fn as_str(self) -> &'static str {
+    match self {
+      SyntaxFeature::ArrowFunction => "arrow-function",
+      SyntaxFeature::Class => "class",
+      SyntaxFeature::LetConst => "let-const",
+      SyntaxFeature::TemplateLiteral => "template-literal",
+      SyntaxFeature::Destructuring => "destructuring",
+      SyntaxFeature::DefaultParameter => "default-parameter",
+      SyntaxFeature::RestSpread => "rest-spread",
+      SyntaxFeature::ShorthandProperty => "shorthand-property",
+      SyntaxFeature::ComputedProperty => "computed-property",
+      SyntaxFeature::Generator => "generator",
+      SyntaxFeature::ForOf => "for-of",
+      SyntaxFeature::Module => "module",
+      SyntaxFeature::Exponentiation => "exponentiation",
+      SyntaxFeature::AsyncFunction => "async-function",
+      SyntaxFeature::AsyncIteration => "async-iteration",
+      SyntaxFeature::ObjectRestSpread => "object-rest-spread",
+      SyntaxFeature::OptionalCatchBinding => "optional-catch-binding",
+      SyntaxFeature::OptionalChaining => "optional-chaining",
+      SyntaxFeature::NullishCoalescing => "nullish-coalescing",
+      SyntaxFeature::BigInt => "bigint",
+      SyntaxFeature::DynamicImport => "dynamic-import",
+      SyntaxFeature::ImportMeta => "import-meta",
+      SyntaxFeature::LogicalAssignment => "logical-assignment",
+      SyntaxFeature::ClassField => "class-field",
+      SyntaxFeature::PrivateName => "private-name",
+    }
+  }
+}
+
+/// The first use of a [SyntaxFeature] in the source.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FeatureUse {
+  pub feature: SyntaxFeature,
+  /// Byte offset of the start of the syntax using the feature.
+  pub start: usize,
+  /// 1-based line of `start`.
+  pub line: usize,
+  /// 1-based column of `start`, in characters.
+  pub column: usize,
+}
+
+/// Error returned when code uses syntax that can't be lowered to ES5, when minifying with the `downlevel` feature and [Target::Es5].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UnsupportedSyntax {
+  pub feature: SyntaxFeature,
+  /// Byte offset in the source of the syntax.
+  pub position: usize,
+}
+
+impl Display for UnsupportedSyntax {
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    match self.feature {
+      // Other `let` and `const` declarations become `var` declarations.
+      SyntaxFeature::LetConst => f.write_str(
+        "`let` or `const` in a loop can't be lowered to ES5 when a function uses it, as each iteration needs its own variable",
+      ),
+      // Other arrow functions become function expressions.
+      SyntaxFeature::ArrowFunction => f.write_str(
+        "arrow function using `super`, or `arguments` outside of a function, can't be lowered to ES5",
+      ),
+      feature => write!(f, "{} syntax can't be lowered to ES5", feature.as_str()),
+    }
+  }
+}
+
+impl Error for UnsupportedSyntax {}
+
+struct Features {
+  // The first use of each feature found so far. Children aren't always visited in source order (e.g. call arguments before the callee), so a later use can be earlier in the source.
+  first: Vec<(SyntaxFeature, usize)>,
+}
+
+impl Features {
+  fn found(&mut self, feature: SyntaxFeature, start: usize) {
+    match self.first.iter_mut().find(|(f, _)| *f == feature) {
+      Some((_, first)) => *first = start.min(*first),
+      None => self.first.push((feature, start)),
+    };
+  }
+
+  fn function(&mut self, is_async: bool, generator: bool, start: usize) {
+    match (is_async, generator) {
+      (true, true) => self.found(SyntaxFeature::AsyncIteration, start),
+      (true, false) => self.found(SyntaxFeature::AsyncFunction, start),
+      (false, true) => self.found(SyntaxFeature::Generator, start),
+      (false, false) => {}
+    };
+  }
+
+  fn member_key(&mut self, key: &ClassOrObjectMemberKey, start: usize) {
+    match key {
+      ClassOrObjectMemberKey::Computed(_) => self.found(SyntaxFeature::ComputedProperty, start),
+      ClassOrObjectMemberKey::Direct(name) if name.as_slice().starts_with(b"#") => {
+        self.found(SyntaxFeature::PrivateName, start)
+      }
+      ClassOrObjectMemberKey::Direct(_) => {}
+    };
+  }
+
+  fn member_value(&mut self, value: &ClassOrObjectMemberValue, start: usize) {
+    if let ClassOrObjectMemberValue::Method {
+      is_async,
+      generator,
+      ..
+    } = value
+    {
+      self.function(*is_async, *generator, start);
+    };
+  }
+
+  fn class(&mut self, members: &[ClassMember], start: usize) {
+    self.found(SyntaxFeature::Class, start);
+    for m in members {
+      self.member_key(&m.key, start);
+      self.member_value(&m.value, start);
+      if let ClassOrObjectMemberValue::Property { .. } = m.value {
+        self.found(SyntaxFeature::ClassField, start);
+      };
+    }
+  }
+}
+
+impl<'a> Visitor<'a> for Features {
+  fn on_syntax_down(&mut self, node: &mut NodeData<'a>, _ctl: &mut JourneyControls) {
+    let start = node.loc.start();
+    match &node.stx {
+      Syntax::ArrowFunctionExpr { is_async, .. } => {
+        self.found(SyntaxFeature::ArrowFunction, start);
+        self.function(*is_async, false, start);
+      }
+      Syntax::FunctionDecl {
+        is_async,
+        generator,
+        export,
+        export_default,
+        ..
+      } => {
+        self.function(*is_async, *generator, start);
+        if *export || *export_default {
+          self.found(SyntaxFeature::Module, start);
+        };
+      }
+      Syntax::FunctionExpr {
+        is_async,
+        generator,
+        ..
+      } => self.function(*is_async, *generator, start),
+      Syntax::ClassDecl {
+        members,
+        export,
+        export_default,
+        ..
+      } => {
+        self.class(members, start);
+        if *export || *export_default {
+          self.found(SyntaxFeature::Module, start);
+        };
+      }
+      Syntax::ClassExpr { members, .. } => self.class(members, start),
+      Syntax::VarDecl { export, mode, .. } => {
+        if *mode != VarDeclMode::Var {
+          self.found(SyntaxFeature::LetConst, start);
+        };
+        if *export {
+          self.found(SyntaxFeature::Module, start);
+        };
+      }
+      Syntax::ImportStmt { .. }
+      | Syntax::ExportListStmt { .. }
+      | Syntax::ExportDefaultExprStmt { .. } => self.found(SyntaxFeature::Module, start),
+      Syntax::LiteralTemplateExpr { .. } => self.found(SyntaxFeature::TemplateLiteral, start),
+      Syntax::ArrayPattern { rest, .. } => {
+        self.found(SyntaxFeature::Destructuring, start);
+        if rest.is_some() {
+          self.found(SyntaxFeature::RestSpread, start);
+        };
+      }
+      Syntax::ObjectPattern { rest, .. } => {
+        self.found(SyntaxFeature::Destructuring, start);
+        if rest.is_some() {
+          self.found(SyntaxFeature::ObjectRestSpread, start);
+        };
+      }
+      Syntax::ParamDecl {
+        rest,
+        default_value,
+        ..
+      } => {
+        if *rest {
+          self.found(SyntaxFeature::RestSpread, start);
+        };
+        if default_value.is_some() {
+          self.found(SyntaxFeature::DefaultParameter, start);
+        };
+      }
+      Syntax::CallArg { spread: true, .. } => self.found(SyntaxFeature::RestSpread, start),
+      Syntax::LiteralArrayExpr { elements }
+        if elements.iter().any(|e| matches!(e, ArrayElement::Rest(_))) =>
+      {
+        self.found(SyntaxFeature::RestSpread, start)
+      }
+      Syntax::ObjectMember { typ } => match typ {
+        ObjectMemberType::Valued { key, value } => {
+          self.member_key(key, start);
+          self.member_value(value, start);
+          if let ClassOrObjectMemberValue::Method { .. } = value {
+            self.found(SyntaxFeature::ShorthandProperty, start);
+          };
+        }
+        ObjectMemberType::Shorthand { .. } => self.found(SyntaxFeature::ShorthandProperty, start),
+        ObjectMemberType::Rest { .. } => self.found(SyntaxFeature::ObjectRestSpread, start),
+      },
+      Syntax::ForStmt {
+        header: ForStmtHeader::InOf {
+          of: true, await_, ..
+        },
+        ..
+      } => {
+        self.found(SyntaxFeature::ForOf, start);
+        if *await_ {
+          self.found(SyntaxFeature::AsyncIteration, start);
+        };
+      }
+      Syntax::BinaryExpr { operator, .. } => match operator {
+        OperatorName::Exponentiation | OperatorName::AssignmentExponentiation => {
+          self.found(SyntaxFeature::Exponentiation, start)
+        }
+        OperatorName::NullishCoalescing => self.found(SyntaxFeature::NullishCoalescing, start),
+        OperatorName::AssignmentLogicalAnd
+        | OperatorName::AssignmentLogicalOr
+        | OperatorName::AssignmentNullishCoalescing => {
+          self.found(SyntaxFeature::LogicalAssignment, start)
+        }
+        _ => {}
+      },
+      Syntax::MemberExpr {
+        optional_chaining,
+        right,
+        ..
+      } => {
+        if *optional_chaining {
+          self.found(SyntaxFeature::OptionalChaining, start);
+        };
+        if right.as_slice().starts_with(b"#") {
+          self.found(SyntaxFeature::PrivateName, start);
+        };
+      }
+      Syntax::ComputedMemberExpr {
+        optional_chaining: true,
+        ..
+      }
+      | Syntax::CallExpr {
+        optional_chaining: true,
+        ..
+      } => self.found(SyntaxFeature::OptionalChaining, start),
+      Syntax::CatchBlock {
+        parameter: None, ..
+      } => self.found(SyntaxFeature::OptionalCatchBinding, start),
+      Syntax::LiteralBigIntExpr { .. } => self.found(SyntaxFeature::BigInt, start),
+      Syntax::ImportExpr { .. } => self.found(SyntaxFeature::DynamicImport, start),
+      Syntax::ImportMeta {} => self.found(SyntaxFeature::ImportMeta, start),
+      _ => {}
+    };
+  }
+}
+
+// The first syntax in the tree that's newer than `target`, in source order.
+#[cfg(feature = "downlevel")]
+pub(crate) fn first_newer_than<'a>(node: &mut NodeData<'a>, target: Target) -> Option<UnsupportedSyntax> {
+  let mut features = Features { first: Vec::new() };
+  features.visit(node);
+  features
+    .first
+    .into_iter()
+    .filter(|(feature, _)| feature.target() > target)
+    .min_by_key(|(_, start)| *start)
+    .map(|(feature, position)| UnsupportedSyntax { feature, position })
+}
+
+/// Finds the syntax introduced after ES5 that code uses, such as optional chaining and class fields, with the first use of each feature, in source order. The newest [SyntaxFeature::target] is the oldest ECMAScript version the code can run on, so this can check that code is compatible with a target without minifying it. `node` should be the top-level node from parsing `source` with [parse_js::parse]; use [MinifyOptions::syntax_features](crate::MinifyOptions::syntax_features) to find them while minifying. Built-in objects and methods (e.g. `Promise` or `Array.prototype.at`) aren't syntax, so aren't included.
+///
+/// # Examples
+///
+/// ```
+/// use minify_js::{parse_js, syntax_features, Session, SyntaxFeature, Target, TopLevelMode};
+///
+/// let code: &[u8] = b"const a = b?.c ?? [...d];\nclass E { #f = 1 }";
+/// let session = Session::new();
+/// let parsed = parse_js::parse(&session, code, TopLevelMode::Global).unwrap();
+/// let features = syntax_features(code, parsed);
+/// assert_eq!(features[0].feature, SyntaxFeature::LetConst);
+/// assert_eq!((features[4].feature, features[4].line), (SyntaxFeature::Class, 2));
+/// let target = features.iter().map(|f| f.feature.target()).max();
+/// assert_eq!(target, Some(Target::Es2022));
+/// ```
+pub fn syntax_features<'a>(source: &[u8], node: &mut NodeData<'a>) -> Vec<FeatureUse> {
+  let mut features = Features { first: Vec::new() };
+  features.visit(node);
+  features.first.sort_by_key(|(_, start)| *start);
+  features
+    .first
+    .into_iter()
+    .map(|(feature, start)| {
+      let (line, column, _) = line_column(source, start);
+      FeatureUse {
+        feature,
+        start,
+        line,
+        column,
+      }
+    })
+    .collect()
+}
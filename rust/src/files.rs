@@ -0,0 +1,137 @@
+use crate::error::render_diagnostic;
+use crate::Diagnostic;
+use crate::PositionMap;
+use crate::SourceMapOptions;
+use parse_js::session::Session;
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+/// Reads source files by name, such as from disk or from memory, so that tools like the bundler don't depend on where the code is stored.
+pub trait FileProvider {
+  /// Returns the contents of the file called `name`, or a message explaining why it can't be read.
+  fn read(&self, name: &str) -> Result<Vec<u8>, String>;
+}
+
+/// Files in memory, by name.
+impl<S: AsRef<[u8]>> FileProvider for HashMap<String, S> {
+  fn read(&self, name: &str) -> Result<Vec<u8>, String> {
+    self
+      .get(name)
+      .map(|code| code.as_ref().to_vec())
+      .ok_or_else(|| "not found".to_string())
+  }
+}
+
+/// Files on disk, with names relative to a root directory.
+#[derive(Clone, Debug)]
+pub struct FsProvider {
+  root: PathBuf,
+}
+
+impl FsProvider {
+  pub fn new<P: Into<PathBuf>>(root: P) -> FsProvider {
+    FsProvider { root: root.into() }
+  }
+}
+
+impl FileProvider for FsProvider {
+  fn read(&self, name: &str) -> Result<Vec<u8>, String> {
+    fs::read(self.root.join(name)).map_err(|err| err.to_string())
+  }
+}
+
+/// Index of a file in [SourceFiles].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FileId(pub usize);
+
+/// Named source files, with their code allocated in a [Session] so that trees parsed from them live as long as the session. Use this when minifying or bundling more than one file, so diagnostics and source maps can refer to the file they came from.
+///
+/// # Examples
+///
+/// ```
+/// use minify_js::{minify, Diagnostic, MinifyOptions, Session, SourceFiles};
+/// use std::collections::HashMap;
+///
+/// let provider = HashMap::from([("b.js".to_string(), "let = 1")]);
+/// let session = Session::new();
+/// let mut files = SourceFiles::new(&session);
+/// let a = files.add("a.js", b"let a = 1;");
+/// let b = files.load(&provider, "b.js").unwrap();
+/// assert_eq!(files.load(&provider, "b.js"), Ok(b));
+/// assert_eq!(files.name(a), "a.js");
+///
+/// let err = minify(&session, files.code(b), &MinifyOptions::new(), &mut Vec::new()).unwrap_err();
+/// let diagnostic = Diagnostic::from_minify_error(files.code(b), &err);
+/// assert!(files.render_diagnostic(b, &diagnostic).contains("--> b.js:1:5"));
+/// ```
+pub struct SourceFiles<'a> {
+  session: &'a Session,
+  files: Vec<(String, &'a [u8])>,
+  ids: HashMap<String, FileId>,
+}
+
+impl<'a> SourceFiles<'a> {
+  pub fn new(session: &'a Session) -> SourceFiles<'a> {
+    SourceFiles {
+      session,
+      files: Vec::new(),
+      ids: HashMap::new(),
+    }
+  }
+
+  /// Adds a file, replacing the name's existing file if there is one.
+  pub fn add(&mut self, name: &str, code: &[u8]) -> FileId {
+    let id = FileId(self.files.len());
+    let code = self.session.get_allocator().alloc_slice_copy(code);
+    self.files.push((name.to_string(), code));
+    self.ids.insert(name.to_string(), id);
+    id
+  }
+
+  /// Returns the file called `name`, reading it from `provider` if it hasn't been added yet.
+  pub fn load(&mut self, provider: &dyn FileProvider, name: &str) -> Result<FileId, String> {
+    if let Some(&id) = self.ids.get(name) {
+      return Ok(id);
+    };
+    let code = provider.read(name)?;
+    Ok(self.add(name, &code))
+  }
+
+  /// Returns the file called `name`, if it's been added.
+  pub fn id(&self, name: &str) -> Option<FileId> {
+    self.ids.get(name).copied()
+  }
+
+  pub fn name(&self, id: FileId) -> &str {
+    &self.files[id.0].0
+  }
+
+  pub fn code(&self, id: FileId) -> &'a [u8] {
+    self.files[id.0].1
+  }
+
+  pub fn len(&self) -> usize {
+    self.files.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.files.is_empty()
+  }
+
+  /// Renders a diagnostic about a file with its name, like [render_diagnostic].
+  pub fn render_diagnostic(&self, id: FileId, diagnostic: &Diagnostic) -> String {
+    render_diagnostic(self.code(id), self.name(id), diagnostic)
+  }
+
+  /// Generates a source map for the output of minifying a file, with the file's name as its source, like [PositionMap::to_source_map_with].
+  pub fn source_map(
+    &self,
+    id: FileId,
+    positions: &PositionMap,
+    output: &[u8],
+    opts: &SourceMapOptions,
+  ) -> String {
+    positions.to_source_map_with(output, self.code(id), self.name(id), opts)
+  }
+}
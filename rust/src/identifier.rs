@@ -0,0 +1,181 @@
+use crate::limits::scan_tokens;
+use memchr::memmem;
+use parse_js::token::TokenType;
+use std::ops::Range;
+use unicode_ident::is_xid_continue;
+use unicode_ident::is_xid_start;
+
+// Words that can't be identifiers, even when escaped, though they can still be property names. Those only reserved in strict mode code (e.g. `let` and `static`) are identifiers elsewhere, so aren't included.
+pub(crate) const RESERVED_WORDS: &[&str] = &[
+  "await",
+  "break",
+  "case",
+  "catch",
+  "class",
+  "const",
+  "continue",
+  "debugger",
+  "default",
+  "delete",
+  "do",
+  "else",
+  "enum",
+  "export",
+  "extends",
+  "false",
+  "finally",
+  "for",
+  "function",
+  "if",
+  "import",
+  "in",
+  "instanceof",
+  "new",
+  "null",
+  "return",
+  "super",
+  "switch",
+  "this",
+  "throw",
+  "true",
+  "try",
+  "typeof",
+  "var",
+  "void",
+  "while",
+  "with",
+  "yield",
+];
+
+// Whether `c` can be in an identifier, or start one if `start`. Those are the characters with the XID_Start Unicode property (or XID_Continue, ZWNJ, and ZWJ), and `$` and `_`.
+pub(crate) fn is_identifier_char(c: char, start: bool) -> bool {
+  c == '$'
+    || c == '_'
+    || if start {
+      is_xid_start(c)
+    } else {
+      is_xid_continue(c) || c == '\u{200c}' || c == '\u{200d}'
+    }
+}
+
+// Whether `code` could have a Unicode escape in an identifier, without lexing.
+pub(crate) fn may_have_identifier_escapes(code: &[u8]) -> bool {
+  memmem::find(code, b"\\u").is_some()
+}
+
+// Decodes a `\uXXXX` or `\u{X}` escape at the start of `s`, returning the character and the escape's length.
+pub(crate) fn decode_unicode_escape(s: &[u8]) -> Option<(char, usize)> {
+  let (digits, len) = match s.strip_prefix(b"\\u")? {
+    [b'{', rest @ ..] => {
+      let end = rest.iter().position(|&c| c == b'}')?;
+      (&rest[..end], end + 4)
+    }
+    rest => (rest.get(..4)?, 6),
+  };
+  let code = u32::from_str_radix(std::str::from_utf8(digits).ok()?, 16).ok()?;
+  Some((char::from_u32(code)?, len))
+}
+
+// The UTF-8 character at `i` in `code`.
+fn char_at(code: &[u8], i: usize) -> Option<char> {
+  let len = match code[i] {
+    0xC0..=0xDF => 2,
+    0xE0..=0xEF => 3,
+    0xF0..=0xFF => 4,
+    _ => 1,
+  };
+  std::str::from_utf8(code.get(i..i + len)?)
+    .ok()?
+    .chars()
+    .next()
+}
+
+// Decodes the identifier starting at `start` in `code`, returning its end. Returns `None` if it has an escape for a character that can't be in an identifier there.
+fn decode_identifier(code: &[u8], start: usize) -> Option<(usize, String)> {
+  let mut name = String::new();
+  let mut i = start;
+  while i < code.len() {
+    let (c, len) = match decode_unicode_escape(&code[i..]) {
+      Some(escape) => escape,
+      None => match char_at(code, i) {
+        Some(c) if is_identifier_char(c, name.is_empty()) => (c, c.len_utf8()),
+        _ => break,
+      },
+    };
+    if !is_identifier_char(c, name.is_empty()) {
+      return None;
+    };
+    name.push(c);
+    i += len;
+  }
+  Some((i, name))
+}
+
+// parse-js doesn't lex Unicode escapes in identifiers (e.g. `\u0061` or `\u{61}` for `a`), and fails on the `\`. This rewrites each identifier with them to the identifier with them decoded, followed by spaces so it's the same length (e.g. `a     `), so that an escaped name and the same name unescaped are the same variable, and names are emitted unescaped. Escapes in strings, templates, and regexes are part of their values, so the code is lexed to leave those. Escapes for characters that can't be in an identifier are left for the parser to report, as are escaped reserved words (e.g. `\u0069f`), which are neither identifiers nor keywords, except as a property name after a `.`.
+pub(crate) fn decode_identifier_escapes(code: &mut [u8]) {
+  let mut literals: Vec<Range<usize>> = Vec::new();
+  let _ = scan_tokens(code, |typ, range, _| {
+    if matches!(
+      typ,
+      TokenType::LiteralString
+        | TokenType::LiteralTemplatePartString
+        | TokenType::LiteralTemplatePartStringEnd
+        | TokenType::LiteralRegex
+    ) {
+      literals.push(range);
+    };
+    Ok::<_, ()>(())
+  });
+  let escapes: Vec<usize> = memmem::find_iter(code, b"\\u").collect();
+  let mut literals = literals.into_iter().peekable();
+  let mut decoded_end = 0;
+  for pos in escapes {
+    while literals.next_if(|literal| literal.end <= pos).is_some() {}
+    if pos < decoded_end || literals.peek().is_some_and(|literal| literal.start <= pos) {
+      continue;
+    };
+    let start = code[..pos]
+      .iter()
+      .rposition(|&c| !(c.is_ascii_alphanumeric() || c == b'_' || c == b'$' || !c.is_ascii()))
+      .map_or(0, |p| p + 1);
+    let Some((end, name)) = decode_identifier(code, start) else {
+      continue;
+    };
+    let after_dot = code[..start]
+      .iter()
+      .rfind(|c| !c.is_ascii_whitespace())
+      == Some(&b'.');
+    if end <= pos || (RESERVED_WORDS.contains(&name.as_str()) && !after_dot) {
+      continue;
+    };
+    code[start..start + name.len()].copy_from_slice(name.as_bytes());
+    code[start + name.len()..end].fill(b' ');
+    decoded_end = end;
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::decode_identifier_escapes;
+
+  #[test]
+  fn test_decode_identifier_escapes() {
+    let decode = |source: &str| {
+      let mut code = source.as_bytes().to_vec();
+      decode_identifier_escapes(&mut code);
+      String::from_utf8(code).unwrap()
+    };
+    assert_eq!(decode(r"\u0061+a\u{62}c"), "a     +abc     ");
+    assert_eq!(decode(r"o.\u{10000}é"), "o.𐀀é     ");
+    assert_eq!(decode(r"o.\u0069f"), "o.if     ");
+    // They're part of strings, templates, and regexes.
+    assert_eq!(
+      decode(r"'\u0061'+`\u0061${\u0061}`+/\u0061/"),
+      r"'\u0061'+`\u0061${a     }`+/\u0061/"
+    );
+    // Escapes that can't be in an identifier and escaped reserved words are left as is.
+    for invalid in [r"\u0061\u0020", r"1\u0061", r"\u{110000}", r"\u0069f", r"\u{66}or"] {
+      assert_eq!(decode(invalid), invalid);
+    }
+  }
+}
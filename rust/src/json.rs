@@ -0,0 +1,281 @@
+use crate::diagnostic::Diagnostic;
+use crate::diagnostic::Severity;
+use crate::error::line_column;
+use parse_js::lex::lex_next;
+use parse_js::lex::LexMode;
+use parse_js::lex::Lexer;
+use parse_js::token::TokenType;
+use std::fmt::Write;
+
+pub fn write_json_string(out: &mut String, s: &str) {
+  out.push('"');
+  for c in s.chars() {
+    match c {
+      '"' => out.push_str("\\\""),
+      '\\' => out.push_str("\\\\"),
+      c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32).unwrap(),
+      c => out.push(c),
+    };
+  }
+  out.push('"');
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Expect {
+  Value,
+  // Just after `[`.
+  ValueOrClose,
+  Key,
+  // Just after `{`.
+  KeyOrClose,
+  Colon,
+  CommaOrClose,
+  End,
+}
+
+fn json_error(
+  source: &[u8],
+  code: &'static str,
+  message: String,
+  start: usize,
+  end: usize,
+) -> Diagnostic {
+  let (line, column, _) = line_column(source, start);
+  Diagnostic {
+    code,
+    message,
+    severity: Severity::Error,
+    start,
+    end,
+    line,
+    column,
+  }
+}
+
+// Checks that the gap between two tokens only contains JSON whitespace, and comments if allowed. The lexer has already skipped JS whitespace and comments, which are a superset.
+fn check_gap(source: &[u8], start: usize, end: usize, comments: bool) -> Result<(), Diagnostic> {
+  let mut i = start;
+  while i < end {
+    match source[i] {
+      b' ' | b'\t' | b'\n' | b'\r' => i += 1,
+      b'/' if comments && source.get(i + 1) == Some(&b'/') => {
+        i = memchr::memchr(b'\n', &source[i..end]).map_or(end, |p| i + p);
+      }
+      b'/' if comments && source.get(i + 1) == Some(&b'*') => {
+        i = memchr::memmem::find(&source[i + 2..end], b"*/").map_or(end, |p| i + 2 + p + 2);
+      }
+      b'/' => {
+        return Err(json_error(
+          source,
+          "E_JSON_UNEXPECTED_CHARACTER",
+          "comments aren't allowed in JSON".to_string(),
+          i,
+          i + 1,
+        ))
+      }
+      _ => {
+        return Err(json_error(
+          source,
+          "E_JSON_UNEXPECTED_CHARACTER",
+          "unexpected character".to_string(),
+          i,
+          i + 1,
+        ))
+      }
+    };
+  }
+  Ok(())
+}
+
+// JS strings are a superset of JSON strings, which must use double quotes, can't contain control characters, and have fewer escapes.
+fn is_json_string(raw: &[u8]) -> bool {
+  if raw.first() != Some(&b'"') {
+    return false;
+  };
+  let mut i = 1;
+  while i < raw.len() - 1 {
+    match raw[i] {
+      0..=0x1f => return false,
+      b'\\' => match raw[i + 1] {
+        b'"' | b'\\' | b'/' | b'b' | b'f' | b'n' | b'r' | b't' => i += 2,
+        b'u' if raw.len() > i + 6 && raw[i + 2..i + 6].iter().all(u8::is_ascii_hexdigit) => i += 6,
+        _ => return false,
+      },
+      _ => i += 1,
+    };
+  }
+  true
+}
+
+// JS numbers are a superset of JSON numbers, which must be decimal, have digits before and after any `.`, have no leading zeros or separators, and can't be BigInts.
+fn is_json_number(raw: &[u8]) -> bool {
+  let digits = |i: usize| raw[i..].iter().take_while(|c| c.is_ascii_digit()).count();
+  let int = digits(0);
+  if int == 0 || (int > 1 && raw[0] == b'0') {
+    return false;
+  };
+  let mut i = int;
+  if raw.get(i) == Some(&b'.') {
+    let frac = digits(i + 1);
+    if frac == 0 {
+      return false;
+    };
+    i += 1 + frac;
+  };
+  if matches!(raw.get(i), Some(b'e' | b'E')) {
+    i += 1;
+    if matches!(raw.get(i), Some(b'+' | b'-')) {
+      i += 1;
+    };
+    let exp = digits(i);
+    if exp == 0 {
+      return false;
+    };
+    i += exp;
+  };
+  i == raw.len()
+}
+
+fn minify_json_with_comments(source: &[u8], comments: bool) -> Result<Vec<u8>, Diagnostic> {
+  let mut out = Vec::with_capacity(source.len());
+  let mut lexer = Lexer::new(source);
+  // Whether each open container is an object rather than an array.
+  let mut objects = Vec::<bool>::new();
+  let mut expect = Expect::Value;
+  let mut prev_end = 0;
+  loop {
+    let t = lex_next(&mut lexer, LexMode::Standard)
+      .map_err(|err| Diagnostic::from_syntax_error(source, &err))?;
+    let (start, end) = (t.loc.start(), t.loc.end());
+    check_gap(source, prev_end, start, comments)?;
+    prev_end = end;
+    let unexpected = || {
+      let (code, message) = match t.typ {
+        TokenType::EOF => ("E_UNEXPECTED_END", "unexpected end of JSON".to_string()),
+        typ => (
+          "E_JSON_UNEXPECTED_TOKEN",
+          format!("unexpected {:?} in JSON", typ),
+        ),
+      };
+      json_error(source, code, message, start, end)
+    };
+    out.extend_from_slice(t.loc.as_slice());
+    let mut closed = false;
+    match (expect, t.typ) {
+      (Expect::Value | Expect::ValueOrClose, TokenType::BraceOpen) => {
+        objects.push(true);
+        expect = Expect::KeyOrClose;
+      }
+      (Expect::Value | Expect::ValueOrClose, TokenType::BracketOpen) => {
+        objects.push(false);
+        expect = Expect::ValueOrClose;
+      }
+      (Expect::Value | Expect::ValueOrClose, TokenType::Hyphen) => {
+        // The number must immediately follow the sign.
+        let n = lex_next(&mut lexer, LexMode::Standard)
+          .map_err(|err| Diagnostic::from_syntax_error(source, &err))?;
+        if n.typ != TokenType::LiteralNumber
+          || n.loc.start() != end
+          || !is_json_number(n.loc.as_slice())
+        {
+          return Err(json_error(
+            source,
+            "E_JSON_INVALID_NUMBER",
+            "invalid number in JSON".to_string(),
+            start,
+            n.loc.end(),
+          ));
+        };
+        out.extend_from_slice(n.loc.as_slice());
+        prev_end = n.loc.end();
+        closed = true;
+      }
+      (Expect::Value | Expect::ValueOrClose, TokenType::LiteralNumber) => {
+        if !is_json_number(t.loc.as_slice()) {
+          return Err(json_error(
+            source,
+            "E_JSON_INVALID_NUMBER",
+            "invalid number in JSON".to_string(),
+            start,
+            end,
+          ));
+        };
+        closed = true;
+      }
+      (
+        Expect::Value | Expect::ValueOrClose,
+        TokenType::LiteralTrue | TokenType::LiteralFalse | TokenType::LiteralNull,
+      ) => closed = true,
+      (
+        Expect::Value | Expect::ValueOrClose | Expect::Key | Expect::KeyOrClose,
+        TokenType::LiteralString,
+      ) => {
+        if !is_json_string(t.loc.as_slice()) {
+          return Err(json_error(
+            source,
+            "E_JSON_INVALID_STRING",
+            "invalid string in JSON".to_string(),
+            start,
+            end,
+          ));
+        };
+        if matches!(expect, Expect::Key | Expect::KeyOrClose) {
+          expect = Expect::Colon;
+        } else {
+          closed = true;
+        };
+      }
+      (Expect::Colon, TokenType::Colon) => expect = Expect::Value,
+      (Expect::CommaOrClose, TokenType::Comma) => {
+        expect = if objects.last() == Some(&true) {
+          Expect::Key
+        } else {
+          Expect::Value
+        };
+      }
+      (Expect::KeyOrClose | Expect::CommaOrClose, TokenType::BraceClose)
+        if objects.last() == Some(&true) =>
+      {
+        objects.pop();
+        closed = true;
+      }
+      (Expect::ValueOrClose | Expect::CommaOrClose, TokenType::BracketClose)
+        if objects.last() == Some(&false) =>
+      {
+        objects.pop();
+        closed = true;
+      }
+      (Expect::End, TokenType::EOF) => break,
+      _ => return Err(unexpected()),
+    };
+    // A value has been completed.
+    if closed {
+      expect = if objects.is_empty() {
+        Expect::End
+      } else {
+        Expect::CommaOrClose
+      };
+    };
+  }
+  Ok(out)
+}
+
+/// Validates and minifies JSON, by removing all whitespace outside of strings. Strings and numbers are kept as is. The input must be UTF-8 JSON as defined by RFC 8259, without a byte order mark; JavaScript-only syntax such as comments, single-quoted strings, trailing commas, and hex numbers is rejected. Use [minify_jsonc] to allow comments.
+///
+/// # Examples
+///
+/// ```
+/// use minify_js::minify_json;
+///
+/// let out = minify_json(b"{ \"a\": [1, -2.5e3, true, null],\n  \"b c\": \"d\" }").unwrap();
+/// assert_eq!(out, br#"{"a":[1,-2.5e3,true,null],"b c":"d"}"#);
+/// assert_eq!(minify_json(b"[1,]").unwrap_err().code, "E_JSON_UNEXPECTED_TOKEN");
+/// ```
+pub fn minify_json(source: &[u8]) -> Result<Vec<u8>, Diagnostic> {
+  minify_json_with_comments(source, false)
+}
+
+/// Minifies JSON like [minify_json], but allows and removes `//` and `/* */` comments, as in JSON with Comments files such as `tsconfig.json`. Trailing commas are still rejected.
+pub fn minify_jsonc(source: &[u8]) -> Result<Vec<u8>, Diagnostic> {
+  minify_json_with_comments(source, true)
+}
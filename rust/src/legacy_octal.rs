@@ -0,0 +1,83 @@
+use crate::escape::decode_normalized;
+use memchr::memchr;
+use parse_js::ast::NodeData;
+use parse_js::ast::Syntax;
+use parse_js::num::JsNumber;
+use parse_js::session::Session;
+use parse_js::visit::JourneyControls;
+use parse_js::visit::Visitor;
+
+// Whether a number literal has a leading zero followed by a digit, which makes it a legacy octal literal (e.g. `0755`) or, if it has an 8 or 9, a decimal one (e.g. `089`). Both are only allowed in non-strict code.
+pub fn has_leading_zero(raw: &[u8]) -> bool {
+  raw.len() > 1 && raw[0] == b'0' && raw[1].is_ascii_digit()
+}
+
+// Whether a string literal has a legacy octal escape (e.g. `\07` or `\1`), or `\8` or `\9`, which are only allowed in non-strict code. `\0` is allowed anywhere, unless a digit follows.
+pub fn has_octal_escape(raw: &[u8]) -> bool {
+  let mut i = 0;
+  while i + 1 < raw.len() {
+    if raw[i] == b'\\' {
+      match raw[i + 1] {
+        b'1'..=b'9' => return true,
+        b'0' if raw.get(i + 2).is_some_and(u8::is_ascii_digit) => return true,
+        _ => {}
+      };
+      i += 2;
+    } else {
+      i += 1;
+    };
+  }
+  false
+}
+
+// Whether a string literal has an escape starting with a digit, including `\0`, which parse-js decodes incorrectly.
+fn has_digit_escape(raw: &[u8]) -> bool {
+  raw.windows(2).any(|w| w[0] == b'\\' && w[1].is_ascii_digit())
+}
+
+struct LegacyOctal<'a> {
+  session: &'a Session,
+}
+
+impl<'a> Visitor<'a> for LegacyOctal<'a> {
+  fn on_syntax_down(&mut self, node: &mut NodeData<'a>, _ctl: &mut JourneyControls) {
+    let raw = node.loc.as_slice();
+    match &mut node.stx {
+      // Those with an 8 or 9 are decimal, which parse-js already gets right.
+      Syntax::LiteralNumberExpr { value }
+        if has_leading_zero(raw) && raw.iter().all(|c| (b'0'..=b'7').contains(c)) =>
+      {
+        *value = JsNumber(raw.iter().fold(0.0, |v, &d| v * 8.0 + f64::from(d - b'0')));
+      }
+      Syntax::LiteralStringExpr { value }
+        if has_digit_escape(raw) || memchr(b'\r', raw).is_some() =>
+      {
+        if let Some(decoded) = decode_normalized(&raw[1..raw.len() - 1]) {
+          *value = self.session.get_allocator().alloc_str(&decoded);
+        };
+      }
+      _ => {}
+    };
+  }
+}
+
+// parse-js lexes legacy octal number literals (e.g. `0755`) as decimal, and decodes escapes starting with a digit in strings (e.g. `"\0"` and `"\07"`) incorrectly, so this fixes the values of those literals in a parsed tree. The legacy forms are only allowed in non-strict scripts, and are early errors elsewhere (see `check_early_errors`), so these literals are always read as them. `\0` on its own is allowed everywhere, and is fixed too, as are strings with a `\` `\r` `\n` line continuation, which `normalize_whitespace` rewrites.
+pub fn fix_legacy_octal<'a>(session: &'a Session, node: &mut NodeData<'a>) {
+  LegacyOctal { session }.visit(node);
+}
+
+#[cfg(test)]
+mod tests {
+  use super::has_octal_escape;
+
+  #[test]
+  fn test_octal_escapes() {
+    assert!(has_octal_escape(br#""\07""#));
+    assert!(has_octal_escape(br#""\1""#));
+    assert!(has_octal_escape(br#""\9""#));
+    assert!(has_octal_escape(br#""\00""#));
+    assert!(!has_octal_escape(br#""\0""#));
+    assert!(!has_octal_escape(br#""\\1""#));
+    assert!(!has_octal_escape(br#""\x01""#));
+  }
+}
@@ -1,14 +1,187 @@
+use accessor::fix_accessors;
+use bailout::collect_bailouts;
+use comment::PreservedComments;
+use console::drop_console;
+use define::replace_defines_in;
+use early_error::check_early_errors;
+use early_error::check_enclosed_early_errors;
 use emit::emit_js;
+use emit::estimate_output_len;
+use emit::Emitter;
+use legacy_octal::fix_legacy_octal;
 use minify::minify_js;
+use minify::KeepTopLevel;
+use normalize::normalize_source;
 use parse_js::ast::Node;
+use parse_js::ast::NodeData;
 use parse_js::parse;
+use property_order::preserving_property_order;
+use report::phase;
+use std::io::Write;
+use template::fix_template_line_terminators;
+use trace::trace_counters;
+use trace::trace_span;
+use verify::verify_output;
+use warning::collect_warnings;
 
+mod accessor;
+mod alphabet;
+mod bailout;
+#[cfg(feature = "rayon")]
+mod batch;
+mod bounded;
+#[cfg(feature = "bundle")]
+mod bundle;
+mod cache;
+mod cfg;
+mod comment;
+mod console;
+mod cst;
+mod deep;
+mod define;
+mod dependency;
+mod diagnostic;
+mod document;
+#[cfg(feature = "downlevel")]
+mod downlevel;
+mod early_error;
 mod emit;
+mod environment;
+mod error;
+mod escape;
+mod expression;
+mod features;
+mod files;
+mod identifier;
+mod json;
+mod legacy_octal;
+mod limits;
+mod line_terminator;
+mod lossless;
+mod metadata;
+mod minifier;
 mod minify;
+mod normalize;
+mod obfuscate;
+mod parallel;
+mod pattern;
+mod position_map;
+mod property_order;
+mod recover;
+mod rename_map;
+mod report;
+mod rewrite;
+mod separator;
+mod stats;
+mod template;
+#[cfg(test)]
+mod tests;
+mod trace;
+mod verify;
+mod warning;
 
+/// The parser and AST used by this crate, re-exported so that custom transforms passed to [emit] use the same version. The lexer and parser have no dependency on minification, so projects that only need them (e.g. analysers) can depend on the `parse-js` crate directly instead of this one.
+pub use parse_js;
+/// The regex crate, re-exported so that patterns passed to [MinifyOptions::keep_names] use the same version.
+pub use regex;
+
+pub use alphabet::NameAlphabet;
+#[cfg(feature = "rayon")]
+pub use batch::minify_batch;
+#[cfg(feature = "rayon")]
+pub use batch::minify_concat;
+#[cfg(feature = "rayon")]
+pub use batch::ConcatOutput;
+#[cfg(feature = "rayon")]
+pub use batch::BatchOutput;
+#[cfg(feature = "rayon")]
+pub use batch::ModuleDiagnostic;
+#[cfg(feature = "rayon")]
+pub use batch::NameCache;
+pub use bounded::minify_bounded;
+#[cfg(feature = "bundle")]
+pub use bundle::bundle;
+#[cfg(feature = "bundle")]
+pub use bundle::BundleError;
+#[cfg(feature = "bundle")]
+pub use bundle::ModuleResolver;
+pub use cache::MinifyCache;
+pub use cfg::CommentAction;
+pub use cfg::CommentFilter;
+pub use cfg::LegalComments;
+pub use cfg::MinifyOptions;
+pub use cfg::Newline;
+pub use cfg::OutputCapacity;
+pub use cfg::RenameDecision;
+pub use cfg::RenameFilter;
+pub use cfg::ScriptKind;
+pub use cfg::SourceMappingUrl;
+pub use cfg::SymbolInfo;
+pub use cfg::Target;
+pub use cfg::Transform;
+pub use cfg::Verify;
+pub use comment::find_source_mapping_url;
+pub use cst::concrete_tree;
+pub use cst::ConcreteTree;
+pub use cst::CstElement;
+pub use cst::CstId;
+pub use cst::CstKind;
+pub use deep::minify_deep;
+pub use define::replace_defines;
+pub use define::Define;
+pub use dependency::module_dependencies;
+pub use dependency::Dependency;
+pub use dependency::DependencyKind;
+pub use diagnostic::diagnostics_to_json;
+pub use diagnostic::syntax_error_code;
+pub use diagnostic::syntax_error_message;
+pub use diagnostic::Diagnostic;
+pub use diagnostic::Severity;
+pub use document::Document;
+pub use environment::Environment;
+pub use error::render_diagnostic;
+pub use error::render_syntax_error;
+pub use error::MinifyError;
+pub use error::WriteError;
+pub use expression::minify_expression;
+pub use features::syntax_features;
+pub use features::FeatureUse;
+pub use features::SyntaxFeature;
+pub use features::UnsupportedSyntax;
+pub use files::FileId;
+pub use files::FileProvider;
+pub use files::FsProvider;
+pub use files::SourceFiles;
+pub use json::minify_json;
+pub use json::minify_jsonc;
+pub use limits::Limit;
+pub use limits::LimitExceeded;
+pub use limits::Limits;
+pub use lossless::print_lossless;
+pub use metadata::module_metadata;
+pub use metadata::Import;
+pub use metadata::ImportSpecifier;
+pub use metadata::ModuleMetadata;
+pub use minifier::Minifier;
+pub use obfuscate::Obfuscation;
+pub use parallel::minify_many;
 pub use parse_js::error::SyntaxError;
 pub use parse_js::parse::toplevel::TopLevelMode;
 pub use parse_js::session::Session;
+pub use parse_js::symbol::ScopeType;
+pub use position_map::Mapping;
+pub use position_map::PositionMap;
+pub use position_map::SourceMap;
+pub use position_map::SourceMapOptions;
+pub use recover::syntax_errors;
+pub use rename_map::Rename;
+pub use rename_map::RenameMap;
+pub use rename_map::RenameScope;
+pub use report::MinifyReport;
+pub use report::PhaseReport;
+pub use rewrite::rewrite_imports;
+pub use rewrite::ImportKind;
+pub use stats::MinifyStats;
 
 /// Emits UTF-8 JavaScript code from a parsed AST in a minified way. This allows custom introspections and transforms on the tree before emitting it to code.
 ///
@@ -17,7 +190,7 @@ pub use parse_js::session::Session;
 /// * `node` - The root node from the parsed AST.
 /// * `output` - Destination to write output JavaScript code.
 pub fn emit<'a>(node: Node<'a>, output: &mut Vec<u8>) -> () {
-  emit_js(output, node);
+  emit_js(&mut Emitter::new(output, &MinifyOptions::new()), node);
 }
 
 /// Minifies UTF-8 JavaScript code, represented as an array of bytes.
@@ -25,29 +198,301 @@ pub fn emit<'a>(node: Node<'a>, output: &mut Vec<u8>) -> () {
 /// # Arguments
 ///
 /// * `session` - Session to use as backing arena memory. Can be reused across calls and cleared at any time allowed by the Rust lifetime checker.
-/// * `top_level_mode` - How to parse the provided code.
-/// * `source` - A vector of bytes representing the source code to minify.
+/// * `source` - The source code to minify, as UTF-8 bytes. It is borrowed rather than copied (except when using `enclose`), so pass a `&str` with `as_bytes()` or a `Vec<u8>` by reference.
+/// * `cfg` - Options to customise minification, including how to parse the provided code.
 /// * `output` - Destination to write minified output JavaScript code.
 ///
+/// Returns statistics about the minification.
+///
+/// The output is deterministic: the same source code and configuration always produce byte-identical output, across runs and platforms.
+///
+/// Invalid or unusual code, including untrusted input, results in an `Err` rather than a panic; any panic is a bug. Use [MinifyOptions::limits] to also reject code that is too large or deeply nested, or [minify_deep] to minify deeply nested code without overflowing the stack. Lexing and parsing are done by the [parse_js] crate, so panics there must be fixed upstream.
+///
 /// # Examples
 ///
 /// ```
-/// use minify_js::{Session, TopLevelMode, minify};
+/// use minify_js::{MinifyOptions, Session, minify};
 ///
 /// let mut code: &[u8] = b"const main = () => { let my_first_variable = 1; };";
 /// let session = Session::new();
 /// let mut out = Vec::new();
-/// minify(&session, TopLevelMode::Global, code, &mut out).unwrap();
+/// let stats = minify(&session, code, &MinifyOptions::new(), &mut out).unwrap();
 /// assert_eq!(out.as_slice(), b"const main=()=>{let a=1}");
+/// assert_eq!(stats.output_len, out.len());
+/// assert_eq!(stats.renaming_savings, 16);
 /// ```
 pub fn minify<'a>(
   session: &'a Session,
-  top_level_mode: TopLevelMode,
   source: &'a [u8],
+  cfg: &MinifyOptions,
+  output: &mut Vec<u8>,
+) -> Result<MinifyStats, MinifyError<'a>> {
+  trace_span!("minify", input_len = source.len());
+  let (parsed, mut stats) = parse_and_minify(session, source, cfg)?;
+  let output_start = output.len();
+  // Range of the output that's emitted from the tree, excluding the banner, footer, and comments.
+  let mut code = 0..0;
+  phase!(stats.report, session, "emit", {
+    output.reserve(match cfg.output_capacity {
+      OutputCapacity::Estimate => estimate_output_len(&stats, cfg),
+      OutputCapacity::Exact(len) => len,
+      OutputCapacity::Grow => 0,
+    });
+    let comments = PreservedComments::collect(source, cfg);
+    comments.emit_top(cfg, output);
+    let code_start = output.len();
+    let mut emitter = Emitter::new(output, cfg);
+    emit_js(&mut emitter, parsed);
+    stats.positions = emitter.take_position_map(output_start);
+    code = code_start..output.len();
+    comments.emit_eof(cfg, output);
+    if cfg.shrink_output {
+      output.shrink_to_fit();
+    };
+  });
+  stats.output_len = output.len() - output_start;
+  trace_counters!(output_len = stats.output_len, "emitted");
+  if cfg.verify != Verify::None {
+    let skipped = phase!(stats.report, session, "verify", {
+      verify_output(source, parsed, &output[code], cfg)
+    })
+    .map_err(MinifyError::Internal)?;
+    stats.warnings.extend(skipped);
+  };
+  Ok(stats)
+}
+
+/// Minifies JavaScript code like [`minify`], but takes and returns strings. Minifying valid UTF-8 always produces valid UTF-8: code is copied from the source as is, and string values are escaped where they wouldn't round-trip (e.g. lone surrogates from joining strings).
+///
+/// # Examples
+///
+/// ```
+/// use minify_js::{MinifyOptions, Session, minify_str};
+///
+/// let session = Session::new();
+/// let out = minify_str(&session, "let café = '☕'; f(café)", &MinifyOptions::new()).unwrap();
+/// assert_eq!(out, "let café=`☕`;f(café)");
+/// ```
+pub fn minify_str<'a>(
+  session: &'a Session,
+  source: &'a str,
+  cfg: &MinifyOptions,
+) -> Result<String, MinifyError<'a>> {
+  let mut output = Vec::new();
+  minify(session, source.as_bytes(), cfg, &mut output)?;
+  Ok(String::from_utf8(output).expect("minified valid UTF-8 is valid UTF-8"))
+}
+
+/// Minifies the contents of an HTML `<script>` element like [`minify`], for HTML minifiers. The code is parsed as a global script or module depending on `kind`, overriding [MinifyOptions::top_level_mode], and the output never contains `</script` or `<!--` (see [MinifyOptions::inline_script]), so it can be put back between the tags as is.
+///
+/// # Examples
+///
+/// ```
+/// use minify_js::{MinifyOptions, ScriptKind, Session, minify_inline_script};
+///
+/// let session = Session::new();
+/// let mut out = Vec::new();
+/// let code = b"document.write('<script>x()</script>'); if (a < !--b) c()";
+/// minify_inline_script(&session, code, ScriptKind::Classic, &MinifyOptions::new(), &mut out).unwrap();
+/// assert_eq!(out.as_slice(), br"document.write(`<script>x()\x3C/script>`);a<! --b&&c()");
+/// ```
+pub fn minify_inline_script<'a>(
+  session: &'a Session,
+  source: &'a [u8],
+  kind: ScriptKind,
+  cfg: &MinifyOptions,
   output: &mut Vec<u8>,
-) -> Result<(), SyntaxError<'a>> {
-  let parsed = parse(session, source, top_level_mode)?;
-  minify_js(session, parsed);
-  emit(parsed, output);
-  Ok(())
+) -> Result<MinifyStats, MinifyError<'a>> {
+  let cfg = cfg
+    .clone()
+    .top_level_mode(kind.top_level_mode())
+    .inline_script(true);
+  minify(session, source, &cfg, output)
+}
+
+/// Minifies UTF-8 JavaScript code like [`minify`], but writes the output to `output` as it's generated instead of building it up in memory. This is useful for large inputs being written to a file or socket.
+///
+/// The output is written in large chunks, so there's no need to wrap `output` in a `BufWriter`. If writing fails, the first error is returned once minification has finished.
+///
+/// # Examples
+///
+/// ```
+/// use minify_js::{MinifyOptions, Session, minify_to_writer};
+///
+/// let mut code: &[u8] = b"const main = () => { let my_first_variable = 1; };";
+/// let session = Session::new();
+/// let mut out = std::io::Cursor::new(Vec::new());
+/// minify_to_writer(&session, code, &MinifyOptions::new(), &mut out).unwrap();
+/// assert_eq!(out.get_ref().as_slice(), b"const main=()=>{let a=1}");
+/// ```
+pub fn minify_to_writer<'a>(
+  session: &'a Session,
+  source: &'a [u8],
+  cfg: &MinifyOptions,
+  output: &mut impl Write,
+) -> Result<MinifyStats, WriteError<'a>> {
+  minify_to_writer_with_buf(session, source, cfg, &mut Vec::new(), output)
+}
+
+// Uses `buf` to build up output before writing it, so it can be reused across calls.
+fn minify_to_writer_with_buf<'a>(
+  session: &'a Session,
+  source: &'a [u8],
+  cfg: &MinifyOptions,
+  buf: &mut Vec<u8>,
+  output: &mut impl Write,
+) -> Result<MinifyStats, WriteError<'a>> {
+  trace_span!("minify", input_len = source.len());
+  let (parsed, mut stats) = parse_and_minify(session, source, cfg)?;
+  phase!(stats.report, session, "emit", {
+    let comments = PreservedComments::collect(source, cfg);
+    buf.clear();
+    comments.emit_top(cfg, buf);
+    let mut emitter = Emitter::with_sink(buf, output, cfg);
+    emit_js(&mut emitter, parsed);
+    stats.positions = emitter.take_position_map(0);
+    let written = emitter.finish()?;
+    comments.emit_eof(cfg, buf);
+    output.write_all(buf)?;
+    stats.output_len = written + buf.len();
+  });
+  trace_counters!(output_len = stats.output_len, "emitted");
+  Ok(stats)
+}
+
+fn parse_and_minify<'a>(
+  session: &'a Session,
+  source: &'a [u8],
+  cfg: &MinifyOptions,
+) -> Result<(&'a mut NodeData<'a>, MinifyStats), MinifyError<'a>> {
+  let input_len = source.len();
+  let mut report = cfg.report.then(MinifyReport::default);
+  phase!(report, session, "check_limits", {
+    cfg.limits.check(source)?;
+  });
+  cfg.check_cancelled()?;
+  let source = normalize_source(session, source);
+  let enclosed = cfg.enclose.is_some() && cfg.top_level_mode == TopLevelMode::Global;
+  let source = match &cfg.enclose {
+    Some(params) if enclosed => enclose(session, source, params, cfg.target),
+    _ => source,
+  };
+  // The lexer is driven by the parser, so they're a single phase.
+  let parsed = phase!(report, session, "parse", {
+    let parsed = parse(session, source, cfg.top_level_mode)?;
+    if enclosed {
+      check_enclosed_early_errors(parsed)?;
+    } else {
+      check_early_errors(parsed)?;
+    };
+    parsed
+  });
+  cfg.check_cancelled()?;
+  let warnings = if cfg.warnings {
+    phase!(report, session, "collect_warnings", {
+      collect_warnings(source, parsed, cfg.environment)
+    })
+  } else {
+    Vec::new()
+  };
+  trace_counters!(warnings = warnings.len(), "parsed");
+  let syntax_features = if cfg.syntax_features {
+    phase!(report, session, "syntax_features", {
+      syntax_features(source, parsed)
+    })
+  } else {
+    Vec::new()
+  };
+  let bailouts = if cfg.bailouts {
+    phase!(report, session, "collect_bailouts", {
+      collect_bailouts(source, parsed, cfg)
+    })
+  } else {
+    Vec::new()
+  };
+  let mut stats = MinifyStats {
+    input_len,
+    warnings,
+    syntax_features,
+    bailouts,
+    ..minify_parsed(session, parsed, cfg, &mut report, KeepTopLevel::default())?
+  };
+  stats.report = report;
+  Ok((parsed, stats))
+}
+
+// Runs the custom transforms, minification, and downlevelling on a parsed tree. Returns stats about renaming; the input and output lengths are left as zero.
+fn minify_parsed<'a>(
+  session: &'a Session,
+  parsed: &mut NodeData<'a>,
+  cfg: &MinifyOptions,
+  report: &mut Option<MinifyReport>,
+  keep: KeepTopLevel,
+) -> Result<MinifyStats, MinifyError<'a>> {
+  fix_legacy_octal(session, parsed);
+  fix_template_line_terminators(session, parsed);
+  fix_accessors(session, parsed);
+  if !cfg.defines.is_empty() || cfg.drop_console || !cfg.before_minify.is_empty() {
+    phase!(*report, session, "before_minify", {
+      if !cfg.defines.is_empty() {
+        replace_defines_in(session, parsed, &cfg.defines);
+      };
+      if cfg.drop_console {
+        drop_console(session, parsed);
+      };
+      for t in cfg.before_minify.iter() {
+        (t.0)(session, parsed);
+        cfg.check_cancelled()?;
+      }
+    });
+  };
+  let stats = preserving_property_order(parsed, |parsed| {
+    minify_js(session, parsed, cfg, report, keep)
+  })?;
+  if !cfg.after_minify.is_empty() {
+    phase!(*report, session, "after_minify", {
+      for t in cfg.after_minify.iter() {
+        (t.0)(session, parsed);
+        cfg.check_cancelled()?;
+      }
+    });
+  };
+  #[cfg(feature = "downlevel")]
+  if cfg.target == Target::Es5 {
+    phase!(*report, session, "downlevel", {
+      preserving_property_order(parsed, |parsed| downlevel::downlevel_js(session, parsed))
+        .map_err(MinifyError::Unsupported)?;
+    });
+  };
+  if cfg.obfuscation.encode_strings || cfg.obfuscation.indirect_properties {
+    phase!(*report, session, "obfuscate", {
+      obfuscate::obfuscate(session, parsed, cfg.obfuscation);
+    });
+  };
+  cfg.check_cancelled()?;
+  Ok(stats)
+}
+
+// Wraps the source in an immediately invoked function. We do this on the source code, as scopes are determined during parsing.
+fn enclose<'a>(session: &'a Session, source: &[u8], params: &[String], target: Target) -> &'a [u8] {
+  let params = params.join(",");
+  // Use an arrow function or `call` so that `this` is still the global object.
+  let (head, tail) = if target >= Target::Es2015 {
+    (format!("(({})=>{{", params), format!("}})({})", params))
+  } else if params.is_empty() {
+    ("(function(){".to_string(), "}).call(this)".to_string())
+  } else {
+    (
+      format!("(function({}){{", params),
+      format!("}}).call(this,{})", params),
+    )
+  };
+  let mut wrapped = Vec::new();
+  wrapped.extend_from_slice(head.as_bytes());
+  wrapped.push(b'\n');
+  wrapped.extend_from_slice(source);
+  // The source could end with a single-line comment.
+  wrapped.push(b'\n');
+  wrapped.extend_from_slice(tail.as_bytes());
+  session.get_allocator().alloc_slice_copy(&wrapped)
 }
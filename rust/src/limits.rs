@@ -0,0 +1,294 @@
+use parse_js::lex::lex_next;
+use parse_js::lex::lex_template_string_continue;
+use parse_js::lex::LexMode;
+use parse_js::lex::Lexer;
+use parse_js::token::TokenType;
+use std::error::Error;
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::ops::Range;
+
+/// Limits on the source code to minify, so that services minifying untrusted code can reject pathological inputs before spending too much time, memory, or stack on them. All limits are disabled by default.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serialize", serde(default))]
+pub struct Limits {
+  /// Maximum size of the source code in bytes.
+  pub max_input_len: Option<usize>,
+  /// Maximum number of tokens, excluding comments. This bounds the size of the parsed tree.
+  pub max_tokens: Option<usize>,
+  /// Maximum depth of nested parentheses, brackets, braces, and template substitutions. Parsing and minifying are recursive, so deeply nested code can overflow the stack; use `max_tokens` to also bound other kinds of nesting, such as long chains of unary operators, or use [minify_deep](crate::minify_deep) to minify it without overflowing.
+  pub max_nesting_depth: Option<usize>,
+}
+
+/// A limit in [Limits].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Limit {
+  InputLen,
+  Tokens,
+  NestingDepth,
+}
+
+/// Error returned when the source code exceeds one of the [Limits].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LimitExceeded {
+  pub limit: Limit,
+  /// The configured maximum.
+  pub max: usize,
+  /// Byte offset in the source where the limit was exceeded.
+  pub position: usize,
+}
+
+impl Display for LimitExceeded {
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    match self.limit {
+      Limit::InputLen => write!(f, "source code is longer than {} bytes", self.max),
+      Limit::Tokens => write!(f, "source code has more than {} tokens", self.max),
+      Limit::NestingDepth => write!(
+        f,
+        "source code is nested more than {} levels deep",
+        self.max
+      ),
+    }
+  }
+}
+
+impl Error for LimitExceeded {}
+
+impl Limits {
+  fn is_unlimited(&self) -> bool {
+    self.max_input_len.is_none() && self.max_tokens.is_none() && self.max_nesting_depth.is_none()
+  }
+
+  // Checks the source code against the limits before parsing it, as the parser is the first thing that could overflow the stack.
+  pub(crate) fn check(&self, source: &[u8]) -> Result<(), LimitExceeded> {
+    if self.is_unlimited() {
+      return Ok(());
+    };
+    if let Some(max) = self.max_input_len {
+      if source.len() > max {
+        return Err(LimitExceeded {
+          limit: Limit::InputLen,
+          max,
+          position: max,
+        });
+      };
+    };
+    if self.max_tokens.is_none() && self.max_nesting_depth.is_none() {
+      return Ok(());
+    };
+    let max_tokens = self.max_tokens.unwrap_or(usize::MAX);
+    let max_depth = self.max_nesting_depth.unwrap_or(usize::MAX);
+    let mut tokens = 0;
+    scan_tokens(source, |_, range, depth| {
+      let position = range.start;
+      tokens += 1;
+      if tokens > max_tokens {
+        return Err(LimitExceeded {
+          limit: Limit::Tokens,
+          max: max_tokens,
+          position,
+        });
+      };
+      if depth > max_depth {
+        return Err(LimitExceeded {
+          limit: Limit::NestingDepth,
+          max: max_depth,
+          position,
+        });
+      };
+      Ok(())
+    })
+  }
+}
+
+// Whether a `/` after this token is division rather than the start of a regex. The lexer needs the parser to tell it this, so we guess like most tools that tokenise without parsing do. A wrong guess only makes the counts slightly inaccurate.
+fn ends_expression(typ: TokenType) -> bool {
+  matches!(
+    typ,
+    TokenType::Identifier
+      | TokenType::KeywordThis
+      | TokenType::KeywordSuper
+      | TokenType::PrivateMember
+      | TokenType::LiteralBigInt
+      | TokenType::LiteralFalse
+      | TokenType::LiteralNull
+      | TokenType::LiteralNumber
+      | TokenType::LiteralRegex
+      | TokenType::LiteralString
+      | TokenType::LiteralTemplatePartStringEnd
+      | TokenType::LiteralTrue
+      | TokenType::ParenthesisClose
+      | TokenType::BracketClose
+      | TokenType::BraceClose
+      | TokenType::PlusPlus
+      | TokenType::HyphenHyphen
+  )
+}
+
+// Lexes the source without parsing, calling `on_token` with the type, range in the source, and nesting depth of brackets and template substitutions after each token, and stopping at the first error it returns. If the lexer fails (e.g. on JSX text, or a wrong guess about a regex), it skips a byte and continues, so that invalid code can't hide tokens from the limits; the parser will report any actual syntax error.
+pub(crate) fn scan_tokens<E>(
+  source: &[u8],
+  on_token: impl FnMut(TokenType, Range<usize>, usize) -> Result<(), E>,
+) -> Result<(), E> {
+  Scanner::new().scan(source, on_token)
+}
+
+// The state of `scan_tokens` between tokens. When `on_token` returns an error, the state is as it was before that token, so the source can be changed from the end of the previous token and scanning resumed from there.
+pub(crate) struct Scanner {
+  // Whether each open bracket is a template substitution, whose closing brace continues the template.
+  open: Vec<bool>,
+  prev: TokenType,
+  prev_is_property: bool,
+  in_template: bool,
+  // Where the previous token ended, or where the lexer skipped to after an error.
+  offset: usize,
+}
+
+impl Scanner {
+  pub(crate) fn new() -> Scanner {
+    Scanner {
+      open: Vec::new(),
+      prev: TokenType::_Dummy,
+      prev_is_property: false,
+      in_template: false,
+      offset: 0,
+    }
+  }
+
+  pub(crate) fn offset(&self) -> usize {
+    self.offset
+  }
+
+  pub(crate) fn scan<E>(
+    &mut self,
+    source: &[u8],
+    mut on_token: impl FnMut(TokenType, Range<usize>, usize) -> Result<(), E>,
+  ) -> Result<(), E> {
+    if self.offset >= source.len() {
+      return Ok(());
+    };
+    let mut base = self.offset;
+    let mut lexer = Lexer::new(&source[base..]);
+    loop {
+      let mode = if ends_expression(self.prev) || self.prev_is_property {
+        LexMode::Standard
+      } else {
+        LexMode::SlashIsRegex
+      };
+      let start = lexer.since_checkpoint(lexer.checkpoint()).start();
+      let res = if self.in_template {
+        lex_template_string_continue(&mut lexer, false)
+      } else {
+        lex_next(&mut lexer, mode)
+      };
+      let t = match res {
+        Ok(t) => t,
+        Err(err) => {
+          base += err.source.start().max(start) + 1;
+          if base >= source.len() {
+            return Ok(());
+          };
+          lexer = Lexer::new(&source[base..]);
+          self.prev = TokenType::_Dummy;
+          self.prev_is_property = false;
+          self.in_template = false;
+          self.offset = base;
+          continue;
+        }
+      };
+      let open_was_template = self.open.last() == Some(&true);
+      let depth = match t.typ {
+        TokenType::EOF => return Ok(()),
+        TokenType::ParenthesisOpen
+        | TokenType::BracketOpen
+        | TokenType::BraceOpen
+        | TokenType::QuestionDotParenthesisOpen
+        | TokenType::QuestionDotBracketOpen
+        | TokenType::LiteralTemplatePartString => self.open.len() + 1,
+        TokenType::ParenthesisClose | TokenType::BracketClose | TokenType::BraceClose => {
+          self.open.len().saturating_sub(1)
+        }
+        _ => self.open.len(),
+      };
+      let range = base + t.loc.start()..base + t.loc.end();
+      on_token(t.typ, range.clone(), depth)?;
+      match t.typ {
+        TokenType::LiteralTemplatePartString => self.open.push(true),
+        _ if depth > self.open.len() => self.open.push(false),
+        _ if depth < self.open.len() => {
+          self.open.pop();
+        }
+        _ => {}
+      };
+      // The closing brace of a template substitution is followed by the rest of the template.
+      self.in_template = t.typ == TokenType::BraceClose && open_was_template;
+      self.prev_is_property = matches!(self.prev, TokenType::Dot | TokenType::QuestionDot);
+      self.prev = t.typ;
+      self.offset = range.end;
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::Limit;
+  use super::LimitExceeded;
+  use super::Limits;
+
+  fn check(limits: Limits, source: &str) -> Result<(), LimitExceeded> {
+    limits.check(source.as_bytes())
+  }
+
+  #[test]
+  fn test_limits() {
+    let tokens = |max| Limits {
+      max_tokens: Some(max),
+      ..Limits::default()
+    };
+    let depth = |max| Limits {
+      max_nesting_depth: Some(max),
+      ..Limits::default()
+    };
+    assert_eq!(check(Limits::default(), &"(".repeat(100000)), Ok(()));
+    assert_eq!(
+      check(
+        Limits {
+          max_input_len: Some(4),
+          ..Limits::default()
+        },
+        "a + b"
+      ),
+      Err(LimitExceeded {
+        limit: Limit::InputLen,
+        max: 4,
+        position: 4,
+      })
+    );
+    // Comments aren't tokens.
+    assert_eq!(check(tokens(4), "a + /* c */ b; // d"), Ok(()));
+    assert_eq!(
+      check(tokens(3), "a + b;"),
+      Err(LimitExceeded {
+        limit: Limit::Tokens,
+        max: 3,
+        position: 5,
+      })
+    );
+    assert_eq!(check(depth(4), "f([{ a: `${b}` }])"), Ok(()));
+    assert_eq!(check(depth(4), "`${ {a: `${(b)}`} }`"), Ok(()));
+    assert_eq!(
+      check(depth(4), "f([{ a: `${(b)}` }])"),
+      Err(LimitExceeded {
+        limit: Limit::NestingDepth,
+        max: 4,
+        position: 11,
+      })
+    );
+    // Brackets in strings, regexes, and templates don't count.
+    assert_eq!(check(depth(2), "f('((', /[(]/, `((${a}((`) / (2)"), Ok(()));
+    // Invalid code can't hide brackets from the limit.
+    assert!(check(depth(1), "'\n(((").is_err());
+  }
+}
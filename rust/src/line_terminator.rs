@@ -0,0 +1,153 @@
+use crate::limits::Scanner;
+use memchr::memchr;
+use memchr::memmem;
+use parse_js::token::TokenType;
+
+// Whitespace and line terminators outside ASCII, which parse-js lexes as part of identifiers, as it lexes any non-ASCII character as one.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Special {
+  // U+2028 LINE SEPARATOR and U+2029 PARAGRAPH SEPARATOR.
+  LineTerminator,
+  // U+FEFF ZERO WIDTH NO-BREAK SPACE, and the characters in the Space_Separator category, such as U+00A0 NO-BREAK SPACE.
+  Whitespace,
+}
+
+fn special(c: char) -> Option<Special> {
+  match c {
+    '\u{2028}' | '\u{2029}' => Some(Special::LineTerminator),
+    '\u{a0}' | '\u{1680}' | '\u{2000}'..='\u{200a}' | '\u{202f}' | '\u{205f}' | '\u{3000}'
+    | '\u{feff}' => Some(Special::Whitespace),
+    _ => None,
+  }
+}
+
+// Calls `f` with the position, length, and kind of each special character in `code`, which starts at `offset` in the source, until it returns false.
+fn for_each_special(
+  code: &[u8],
+  offset: usize,
+  mut f: impl FnMut(usize, usize, Special) -> bool,
+) -> bool {
+  let mut i = 0;
+  while i < code.len() {
+    let len = match code[i] {
+      0xE0..=0xEF => 3,
+      0xC0..=0xDF => 2,
+      _ => {
+        i += 1;
+        continue;
+      }
+    };
+    let c = code
+      .get(i..i + len)
+      .and_then(|c| std::str::from_utf8(c).ok())
+      .and_then(|c| c.chars().next());
+    if let Some(kind) = c.and_then(special) {
+      if !f(offset + i, len, kind) {
+        return false;
+      };
+    };
+    i += len;
+  }
+  true
+}
+
+// Finds the first special character that's between tokens (including in comments) or that the lexer has mistaken for part of an identifier, from where `scanner` is. Those in strings and templates are part of them. Whitespace between tokens can only be in comments, where it makes no difference, so it's not returned. Regexes can't have line terminators, but parse-js accepts them after a `\`, and any that aren't `\n`, so they're returned as `Special::LineTerminator` (with the position of the `\`, if there is one) to be rewritten to code that parse-js rejects.
+fn find_special(scanner: &mut Scanner, source: &[u8]) -> Option<(usize, usize, Special)> {
+  let first = |code: &[u8], offset: usize, whitespace: bool| {
+    let mut found = None;
+    for_each_special(code, offset, |pos, len, kind| {
+      if kind == Special::LineTerminator || whitespace {
+        found = Some((pos, len, kind));
+      };
+      found.is_none()
+    });
+    found
+  };
+  let mut prev_end = scanner.offset();
+  let res = scanner.scan(source, |typ, range, _| {
+    let gap_start = prev_end.min(range.start);
+    let token = &source[range.clone()];
+    let found = first(&source[gap_start..range.start], gap_start, false).or_else(|| match typ {
+      TokenType::Identifier => first(token, range.start, true),
+      TokenType::LiteralRegex => match memmem::find(token, b"\\\n") {
+        Some(pos) => Some((range.start + pos, 1, Special::Whitespace)),
+        None => first(token, range.start, false),
+      },
+      _ => None,
+    });
+    prev_end = range.end;
+    found.map_or(Ok(()), Err)
+  });
+  match res {
+    Err(found) => Some(found),
+    Ok(()) => {
+      let gap_start = prev_end.min(source.len());
+      first(&source[gap_start..], gap_start, false)
+    }
+  }
+}
+
+// Whether there's anything for `normalize_whitespace` to rewrite, without lexing.
+pub(crate) fn has_whitespace_to_normalize(source: &[u8]) -> bool {
+  memchr(b'\r', source).is_some()
+    || memmem::find(source, b"\\\n").is_some()
+    || !for_each_special(source, 0, |_, _, _| false)
+}
+
+// parse-js only treats `\n` as a line terminator and ASCII whitespace as whitespace, but ECMAScript also has `\r` (on its own or before `\n`), U+2028, and U+2029 as line terminators, which end single-line comments, allow automatic semicolon insertion, and separate tokens, and Unicode spaces (e.g. U+00A0) as whitespace. This rewrites them to `\n` and spaces of the same length. `\r\n` is left as is, as the `\n` already ends the line.
+//
+// A lone `\r` can always be rewritten: it's not allowed in strings, and templates read it as `\n` anyway. A `\` before `\r\n` continues the line in strings and templates, but parse-js only skips the `\r`, so it's rewritten to `\` `\n` `\r`, which parse-js lexes the same way, as `\r` is whitespace to it; a lone `\r` can't otherwise be left, so it's dropped from the values of those literals after parsing (see `decode_normalized`). Regexes can't have line terminators at all, even after a `\`, so they're rewritten to `\n` without the `\`, which parse-js rejects. Other characters are only rewritten outside strings, templates, and regexes, where they're part of the value, so the code is lexed to find them. Each line terminator rewritten can end a comment and change how the rest is lexed, so lexing resumes from the token before it.
+pub(crate) fn normalize_whitespace(code: &mut [u8]) {
+  let mut i = 0;
+  while i < code.len() {
+    if code[i] == b'\r' {
+      if code.get(i + 1) != Some(&b'\n') {
+        code[i] = b'\n';
+      } else if i > 0 && code[i - 1] == b'\\' {
+        code[i..i + 2].copy_from_slice(b"\n\r");
+        i += 1;
+      };
+    };
+    i += 1;
+  }
+  let mut scanner = Scanner::new();
+  while let Some((pos, len, kind)) = find_special(&mut scanner, code) {
+    code[pos..pos + len].fill(b' ');
+    if kind == Special::LineTerminator {
+      code[pos] = b'\n';
+    };
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::normalize_whitespace;
+
+  #[test]
+  fn test_normalize_whitespace() {
+    let normalize = |source: &str| {
+      let mut code = source.as_bytes().to_vec();
+      normalize_whitespace(&mut code);
+      String::from_utf8(code).unwrap()
+    };
+    assert_eq!(normalize("a\r\nb"), "a\r\nb");
+    assert_eq!(normalize("a\rb // c\rd"), "a\nb // c\nd");
+    assert_eq!(normalize("`a\rb`"), "`a\nb`");
+    assert_eq!(normalize("a\u{2028}b // c\u{2029}d"), "a\n  b // c\n  d");
+    assert_eq!(normalize("a\u{a0}+\u{feff}b"), "a  +   b");
+    assert_eq!(normalize("é\u{3000}ü"), "é   ü");
+    // They're part of strings and templates.
+    assert_eq!(normalize("'\u{2028}\u{a0}'"), "'\u{2028}\u{a0}'");
+    assert_eq!(normalize("`${a}\u{2028}`\u{2029}"), "`${a}\u{2028}`\n  ");
+    // Except for regexes, which are rewritten so that parse-js rejects them.
+    assert_eq!(normalize("/\u{2028}/"), "/\n  /");
+    assert_eq!(normalize("/a\\\r/ /b\\\r\n/"), "/a \n/ /b \n\r/");
+    // A `\` before `\r\n` continues the line.
+    assert_eq!(normalize("'a\\\r\nb'"), "'a\\\n\rb'");
+    // A line terminator ends a comment, so what follows is code.
+    assert_eq!(
+      normalize("// a\u{2028}'\u{2029}'"),
+      "// a\n  '\u{2029}'"
+    );
+  }
+}
@@ -0,0 +1,170 @@
+use crate::cst::syntax_kind;
+use parse_js::ast::NodeData;
+use parse_js::source::SourceRange;
+use parse_js::visit::JourneyControls;
+use parse_js::visit::Visitor;
+
+pub(crate) fn is_from_source(source: &[u8], loc: SourceRange) -> bool {
+  loc.end() <= source.len() && loc.as_slice().as_ptr() == source[loc.start()..].as_ptr()
+}
+
+pub(crate) struct Extent<'a> {
+  pub loc: SourceRange<'a>,
+  // Name of the node's syntax variant.
+  pub kind: &'static str,
+  pub children: Vec<usize>,
+  // Some nodes' ranges don't cover all of their children (e.g. an import statement's range is only the `import` keyword, and a catch block's range doesn't include its parameter), so we use the range covering the node and all its descendants.
+  pub start: usize,
+  pub end: usize,
+}
+
+pub(crate) struct RangeTree<'a> {
+  source: &'a [u8],
+  // The first node whose range isn't from `source`, such as one created by a transform.
+  pub foreign: Option<SourceRange<'a>>,
+  // Extent of each node, in pre-order. The first node is the root.
+  pub nodes: Vec<Extent<'a>>,
+  // Ancestors of the node currently being visited.
+  stack: Vec<usize>,
+}
+
+impl<'a> RangeTree<'a> {
+  pub fn new(source: &'a [u8]) -> RangeTree<'a> {
+    RangeTree {
+      source,
+      foreign: None,
+      nodes: Vec::new(),
+      stack: Vec::new(),
+    }
+  }
+}
+
+impl<'a> Visitor<'a> for RangeTree<'a> {
+  fn on_syntax_down(&mut self, node: &mut NodeData<'a>, _ctl: &mut JourneyControls) {
+    if self.foreign.is_none() && !is_from_source(self.source, node.loc) {
+      self.foreign = Some(node.loc);
+    };
+    let id = self.nodes.len();
+    self.nodes.push(Extent {
+      loc: node.loc,
+      kind: syntax_kind(&node.stx),
+      children: Vec::new(),
+      start: node.loc.start(),
+      end: node.loc.end(),
+    });
+    if let Some(&parent) = self.stack.last() {
+      self.nodes[parent].children.push(id);
+    };
+    self.stack.push(id);
+  }
+
+  fn on_syntax_up(&mut self, _node: &mut NodeData<'a>) {
+    let id = self.stack.pop().unwrap();
+    if let Some(&parent) = self.stack.last() {
+      let (start, end) = (self.nodes[id].start, self.nodes[id].end);
+      let parent = &mut self.nodes[parent];
+      parent.start = parent.start.min(start);
+      parent.end = parent.end.max(end);
+    };
+  }
+}
+
+// Reconstructs the source from a tree using only the source range of each node: the text of a node not covered by its children (keywords, punctuation, whitespace, comments, etc.) is copied from the source, and the text of each child is reconstructed recursively.
+// This only succeeds if nodes don't overlap their siblings, which is a useful check of the parser. Transforms that change a node's syntax without changing its range aren't reflected in the output.
+struct LosslessPrinter<'a, 'o> {
+  source: &'a [u8],
+  extents: Vec<Extent<'a>>,
+  out: &'o mut Vec<u8>,
+  // Position in `source` up to which we've emitted.
+  cursor: usize,
+}
+
+impl<'a, 'o> LosslessPrinter<'a, 'o> {
+  fn emit_source_until(&mut self, end: usize) {
+    if end > self.cursor {
+      self.out.extend_from_slice(&self.source[self.cursor..end]);
+      self.cursor = end;
+    };
+  }
+
+  fn print(&mut self, id: usize) -> Result<(), SourceRange<'a>> {
+    let Extent {
+      loc,
+      start,
+      end,
+      ref mut children,
+      ..
+    } = self.extents[id];
+    let mut children = core::mem::take(children);
+    if start < self.cursor {
+      return Err(loc);
+    };
+    self.emit_source_until(start);
+    // The visitor visits children in evaluation order (e.g. the iterated value of a `for-of` loop before its declaration), so sort them into source order.
+    children.sort_by_key(|&c| self.extents[c].start);
+    for c in children {
+      self.print(c)?;
+    }
+    self.emit_source_until(end);
+    Ok(())
+  }
+}
+
+/// Reproduces the exact source code of a parsed tree, including whitespace and comments, by walking the tree and copying the source text of each node. Useful for validating that every node's source range is consistent with the tree.
+///
+/// Returns the source range of the first node that isn't from `source` or that overlaps its previous sibling. Nodes created or moved by transforms will generally fail this check.
+///
+/// # Arguments
+///
+/// * `source` - The source code that was parsed.
+/// * `node` - The root node of the tree parsed from `source`.
+/// * `output` - Destination to write the reproduced source code.
+pub fn print_lossless<'a>(
+  source: &'a [u8],
+  node: &mut NodeData<'a>,
+  output: &mut Vec<u8>,
+) -> Result<(), SourceRange<'a>> {
+  let mut tree = RangeTree::new(source);
+  tree.visit(node);
+  if let Some(loc) = tree.foreign {
+    return Err(loc);
+  };
+  let mut printer = LosslessPrinter {
+    source,
+    extents: tree.nodes,
+    out: output,
+    cursor: 0,
+  };
+  printer.print(0)?;
+  printer.emit_source_until(source.len());
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::print_lossless;
+  use crate::Session;
+  use crate::TopLevelMode;
+  use parse_js::parse;
+
+  fn check(source: &str) {
+    let session = Session::new();
+    let node = parse(&session, source.as_bytes(), TopLevelMode::Module).unwrap();
+    let mut out = Vec::new();
+    print_lossless(source.as_bytes(), node, &mut out).unwrap();
+    assert_eq!(std::str::from_utf8(&out).unwrap(), source);
+  }
+
+  #[test]
+  fn test_print_lossless() {
+    check("  // leading\nlet a = 1 /* b */ + 2;\n\n");
+    check(
+      "import {x as y} from 'z';\nexport default function f(a, b = 2, ...c) { return a ? b : c }",
+    );
+    check("class A extends B { static #x = 1; get y() { return this.#x } [z](q) {} }");
+    check("for (const [a, {b, c: d}] of e) if (a) { continue } else label: while (1) break label;");
+    check("const o = { a, b: 1, [c]: 2, ...d, e() {}, async *f() { yield* g; await h } };");
+    check("x = `a${b}c${`d${e}`}`; y = a?.b?.[c]?.(d); z = new F(...g) ?? /re/g;");
+    check("try { throw a } catch ({ b }) {} finally { switch (c) { case 1: default: } }");
+  }
+}
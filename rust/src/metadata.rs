@@ -0,0 +1,419 @@
+use parse_js::ast::ExportNames;
+use parse_js::ast::NodeData;
+use parse_js::ast::Syntax;
+use parse_js::operator::OperatorName;
+use parse_js::symbol::Identifier;
+use parse_js::symbol::Scope;
+use parse_js::symbol::ScopeType;
+use parse_js::visit::JourneyControls;
+use parse_js::visit::Visitor;
+use std::collections::BTreeSet;
+
+/// A binding created by an import statement.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ImportSpecifier {
+  /// Name of the export being imported: `default` for a default import (`import a from "x"`), or `*` for a namespace import (`import * as a from "x"`).
+  pub imported: String,
+  /// Name of the local variable it's bound to.
+  pub local: String,
+}
+
+/// An import statement.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Import {
+  /// The module specifier, with escapes resolved.
+  pub module: String,
+  /// Bindings created by the import, in source order.
+  pub specifiers: Vec<ImportSpecifier>,
+}
+
+/// Information about a module's dependencies and interface, as returned by [module_metadata].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ModuleMetadata {
+  /// Names exported by the module, in source order, including `default`. Names re-exported with `export * from "x"` aren't known, so aren't included.
+  pub exports: Vec<String>,
+  /// Static import statements, in source order.
+  pub imports: Vec<Import>,
+  /// Modules re-exported from with `export ... from "x"`, in source order.
+  pub reexports: Vec<String>,
+  /// Variables that are referenced but never declared, sorted and without duplicates. These are usually globals provided by the environment, such as `window` or `process`.
+  pub globals: Vec<String>,
+  /// Whether the code references `require`, `module`, or `exports` without declaring them, which suggests it's a CommonJS module.
+  pub uses_commonjs: bool,
+  /// Modules loaded with `require("x")`, in source order. Only calls with a single string literal are included, as others can't be resolved without running the code.
+  pub requires: Vec<String>,
+  /// Names exported with CommonJS, in source order and without duplicates: properties assigned to `exports` or `module.exports`, such as `exports.a = 1`, or defined on them with `Object.defineProperty`.
+  pub commonjs_exports: Vec<String>,
+  /// Whether `module.exports` is assigned to, such as `module.exports = function () {}`, which replaces all the CommonJS exports.
+  pub assigns_module_exports: bool,
+}
+
+// Adds the names of all variables declared by a pattern.
+pub fn pattern_names<'a>(pattern: &NodeData<'a>, out: &mut Vec<String>) {
+  match &pattern.stx {
+    Syntax::IdentifierPattern { name } => out.push(name.as_str().to_string()),
+    Syntax::ArrayPattern { elements, rest } => {
+      for e in elements.iter().flatten() {
+        pattern_names(e.target, out);
+      }
+      if let Some(rest) = rest {
+        pattern_names(rest, out);
+      };
+    }
+    Syntax::ObjectPattern { properties, rest } => {
+      for p in properties.iter() {
+        if let Syntax::ObjectPatternProperty { target, .. } = &p.stx {
+          pattern_names(target, out);
+        };
+      }
+      if let Some(rest) = rest {
+        pattern_names(rest, out);
+      };
+    }
+    _ => {}
+  };
+}
+
+// Import and export aliases are always IdentifierPattern nodes.
+pub fn alias_name<'a>(alias: &NodeData<'a>) -> String {
+  match &alias.stx {
+    Syntax::IdentifierPattern { name } => name.as_str().to_string(),
+    _ => unreachable!(),
+  }
+}
+
+struct MetadataVisitor {
+  out: ModuleMetadata,
+  globals: BTreeSet<String>,
+  // Declarations at the top level of a global script. The parser doesn't declare block-scoped ones in the global scope, so references to them look like globals.
+  global_lexical_names: Vec<String>,
+}
+
+// Whether `node` is a reference to the undeclared variable `name`.
+pub fn is_undeclared<'a>(node: &NodeData<'a>, name: &str) -> bool {
+  match &node.stx {
+    Syntax::IdentifierExpr { name: n } => {
+      n.as_str() == name && node.scope.find_symbol(*n).is_none()
+    }
+    _ => false,
+  }
+}
+
+// Whether `node` is `exports` or `module.exports`, when they aren't declared.
+fn is_commonjs_exports<'a>(node: &NodeData<'a>) -> bool {
+  match &node.stx {
+    Syntax::MemberExpr {
+      optional_chaining: false,
+      left,
+      right,
+      ..
+    } => right.as_str() == "exports" && is_undeclared(left, "module"),
+    _ => is_undeclared(node, "exports"),
+  }
+}
+
+// The value of a string literal argument.
+pub fn string_arg<'a>(arg: &NodeData<'a>) -> Option<&'a str> {
+  match &arg.stx {
+    Syntax::CallArg {
+      spread: false,
+      value,
+    } => match &value.stx {
+      Syntax::LiteralStringExpr { value } => Some(value),
+      _ => None,
+    },
+    _ => None,
+  }
+}
+
+impl MetadataVisitor {
+  fn reference<'a>(&mut self, scope: Scope<'a>, name: Identifier<'a>) {
+    if scope.find_symbol(name).is_some() {
+      return;
+    };
+    let name = name.as_str();
+    // `arguments` is implicitly declared in every non-arrow function.
+    if name == "arguments"
+      && scope
+        .find_self_or_ancestor(|t| t == ScopeType::NonArrowFunction)
+        .is_some()
+    {
+      return;
+    };
+    if !self.globals.contains(name) {
+      self.globals.insert(name.to_string());
+    };
+  }
+
+  fn commonjs_export(&mut self, name: &str) {
+    if !self.out.commonjs_exports.iter().any(|n| n == name) {
+      self.out.commonjs_exports.push(name.to_string());
+    };
+  }
+}
+
+impl<'a> Visitor<'a> for MetadataVisitor {
+  fn on_syntax_down(&mut self, node: &mut NodeData<'a>, ctl: &mut JourneyControls) {
+    let is_global = node.scope.typ() == ScopeType::Global;
+    match &node.stx {
+      Syntax::IdentifierExpr { name } | Syntax::IdentifierPattern { name } => {
+        self.reference(node.scope, *name);
+      }
+      Syntax::ClassDecl {
+        export,
+        export_default,
+        name,
+        ..
+      }
+      | Syntax::FunctionDecl {
+        export,
+        export_default,
+        name,
+        ..
+      } => {
+        let name = name.as_ref().and_then(|n| match &n.stx {
+          Syntax::ClassOrFunctionName { name } => Some(name.as_str().to_string()),
+          _ => None,
+        });
+        if *export_default {
+          self.out.exports.push("default".to_string());
+        } else if *export {
+          self.out.exports.extend(name.clone());
+        };
+        if is_global && matches!(node.stx, Syntax::ClassDecl { .. }) {
+          self.global_lexical_names.extend(name);
+        };
+      }
+      Syntax::VarDecl {
+        export,
+        declarators,
+        ..
+      } => {
+        let mut names = Vec::new();
+        for d in declarators.iter() {
+          pattern_names(d.pattern, &mut names);
+        }
+        if *export {
+          self.out.exports.extend(names.iter().cloned());
+        };
+        if is_global {
+          self.global_lexical_names.extend(names);
+        };
+      }
+      Syntax::ExportDefaultExprStmt { .. } => {
+        self.out.exports.push("default".to_string());
+      }
+      Syntax::CallExpr {
+        callee, arguments, ..
+      } => {
+        if is_undeclared(callee, "require") {
+          if let [arg] = arguments.as_slice() {
+            self
+              .out
+              .requires
+              .extend(string_arg(arg).map(str::to_string));
+          };
+        } else if let Syntax::MemberExpr { left, right, .. } = &callee.stx {
+          // `Object.defineProperty(exports, "a", ...)`, which TypeScript and Babel output.
+          if right.as_str() == "defineProperty" && is_undeclared(left, "Object") {
+            if let [target, name, ..] = arguments.as_slice() {
+              if let (Syntax::CallArg { value, .. }, Some(name)) = (&target.stx, string_arg(name)) {
+                if is_commonjs_exports(value) {
+                  self.commonjs_export(name);
+                };
+              };
+            };
+          };
+        };
+      }
+      Syntax::BinaryExpr {
+        operator: OperatorName::Assignment,
+        left,
+        ..
+      } => match &left.stx {
+        Syntax::MemberExpr { left, right, .. } if is_commonjs_exports(left) => {
+          self.commonjs_export(right.as_str());
+        }
+        Syntax::ComputedMemberExpr { object, member, .. } if is_commonjs_exports(object) => {
+          if let Syntax::LiteralStringExpr { value } = &member.stx {
+            self.commonjs_export(value);
+          };
+        }
+        Syntax::MemberExpr { .. } if is_commonjs_exports(left) => {
+          self.out.assigns_module_exports = true;
+        }
+        _ => {}
+      },
+      Syntax::ExportListStmt { names, from } => {
+        match names {
+          ExportNames::All(alias) => {
+            self.out.exports.extend(alias.as_deref().map(alias_name));
+          }
+          ExportNames::Specific(names) => {
+            self
+              .out
+              .exports
+              .extend(names.iter().map(|n| alias_name(n.alias)));
+          }
+        };
+        if let Some(from) = from {
+          self.out.reexports.push(from.to_string());
+        };
+        // The exported names aren't variables, so don't visit them, but the local names are.
+        if let (ExportNames::Specific(names), None) = (names, from) {
+          for n in names.iter() {
+            self.reference(node.scope, n.target);
+          }
+        };
+        ctl.skip();
+      }
+      Syntax::ImportStmt {
+        default,
+        names,
+        module,
+      } => {
+        let mut specifiers = Vec::new();
+        if let Some(default) = default {
+          specifiers.push(ImportSpecifier {
+            imported: "default".to_string(),
+            local: alias_name(default),
+          });
+        };
+        match names {
+          Some(ExportNames::All(Some(alias))) => {
+            specifiers.push(ImportSpecifier {
+              imported: "*".to_string(),
+              local: alias_name(alias),
+            });
+          }
+          Some(ExportNames::Specific(names)) => {
+            for n in names.iter() {
+              specifiers.push(ImportSpecifier {
+                imported: n.target.as_str().to_string(),
+                local: alias_name(n.alias),
+              });
+            }
+          }
+          _ => {}
+        };
+        self.out.imports.push(Import {
+          module: module.to_string(),
+          specifiers,
+        });
+        ctl.skip();
+      }
+      _ => {}
+    };
+  }
+}
+
+/// Finds a module's exports, imports, and the globals it uses, so that bundlers and other tools don't have to search the code for them. `node` should be the top-level node from parsing the code with [parse_js::parse].
+///
+/// # Examples
+///
+/// ```
+/// use minify_js::{Session, TopLevelMode, module_metadata, parse_js};
+///
+/// let code: &[u8] = b"import React, { useState as s } from 'react'; export const a = s(window.x);";
+/// let session = Session::new();
+/// let parsed = parse_js::parse(&session, code, TopLevelMode::Module).unwrap();
+/// let metadata = module_metadata(parsed);
+/// assert_eq!(metadata.exports, ["a"]);
+/// assert_eq!(metadata.imports[0].module, "react");
+/// assert_eq!(metadata.imports[0].specifiers[1].imported, "useState");
+/// assert_eq!(metadata.imports[0].specifiers[1].local, "s");
+/// assert_eq!(metadata.globals, ["window"]);
+/// ```
+pub fn module_metadata<'a>(node: &mut NodeData<'a>) -> ModuleMetadata {
+  let mut visitor = MetadataVisitor {
+    out: ModuleMetadata::default(),
+    globals: BTreeSet::new(),
+    global_lexical_names: Vec::new(),
+  };
+  visitor.visit(node);
+  for name in visitor.global_lexical_names.iter() {
+    visitor.globals.remove(name);
+  }
+  let mut out = visitor.out;
+  out.uses_commonjs = ["require", "module", "exports"]
+    .iter()
+    .any(|&name| visitor.globals.contains(name));
+  out.globals = visitor.globals.into_iter().collect();
+  out
+}
+
+#[cfg(test)]
+mod tests {
+  use super::module_metadata;
+  use super::Import;
+  use super::ImportSpecifier;
+  use crate::Session;
+  use crate::TopLevelMode;
+  use parse_js::parse;
+
+  #[test]
+  fn test_module_metadata() {
+    let source = r#"
+      import def, * as ns from "a";
+      import { b, default as c } from "b";
+      export * from "d";
+      export { e as f } from "e";
+      export const [g, { h }] = ns.list;
+      export function i() { return arguments.length + document.title; }
+      export default class {}
+      let j = 1;
+      export { j as k, l };
+      console.log(def, b, c, j);
+    "#;
+    let session = Session::new();
+    let parsed = parse(&session, source.as_bytes(), TopLevelMode::Module).unwrap();
+    let metadata = module_metadata(parsed);
+    assert_eq!(metadata.exports, ["f", "g", "h", "i", "default", "k", "l"]);
+    let import = |module: &str, specifiers: &[(&str, &str)]| Import {
+      module: module.to_string(),
+      specifiers: specifiers
+        .iter()
+        .map(|(imported, local)| ImportSpecifier {
+          imported: imported.to_string(),
+          local: local.to_string(),
+        })
+        .collect(),
+    };
+    assert_eq!(
+      metadata.imports,
+      [
+        import("a", &[("default", "def"), ("*", "ns")]),
+        import("b", &[("b", "b"), ("default", "c")]),
+      ]
+    );
+    assert_eq!(metadata.reexports, ["d", "e"]);
+    assert_eq!(metadata.globals, ["console", "document", "l"]);
+    assert!(!metadata.uses_commonjs);
+
+    let source =
+      "let a = require('a'); module.exports = function () { return a(arguments) }; b = arguments;";
+    let session = Session::new();
+    let parsed = parse(&session, source.as_bytes(), TopLevelMode::Global).unwrap();
+    let metadata = module_metadata(parsed);
+    assert_eq!(metadata.globals, ["arguments", "b", "module", "require"]);
+    assert!(metadata.uses_commonjs);
+    assert_eq!(metadata.requires, ["a"]);
+    assert!(metadata.commonjs_exports.is_empty());
+    assert!(metadata.assigns_module_exports);
+
+    let source = r#"
+      Object.defineProperty(exports, "__esModule", { value: true });
+      exports.a = exports["b-c"] = void 0;
+      module.exports.d = require("d" + 1);
+      exports.a = require(`e`);
+      function f(exports, require) { exports.g = require("g"); }
+    "#;
+    let session = Session::new();
+    let parsed = parse(&session, source.as_bytes(), TopLevelMode::Global).unwrap();
+    let metadata = module_metadata(parsed);
+    assert!(metadata.uses_commonjs);
+    assert!(metadata.requires.is_empty());
+    assert_eq!(metadata.commonjs_exports, ["__esModule", "a", "b-c", "d"]);
+    assert!(!metadata.assigns_module_exports);
+  }
+}
@@ -0,0 +1,109 @@
+use crate::minify;
+use crate::minify_str;
+use crate::minify_to_writer_with_buf;
+use crate::MinifyError;
+use crate::MinifyOptions;
+use crate::MinifyStats;
+use crate::WriteError;
+use parse_js::session::Session;
+use std::alloc::Layout;
+use std::io::Write;
+
+// Creates a session whose arena already has at least `capacity` bytes, which are kept across resets.
+fn warm_session(capacity: usize) -> Session {
+  let mut session = Session::new();
+  if capacity > 0 {
+    session
+      .get_allocator()
+      .alloc_layout(Layout::from_size_align(capacity, 1).unwrap());
+  };
+  session.reset();
+  session
+}
+
+/// Minifies many sources with the same options, reusing memory across calls. This is faster than calling [`minify`] with a new [`Session`] each time when minifying many (especially small) sources, such as in a bundler.
+///
+/// Each call frees everything allocated by the previous call, but keeps the memory for reuse, so the borrow checker won't allow a call while an error from a previous call is still in use. The memory kept is about as much as the largest source needed, so after the first few calls in a watch mode or server, tokens and nodes are allocated without calling the system allocator. Use [Minifier::with_capacity] to start warm, and [Minifier::shrink_to] to give memory back after an unusually large source.
+///
+/// # Examples
+///
+/// ```
+/// use minify_js::{Minifier, MinifyOptions, TopLevelMode};
+///
+/// let mut minifier = Minifier::new(MinifyOptions::new().top_level_mode(TopLevelMode::Module));
+/// let mut out = Vec::new();
+/// for code in [b"let first = 1; f(first)", b"let other = 2; g(other)"] {
+///   out.clear();
+///   minifier.minify(code, &mut out).unwrap();
+/// }
+/// assert_eq!(out.as_slice(), b"let a=2;g(a)");
+/// ```
+pub struct Minifier {
+  session: Session,
+  options: MinifyOptions,
+  // Output buffer for `minify_to_writer`.
+  buf: Vec<u8>,
+}
+
+impl Minifier {
+  pub fn new(options: MinifyOptions) -> Minifier {
+    Minifier {
+      session: Session::new(),
+      options,
+      buf: Vec::new(),
+    }
+  }
+
+  /// Creates a minifier that already has `capacity` bytes of memory for parsing and minifying, and for the output buffer of [Minifier::minify_to_writer], so the first calls don't start cold. Code usually needs several times its length in memory, which [Minifier::capacity] can measure.
+  pub fn with_capacity(options: MinifyOptions, capacity: usize) -> Minifier {
+    Minifier {
+      session: warm_session(capacity),
+      options,
+      buf: Vec::with_capacity(capacity),
+    }
+  }
+
+  /// Number of bytes of memory kept for parsing and minifying the next source.
+  pub fn capacity(&self) -> usize {
+    self.session.get_allocator().allocated_bytes()
+  }
+
+  /// Frees memory kept for reuse, down to about `capacity` bytes, such as after minifying an unusually large source.
+  pub fn shrink_to(&mut self, capacity: usize) {
+    if self.capacity() > capacity {
+      self.session = warm_session(capacity);
+    };
+    self.buf.clear();
+    self.buf.shrink_to(capacity);
+  }
+
+  pub fn options(&self) -> &MinifyOptions {
+    &self.options
+  }
+
+  /// Minifies `source` like [`minify`], appending the output to `output`.
+  pub fn minify<'a>(
+    &'a mut self,
+    source: &'a [u8],
+    output: &mut Vec<u8>,
+  ) -> Result<MinifyStats, MinifyError<'a>> {
+    self.session.reset();
+    minify(&self.session, source, &self.options, output)
+  }
+
+  /// Minifies `source` like [`minify_str`](crate::minify_str).
+  pub fn minify_str<'a>(&'a mut self, source: &'a str) -> Result<String, MinifyError<'a>> {
+    self.session.reset();
+    minify_str(&self.session, source, &self.options)
+  }
+
+  /// Minifies `source` like [`minify_to_writer`](crate::minify_to_writer).
+  pub fn minify_to_writer<'a>(
+    &'a mut self,
+    source: &'a [u8],
+    output: &mut impl Write,
+  ) -> Result<MinifyStats, WriteError<'a>> {
+    self.session.reset();
+    minify_to_writer_with_buf(&self.session, source, &self.options, &mut self.buf, output)
+  }
+}
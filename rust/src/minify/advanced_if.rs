@@ -85,6 +85,7 @@ pub struct ProcessedIfBranch<'a> {
 fn process_if_branch_block<'a, 'b>(
   session: &'a Session,
   scope: Scope<'a>,
+  branch_loc: SourceRange<'a>,
   body: &'b mut [Node<'a>],
 ) -> ProcessedIfBranch<'a> {
   let mut returns = false;
@@ -157,7 +158,7 @@ fn process_if_branch_block<'a, 'b>(
           },
         ));
 
-        let mut remaining = process_if_branch_block(session, scope, &mut body[i + 1..]);
+        let mut remaining = process_if_branch_block(session, scope, loc, &mut body[i + 1..]);
         assert!(remaining.returns);
         hoisted_vars.append(&mut remaining.hoisted_vars);
         let alternate = remaining.expression;
@@ -185,7 +186,12 @@ fn process_if_branch_block<'a, 'b>(
           right,
         })
       })
-      .unwrap(),
+      // The branch is empty (e.g. `if (x) {}`).
+      .unwrap_or_else(|| {
+        new_node(session, scope, branch_loc, Syntax::IdentifierExpr {
+          name: SourceRange::from_slice(b"undefined"),
+        })
+      }),
     hoisted_vars,
     returns,
   }
@@ -196,9 +202,10 @@ pub fn process_if_branch<'a, 'b>(
   scope: Scope<'a>,
   branch: &'b mut NodeData<'a>,
 ) -> ProcessedIfBranch<'a> {
+  let loc = branch.loc;
   let Syntax::BlockStmt { body } = &mut branch.stx else {
     // We should have already normalised all `if` branches into a block if they were single statements, so this should not be possible.
     unreachable!()
   };
-  process_if_branch_block(session, scope, body)
+  process_if_branch_block(session, scope, loc, body)
 }
@@ -1,3 +1,5 @@
+use super::intern::Atom;
+use super::intern::Interner;
 use super::lexical_lifetimes::LexicalLifetime;
 use parse_js::ast::Node;
 use parse_js::session::Session;
@@ -20,6 +22,8 @@ pub struct MinifySymbol<'a> {
   pub is_used_as_constructor: bool,
   // Similar to `is_used_as_constructor`, although a weaker signal, since the presence of `prototype` is highly likely to mean it's a constructor function, but not as certain as `new`.
   pub has_prototype: bool,
+  // Whether to keep the original name, such as if it's the name of a function or class whose `name` property could be read.
+  pub keep_name: bool,
   pub lexical_lifetime_start: LexicalLifetime<'a>,
   pub lexical_lifetime_end: LexicalLifetime<'a>,
 }
@@ -32,6 +36,7 @@ impl<'a> MinifySymbol<'a> {
       has_usage: false,
       is_used_as_constructor: false,
       has_prototype: false,
+      keep_name: false,
       lexical_lifetime_start: LexicalLifetime::new_infinite(session),
       lexical_lifetime_end: LexicalLifetime::new_zero(session),
     }
@@ -49,9 +54,9 @@ impl<'a> MinifySymbol<'a> {
 
 // Our additional state that's associated with each Scope.
 pub struct MinifyScope<'a> {
-  // Variables that are declared by an ancestor (not own) scope (or is not declared anywhere and assumed to be global), and used by code in own or any descendant scope.
-  pub inherited_vars: SessionHashSet<'a, Identifier<'a>>,
-  // Function declarations within this closure-like scope that must be hoisted to declarations at the very beginning of this closure's code (so we can transform them to `var` and still have them work correctly). There may be multiple closures with the same name, nested deep with many blocks and branches, which is why we use a map; the last visited (lexical) declaration wins. Note that this is only populated if this scope is a closure; function declarations don't hoist to blocks.
+  // Variables that are declared by an ancestor (not own) scope (or is not declared anywhere and assumed to be global), and used by code in own or any descendant scope. It's only used to build the set of names a minified name must avoid, so its iteration order doesn't affect the output.
+  pub inherited_vars: SessionHashSet<'a, Atom>,
+  // Function declarations within this closure-like scope that must be hoisted to declarations at the very beginning of this closure's code (so we can transform them to `var` and still have them work correctly). There may be multiple closures with the same name, nested deep with many blocks and branches, which is why we use a map; the last visited (lexical) declaration wins. As the map's iteration order isn't deterministic, Pass3 inserts them in source order. Note that this is only populated if this scope is a closure; function declarations don't hoist to blocks.
   // Since they could be deep and anywhere, we must take them and move them into this map; we can't just look at a BlockStmt's children as they may not always be there.
   pub hoisted_functions: SessionHashMap<'a, Identifier<'a>, Node<'a>>,
   // `var` declarations in this closure that need to be moved to allow for some optimisation.
@@ -72,11 +77,13 @@ pub struct Ctx<'a, 'b> {
   pub session: &'a Session,
   pub symbols: &'b mut SessionHashMap<'a, Symbol, MinifySymbol<'a>>,
   pub scopes: &'b mut SessionHashMap<'a, Scope<'a>, MinifyScope<'a>>,
+  pub interner: &'b mut Interner<'a>,
 }
 
 impl<'a, 'b> Ctx<'a, 'b> {
   // See [notes/Name minification.md] for the algorithm in more detail.
   pub fn track_variable_usage(&mut self, scope: Scope<'a>, name: Identifier<'a>) {
+    let atom = self.interner.intern(name);
     let mut cur = Some(scope);
     while let Some(scope) = cur {
       if let Some(sym) = scope.get_symbol(name) {
@@ -92,7 +99,7 @@ impl<'a, 'b> Ctx<'a, 'b> {
         .entry(scope)
         .or_insert_with(|| MinifyScope::new(self.session))
         .inherited_vars
-        .insert(name);
+        .insert(atom);
       cur = scope.parent();
     }
   }
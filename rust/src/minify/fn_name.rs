@@ -0,0 +1,64 @@
+use parse_js::ast::NodeData;
+use parse_js::ast::Syntax;
+use parse_js::operator::OperatorName;
+use parse_js::symbol::Symbol;
+
+// Whether `n` is a function or class without its own name, which takes the name of the variable it's assigned to (e.g. `f` in `let f = () => {}`).
+fn is_anonymous_fn(n: &NodeData<'_>) -> bool {
+  matches!(
+    n.stx,
+    Syntax::ArrowFunctionExpr { .. }
+      | Syntax::ClassExpr { name: None, .. }
+      | Syntax::FunctionExpr { name: None, .. }
+  )
+}
+
+// Finds the variables whose names can be observed through the `name` property of a function or class: the names of function and class declarations and named expressions, and variables assigned an anonymous function or class, which takes the variable's name. Renaming them changes `name`. Whether it's read can't be known, as functions and classes can be passed to other code (e.g. `g(f)` or `export`), so they're all kept.
+#[derive(Default)]
+pub struct FnNames {
+  symbols: Vec<Symbol>,
+}
+
+impl FnNames {
+  // Call for each node when visiting down.
+  pub fn visit(&mut self, n: &NodeData<'_>) {
+    match &n.stx {
+      Syntax::ClassOrFunctionName { name } => {
+        self.symbols.extend(n.scope.find_symbol(*name));
+      }
+      Syntax::VarDecl { declarators, .. } => {
+        for decl in declarators.iter() {
+          if let (Syntax::IdentifierPattern { name }, Some(init)) =
+            (&decl.pattern.stx, &decl.initializer)
+          {
+            if is_anonymous_fn(init) {
+              self.symbols.extend(decl.pattern.scope.find_symbol(*name));
+            };
+          };
+        }
+      }
+      Syntax::BinaryExpr {
+        operator:
+          OperatorName::Assignment
+          | OperatorName::AssignmentLogicalAnd
+          | OperatorName::AssignmentLogicalOr
+          | OperatorName::AssignmentNullishCoalescing,
+        left,
+        right,
+        ..
+      } => {
+        if let Syntax::IdentifierPattern { name } = &left.stx {
+          if is_anonymous_fn(right) {
+            self.symbols.extend(left.scope.find_symbol(*name));
+          };
+        };
+      }
+      _ => {}
+    };
+  }
+
+  // The variables whose names must be kept so that reading `name` gives the same result.
+  pub fn kept(&self) -> &[Symbol] {
+    &self.symbols
+  }
+}
@@ -0,0 +1,39 @@
+use parse_js::session::Session;
+use parse_js::session::SessionHashMap;
+use parse_js::session::SessionVec;
+use parse_js::symbol::Identifier;
+
+// An interned name, which is hashed and compared as a number instead of by its bytes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Atom(u32);
+
+// Interns variable names for one minification, so the sets of names built for every scope when renaming (e.g. `MinifyScope.inherited_vars`) hold `u32`s and don't rehash names. Scopes themselves are from parse-js and still look up symbols by name.
+pub struct Interner<'a> {
+  atoms: SessionHashMap<'a, Identifier<'a>, Atom>,
+  names: SessionVec<'a, Identifier<'a>>,
+}
+
+impl<'a> Interner<'a> {
+  pub fn new(session: &'a Session) -> Interner<'a> {
+    Interner {
+      atoms: session.new_hashmap(),
+      names: session.new_vec(),
+    }
+  }
+
+  pub fn intern(&mut self, name: Identifier<'a>) -> Atom {
+    *self.atoms.entry(name).or_insert_with(|| {
+      self.names.push(name);
+      Atom((self.names.len() - 1).try_into().unwrap())
+    })
+  }
+
+  // Returns the atom for a name if it's been interned, without interning it. A name that hasn't been interned can't be in any set of atoms.
+  pub fn get(&self, name: Identifier<'a>) -> Option<Atom> {
+    self.atoms.get(&name).copied()
+  }
+
+  pub fn name(&self, atom: Atom) -> Identifier<'a> {
+    self.names[atom.0 as usize]
+  }
+}
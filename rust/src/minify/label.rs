@@ -0,0 +1,33 @@
+use parse_js::source::SourceRange;
+
+// Tracks which labelled statements are the target of a `break` or `continue`, while visiting them. A statement whose label isn't targeted can have its label dropped, and then be merged or collapsed like any other statement; one that is targeted must keep its label and stay whole, as jumping out of it skips the rest of it.
+// Labels can't be referenced from nested functions, so they don't need to be handled specially.
+#[derive(Default)]
+pub struct LabelTargets<'a> {
+  // Labels of the statements being visited, innermost last, and whether they're targeted.
+  stack: Vec<(SourceRange<'a>, bool)>,
+}
+
+impl<'a> LabelTargets<'a> {
+  // Call when visiting down into a labelled statement.
+  pub fn enter(&mut self, label: SourceRange<'a>) {
+    self.stack.push((label, false));
+  }
+
+  // Call for each `break` or `continue` with a label.
+  pub fn jump(&mut self, label: SourceRange<'a>) {
+    if let Some((_, targeted)) = self
+      .stack
+      .iter_mut()
+      .rev()
+      .find(|(l, _)| l.as_slice() == label.as_slice())
+    {
+      *targeted = true;
+    };
+  }
+
+  // Call when visiting up from a labelled statement. Returns whether its label is targeted.
+  pub fn exit(&mut self) -> bool {
+    self.stack.pop().unwrap().1
+  }
+}
@@ -1,18 +1,34 @@
 pub mod advanced_if;
 pub mod ctx;
+pub mod fn_name;
+pub mod intern;
+pub mod label;
 pub mod lexical_lifetimes;
 pub mod name;
 pub mod pass1;
 pub mod pass2;
 pub mod pass3;
+pub mod purity;
 
 use self::ctx::Ctx;
 use self::ctx::MinifyScope;
 use self::ctx::MinifySymbol;
+use self::fn_name::FnNames;
+use self::intern::Interner;
+use self::label::LabelTargets;
+use self::name::collect_rename_map;
 use self::name::minify_names;
 use self::pass1::Pass1;
 use self::pass2::Pass2;
 use self::pass3::Pass3;
+use self::purity::Purity;
+use crate::cfg::MinifyOptions;
+use crate::cfg::Target;
+use crate::error::MinifyError;
+use crate::report::phase;
+use crate::report::MinifyReport;
+use crate::stats::MinifyStats;
+use crate::trace::trace_counters;
 use parse_js::ast::new_node;
 use parse_js::ast::ExportName;
 use parse_js::ast::ExportNames;
@@ -23,51 +39,134 @@ use parse_js::symbol::Scope;
 use parse_js::symbol::Symbol;
 use parse_js::visit::Visitor;
 
-pub fn minify_js<'a>(session: &'a Session, top_level_node: &mut NodeData<'a>) -> () {
+// What to keep as is at the top level, for when `top_level_node` isn't all of the code.
+#[derive(Clone, Copy, Default)]
+pub struct KeepTopLevel {
+  // Function declarations in the top-level scope aren't turned into `var` declarations, as they must stay hoisted above code that isn't part of `top_level_node` (see `minify_bounded`).
+  pub functions: bool,
+  // Top-level statements without side effects aren't dropped, as they stand for something other than a statement (see `minify_expression`).
+  pub statements: bool,
+  // The top level follows code that isn't part of `top_level_node` (see `minify_bounded`), so strings at its start aren't directives unless all code before them is too.
+  pub after_code: bool,
+  // No statements without side effects are dropped, even in functions, so tests of other transforms can use them.
+  pub pure_statements: bool,
+}
+
+// Returns stats about renaming; the input and output lengths are left as zero. Returns an error if cancelled between passes. Each pass is added to `report` if it's enabled.
+pub fn minify_js<'a>(
+  session: &'a Session,
+  top_level_node: &mut NodeData<'a>,
+  cfg: &MinifyOptions,
+  report: &mut Option<MinifyReport>,
+  keep: KeepTopLevel,
+) -> Result<MinifyStats, MinifyError<'a>> {
   let top_level_scope = top_level_node.scope;
 
+  // These hash maps are only looked up by key, never iterated, as their iteration order would make the output nondeterministic.
   // Our custom data/state associated with a Symbol.
   let mut symbols = session.new_hashmap::<Symbol, MinifySymbol>();
   // Our custom data/state associated with a Scope.
   let mut scopes = session.new_hashmap::<Scope<'a>, MinifyScope<'a>>();
+  // Names of variables, interned for the renaming passes.
+  let mut interner = Interner::new(session);
   // Exports: what they refer to and what they're named.
   let mut export_bindings = Vec::new();
 
-  Pass1 {
-    ctx: Ctx {
-      scopes: &mut scopes,
-      session,
-      symbols: &mut symbols,
-    },
-  }
-  .visit(top_level_node);
+  phase!(*report, session, "pass1", {
+    let mut pass1 = Pass1 {
+      ctx: Ctx {
+        scopes: &mut scopes,
+        session,
+        symbols: &mut symbols,
+        interner: &mut interner,
+      },
+      purity: Purity::new(
+        top_level_node.loc,
+        cfg.assume_plain_objects,
+        cfg.environment,
+      ),
+      labels: LabelTargets::default(),
+      fn_names: FnNames::default(),
+      keep_top_level_stmts: keep.statements,
+      keep_pure_stmts: keep.pure_statements,
+      top_level_after_code: keep.after_code,
+    };
+    pass1.visit(top_level_node);
+    let fn_names = pass1.fn_names;
+    if cfg.keep_fn_names {
+      for &sym in fn_names.kept() {
+        symbols
+          .entry(sym)
+          .or_insert_with(|| MinifySymbol::new(session))
+          .keep_name = true;
+      }
+    };
+  });
+  cfg.check_cancelled()?;
 
-  Pass2 {
-    ctx: Ctx {
-      scopes: &mut scopes,
-      session,
-      symbols: &mut symbols,
-    },
-  }
-  .visit(top_level_node);
+  phase!(*report, session, "pass2", {
+    Pass2 {
+      ctx: Ctx {
+        scopes: &mut scopes,
+        session,
+        symbols: &mut symbols,
+        interner: &mut interner,
+      },
+    }
+    .visit(top_level_node);
+  });
+  cfg.check_cancelled()?;
 
-  minify_names(session, top_level_scope, &mut scopes, &mut symbols);
+  // When downlevelling, `let` and `const` declarations will become `var` declarations, so they must not shadow any other variable in the same closure.
+  let unique_in_closure = cfg!(feature = "downlevel") && cfg.target == Target::Es5;
+  phase!(*report, session, "minify_names", {
+    minify_names(
+      &mut Ctx {
+        scopes: &mut scopes,
+        session,
+        symbols: &mut symbols,
+        interner: &mut interner,
+      },
+      top_level_scope,
+      unique_in_closure,
+      cfg,
+      top_level_node.loc.as_slice(),
+    );
+  });
+  let renames = cfg
+    .rename_map
+    .then(|| collect_rename_map(top_level_scope, &symbols));
+  cfg.check_cancelled()?;
 
-  Pass3 {
+  let mut pass3 = Pass3 {
     session,
+    target: cfg.target,
     export_bindings: &mut export_bindings,
     symbols: &mut symbols,
     scopes: &mut scopes,
-  }
-  .visit(top_level_node);
+    keep_top_level_functions: keep.functions,
+    renaming_savings: 0,
+  };
+  phase!(*report, session, "pass3", {
+    pass3.visit(top_level_node);
+  });
+  let renaming_savings = pass3.renaming_savings;
+  trace_counters!(
+    scopes = scopes.len(),
+    symbols = symbols.len(),
+    renaming_savings,
+    "minified"
+  );
 
   let mut export_names = session.new_vec();
   for e in export_bindings.iter() {
-    let target_symbol = top_level_scope
-      .find_symbol(e.target)
-      .expect(format!("failed to find top-level export `{:?}`", e.target).as_str());
+    // Exporting an undeclared variable is an error when the module is linked, and block-scoped declarations in a global script aren't declared in the global scope, so there may be no symbol. Their names aren't minified, so leave them as is.
+    let target = match top_level_scope.find_symbol(e.target) {
+      Some(sym) => symbols[&sym].minified_name.unwrap(),
+      None => e.target,
+    };
     export_names.push(ExportName {
-      target: symbols[&target_symbol].minified_name.unwrap(),
+      target,
       alias: new_node(
         session,
         top_level_scope,
@@ -94,4 +193,10 @@ pub fn minify_js<'a>(session: &'a Session, top_level_node: &mut NodeData<'a>) ->
       _ => unreachable!(),
     }
   }
+
+  Ok(MinifyStats {
+    renaming_savings,
+    renames,
+    ..MinifyStats::default()
+  })
 }
@@ -1,8 +1,16 @@
+use super::ctx::Ctx;
 use super::ctx::MinifyScope;
 use super::ctx::MinifySymbol;
-use parse_js::char::ID_CONTINUE_CHARSTR;
-use parse_js::char::ID_START_CHARSTR;
-use parse_js::lex::KEYWORD_STRS;
+use super::intern::Atom;
+use super::intern::Interner;
+use crate::cfg::MinifyOptions;
+use crate::cfg::RenameDecision;
+use crate::cfg::RenameFilter;
+use crate::cfg::SymbolInfo;
+use crate::obfuscate::Rng;
+use crate::rename_map::Rename;
+use crate::rename_map::RenameMap;
+use crate::rename_map::RenameScope;
 use parse_js::session::Session;
 use parse_js::session::SessionHashMap;
 use parse_js::session::SessionHashSet;
@@ -10,19 +18,92 @@ use parse_js::session::SessionVec;
 use parse_js::source::SourceRange;
 use parse_js::symbol::Identifier;
 use parse_js::symbol::Scope;
+use parse_js::symbol::ScopeType;
 use parse_js::symbol::Symbol;
+use regex::Regex;
+
+// Keywords, and literals like `null`, that parse-js lexes as their own tokens, so they can't be used as variable names. Also words that are reserved or can't be declared in strict mode, which only come up with long names or a custom alphabet.
+fn is_keyword(name: &[u8]) -> bool {
+  matches!(
+    name,
+    b"arguments"
+      | b"as"
+      | b"async"
+      | b"await"
+      | b"break"
+      | b"case"
+      | b"catch"
+      | b"class"
+      | b"const"
+      | b"constructor"
+      | b"continue"
+      | b"debugger"
+      | b"default"
+      | b"delete"
+      | b"do"
+      | b"else"
+      | b"enum"
+      | b"eval"
+      | b"export"
+      | b"extends"
+      | b"false"
+      | b"finally"
+      | b"for"
+      | b"from"
+      | b"function"
+      | b"get"
+      | b"if"
+      | b"implements"
+      | b"import"
+      | b"in"
+      | b"instanceof"
+      | b"interface"
+      | b"let"
+      | b"new"
+      | b"null"
+      | b"of"
+      | b"package"
+      | b"private"
+      | b"protected"
+      | b"public"
+      | b"return"
+      | b"set"
+      | b"static"
+      | b"super"
+      | b"switch"
+      | b"this"
+      | b"throw"
+      | b"true"
+      | b"try"
+      | b"typeof"
+      | b"var"
+      | b"void"
+      | b"while"
+      | b"with"
+      | b"yield"
+  )
+}
 
 // Generator of minified names. Works by generating the next smallest possible name (starting from `a`), and then repeats until it finds one that is not a keyword or would conflict with an inherited variable (a variable that is in scope **and** used by code that we would otherwise shadow).
 pub struct MinifiedNameGenerator<'a> {
   session: &'a Session,
+  // Characters that can start a name, and all characters, from the `NameAlphabet`.
+  start_chars: &'a [u8],
+  chars: &'a [u8],
   // Index of each character of the last generated name, in reverse order (i.e. first character is last element) for optimised extension.
   state: SessionVec<'a, usize>,
 }
 
 impl<'a> MinifiedNameGenerator<'a> {
-  pub fn new(session: &'a Session) -> MinifiedNameGenerator<'a> {
+  pub fn new(
+    session: &'a Session,
+    start_chars: &'a [u8],
+    chars: &'a [u8],
+  ) -> MinifiedNameGenerator<'a> {
     MinifiedNameGenerator {
       session,
+      start_chars,
+      chars,
       state: session.new_vec(),
     }
   }
@@ -32,9 +113,9 @@ impl<'a> MinifiedNameGenerator<'a> {
     let mut overflow = true;
     for i in 0..n.len() {
       let charset = if i == n.len() - 1 {
-        ID_START_CHARSTR
+        self.start_chars
       } else {
-        ID_CONTINUE_CHARSTR
+        self.chars
       };
       if n[i] == charset.len() - 1 {
         n[i] = 0;
@@ -51,9 +132,9 @@ impl<'a> MinifiedNameGenerator<'a> {
     let mut name = self.session.new_vec(); // TODO Capacity
     for (i, idx) in n.iter().enumerate() {
       let charset = if i == n.len() - 1 {
-        ID_START_CHARSTR
+        self.start_chars
       } else {
-        ID_CONTINUE_CHARSTR
+        self.chars
       };
       name.push(charset[*idx]);
     }
@@ -61,14 +142,22 @@ impl<'a> MinifiedNameGenerator<'a> {
     name
   }
 
+  // Skips the next `n` possible names, which may be keywords or unavailable anyway.
+  pub fn skip(&mut self, n: u64) {
+    for _ in 0..n {
+      self.transition_to_next_possible_minified_name();
+    }
+  }
+
   // TODO This needs optimisation, in case inherited_vars has a long sequence of used minified names (likely).
   pub fn generate_next_available_minified_name(
     &mut self,
-    inherited_vars: &SessionHashSet<Identifier<'a>>,
+    inherited_vars: &SessionHashSet<Atom>,
+    interner: &Interner<'a>,
   ) -> Identifier<'a> {
     loop {
       let name = self.transition_to_next_possible_minified_name();
-      if KEYWORD_STRS.contains_key(name.as_slice()) {
+      if is_keyword(name.as_slice()) {
         continue;
       };
       let name = self
@@ -76,7 +165,10 @@ impl<'a> MinifiedNameGenerator<'a> {
         .get_allocator()
         .alloc_slice_copy(name.as_slice());
       let as_ident = SourceRange::new(name, 0, name.len());
-      if inherited_vars.contains(&as_ident) {
+      if interner
+        .get(as_ident)
+        .is_some_and(|atom| inherited_vars.contains(&atom))
+      {
         continue;
       };
       return as_ident;
@@ -87,20 +179,150 @@ impl<'a> MinifiedNameGenerator<'a> {
 // This should be run after Pass2 and before Pass3 visitor runs.
 // The Pass1 pass collects all usages of variables to determine inherited variables for each scope, so we can know what minified names can be safely used (see `MinifiedNameGenerator`). This function will then go through each declaration in each scope and generate and update their corresponding `MinifySymbol.minified_name`.
 // Some pecularities to note: globals aren't minified (whether declared or not), so when blacklisting minified names, they are directly disallowed. However, all other variables will be minified, so we need to blacklist their minified name, not their original name. This is why this function processes scopes top-down (from the root), as we need to know the minified names of ancestor variables first before we can blacklist them.
+// If `unique_in_closure` is true, variables in block scopes will not share a minified name with any other variable in the same closure (including other block scopes), so that they can later be safely changed from `let`/`const` to `var`.
+// Variables with `keep_name` set or a name matching `cfg.keep_names` keep their original names (as do all variables if `cfg.mangle` is off), and if `cfg.rename_filter` is provided, it's asked for each other variable's name first. These names are pinned: no generated name anywhere will be the same, so they can't be shadowed by or shadow a generated name.
+// Generated names use the characters of `cfg.name_alphabet`, ordered for `source` if needed. If `Obfuscation::shuffle_names` is set, a random number of names is skipped before each generated name.
 pub fn minify_names<'a>(
-  session: &'a Session,
+  ctx: &mut Ctx<'a, '_>,
+  scope: Scope<'a>,
+  unique_in_closure: bool,
+  cfg: &MinifyOptions,
+  source: &[u8],
+) {
+  let (start_chars, chars) = cfg.name_alphabet.chars_for(source);
+  let alloc = ctx.session.get_allocator();
+  let mut rules = NamingRules {
+    start_chars: alloc.alloc_slice_copy(&start_chars),
+    chars: alloc.alloc_slice_copy(&chars),
+    unique_in_closure,
+    pinned: ctx.session.new_hashmap(),
+    reserved: ctx.session.new_hashset(),
+    shuffle: cfg
+      .obfuscation
+      .shuffle_names
+      .then(|| Rng::new(cfg.obfuscation.seed)),
+  };
+  collect_pinned_names(
+    ctx,
+    scope,
+    cfg.mangle,
+    cfg.rename_filter.as_ref(),
+    &cfg.keep_names,
+    &mut rules,
+  );
+  for global in cfg.environment.globals() {
+    let name = SourceRange::new(global.as_bytes(), 0, global.len());
+    rules.reserved.insert(ctx.interner.intern(name));
+  }
+  let mut closure_names = ctx.session.new_hashset();
+  minify_scope_names(ctx, scope, &mut rules, scope.typ(), &mut closure_names);
+}
+
+struct NamingRules<'a> {
+  // Characters from the `NameAlphabet`.
+  start_chars: &'a [u8],
+  chars: &'a [u8],
+  unique_in_closure: bool,
+  // Names decided by a `RenameFilter`.
+  pinned: SessionHashMap<'a, Symbol, Identifier<'a>>,
+  // All values in `pinned`, and the globals of the environment, which generated names must avoid.
+  reserved: SessionHashSet<'a, Atom>,
+  // Decides how many names to skip before each generated name, when shuffling names.
+  shuffle: Option<Rng>,
+}
+
+impl<'a> NamingRules<'a> {
+  fn next_name(
+    &mut self,
+    generator: &mut MinifiedNameGenerator<'a>,
+    inherited_vars: &SessionHashSet<Atom>,
+    interner: &Interner<'a>,
+  ) -> Identifier<'a> {
+    if let Some(rng) = &mut self.shuffle {
+      generator.skip(rng.below(16));
+    };
+    generator.generate_next_available_minified_name(inherited_vars, interner)
+  }
+}
+
+fn collect_pinned_names<'a>(
+  ctx: &mut Ctx<'a, '_>,
+  scope: Scope<'a>,
+  mangle: bool,
+  filter: Option<&RenameFilter>,
+  keep_names: &[Regex],
+  rules: &mut NamingRules<'a>,
+) {
+  for &sym_name in scope.symbol_names().iter() {
+    let sym = scope.get_symbol(sym_name).unwrap();
+    let name = String::from_utf8_lossy(sym_name.as_slice());
+    let decision = if !mangle
+      || ctx.symbols.get(&sym).is_some_and(|s| s.keep_name)
+      || keep_names.iter().any(|p| p.is_match(&name))
+    {
+      RenameDecision::Keep
+    } else if let Some(filter) = filter {
+      (filter.0)(&SymbolInfo {
+        name: &name,
+        position: sym_name.start(),
+        scope: scope.typ(),
+      })
+    } else {
+      continue;
+    };
+    let name = match decision {
+      RenameDecision::Minify => continue,
+      RenameDecision::Keep => sym_name,
+      RenameDecision::Rename(name) => {
+        let name = ctx
+          .session
+          .get_allocator()
+          .alloc_slice_copy(name.as_bytes());
+        SourceRange::new(name, 0, name.len())
+      }
+    };
+    if !name.is_empty() && !is_keyword(name.as_slice()) {
+      rules.pinned.insert(sym, name);
+      rules.reserved.insert(ctx.interner.intern(name));
+    };
+  }
+  for &c in scope.children().iter() {
+    collect_pinned_names(ctx, c, mangle, filter, keep_names, rules);
+  }
+}
+
+fn minify_scope_names<'a>(
+  ctx: &mut Ctx<'a, '_>,
   scope: Scope<'a>,
-  minify_scopes: &mut SessionHashMap<'a, Scope<'a>, MinifyScope<'a>>,
-  minify_symbols: &mut SessionHashMap<'a, Symbol, MinifySymbol<'a>>,
+  rules: &mut NamingRules<'a>,
+  // Type of, and minified names used so far in, the nearest ancestor-or-self scope that isn't a block.
+  closure_typ: ScopeType,
+  closure_names: &mut SessionHashSet<'a, Atom>,
 ) {
+  let Ctx {
+    session,
+    symbols: minify_symbols,
+    scopes: minify_scopes,
+    interner,
+  } = ctx;
+  let session = *session;
+  let is_block = scope.typ() == ScopeType::Block;
+  let mut own_closure_names = session.new_hashset();
+  let (closure_typ, closure_names) = if is_block {
+    (closure_typ, closure_names)
+  } else {
+    (scope.typ(), &mut own_closure_names)
+  };
+  // Block-scoped variables at the top level of a global script will become global variables, so keep their names to avoid clobbering other globals.
+  let keep_names = rules.unique_in_closure && is_block && closure_typ == ScopeType::Global;
   // It's possible that the entry doesn't exist, if there were no inherited variables during the first pass.
   let minify_scope = minify_scopes
     .entry(scope)
     .or_insert_with(|| MinifyScope::new(session));
-  // Our `inherited_vars` contains original names; we need to retrieve their minified names.
+  // Our `inherited_vars` contains original names; we need to retrieve their minified names. These only go into a set, so the order they're visited in doesn't matter.
   let mut minified_inherited_vars = session.new_hashset();
   for &original_inherited_var in minify_scope.inherited_vars.iter() {
-    match scope.find_symbol(original_inherited_var) {
+    match scope.find_symbol(interner.name(original_inherited_var)) {
       None => {
         // Global (undeclared or declared).
         minified_inherited_vars.insert(original_inherited_var);
@@ -108,42 +330,123 @@ pub fn minify_names<'a>(
       Some(sym) => {
         let min_sym = minify_symbols.get(&sym).unwrap();
         let min_name = min_sym.minified_name.unwrap();
-        minified_inherited_vars.insert(min_name);
+        minified_inherited_vars.insert(interner.intern(min_name));
       }
     };
   }
-  // Yes, we start from the very beginning in case there are possible gaps/opportunities due to inherited variables on ancestors.
-  let mut next_min_name = MinifiedNameGenerator::new(session);
+  if rules.unique_in_closure && is_block {
+    minified_inherited_vars.extend(closure_names.iter().copied());
+  };
+  // Assign pinned names first. A pinned name can't be used if it would shadow a variable used in this scope or its descendants, or is taken by another variable in this scope (including as a JSX component that needs a capitalised name), so these variables are minified as usual instead.
+  let mut pinned_in_scope = session.new_hashset();
   for &sym_name in scope.symbol_names().iter() {
     let sym = scope.get_symbol(sym_name).unwrap();
     let min_sym = minify_symbols
       .entry(sym)
       .or_insert_with(|| MinifySymbol::new(session));
     assert!(min_sym.minified_name.is_none());
+    if keep_names {
+      min_sym.minified_name = Some(sym_name);
+      continue;
+    };
+    let Some(&name) = rules.pinned.get(&sym) else {
+      continue;
+    };
+    let atom = interner.intern(name);
+    if minified_inherited_vars.contains(&atom)
+      || (min_sym.is_used_as_jsx_component && name.as_slice()[0].is_ascii_lowercase())
+      || !pinned_in_scope.insert(atom)
+    {
+      continue;
+    };
+    min_sym.minified_name = Some(name);
+  }
+  minified_inherited_vars.extend(rules.reserved.iter().copied());
+  // Yes, we start from the very beginning in case there are possible gaps/opportunities due to inherited variables on ancestors.
+  let mut next_min_name = MinifiedNameGenerator::new(session, rules.start_chars, rules.chars);
+  for &sym_name in scope.symbol_names().iter() {
+    let sym = scope.get_symbol(sym_name).unwrap();
+    let min_sym = minify_symbols.get_mut(&sym).unwrap();
+    if min_sym.minified_name.is_some() {
+      continue;
+    };
     if min_sym.is_used_as_jsx_component {
       // We'll process these in another iteration, as there's fewer characters allowed for the identifier start, and we don't want to skip past valid identifiers for non-JSX-component names.
       continue;
     };
     min_sym.minified_name =
-      Some(next_min_name.generate_next_available_minified_name(&minified_inherited_vars));
+      Some(rules.next_name(&mut next_min_name, &minified_inherited_vars, interner));
   }
+  let mut jsx_min_name = None;
   for &sym_name in scope.symbol_names().iter() {
     let sym = scope.get_symbol(sym_name).unwrap();
     let min_sym = minify_symbols.get_mut(&sym).unwrap();
-    if !min_sym.is_used_as_jsx_component {
+    if !min_sym.is_used_as_jsx_component || min_sym.minified_name.is_some() {
+      continue;
+    };
+    if rules.start_chars.iter().all(|c| c.is_ascii_lowercase()) {
+      // The alphabet can't start a JSX component name, so these start with an uppercase letter instead, which can't be the same as any other generated name.
+      let jsx_min_name = jsx_min_name.get_or_insert_with(|| {
+        MinifiedNameGenerator::new(session, b"ABCDEFGHIJKLMNOPQRSTUVWXYZ", rules.chars)
+      });
+      min_sym.minified_name =
+        Some(rules.next_name(jsx_min_name, &minified_inherited_vars, interner));
       continue;
     };
     // TODO This is very slow and dumb.
     let mut min_name;
     loop {
-      min_name = next_min_name.generate_next_available_minified_name(&minified_inherited_vars);
+      min_name = rules.next_name(&mut next_min_name, &minified_inherited_vars, interner);
       if !min_name.as_slice()[0].is_ascii_lowercase() {
         break;
       };
     }
     min_sym.minified_name = Some(min_name)
   }
+  if rules.unique_in_closure {
+    for &sym_name in scope.symbol_names().iter() {
+      let sym = scope.get_symbol(sym_name).unwrap();
+      closure_names.insert(interner.intern(minify_symbols[&sym].minified_name.unwrap()));
+    }
+  };
+  for &c in scope.children().iter() {
+    minify_scope_names(ctx, c, rules, closure_typ, closure_names);
+  }
+}
+
+// This should be run after `minify_names`.
+pub fn collect_rename_map<'a>(
+  scope: Scope<'a>,
+  minify_symbols: &SessionHashMap<'a, Symbol, MinifySymbol<'a>>,
+) -> RenameMap {
+  let mut map = RenameMap::default();
+  collect_scope_renames(&mut map, None, scope, minify_symbols);
+  map
+}
+
+fn collect_scope_renames<'a>(
+  map: &mut RenameMap,
+  parent: Option<usize>,
+  scope: Scope<'a>,
+  minify_symbols: &SessionHashMap<'a, Symbol, MinifySymbol<'a>>,
+) {
+  let mut renames = Vec::new();
+  for &sym_name in scope.symbol_names().iter() {
+    let sym = scope.get_symbol(sym_name).unwrap();
+    let minified = minify_symbols[&sym].minified_name.unwrap();
+    renames.push(Rename {
+      original: String::from_utf8_lossy(sym_name.as_slice()).into_owned(),
+      minified: String::from_utf8_lossy(minified.as_slice()).into_owned(),
+      position: sym_name.start(),
+    });
+  }
+  let id = map.scopes.len();
+  map.scopes.push(RenameScope {
+    parent,
+    typ: scope.typ(),
+    renames,
+  });
   for &c in scope.children().iter() {
-    minify_names(session, c, minify_scopes, minify_symbols);
+    collect_scope_renames(map, Some(id), c, minify_symbols);
   }
 }
@@ -3,8 +3,12 @@ use super::advanced_if::process_if_branch;
 use super::ctx::Ctx;
 use super::ctx::MinifyScope;
 use super::ctx::MinifySymbol;
+use super::fn_name::FnNames;
+use super::label::LabelTargets;
+use super::purity::Purity;
 use parse_js::ast::new_node;
 use parse_js::ast::NodeData;
+use parse_js::ast::ObjectMemberType;
 use parse_js::ast::Syntax;
 use parse_js::operator::Operator;
 use parse_js::operator::OperatorName;
@@ -17,15 +21,50 @@ use std::str::from_utf8_unchecked;
 // - Find all references of variables so we can determine inherited variables (see `MinifiedNameGenerator` and `MinifyScope`). This is because JS allows variables to be lexically referenced before they're used, so we cannot do this in the same pass. For example, `let b = 1; { let a = () => b; let b = 2; }`.
 // - Find uses of `new <var>` and set `is_used_as_constructor`.
 // - Find uses of `<var>.prototype` and set `has_prototype`.
+// - Find variables used as `{ __proto__ }` and set `keep_name`.
 // - Combine consecutive expression statements into one.
 // - Convert `if (x) { expr; }` to `x && expr`.
 // - Convert `if (x) { expr1; } else { expr2; }` to `x ? expr1 : expr2`.
 // - Concatenate addition of two literal strings.
 // - Unwrap unnecessary block statements.
 // - Drop debugger statements.
+// - Drop statements without side effects (see `Purity`).
+// - Drop labels that no `break` or `continue` targets, so the labelled statement can be merged or collapsed.
+// - Find the variables that name functions and classes (see `FnNames`).
+// - Track where `let`, `const`, and `class` bindings are initialised, so reads in their temporal dead zone (which throw) aren't dropped.
 // - Normalise `if-else` branches into block statements.
 pub struct Pass1<'a, 'b> {
   pub ctx: Ctx<'a, 'b>,
+  pub purity: Purity,
+  pub labels: LabelTargets<'a>,
+  pub fn_names: FnNames,
+  // Whether to keep top-level statements without side effects.
+  pub keep_top_level_stmts: bool,
+  // Whether to keep all statements without side effects.
+  pub keep_pure_stmts: bool,
+  // Whether the top level follows other code, so has no directives.
+  pub top_level_after_code: bool,
+}
+
+impl<'a, 'b> Pass1<'a, 'b> {
+  // Whether a statement in a block, function body, or the top level has no side effects, so can be dropped. Directives (e.g. `"use strict"`) must be kept, but other strings can be dropped like any other expression; keeping them could make them directives once the statements before them are dropped (e.g. `1; "use strict"`).
+  fn is_droppable(&self, stmt: &NodeData<'a>, directive: bool) -> bool {
+    !directive && !self.keep_pure_stmts && self.purity.is_pure_stmt(stmt)
+  }
+}
+
+fn is_string_stmt(stmt: &NodeData<'_>) -> bool {
+  matches!(&stmt.stx, Syntax::ExpressionStmt {
+    expression: NodeData {
+      stx: Syntax::LiteralStringExpr { .. },
+      ..
+    },
+  })
+}
+
+// The number of directives at the start of a function body or the top level, which are the strings before any other statement.
+fn directive_count(body: &[&mut NodeData<'_>]) -> usize {
+  body.iter().take_while(|s| is_string_stmt(s)).count()
 }
 
 fn stmt_has_return<'a>(stx: &Syntax<'a>) -> bool {
@@ -112,6 +151,7 @@ mod tests {
   use crate::minify::ctx::Ctx;
   use crate::minify::ctx::MinifyScope;
   use crate::minify::ctx::MinifySymbol;
+  use crate::minify::intern::Interner;
   use crate::minify::pass1::maybe_combine_string_literals;
   use crate::minify::pass1::maybe_ensure_if_statement_consequent_and_alternate_are_wrapped;
   use parse_js::ast::NodeData;
@@ -130,10 +170,12 @@ mod tests {
       let $n = parse_js::parse(&session, $source.as_bytes(), TopLevelMode::Global).unwrap();
       let mut symbols = session.new_hashmap::<Symbol, MinifySymbol>();
       let mut scopes = session.new_hashmap::<Scope<'_>, MinifyScope<'_>>();
+      let mut interner = Interner::new(&session);
       let $ctx = Ctx {
         scopes: &mut scopes,
         session: &session,
         symbols: &mut symbols,
+        interner: &mut interner,
       };
     };
   }
@@ -187,11 +229,11 @@ mod tests {
 
     setup!(n, ctx, "'a' + 'b';");
     P { ctx }.visit(n);
-    check!(n, "`ab`");
+    check!(n, "\"ab\"");
 
     setup!(n, ctx, "'a' + 'b' + 'c';");
     P { ctx }.visit(n);
-    check!(n, "`abc`");
+    check!(n, "\"abc\"");
   }
 }
 
@@ -199,6 +241,26 @@ impl<'a, 'b> Visitor<'a> for Pass1<'a, 'b> {
   fn on_syntax_down(&mut self, n: &mut NodeData<'a>, _ctl: &mut JourneyControls) -> () {
     let scope = n.scope;
     maybe_ensure_if_statement_consequent_and_alternate_are_wrapped(&mut self.ctx, n);
+    self.fn_names.visit(n);
+    match &n.stx {
+      Syntax::TopLevel { body } | Syntax::BlockStmt { body } => {
+        for stmt in body.iter() {
+          self.purity.track_declarations(stmt, false);
+        }
+      }
+      Syntax::SwitchBranch { body, .. } => {
+        for stmt in body.iter() {
+          self.purity.track_declarations(stmt, true);
+        }
+      }
+      // Declarations in loop headers.
+      Syntax::VarDecl { .. } => self.purity.track_declarations(n, false),
+      Syntax::LabelStmt { name, .. } => self.labels.enter(*name),
+      Syntax::BreakStmt { label: Some(label) } | Syntax::ContinueStmt { label: Some(label) } => {
+        self.labels.jump(*label)
+      }
+      _ => {}
+    };
     match &mut n.stx {
       Syntax::BlockStmt { body } => {
         let mut i = 0;
@@ -268,8 +330,12 @@ impl<'a, 'b> Visitor<'a> for Pass1<'a, 'b> {
         argument,
       } => {
         let var_name = match &argument.stx {
-          // e.g. `new Array()`.
-          Syntax::CallExpr { callee, .. } => match &callee.stx {
+          // e.g. `new Array()`, but not `new (Array())`.
+          Syntax::CallExpr {
+            parenthesised: false,
+            callee,
+            ..
+          } => match &callee.stx {
             Syntax::IdentifierExpr { name } => Some(*name),
             _ => None,
           },
@@ -288,6 +354,19 @@ impl<'a, 'b> Visitor<'a> for Pass1<'a, 'b> {
           };
         };
       }
+      // Expanding `{ __proto__ }` would set the prototype instead of defining a property, so the variable must keep its name.
+      Syntax::ObjectMember {
+        typ: ObjectMemberType::Shorthand { identifier },
+      } if identifier.loc.as_slice() == b"__proto__" => {
+        if let Some(sym) = scope.find_symbol(identifier.loc) {
+          self
+            .ctx
+            .symbols
+            .entry(sym)
+            .or_insert_with(|| MinifySymbol::new(self.ctx.session))
+            .keep_name = true;
+        };
+      }
       Syntax::JsxElement {
         name:
           Some(NodeData {
@@ -310,11 +389,40 @@ impl<'a, 'b> Visitor<'a> for Pass1<'a, 'b> {
   }
 
   fn on_syntax_up(&mut self, node: &mut NodeData<'a>) -> () {
+    if let Syntax::LabelStmt { statement, .. } = &mut node.stx {
+      // The statement has already been visited, so don't process it again.
+      if !self.labels.exit() {
+        let statement = statement.take(self.ctx.session);
+        core::mem::swap(node, statement);
+      };
+      return;
+    };
     let loc = node.loc;
     let scope = node.scope;
     match &mut node.stx {
+      Syntax::TopLevel { body } if !self.keep_top_level_stmts => {
+        let directives = if self.top_level_after_code {
+          0
+        } else {
+          directive_count(body)
+        };
+        let mut w = 0;
+        for r in 0..body.len() {
+          if !self.is_droppable(body[r], r < directives) {
+            body.swap(w, r);
+            w += 1;
+          };
+        }
+        body.truncate(w);
+      }
       // This is bottom-up as we could remove nested blocks recursively.
       Syntax::BlockStmt { body } => {
+        // A function body has the function's scope, while other blocks have their own.
+        let directives = if scope.typ().is_closure() {
+          directive_count(body)
+        } else {
+          0
+        };
         let mut returned = false;
         // Next writable slot when shifting down due to gaps from deleting merged ExpressionStmt values.
         let mut w = 0;
@@ -327,10 +435,13 @@ impl<'a, 'b> Visitor<'a> for Pass1<'a, 'b> {
             // TODO There may be more code outside this block that's now unreachable and can be removed.
             break;
           };
-          // Get `scope` before we borrow mutably for `stx`.
+          // Get `scope` and purity before we borrow mutably for `stx`.
           let r_scope = body[r].scope;
+          // Blocks are only unwrapped after the directives, so these are still at the start.
+          let pure = self.is_droppable(body[r], r < directives);
           let keep = match &mut body[r].stx {
             // NOTE: We must match here as BlockStmt may not always be a block statement (e.g. `for`, `while`, function bodies).
+            // A block that's the target of a `break` is always wrapped in its LabelStmt, so is never unwrapped here.
             Syntax::BlockStmt { body: block_body } => {
               if block_body.is_empty() {
                 false
@@ -347,15 +458,11 @@ impl<'a, 'b> Visitor<'a> for Pass1<'a, 'b> {
                 true
               }
             }
-            Syntax::ExpressionStmt { expression: _ } => {
-              // TODO Remove if pure.
-              true
-            }
             Syntax::ReturnStmt { .. } => {
               returned = true;
               true
             }
-            _ => true,
+            _ => !pure,
           };
           if keep {
             body.swap(w, r);
@@ -403,7 +510,11 @@ impl<'a, 'b> Visitor<'a> for Pass1<'a, 'b> {
               };
             }
           }
-          (true, Some(true)) => {
+          // Branches are normalised so that `else` is unwrapped if `if` returns, but only for `if` statements directly in a block (not e.g. in a `switch` case), so make sure that either both or neither branch returns.
+          (true, Some(true))
+            if stmt_has_return(&consequent.stx)
+              == alternate.as_ref().is_some_and(|alt| stmt_has_return(&alt.stx)) =>
+          {
             let closure_scope = scope.find_self_or_ancestor(|t| t.is_closure()).unwrap_or(scope);
             let cons_expr = process_if_branch(self.ctx.session, scope, consequent);
             let alt_expr = process_if_branch(self.ctx.session, scope, alternate.as_mut().unwrap());
@@ -418,7 +529,6 @@ impl<'a, 'b> Visitor<'a> for Pass1<'a, 'b> {
             min_scope
               .hoisted_vars
               .extend_from_slice(&alt_expr.hoisted_vars);
-            // Due to normalisation, it's not possible for an `if-else` to return in either branch, because one branch would've been unwrapped.
             assert!(cons_expr.returns == alt_expr.returns);
             let test = test.take(self.ctx.session);
             let consequent = cons_expr.expression;
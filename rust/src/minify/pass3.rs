@@ -1,5 +1,6 @@
 use super::ctx::MinifyScope;
 use super::ctx::MinifySymbol;
+use crate::cfg::Target;
 use parse_js::ast::new_node;
 use parse_js::ast::ClassOrObjectMemberKey;
 use parse_js::ast::ClassOrObjectMemberValue;
@@ -15,6 +16,7 @@ use parse_js::session::SessionHashMap;
 use parse_js::source::SourceRange;
 use parse_js::symbol::Scope;
 use parse_js::symbol::ScopeFlag;
+use parse_js::symbol::ScopeType;
 use parse_js::symbol::Symbol;
 use parse_js::visit::JourneyControls;
 use parse_js::visit::Visitor;
@@ -33,16 +35,56 @@ fn unwrap_block_statement_if_possible<'a>(session: &'a Session, node: &mut NodeD
   };
 }
 
+// Whether the statement ends with an `if` without an `else`, which would take the `else` of an `if` whose consequent it is, so a block around it can't be unwrapped.
+fn ends_with_if_without_else(node: &NodeData<'_>) -> bool {
+  match &node.stx {
+    Syntax::IfStmt {
+      alternate: None, ..
+    } => true,
+    Syntax::IfStmt {
+      alternate: Some(alternate),
+      ..
+    } => ends_with_if_without_else(alternate),
+    Syntax::WhileStmt { body, .. } | Syntax::ForStmt { body, .. } => ends_with_if_without_else(body),
+    Syntax::LabelStmt { statement, .. } => ends_with_if_without_else(statement),
+    _ => false,
+  }
+}
+
+// Functions that use `this` or `arguments` can't become arrow functions. Each flag is checked on its own, as parse-js's `Flags::from_raw` drops its argument, so `ScopeFlag::UsesArguments | ScopeFlag::UsesThis` is always empty.
+fn uses_this_or_arguments(scope: Scope<'_>) -> bool {
+  let flags = scope.flags();
+  flags.has(ScopeFlag::UsesThis) || flags.has(ScopeFlag::UsesArguments)
+}
+
 // This should be run after the `minify_names` function.
 pub struct Pass3<'a, 'b> {
   pub session: &'a Session,
+  pub target: Target,
   // Exports with the same exported name (including multiple default exports) are illegal, so we don't have to worry about/handle that case.
   pub export_bindings: &'b mut Vec<ExportBinding<'a>>,
   pub symbols: &'b mut SessionHashMap<'a, Symbol, MinifySymbol<'a>>,
   pub scopes: &'b mut SessionHashMap<'a, Scope<'a>, MinifyScope<'a>>,
+  // Whether to keep function declarations that are hoisted to the top-level scope as is.
+  pub keep_top_level_functions: bool,
+  // Bytes saved by replacing names with their minified names.
+  pub renaming_savings: isize,
 }
 
 impl<'a, 'b> Pass3<'a, 'b> {
+  fn minified_name(&mut self, sym: Symbol, name: SourceRange<'a>) -> SourceRange<'a> {
+    let minified = self.symbols[&sym].minified_name.unwrap();
+    self.renaming_savings += name.len() as isize - minified.len() as isize;
+    minified
+  }
+
+  // Whether a variable will have a different name, so shorthand properties using it must be expanded.
+  fn is_renamed(&self, scope: Scope<'a>, name: SourceRange<'a>) -> bool {
+    scope
+      .find_symbol(name)
+      .is_some_and(|sym| self.symbols[&sym].minified_name.unwrap() != name)
+  }
+
   fn visit_exported_pattern(&mut self, n: &mut NodeData<'a>) -> () {
     match &mut n.stx {
       Syntax::ArrayPattern { elements, rest } => {
@@ -107,10 +149,13 @@ impl<'a, 'b> Visitor<'a> for Pass3<'a, 'b> {
                 }),
               );
             }
-            for fn_decl in min_scope.hoisted_functions.values_mut() {
-              // TODO Batch prepend to avoid repeated Vec shifting.
-              body.insert(0, fn_decl.take(self.session));
-            }
+            // Order by position in the source, so that the output doesn't depend on hash map iteration order.
+            let mut fn_decls = min_scope.hoisted_functions.values_mut().collect::<Vec<_>>();
+            fn_decls.sort_unstable_by_key(|d| d.loc.start());
+            body.splice(0..0, fn_decls.into_iter().map(|d| d.take(self.session)));
+            // Other blocks can have the same scope as the closure (e.g. blocks wrapping `if` branches), so only insert into the first one visited, which is the closure's body.
+            min_scope.hoisted_vars.clear();
+            min_scope.hoisted_functions.clear();
           };
         };
       }
@@ -149,9 +194,8 @@ impl<'a, 'b> Visitor<'a> for Pass3<'a, 'b> {
         // TODO Can this work sometimes even when `arguments` is used?
         // TODO This is still not risk-free, as the function's prototype could still be used even if there is no `this`.
         // TODO Detect `function(){}.bind(this)`, which is pretty much risk free unless somehow Function.prototype.bind has been overridden. However, any other value for the first argument of `.bind` means that it is no longer safe.
-        if !fn_scope
-          .flags()
-          .has_any(ScopeFlag::UsesArguments | ScopeFlag::UsesThis)
+        if self.target >= Target::Es2015
+          && !uses_this_or_arguments(fn_scope)
         {
           new_stx = Some(Syntax::ArrowFunctionExpr {
             // TODO
@@ -177,7 +221,12 @@ impl<'a, 'b> Visitor<'a> for Pass3<'a, 'b> {
         // TODO Can this work sometimes even when `arguments` is used?
         // TODO This is still not risk-free, as the function's prototype could still be used even if there is no `this`.
         // TODO Detect `function(){}.bind(this)`, which is pretty much risk free unless somehow Function.prototype.bind has been overridden. However, any other value for the first argument of `.bind` means that it is no longer safe.
-        if !fn_scope.flags().has_any(ScopeFlag::UsesArguments | ScopeFlag::UsesThis)
+        let is_top_level = scope
+          .find_self_or_ancestor(|t| t.is_closure_or_global())
+          .is_some_and(|s| matches!(s.typ(), ScopeType::Global | ScopeType::Module));
+        if self.target >= Target::Es2015
+          && !(self.keep_top_level_functions && is_top_level)
+          && !uses_this_or_arguments(fn_scope)
           // Use `find_symbol` as we might not be in a closure scope and the function declaration's symbol would've been added to an ancestor.
           // If no symbol is found (e.g. global), or it exists but is not `is_used_as_constructor` and not `has_prototype`, then we can safely proceed.
           && scope.find_symbol(name.loc).and_then(|sym| self.symbols.get(&sym)).filter(|sym| sym.is_used_as_constructor || sym.has_prototype).is_none()
@@ -222,21 +271,21 @@ impl<'a, 'b> Visitor<'a> for Pass3<'a, 'b> {
       Syntax::IdentifierPattern { name } => {
         let sym = scope.find_symbol(*name);
         if let Some(sym) = sym {
-          let minified = self.symbols[&sym].minified_name.unwrap();
+          let minified = self.minified_name(sym, *name);
           new_stx = Some(Syntax::IdentifierPattern { name: minified });
         };
       }
       Syntax::IdentifierExpr { name } => {
         let sym = scope.find_symbol(*name);
         if let Some(sym) = sym {
-          let minified = self.symbols[&sym].minified_name.unwrap();
+          let minified = self.minified_name(sym, *name);
           new_stx = Some(Syntax::IdentifierExpr { name: minified });
         };
       }
       Syntax::ClassOrFunctionName { name } => {
         let sym = scope.find_symbol(*name);
         if let Some(sym) = sym {
-          let minified = self.symbols[&sym].minified_name.unwrap();
+          let minified = self.minified_name(sym, *name);
           new_stx = Some(Syntax::ClassOrFunctionName { name: minified });
         };
       }
@@ -306,7 +355,9 @@ impl<'a, 'b> Visitor<'a> for Pass3<'a, 'b> {
         default_value,
         ..
       } => {
-        if scope.find_symbol(*name).is_some() {
+        if self.is_renamed(scope, *name) {
+          // The key will now be emitted in addition to the minified name, followed by a colon.
+          self.renaming_savings -= name.len() as isize + 1;
           // If the symbol declaration exists, we know it definitely has a minified name. However, because the parser recurses into changed subtrees, we must simply expand this property with a IdentifierPattern target referencing the original name, so that the visitor for it will then change it to the minified name. Otherwise, we'll retrieve the minified name for a minified name, which is incorrect. Note that we can't simply skip the subtree entirely as there are still other parts.
           new_stx = Some(Syntax::ObjectPatternProperty {
             key: ClassOrObjectMemberKey::Direct(*name),
@@ -320,8 +371,9 @@ impl<'a, 'b> Visitor<'a> for Pass3<'a, 'b> {
         typ: ObjectMemberType::Shorthand { identifier },
       } => {
         let name = identifier.loc;
-        if scope.find_symbol(name).is_some() {
+        if self.is_renamed(scope, name) {
           // See Syntax::ObjectPatternProperty match branch.
+          self.renaming_savings -= name.len() as isize + 1;
           let replacement_initializer_node = identifier.take(self.session);
           new_stx = Some(Syntax::ObjectMember {
             typ: ObjectMemberType::Valued {
@@ -348,7 +400,11 @@ impl<'a, 'b> Visitor<'a> for Pass3<'a, 'b> {
         alternate,
         ..
       } => {
-        unwrap_block_statement_if_possible(self.session, consequent);
+        let dangling_else = alternate.is_some()
+          && matches!(&consequent.stx, Syntax::BlockStmt { body } if body.len() == 1 && ends_with_if_without_else(body[0]));
+        if !dangling_else {
+          unwrap_block_statement_if_possible(self.session, consequent);
+        };
         if let Some(alt) = alternate {
           unwrap_block_statement_if_possible(self.session, alt);
         }
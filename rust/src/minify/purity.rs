@@ -0,0 +1,411 @@
+use crate::comment::collect_comments;
+use crate::environment::Environment;
+use crate::pattern::Patterns;
+use parse_js::ast::ArrayElement;
+use parse_js::ast::ClassOrObjectMemberKey;
+use parse_js::ast::ClassOrObjectMemberValue;
+use parse_js::ast::LiteralTemplatePart;
+use parse_js::ast::NodeData;
+use parse_js::ast::ObjectMemberType;
+use parse_js::ast::Syntax;
+use parse_js::ast::VarDeclMode;
+use parse_js::operator::OperatorName;
+use parse_js::source::SourceRange;
+use parse_js::symbol::Scope;
+use parse_js::symbol::Symbol;
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+static PURE_ANNOTATION_PATTERNS: Patterns = Patterns::new(&[b"@__PURE__", b"#__PURE__"]);
+
+// Globals that are always a primitive value.
+const PRIMITIVE_GLOBALS: &[&[u8]] = &[b"Infinity", b"NaN", b"undefined"];
+
+// Global functions that don't call any user code when their arguments are primitives, nor throw.
+const PURE_GLOBAL_FUNCTIONS: &[&[u8]] = &[
+  b"Boolean",
+  b"Number",
+  b"String",
+  b"Symbol",
+  b"isFinite",
+  b"isNaN",
+  b"parseFloat",
+  b"parseInt",
+];
+
+// Static methods of global objects that don't call any user code when their arguments are primitives, nor throw.
+const PURE_GLOBAL_METHODS: &[(&[u8], &[&[u8]])] = &[
+  (b"Array", &[b"isArray", b"of"]),
+  (
+    b"Math",
+    &[
+      b"abs", b"acos", b"acosh", b"asin", b"asinh", b"atan", b"atan2", b"atanh", b"cbrt", b"ceil",
+      b"clz32", b"cos", b"cosh", b"exp", b"expm1", b"floor", b"fround", b"hypot", b"imul", b"log",
+      b"log10", b"log1p", b"log2", b"max", b"min", b"pow", b"round", b"sign", b"sin", b"sinh",
+      b"sqrt", b"tan", b"tanh", b"trunc",
+    ],
+  ),
+  (
+    b"Number",
+    &[
+      b"isFinite",
+      b"isInteger",
+      b"isNaN",
+      b"isSafeInteger",
+      b"parseFloat",
+      b"parseInt",
+    ],
+  ),
+  (b"Object", &[b"is"]),
+  (b"String", &[b"fromCharCode"]),
+];
+
+// Global constructors that don't call any user code nor throw when constructed without arguments.
+const PURE_GLOBAL_CONSTRUCTORS: &[&[u8]] =
+  &[b"Array", b"Map", b"Object", b"Set", b"WeakMap", b"WeakSet"];
+
+// Whether evaluating code can have side effects, such as assigning to variables, calling functions, or throwing. Any pass that removes or reorders code should ask this rather than deciding itself, so they all agree on what's safe. The analysis is conservative, so code is only pure if we can be sure of it:
+// - Literals are pure, as are arrays, objects, and templates made of pure values, but not if they spread values (which runs iterators and getters, though object spreads are pure with `assume_plain_objects`) or convert values to strings or numbers (which can call `toString` and `valueOf`).
+// - Reading a declared variable is pure, but reading an undeclared variable throws if it doesn't exist (unless it's a global of `MinifyOptions::environment`), and reading a `let`, `const`, or `class` binding throws before it's initialised (i.e. in its temporal dead zone). Reads are only known to be after initialisation if they're later in the source and in the same function, as functions can be called before then.
+// - Reading a property could run a getter, or throw if the object is `null` or `undefined`, so is never pure, unless `assume_plain_objects` is set (see `MinifyOptions::assume_plain_objects`).
+// - Calls are only pure if they're to known global functions (e.g. `Math.max`) with primitive arguments, or annotated with `/*#__PURE__*/` and have pure arguments.
+// - Defining functions and arrow functions is pure, as they're not run.
+pub struct Purity {
+  // Positions of calls and `new` expressions that follow a `/*#__PURE__*/` annotation.
+  annotated: HashSet<usize>,
+  assume_plain_objects: bool,
+  // Globals that exist, so reading them doesn't throw.
+  environment: Environment,
+  // For `let`, `const`, and `class` bindings, the position in the source after which they're initialised. It's `usize::MAX` if we can't tell, such as for declarations in `switch` cases (which can be jumped over) and classes.
+  initialized_at: HashMap<Symbol, usize>,
+}
+
+// Adds the names declared by a pattern.
+pub fn pattern_names<'a>(pattern: &NodeData<'a>, out: &mut Vec<SourceRange<'a>>) {
+  match &pattern.stx {
+    Syntax::IdentifierPattern { name } => out.push(*name),
+    Syntax::ArrayPattern { elements, rest } => {
+      for e in elements.iter().flatten() {
+        pattern_names(e.target, out);
+      }
+      if let Some(rest) = rest {
+        pattern_names(rest, out);
+      };
+    }
+    Syntax::ObjectPattern { properties, rest } => {
+      for p in properties.iter() {
+        if let Syntax::ObjectPatternProperty { target, .. } = &p.stx {
+          pattern_names(target, out);
+        };
+      }
+      if let Some(rest) = rest {
+        pattern_names(rest, out);
+      };
+    }
+    _ => {}
+  };
+}
+
+pub(crate) fn closure_of(scope: Scope<'_>) -> Option<Scope<'_>> {
+  scope.find_self_or_ancestor(|t| t.is_closure_or_global())
+}
+
+impl Purity {
+  // The parser discards comments, so annotations are found by lexing `source` again, which is only done if it contains one.
+  pub fn new(
+    source: SourceRange<'_>,
+    assume_plain_objects: bool,
+    environment: Environment,
+  ) -> Purity {
+    let mut annotated = HashSet::new();
+    let raw = source.as_slice();
+    if PURE_ANNOTATION_PATTERNS.is_match(raw) {
+      for c in collect_comments(raw) {
+        if !c.raw.starts_with(b"/*") || !PURE_ANNOTATION_PATTERNS.is_match(c.raw) {
+          continue;
+        };
+        let mut next = c.raw.as_ptr() as usize - raw.as_ptr() as usize + c.raw.len();
+        while raw.get(next).is_some_and(|c| c.is_ascii_whitespace()) {
+          next += 1;
+        }
+        annotated.insert(source.start() + next);
+      }
+    };
+    Purity {
+      annotated,
+      assume_plain_objects,
+      environment,
+      initialized_at: HashMap::new(),
+    }
+  }
+
+  // Tracks where the `let`, `const`, and `class` declarations in a statement are initialised. This must be called for all statements in a block (or `switch` case if `in_switch`) before any code in the block is checked, as code earlier in the block can refer to declarations later in it. Declarations that have already been tracked are left as is, so loop headers can also be tracked when visited.
+  pub fn track_declarations<'a>(&mut self, stmt: &NodeData<'a>, in_switch: bool) {
+    let mut declare = |name: SourceRange<'a>, at: usize| {
+      if let Some(sym) = stmt.scope.find_symbol(name) {
+        self
+          .initialized_at
+          .entry(sym)
+          .or_insert(if in_switch { usize::MAX } else { at });
+      };
+    };
+    match &stmt.stx {
+      Syntax::VarDecl {
+        mode: VarDeclMode::Let | VarDeclMode::Const,
+        declarators,
+        ..
+      } => {
+        for decl in declarators.iter() {
+          let mut names = Vec::new();
+          pattern_names(decl.pattern, &mut names);
+          let end = decl
+            .initializer
+            .as_ref()
+            .map_or(decl.pattern.loc.end(), |init| init.loc.end());
+          for name in names {
+            declare(name, end);
+          }
+        }
+      }
+      Syntax::ClassDecl {
+        name: Some(name), ..
+      } => {
+        if let Syntax::ClassOrFunctionName { name } = &name.stx {
+          declare(*name, usize::MAX);
+        };
+      }
+      _ => {}
+    };
+  }
+
+  // Whether reading a declared variable can't be in its temporal dead zone.
+  fn is_initialized<'a>(&self, read: &NodeData<'a>, decl_scope: Scope<'a>, sym: Symbol) -> bool {
+    match self.initialized_at.get(&sym) {
+      // Not a `let`, `const`, or `class` binding.
+      None => true,
+      Some(&at) => closure_of(read.scope) == closure_of(decl_scope) && read.loc.start() >= at,
+    }
+  }
+
+  // The name of a global variable that isn't shadowed by a declaration.
+  fn global_name<'n>(node: &'n NodeData<'_>) -> Option<&'n [u8]> {
+    match &node.stx {
+      Syntax::IdentifierExpr { name } if node.scope.find_symbol(*name).is_none() => {
+        Some(name.as_slice())
+      }
+      _ => None,
+    }
+  }
+
+  fn is_global(node: &NodeData<'_>, names: &[&[u8]]) -> bool {
+    Purity::global_name(node).is_some_and(|name| names.contains(&name))
+  }
+
+  // The value of a call argument, unless it's spread (which runs an iterator).
+  fn arg_value<'n, 'a>(arg: &'n NodeData<'a>) -> Option<&'n NodeData<'a>> {
+    match &arg.stx {
+      Syntax::CallArg {
+        spread: false,
+        value,
+      } => Some(value),
+      _ => None,
+    }
+  }
+
+  // Whether the value is always a primitive, so converting it can't call any user code. BigInts are excluded, as mixing them with numbers throws.
+  fn is_primitive(node: &NodeData<'_>) -> bool {
+    match &node.stx {
+      Syntax::LiteralBooleanExpr { .. }
+      | Syntax::LiteralNull {}
+      | Syntax::LiteralNumberExpr { .. }
+      | Syntax::LiteralStringExpr { .. } => true,
+      Syntax::LiteralTemplateExpr { parts } => parts.iter().all(|p| match p {
+        LiteralTemplatePart::Substitution(e) => Purity::is_primitive(e),
+        LiteralTemplatePart::String(_) => true,
+      }),
+      Syntax::UnaryExpr {
+        operator:
+          OperatorName::BitwiseNot
+          | OperatorName::LogicalNot
+          | OperatorName::Typeof
+          | OperatorName::UnaryNegation
+          | OperatorName::UnaryPlus
+          | OperatorName::Void,
+        argument,
+        ..
+      } => Purity::is_primitive(argument),
+      _ => Purity::is_global(node, PRIMITIVE_GLOBALS),
+    }
+  }
+
+  fn is_pure_global_call(&self, callee: &NodeData<'_>, arguments: &[&mut NodeData<'_>]) -> bool {
+    let known = match &callee.stx {
+      Syntax::IdentifierExpr { .. } => Purity::is_global(callee, PURE_GLOBAL_FUNCTIONS),
+      Syntax::MemberExpr {
+        optional_chaining: false,
+        left,
+        right,
+        ..
+      } => Purity::global_name(left).is_some_and(|obj| {
+        PURE_GLOBAL_METHODS
+          .iter()
+          .any(|(o, methods)| *o == obj && methods.contains(&right.as_slice()))
+      }),
+      _ => false,
+    };
+    known
+      && arguments
+        .iter()
+        .all(|a| Purity::arg_value(a).is_some_and(Purity::is_primitive))
+  }
+
+  fn is_annotated(&self, node: &NodeData<'_>) -> bool {
+    self.annotated.contains(&node.loc.start())
+  }
+
+  fn is_pure_args(&self, arguments: &[&mut NodeData<'_>]) -> bool {
+    arguments
+      .iter()
+      .all(|a| Purity::arg_value(a).is_some_and(|v| self.is_pure(v)))
+  }
+
+  fn is_pure_member(&self, member: &NodeData<'_>) -> bool {
+    let Syntax::ObjectMember { typ } = &member.stx else {
+      return false;
+    };
+    match typ {
+      ObjectMemberType::Valued { key, value } => {
+        // Computed keys are converted to strings.
+        let key_ok = match key {
+          ClassOrObjectMemberKey::Direct(_) => true,
+          ClassOrObjectMemberKey::Computed(key) => Purity::is_primitive(key),
+        };
+        key_ok
+          && match value {
+            ClassOrObjectMemberValue::Property {
+              initializer: Some(init),
+            } => self.is_pure(init),
+            ClassOrObjectMemberValue::Property { initializer: None } => false,
+            // Accessors and methods are only defined, not run.
+            _ => true,
+          }
+      }
+      ObjectMemberType::Shorthand { identifier } => self.is_pure(identifier),
+      // Spreading reads each property.
+      ObjectMemberType::Rest { value } => self.assume_plain_objects && self.is_pure(value),
+    }
+  }
+
+  pub fn is_pure(&self, node: &NodeData<'_>) -> bool {
+    match &node.stx {
+      Syntax::ArrowFunctionExpr { .. }
+      | Syntax::FunctionExpr { .. }
+      | Syntax::LiteralBigIntExpr { .. }
+      | Syntax::LiteralBooleanExpr { .. }
+      | Syntax::LiteralNull {}
+      | Syntax::LiteralNumberExpr { .. }
+      | Syntax::LiteralRegexExpr {}
+      | Syntax::LiteralStringExpr { .. } => true,
+      Syntax::IdentifierExpr { name } => match node.scope.find_symbol_with_scope(*name) {
+        Some((decl_scope, sym)) => self.is_initialized(node, decl_scope, sym),
+        None => Purity::is_primitive(node) || self.environment.has_global(name.as_slice()),
+      },
+      Syntax::LiteralArrayExpr { elements } => elements.iter().all(|e| match e {
+        ArrayElement::Single(e) => self.is_pure(e),
+        ArrayElement::Rest(_) => false,
+        ArrayElement::Empty => true,
+      }),
+      Syntax::LiteralObjectExpr { members } => members.iter().all(|m| self.is_pure_member(m)),
+      Syntax::LiteralTemplateExpr { .. } => Purity::is_primitive(node),
+      Syntax::MemberExpr { left, .. } => self.assume_plain_objects && self.is_pure(left),
+      // Computed keys are converted to strings.
+      Syntax::ComputedMemberExpr { object, member, .. } => {
+        self.assume_plain_objects && self.is_pure(object) && Purity::is_primitive(member)
+      }
+      Syntax::ConditionalExpr {
+        test,
+        consequent,
+        alternate,
+        ..
+      } => self.is_pure(test) && self.is_pure(consequent) && self.is_pure(alternate),
+      Syntax::UnaryExpr {
+        operator, argument, ..
+      } => match operator {
+        OperatorName::LogicalNot | OperatorName::Void => self.is_pure(argument),
+        // `typeof` doesn't throw for undeclared variables, but still throws in a temporal dead zone.
+        OperatorName::Typeof => match &argument.stx {
+          Syntax::IdentifierExpr { name } => match argument.scope.find_symbol_with_scope(*name) {
+            Some((decl_scope, sym)) => self.is_initialized(argument, decl_scope, sym),
+            None => true,
+          },
+          _ => self.is_pure(argument),
+        },
+        OperatorName::BitwiseNot | OperatorName::UnaryNegation | OperatorName::UnaryPlus => {
+          Purity::is_primitive(argument)
+        }
+        OperatorName::New => match &argument.stx {
+          // `new (f())` constructs what `f()` returns.
+          Syntax::CallExpr {
+            optional_chaining: false,
+            parenthesised: false,
+            callee,
+            arguments,
+          } => {
+            (arguments.is_empty() && Purity::is_global(callee, PURE_GLOBAL_CONSTRUCTORS))
+              || (self.is_annotated(node) && self.is_pure_args(arguments))
+          }
+          _ => Purity::is_global(argument, PURE_GLOBAL_CONSTRUCTORS) || self.is_annotated(node),
+        },
+        _ => false,
+      },
+      Syntax::BinaryExpr {
+        operator,
+        left,
+        right,
+        ..
+      } => match operator {
+        OperatorName::Comma
+        | OperatorName::LogicalAnd
+        | OperatorName::LogicalOr
+        | OperatorName::NullishCoalescing
+        | OperatorName::StrictEquality
+        | OperatorName::StrictInequality => self.is_pure(left) && self.is_pure(right),
+        // These can throw for values that aren't objects.
+        OperatorName::In | OperatorName::Instanceof => false,
+        o if o.is_assignment() => false,
+        _ => Purity::is_primitive(left) && Purity::is_primitive(right),
+      },
+      Syntax::CallExpr {
+        optional_chaining: false,
+        callee,
+        arguments,
+        ..
+      } => {
+        self.is_pure_global_call(callee, arguments)
+          || (self.is_annotated(node) && self.is_pure_args(arguments))
+      }
+      _ => false,
+    }
+  }
+
+  // Whether a statement can be removed without changing what the code does. Statements that declare anything are never pure, as other code could refer to their declarations.
+  pub fn is_pure_stmt(&self, node: &NodeData<'_>) -> bool {
+    match &node.stx {
+      Syntax::EmptyStmt {} => true,
+      Syntax::ExpressionStmt { expression } => self.is_pure(expression),
+      Syntax::BlockStmt { body } => {
+        node.scope.symbol_names().is_empty() && body.iter().all(|s| self.is_pure_stmt(s))
+      }
+      Syntax::IfStmt {
+        test,
+        consequent,
+        alternate,
+      } => {
+        self.is_pure(test)
+          && self.is_pure_stmt(consequent)
+          && alternate.as_ref().is_none_or(|a| self.is_pure_stmt(a))
+      }
+      _ => false,
+    }
+  }
+}
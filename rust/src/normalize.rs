@@ -0,0 +1,36 @@
+use crate::comment::hashbang;
+use crate::identifier::decode_identifier_escapes;
+use crate::identifier::may_have_identifier_escapes;
+use crate::separator::may_have_numeric_separators;
+use crate::separator::strip_numeric_separators;
+use crate::line_terminator::has_whitespace_to_normalize;
+use crate::line_terminator::normalize_whitespace;
+use parse_js::session::Session;
+
+// Rewrites code that parse-js doesn't lex as ECMAScript does to equivalent code that it does, without changing the length of the code, so that positions in the rewritten code are the same as in the source, for diagnostics and source maps. The hashbang comment (e.g. `#!/usr/bin/env node`) is rewritten to spaces, as parse-js doesn't lex it. Whitespace goes next, as rewriting a line terminator can end a comment and change how the rest is lexed.
+//
+// Returns `source` itself if there's nothing to rewrite, otherwise a copy allocated in `session`.
+pub(crate) fn normalize_source<'a>(session: &'a Session, source: &'a [u8]) -> &'a [u8] {
+  let hashbang_len = hashbang(source).map_or(0, <[u8]>::len);
+  let whitespace = has_whitespace_to_normalize(source);
+  let escapes = may_have_identifier_escapes(source);
+  let separators = may_have_numeric_separators(source);
+  if hashbang_len == 0 && !whitespace && !escapes && !separators {
+    return source;
+  };
+  let mut normalized = source.to_vec();
+  normalized[..hashbang_len].fill(b' ');
+  if whitespace {
+    normalize_whitespace(&mut normalized);
+  };
+  if escapes {
+    decode_identifier_escapes(&mut normalized);
+  };
+  if separators {
+    strip_numeric_separators(&mut normalized);
+  };
+  if normalized == source {
+    return source;
+  };
+  session.get_allocator().alloc_slice_copy(&normalized)
+}
@@ -0,0 +1,277 @@
+use parse_js::ast::new_node;
+use parse_js::ast::Node;
+use parse_js::ast::NodeData;
+use parse_js::ast::Syntax;
+use parse_js::num::JsNumber;
+use parse_js::parse;
+use parse_js::parse::toplevel::TopLevelMode;
+use parse_js::session::Session;
+use parse_js::source::SourceRange;
+use parse_js::symbol::Scope;
+use parse_js::visit::JourneyControls;
+use parse_js::visit::Visitor;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fmt::Write;
+
+/// Opt-in obfuscation, for making the output harder to read and reverse engineer rather than smaller. Every part makes the output larger and a little slower, and none of them is encryption: anyone who runs the code can recover the strings and names it uses. All parts are disabled by default.
+///
+/// # Examples
+///
+/// ```
+/// use minify_js::{MinifyOptions, Obfuscation, Session, minify_str};
+///
+/// let session = Session::new();
+/// let cfg = MinifyOptions::new().obfuscation(Obfuscation::all(42));
+/// let out = minify_str(&session, "console.log('secret')", &cfg).unwrap();
+/// assert!(!out.contains("secret") && !out.contains("log"));
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serialize", serde(default))]
+pub struct Obfuscation {
+  /// Replace string literals with calls to a decoder function added at the start of the code, which decodes each string from a table when it's first used. Directives (e.g. `"use strict"`), dynamic import specifiers, and template literals are kept as is. In a global script, the decoder is a global function, with a random name that avoids every name used in the script.
+  pub encode_strings: bool,
+  /// Replace property accesses with computed ones (e.g. `a.b` with `a["b"]`), so that property names are strings, which `encode_strings` then hides. Private names (e.g. `this.#a`) are kept as is.
+  pub indirect_properties: bool,
+  /// Skip a random number of names before each minified name, so names don't follow the usual predictable sequence.
+  pub shuffle_names: bool,
+  /// Seed for the random choices, such as the decoder's name and key. The output is still deterministic for the same seed.
+  pub seed: u64,
+}
+
+impl Obfuscation {
+  /// Enables every kind of obfuscation, with the given seed.
+  pub fn all(seed: u64) -> Obfuscation {
+    Obfuscation {
+      encode_strings: true,
+      indirect_properties: true,
+      shuffle_names: true,
+      seed,
+    }
+  }
+}
+
+// A small pseudo-random number generator (SplitMix64), so that obfuscated output depends only on the seed.
+pub struct Rng(u64);
+
+impl Rng {
+  pub fn new(seed: u64) -> Rng {
+    Rng(seed)
+  }
+
+  pub fn next_u64(&mut self) -> u64 {
+    self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+    let mut z = self.0;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+  }
+
+  // Returns a number below `n`.
+  pub fn below(&mut self, n: u64) -> u64 {
+    self.next_u64() % n
+  }
+}
+
+// Whether `value` contains a lone surrogate, which can only arrive as WTF-8 bytes and can't be decoded from UTF-8 by the decoder.
+fn has_lone_surrogate(value: &str) -> bool {
+  value
+    .as_bytes()
+    .windows(2)
+    .any(|w| w[0] == 0xed && w[1] >= 0xa0)
+}
+
+// Collects every name used in the tree, which the decoder's name must avoid, and hashes the strings and property names in it, so that code with different strings (e.g. another batch from `minify_bounded`) gets a decoder with a different name.
+struct Names {
+  names: HashSet<Vec<u8>>,
+  hash: u64,
+}
+
+impl Names {
+  fn add_to_hash(&mut self, bytes: &[u8]) {
+    for &b in bytes.iter().chain([0].iter()) {
+      self.hash ^= b as u64;
+      self.hash = self.hash.wrapping_mul(0x100000001b3);
+    }
+  }
+}
+
+impl<'a> Visitor<'a> for Names {
+  fn on_syntax_down(&mut self, node: &mut NodeData<'a>, _ctl: &mut JourneyControls) {
+    match &node.stx {
+      Syntax::IdentifierExpr { name }
+      | Syntax::IdentifierPattern { name }
+      | Syntax::ClassOrFunctionName { name } => {
+        self.names.insert(name.as_slice().to_vec());
+      }
+      Syntax::LiteralStringExpr { value } => self.add_to_hash(value.as_bytes()),
+      Syntax::MemberExpr { right, .. } => self.add_to_hash(right.as_slice()),
+      _ => {}
+    };
+  }
+}
+
+struct Obfuscate<'a> {
+  session: &'a Session,
+  cfg: Obfuscation,
+  decoder: SourceRange<'a>,
+  // Strings to encode in order of first use, and the index of each in the table.
+  strings: Vec<&'a str>,
+  indices: HashMap<&'a str, usize>,
+}
+
+impl<'a> Obfuscate<'a> {
+  fn node(&self, scope: Scope<'a>, loc: SourceRange<'a>, stx: Syntax<'a>) -> Node<'a> {
+    new_node(self.session, scope, loc, stx)
+  }
+
+  // Returns a call to the decoder for `value`.
+  fn decode_call(&mut self, node: &NodeData<'a>, value: &'a str) -> Syntax<'a> {
+    let next = self.strings.len();
+    let index = *self.indices.entry(value).or_insert(next);
+    if index == next {
+      self.strings.push(value);
+    };
+    let mut arguments = self.session.new_vec();
+    arguments.push(self.node(
+      node.scope,
+      node.loc,
+      Syntax::CallArg {
+        spread: false,
+        value: self.node(
+          node.scope,
+          node.loc,
+          Syntax::LiteralNumberExpr {
+            value: JsNumber(index as f64),
+          },
+        ),
+      },
+    ));
+    Syntax::CallExpr {
+      optional_chaining: false,
+      parenthesised: false,
+      callee: self.node(
+        node.scope,
+        node.loc,
+        Syntax::IdentifierExpr { name: self.decoder },
+      ),
+      arguments,
+    }
+  }
+}
+
+impl<'a> Visitor<'a> for Obfuscate<'a> {
+  fn on_syntax_down(&mut self, node: &mut NodeData<'a>, ctl: &mut JourneyControls) {
+    match &mut node.stx {
+      // Directives only work as string literals, and bundlers only recognise string literal import specifiers.
+      Syntax::ExpressionStmt { expression }
+        if matches!(expression.stx, Syntax::LiteralStringExpr { .. }) =>
+      {
+        ctl.skip();
+      }
+      Syntax::ImportExpr { .. } => ctl.skip(),
+      Syntax::LiteralStringExpr { value }
+        if self.cfg.encode_strings && !value.is_empty() && !has_lone_surrogate(value) =>
+      {
+        let value = *value;
+        node.stx = self.decode_call(node, value);
+      }
+      // Parenthesised optional chains can't be represented without the parentheses (e.g. `(a?.b).c`), and names with escapes would need decoding.
+      Syntax::MemberExpr {
+        parenthesised: false,
+        optional_chaining,
+        assignment_target,
+        left,
+        right,
+      } if self.cfg.indirect_properties
+        && !right.as_slice().starts_with(b"#")
+        && !right.as_slice().contains(&b'\\') =>
+      {
+        let optional_chaining = *optional_chaining;
+        let assignment_target = *assignment_target;
+        let object = left.take(self.session);
+        let value = self.session.get_allocator().alloc_str(right.as_str());
+        let member = self.node(node.scope, *right, Syntax::LiteralStringExpr { value });
+        node.stx = Syntax::ComputedMemberExpr {
+          assignment_target,
+          optional_chaining,
+          object,
+          member,
+        };
+      }
+      _ => {}
+    };
+  }
+}
+
+// Returns the source code of the decoder: a function declaration, so it's hoisted above any code that uses it. Each string is stored as hex of its UTF-8 bytes, XORed with a key that changes with each byte; it's decoded by turning the bytes back into percent escapes for `decodeURIComponent`. Decoded strings are cached on the function.
+fn decoder_source(name: &str, key: u8, strings: &[&str]) -> String {
+  let mut table = String::new();
+  for (i, s) in strings.iter().enumerate() {
+    if i > 0 {
+      table.push(',');
+    };
+    table.push('"');
+    for (j, b) in s.bytes().enumerate() {
+      write!(table, "{:02x}", b ^ (key as usize + j) as u8).unwrap();
+    }
+    table.push('"');
+  }
+  format!(
+    "function {n}(i){{var t={n}.t||({n}.t=[{table}]),c={n}.c||({n}.c={{}}),s,r,j;if(!(i in c)){{for(s=t[i],r=\"\",j=0;j<s.length;j+=2)r+=\"%\"+(256|parseInt(s.substr(j,2),16)^{key}+j/2&255).toString(16).slice(1);c[i]=decodeURIComponent(r)}}return c[i]}}",
+    n = name,
+    table = table,
+    key = key,
+  )
+}
+
+// Applies `cfg` to a minified tree. This should be run last, after all other transforms, as it makes strings and property accesses opaque. Names are shuffled by `minify_names` instead.
+pub fn obfuscate<'a>(session: &'a Session, top_level_node: &mut NodeData<'a>, cfg: Obfuscation) {
+  let mut names = Names {
+    names: HashSet::new(),
+    hash: 0xcbf29ce484222325,
+  };
+  names.visit(top_level_node);
+  let mut rng = Rng::new(cfg.seed ^ names.hash);
+  let decoder = loop {
+    let name = format!("_0x{:06x}", rng.below(1 << 24));
+    if !names.names.contains(name.as_bytes()) {
+      break session.get_allocator().alloc_str(&name);
+    };
+  };
+  let key = rng.below(255) as u8 + 1;
+  let mut pass = Obfuscate {
+    session,
+    cfg,
+    decoder: SourceRange::from_slice(decoder.as_bytes()),
+    strings: Vec::new(),
+    indices: HashMap::new(),
+  };
+  pass.visit(top_level_node);
+  if pass.strings.is_empty() {
+    return;
+  };
+  let source = session
+    .get_allocator()
+    .alloc_slice_copy(decoder_source(decoder, key, &pass.strings).as_bytes());
+  let parsed = parse(session, source, TopLevelMode::Global).expect("decoder should parse");
+  let Syntax::TopLevel { body: decoder_body } = &mut parsed.stx else {
+    unreachable!();
+  };
+  let decl = decoder_body.pop().unwrap();
+  let Syntax::TopLevel { body } = &mut top_level_node.stx else {
+    unreachable!();
+  };
+  // Keep any directive prologue first.
+  let prologue = body
+    .iter()
+    .take_while(|stmt| match &stmt.stx {
+      Syntax::ExpressionStmt { expression } => {
+        matches!(expression.stx, Syntax::LiteralStringExpr { .. })
+      }
+      _ => false,
+    })
+    .count();
+  body.insert(prologue, decl);
+}
@@ -0,0 +1,71 @@
+use crate::diagnostic::Diagnostic;
+use crate::minify;
+use crate::MinifyOptions;
+use crate::MinifyStats;
+use parse_js::session::Session;
+use std::num::NonZeroUsize;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Mutex;
+use std::thread;
+
+/// Minifies many independent sources with the same options across a pool of threads, such as all the files in a dist folder. Each thread reuses its own [`Session`], like [`Minifier`](crate::Minifier).
+///
+/// `threads` is the number of threads to use, or 0 to use one per available CPU. No more threads than sources are started, and with one thread the sources are minified on the calling thread.
+///
+/// Returns the output and stats of each source, in the same order as `sources`. Errors are converted to [Diagnostic]s, as they borrow memory that's freed when their thread moves on to the next source.
+///
+/// # Examples
+///
+/// ```
+/// use minify_js::{MinifyOptions, minify_many};
+///
+/// let results = minify_many(&["let first = 1; f(first)", "let = ;"], &MinifyOptions::new(), 0);
+/// assert_eq!(results[0].as_ref().unwrap().0, b"let first=1;f(first)");
+/// assert_eq!(results[1].as_ref().unwrap_err().code, "E_EXPECTED_SYNTAX");
+/// ```
+pub fn minify_many<S: AsRef<[u8]> + Sync>(
+  sources: &[S],
+  cfg: &MinifyOptions,
+  threads: usize,
+) -> Vec<Result<(Vec<u8>, MinifyStats), Diagnostic>> {
+  let threads = match threads {
+    0 => thread::available_parallelism().map_or(1, NonZeroUsize::get),
+    n => n,
+  }
+  .min(sources.len());
+  let results = Mutex::new(Vec::from_iter((0..sources.len()).map(|_| None)));
+  // Sources are handed out one at a time, so a thread that gets a large source doesn't hold up others.
+  let next = AtomicUsize::new(0);
+  let work = || {
+    let mut session = Session::new();
+    loop {
+      let i = next.fetch_add(1, Ordering::Relaxed);
+      let Some(source) = sources.get(i) else {
+        break;
+      };
+      let source = source.as_ref();
+      session.reset();
+      let mut output = Vec::new();
+      let result = minify(&session, source, cfg, &mut output)
+        .map(|stats| (output, stats))
+        .map_err(|err| Diagnostic::from_minify_error(source, &err));
+      results.lock().unwrap()[i] = Some(result);
+    }
+  };
+  if threads <= 1 {
+    work();
+  } else {
+    thread::scope(|s| {
+      for _ in 0..threads {
+        s.spawn(work);
+      }
+    });
+  };
+  results
+    .into_inner()
+    .unwrap()
+    .into_iter()
+    .map(|r| r.expect("every source is minified"))
+    .collect()
+}
@@ -0,0 +1,57 @@
+use memchr::memchr3;
+
+// A set of byte strings to search for, built at compile time so there's no setup cost on first use. Candidates are found with `memchr3` on the patterns' first bytes, so there can be at most three distinct first bytes, which is checked when the set is built.
+pub struct Patterns {
+  patterns: &'static [&'static [u8]],
+  first_bytes: [u8; 3],
+}
+
+impl Patterns {
+  pub const fn new(patterns: &'static [&'static [u8]]) -> Patterns {
+    assert!(!patterns.is_empty());
+    let mut first_bytes = [patterns[0][0]; 3];
+    let mut count = 1;
+    let mut i = 1;
+    while i < patterns.len() {
+      let b = patterns[i][0];
+      let mut seen = false;
+      let mut j = 0;
+      while j < count {
+        seen |= first_bytes[j] == b;
+        j += 1;
+      }
+      if !seen {
+        assert!(
+          count < 3,
+          "patterns have more than three distinct first bytes"
+        );
+        first_bytes[count] = b;
+        count += 1;
+      };
+      i += 1;
+    }
+    Patterns {
+      patterns,
+      first_bytes,
+    }
+  }
+
+  // Returns the range of the leftmost match, preferring earlier patterns if several start at the same position.
+  pub fn find(&self, haystack: &[u8]) -> Option<(usize, usize)> {
+    let [a, b, c] = self.first_bytes;
+    let mut pos = 0;
+    while let Some(i) = memchr3(a, b, c, &haystack[pos..]) {
+      let start = pos + i;
+      let rest = &haystack[start..];
+      if let Some(p) = self.patterns.iter().find(|p| rest.starts_with(p)) {
+        return Some((start, start + p.len()));
+      };
+      pos = start + 1;
+    }
+    None
+  }
+
+  pub fn is_match(&self, haystack: &[u8]) -> bool {
+    self.find(haystack).is_some()
+  }
+}
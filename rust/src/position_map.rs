@@ -0,0 +1,386 @@
+use crate::json::write_json_string;
+use std::ops::Range;
+
+/// A syntax node in the output and the source code it was generated from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Mapping {
+  /// Byte range in the output.
+  pub output: Range<usize>,
+  /// Byte range in the source code.
+  pub source: Range<usize>,
+}
+
+/// Maps positions in minified output back to the source code they were generated from, such as to report where an error thrown by minified code came from, without generating and parsing a source map.
+///
+/// Positions are only as precise as syntax nodes; for example, a position within a string maps to the entire string literal in the source. Nodes created while minifying map to the source of the code they replaced.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PositionMap {
+  /// All emitted syntax nodes, sorted by the start of their output, with parents before their children.
+  pub mappings: Vec<Mapping>,
+}
+
+impl PositionMap {
+  /// Returns the source range of the innermost syntax node whose output contains the byte at `pos`, or `None` if there isn't one (e.g. a preserved comment or banner).
+  pub fn source_range(&self, pos: usize) -> Option<Range<usize>> {
+    let end = self.mappings.partition_point(|m| m.output.start <= pos);
+    self.mappings[..end]
+      .iter()
+      .rev()
+      .find(|m| pos < m.output.end)
+      .map(|m| m.source.clone())
+  }
+
+  /// Like [PositionMap::source_range], but takes the 1-based line and column (in characters) of a position in `output`, which must be the output this map was returned with.
+  pub fn source_range_at(&self, output: &[u8], line: usize, column: usize) -> Option<Range<usize>> {
+    let line_start = if line <= 1 {
+      0
+    } else {
+      memchr::memchr_iter(b'\n', output).nth(line - 2)? + 1
+    };
+    let line_end =
+      memchr::memchr(b'\n', &output[line_start..]).map_or(output.len(), |i| line_start + i);
+    let offset = String::from_utf8_lossy(&output[line_start..line_end])
+      .char_indices()
+      .nth(column.checked_sub(1)?)?
+      .0;
+    self.source_range(line_start + offset)
+  }
+
+  /// Generates a [source map](https://tc39.es/source-map/) (version 3) as JSON, for tools like browsers and error trackers. Each emitted syntax node is mapped to the start of its source code.
+  ///
+  /// # Arguments
+  ///
+  /// * `output` - The output this map was returned with.
+  /// * `source` - The source code the positions refer to. If `enclose` was used, this is the wrapped source, which isn't available, so don't generate a source map.
+  /// * `source_name` - The path or URL of the source code, relative to the source map.
+  pub fn to_source_map(&self, output: &[u8], source: &[u8], source_name: &str) -> String {
+    self.to_source_map_with(output, source, source_name, &SourceMapOptions::default())
+  }
+
+  /// Like [PositionMap::to_source_map], but with [SourceMapOptions]. If there's an input map, `source_name` isn't used, as the sources are those of the input map.
+  pub fn to_source_map_with(
+    &self,
+    output: &[u8],
+    source: &[u8],
+    source_name: &str,
+    opts: &SourceMapOptions,
+  ) -> String {
+    let source_lines = LineIndex::new(source);
+    let input_lines = opts
+      .input_map
+      .as_ref()
+      .map(|m| decode_mappings(&m.mappings));
+    let mut mappings = String::new();
+    // Position in the output up to which `out_column` has been counted.
+    let mut out_pos = 0;
+    let mut out_column = 0;
+    // Previous output column, source index, source line, source column, and name index, as each is encoded relative to the previous one.
+    let mut prev = [0; 5];
+    let mut first_on_line = true;
+    for (i, m) in self.mappings.iter().enumerate() {
+      // Nested nodes can start at the same position; the innermost one comes last.
+      if self
+        .mappings
+        .get(i + 1)
+        .is_some_and(|next| next.output.start == m.output.start)
+      {
+        continue;
+      };
+      let start = m.output.start;
+      if let Some(last) = memchr::memrchr(b'\n', &output[out_pos..start]) {
+        for _ in memchr::memchr_iter(b'\n', &output[out_pos..start]) {
+          mappings.push(';');
+        }
+        out_pos += last + 1;
+        out_column = 0;
+        prev[0] = 0;
+        first_on_line = true;
+      };
+      out_column += utf16_len(&output[out_pos..start]);
+      out_pos = start;
+      let (src_line, src_column) = source_lines.position(m.source.start);
+      let target = match (&input_lines, &opts.input_map) {
+        (Some(lines), Some(input_map)) => {
+          // Code the input map doesn't map (e.g. generated by a compiler) is left unmapped.
+          let Some(target) = lookup(lines, src_line, src_column) else {
+            continue;
+          };
+          if target.source >= input_map.sources.len()
+            || target.name.is_some_and(|n| n >= input_map.names.len())
+          {
+            continue;
+          };
+          target
+        }
+        _ => Target {
+          source: 0,
+          line: src_line,
+          column: src_column,
+          name: None,
+        },
+      };
+      if !first_on_line {
+        mappings.push(',');
+      };
+      first_on_line = false;
+      let mut fields = vec![
+        out_column as isize,
+        target.source as isize,
+        target.line as isize,
+        target.column as isize,
+      ];
+      fields.extend(target.name.map(|n| n as isize));
+      for (field, prev) in fields.into_iter().zip(prev.iter_mut()) {
+        write_vlq(&mut mappings, field - *prev);
+        *prev = field;
+      }
+    }
+
+    let source_name = source_name.to_string();
+    let source_content = String::from_utf8_lossy(source).into_owned();
+    let (sources, sources_content, names) = match &opts.input_map {
+      Some(input_map) => (
+        input_map.source_urls(),
+        (0..input_map.sources.len())
+          .map(|i| input_map.sources_content.get(i).cloned().flatten())
+          .collect(),
+        input_map.names.as_slice(),
+      ),
+      None => (vec![source_name], vec![Some(source_content)], &[][..]),
+    };
+    let mut json = String::from("{\"version\":3,");
+    if let Some(root) = &opts.source_root {
+      json.push_str("\"sourceRoot\":");
+      write_json_string(&mut json, root);
+      json.push(',');
+    };
+    json.push_str("\"sources\":");
+    write_json_array(&mut json, &sources, |json, s| write_json_string(json, s));
+    if opts.sources_content {
+      json.push_str(",\"sourcesContent\":");
+      write_json_array(&mut json, &sources_content, |json, c| match c {
+        Some(c) => write_json_string(json, c),
+        None => json.push_str("null"),
+      });
+    };
+    json.push_str(",\"names\":");
+    write_json_array(&mut json, names, |json, n| write_json_string(json, n));
+    json.push_str(",\"mappings\":");
+    write_json_string(&mut json, &mappings);
+    json.push('}');
+    json
+  }
+}
+
+/// Options for generating a source map with [PositionMap::to_source_map_with].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SourceMapOptions {
+  /// If provided, emitted as the `sourceRoot` field, which tools prepend to the path or URL of each source.
+  pub source_root: Option<String>,
+  /// Include the source code in the `sourcesContent` field, so that the source map can be used without access to the source files.
+  pub sources_content: bool,
+  /// Source map of the source code itself, such as one generated by a compiler or bundler. Positions are mapped through it, so that the generated source map refers to the original sources and names instead of the minifier's input.
+  pub input_map: Option<SourceMap>,
+}
+
+/// A [source map](https://tc39.es/source-map/) (version 3), such as one referenced by the source code with a `sourceMappingURL` comment. Index maps with `sections` aren't supported.
+///
+/// With the `serialize` feature, this can be loaded from the JSON of a source map using serde.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serialize", serde(default, rename_all = "camelCase"))]
+pub struct SourceMap {
+  pub source_root: Option<String>,
+  pub sources: Vec<String>,
+  /// The content of each source, if included.
+  pub sources_content: Vec<Option<String>>,
+  pub names: Vec<String>,
+  /// The VLQ encoded mappings.
+  pub mappings: String,
+}
+
+impl SourceMap {
+  /// Returns the path or URL of each source, with `source_root` prepended.
+  pub fn source_urls(&self) -> Vec<String> {
+    match self.source_root.as_deref() {
+      None | Some("") => self.sources.clone(),
+      Some(root) => self
+        .sources
+        .iter()
+        .map(|s| format!("{}/{}", root.trim_end_matches('/'), s))
+        .collect(),
+    }
+  }
+}
+
+// Where a position in an input map's generated code came from.
+#[derive(Clone, Copy)]
+struct Target {
+  source: usize,
+  line: usize,
+  column: usize,
+  name: Option<usize>,
+}
+
+// A decoded segment of a source map: a column in a line of the generated code, and where it came from, if anywhere.
+type Segment = (usize, Option<Target>);
+
+// Decodes mappings into their segments on each line, sorted by column. Invalid segments are skipped rather than failing, as source maps are best effort.
+fn decode_mappings(mappings: &str) -> Vec<Vec<Segment>> {
+  let mut prev = [0; 5];
+  mappings
+    .split(';')
+    .map(|line| {
+      prev[0] = 0;
+      let mut segments = Vec::new();
+      for segment in line.split(',').filter(|s| !s.is_empty()) {
+        let Some(fields) = read_vlqs(segment) else {
+          continue;
+        };
+        for (field, prev) in fields.iter().zip(prev.iter_mut()) {
+          *prev += field;
+        }
+        let field = |i: usize| usize::try_from(prev[i]).ok();
+        let target = || {
+          Some(Target {
+            source: field(1)?,
+            line: field(2)?,
+            column: field(3)?,
+            name: match fields.len() {
+              5 => Some(field(4)?),
+              _ => None,
+            },
+          })
+        };
+        let segment = match fields.len() {
+          1 => field(0).map(|column| (column, None)),
+          4 | 5 => field(0).zip(target()).map(|(column, t)| (column, Some(t))),
+          _ => None,
+        };
+        segments.extend(segment);
+      }
+      segments.sort_by_key(|s| s.0);
+      segments
+    })
+    .collect()
+}
+
+// Finds where a position in generated code came from, using the last segment at or before it on its line.
+fn lookup(lines: &[Vec<Segment>], line: usize, column: usize) -> Option<Target> {
+  let segments = lines.get(line)?;
+  let i = segments.partition_point(|s| s.0 <= column);
+  segments.get(i.checked_sub(1)?)?.1
+}
+
+fn write_json_array<T>(out: &mut String, items: &[T], write: impl Fn(&mut String, &T)) {
+  out.push('[');
+  for (i, item) in items.iter().enumerate() {
+    if i > 0 {
+      out.push(',');
+    };
+    write(out, item);
+  }
+  out.push(']');
+}
+
+pub(crate) fn utf16_len(s: &[u8]) -> usize {
+  String::from_utf8_lossy(s)
+    .chars()
+    .map(char::len_utf16)
+    .sum()
+}
+
+// Appends a signed number in the base64 VLQ encoding used by source maps.
+fn write_vlq(out: &mut String, n: isize) {
+  const BASE64: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+  let mut v = if n < 0 {
+    (-n as usize) << 1 | 1
+  } else {
+    (n as usize) << 1
+  };
+  loop {
+    let digit = v & 0b11111;
+    v >>= 5;
+    if v == 0 {
+      out.push(BASE64[digit] as char);
+      break;
+    };
+    out.push(BASE64[digit | 0b100000] as char);
+  }
+}
+
+// Reads the base64 VLQ encoded numbers in a segment.
+fn read_vlqs(segment: &str) -> Option<Vec<isize>> {
+  let mut values = Vec::new();
+  let mut value = 0usize;
+  let mut shift = 0;
+  for c in segment.bytes() {
+    let digit = match c {
+      b'A'..=b'Z' => c - b'A',
+      b'a'..=b'z' => c - b'a' + 26,
+      b'0'..=b'9' => c - b'0' + 52,
+      b'+' => 62,
+      b'/' => 63,
+      _ => return None,
+    } as usize;
+    if shift > 55 {
+      return None;
+    };
+    value |= (digit & 0b11111) << shift;
+    if digit & 0b100000 != 0 {
+      shift += 5;
+      continue;
+    };
+    let magnitude = (value >> 1) as isize;
+    values.push(if value & 1 == 1 {
+      -magnitude
+    } else {
+      magnitude
+    });
+    value = 0;
+    shift = 0;
+  }
+  // A segment can't end partway through a number.
+  (shift == 0).then_some(values)
+}
+
+// Converts byte positions in the source code to 0-based lines and columns in UTF-16 code units, which is what source maps use.
+struct LineIndex<'a> {
+  source: &'a [u8],
+  line_starts: Vec<usize>,
+  // Whether each line is ASCII, so its columns are the same as its byte offsets. Minified input can have very long lines, so we avoid counting characters on those where possible.
+  line_is_ascii: Vec<bool>,
+}
+
+impl<'a> LineIndex<'a> {
+  fn new(source: &'a [u8]) -> LineIndex<'a> {
+    let line_starts = std::iter::once(0)
+      .chain(memchr::memchr_iter(b'\n', source).map(|i| i + 1))
+      .collect::<Vec<_>>();
+    let line_is_ascii = line_starts
+      .iter()
+      .enumerate()
+      .map(|(i, &start)| {
+        let end = line_starts.get(i + 1).copied().unwrap_or(source.len());
+        source[start..end].is_ascii()
+      })
+      .collect();
+    LineIndex {
+      source,
+      line_starts,
+      line_is_ascii,
+    }
+  }
+
+  fn position(&self, pos: usize) -> (usize, usize) {
+    let pos = pos.min(self.source.len());
+    let line = self.line_starts.partition_point(|&s| s <= pos) - 1;
+    let start = self.line_starts[line];
+    let column = if self.line_is_ascii[line] {
+      pos - start
+    } else {
+      utf16_len(&self.source[start..pos])
+    };
+    (line, column)
+  }
+}
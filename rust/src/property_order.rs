@@ -0,0 +1,145 @@
+use parse_js::ast::ClassOrObjectMemberKey;
+use parse_js::ast::ClassOrObjectMemberValue;
+use parse_js::ast::NodeData;
+use parse_js::ast::ObjectMemberType;
+use parse_js::ast::Syntax;
+use parse_js::parse::literal::normalise_literal_number;
+use parse_js::source::SourceRange;
+use parse_js::visit::JourneyControls;
+use parse_js::visit::Visitor;
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+
+// A step in evaluating an object literal, as far as the order of its properties is concerned.
+#[derive(Debug, PartialEq, Eq)]
+enum Step {
+  // Defines a property with a key that isn't an integer index, unless it's already defined.
+  Key(String),
+  // `__proto__: value` sets the prototype instead of defining a property.
+  Prototype,
+  // A computed key that isn't a literal, or a spread, which could define any properties. Identified by where its expression starts in the source.
+  Unknown(usize),
+}
+
+// The order in which an object literal's own properties are enumerated: integer keys in ascending order, then other keys in the order they're first defined.
+#[derive(Debug, PartialEq, Eq)]
+struct PropertyOrder {
+  integer_keys: BTreeSet<u32>,
+  steps: Vec<Step>,
+}
+
+// Returns the key that a non-computed key (an identifier, keyword, string, or number) defines. Escapes in strings are left as is.
+pub fn direct_key(raw: SourceRange<'_>) -> String {
+  let s = raw.as_slice();
+  match s.first() {
+    Some(b'"' | b'\'') => String::from_utf8_lossy(&s[1..s.len() - 1]).into_owned(),
+    Some(c) if c.is_ascii_digit() || *c == b'.' => match normalise_literal_number(raw) {
+      Ok(n) => n.to_string(),
+      Err(_) => String::from_utf8_lossy(s).into_owned(),
+    },
+    _ => String::from_utf8_lossy(s).into_owned(),
+  }
+}
+
+// Array indices (integers below 2^32 - 1 in canonical form) are enumerated before all other keys.
+fn integer_index(key: &str) -> Option<u32> {
+  key
+    .parse::<u32>()
+    .ok()
+    .filter(|&i| i != u32::MAX && i.to_string() == key)
+}
+
+impl PropertyOrder {
+  fn of(members: &[&mut NodeData<'_>]) -> PropertyOrder {
+    let mut order = PropertyOrder {
+      integer_keys: BTreeSet::new(),
+      steps: Vec::new(),
+    };
+    for m in members {
+      let Syntax::ObjectMember { typ } = &m.stx else {
+        continue;
+      };
+      let step = match typ {
+        ObjectMemberType::Valued {
+          key: ClassOrObjectMemberKey::Direct(key),
+          value,
+        } => match direct_key(*key) {
+          key
+            if key == "__proto__" && matches!(value, ClassOrObjectMemberValue::Property { .. }) =>
+          {
+            Step::Prototype
+          }
+          key => Step::Key(key),
+        },
+        ObjectMemberType::Valued {
+          key: ClassOrObjectMemberKey::Computed(key),
+          ..
+        } => match &key.stx {
+          Syntax::LiteralStringExpr { value } => Step::Key(value.to_string()),
+          Syntax::LiteralNumberExpr { value } => Step::Key(value.to_string()),
+          _ => Step::Unknown(key.loc.start()),
+        },
+        ObjectMemberType::Shorthand { identifier } => match &identifier.stx {
+          Syntax::IdentifierExpr { name } => Step::Key(name.as_str().to_string()),
+          _ => Step::Unknown(identifier.loc.start()),
+        },
+        ObjectMemberType::Rest { value } => Step::Unknown(value.loc.start()),
+      };
+      match step {
+        Step::Key(key) => {
+          if let Some(i) = integer_index(&key) {
+            order.integer_keys.insert(i);
+          } else if !order.steps.iter().any(|s| *s == Step::Key(key.clone())) {
+            order.steps.push(Step::Key(key));
+          };
+        }
+        step => order.steps.push(step),
+      };
+    }
+    order
+  }
+}
+
+// The property order of each object literal in a tree, by its location in the source.
+#[derive(Default)]
+struct PropertyOrders(HashMap<(usize, usize), PropertyOrder>);
+
+impl<'a> Visitor<'a> for PropertyOrders {
+  fn on_syntax_down(&mut self, node: &mut NodeData<'a>, _ctl: &mut JourneyControls) {
+    if let Syntax::LiteralObjectExpr { members } = &node.stx {
+      self.0.insert(
+        (node.loc.start(), node.loc.end()),
+        PropertyOrder::of(members),
+      );
+    };
+  }
+}
+
+fn property_orders(node: &mut NodeData<'_>) -> PropertyOrders {
+  let mut orders = PropertyOrders::default();
+  orders.visit(node);
+  orders
+}
+
+// Runs one of our own transforms on `node`. Any transform of object literals (e.g. expanding shorthands, merging spreads, dropping duplicate keys, or unquoting keys) must preserve the order in which their properties are enumerated, including integer keys coming first; in debug builds, this checks that it does, and panics otherwise. Object literals that the transform drops or creates aren't checked.
+pub fn preserving_property_order<'a, R>(
+  node: &mut NodeData<'a>,
+  transform: impl FnOnce(&mut NodeData<'a>) -> R,
+) -> R {
+  if !cfg!(debug_assertions) {
+    return transform(node);
+  };
+  let before = property_orders(node);
+  let res = transform(node);
+  // Only the order in which mismatches are found depends on the hash map, and the first one panics.
+  for (loc, order) in property_orders(node).0 {
+    if let Some(expected) = before.0.get(&loc) {
+      assert_eq!(
+        *expected, order,
+        "transform changed the property order of the object literal at {}",
+        loc.0
+      );
+    };
+  }
+  res
+}
@@ -0,0 +1,182 @@
+use crate::early_error::StatementEarlyErrors;
+use crate::normalize::normalize_source;
+use parse_js::error::SyntaxError;
+use parse_js::error::SyntaxResult;
+use parse_js::lex::Lexer;
+use parse_js::parse::pattern::ParsePatternRules;
+use parse_js::parse::toplevel::TopLevelMode;
+use parse_js::parse::ParseCtx;
+use parse_js::parse::Parser;
+use parse_js::session::Session;
+use parse_js::symbol::Scope;
+use parse_js::symbol::ScopeType;
+use parse_js::symbol::SymbolGenerator;
+use parse_js::token::TokenType;
+
+// Skips tokens until the end of the statement containing `error_pos`, which we guess is the first `;` or line break outside any brackets after the error. Returns an error if the lexer fails.
+fn skip_statement<'a>(parser: &mut Parser<'a>, error_pos: usize) -> SyntaxResult<'a, ()> {
+  // Open brackets, innermost last.
+  let mut open = Vec::new();
+  loop {
+    let t = parser.peek()?;
+    if t.typ == TokenType::EOF
+      || (open.is_empty() && t.preceded_by_line_terminator && t.loc.start() > error_pos)
+    {
+      return Ok(());
+    };
+    parser.consume_peeked();
+    match t.typ {
+      TokenType::BraceOpen | TokenType::BracketOpen | TokenType::ParenthesisOpen => {
+        open.push(t.typ)
+      }
+      TokenType::QuestionDotBracketOpen => open.push(TokenType::BracketOpen),
+      TokenType::QuestionDotParenthesisOpen => open.push(TokenType::ParenthesisOpen),
+      // A closing brace also closes any unclosed brackets within it.
+      TokenType::BraceClose => {
+        while let Some(o) = open.pop() {
+          if o == TokenType::BraceOpen {
+            break;
+          };
+        }
+      }
+      TokenType::BracketClose if open.last() == Some(&TokenType::BracketOpen) => {
+        open.pop();
+      }
+      TokenType::ParenthesisClose if open.last() == Some(&TokenType::ParenthesisOpen) => {
+        open.pop();
+      }
+      TokenType::Semicolon if open.is_empty() && t.loc.start() >= error_pos => return Ok(()),
+      _ => {}
+    };
+  }
+}
+
+// The lexer can't skip past invalid code (e.g. an unterminated string), so we replace the line containing it with spaces in a copy of the source, which keeps all positions the same. Returns None if there's nothing left to blank.
+fn blank_line<'a>(session: &'a Session, source: &[u8], pos: usize) -> Option<&'a [u8]> {
+  // The error could be at the line terminator ending the invalid line.
+  let pos = pos.min(source.len()).saturating_sub(1);
+  let start = source[..pos]
+    .iter()
+    .rposition(|&c| c == b'\n')
+    .map_or(0, |i| i + 1);
+  let end = source[pos..]
+    .iter()
+    .position(|&c| c == b'\n')
+    .map_or(source.len(), |i| pos + i);
+  if source[start..end].iter().all(|c| c.is_ascii_whitespace()) {
+    return None;
+  };
+  let blanked = session.get_allocator().alloc_slice_copy(source);
+  blanked[start..end].fill(b' ');
+  Some(blanked)
+}
+
+/// Parses UTF-8 JavaScript code and returns all syntax errors found, in source order, instead of stopping at the first one like [`minify`](crate::minify). Returns an empty vector if the code is valid.
+///
+/// After an error, parsing resumes at the next top-level statement, skipping the rest of the statement containing the error. This means only the first error in each top-level statement (e.g. a function declaration) is found, and an error can occasionally cause spurious errors in the code that follows it.
+///
+/// # Arguments
+///
+/// * `session` - Session to use as backing arena memory.
+/// * `source` - The source code to check, as UTF-8 bytes.
+/// * `top_level_mode` - How to parse the provided code.
+pub fn syntax_errors<'a>(
+  session: &'a Session,
+  source: &'a [u8],
+  top_level_mode: TopLevelMode,
+) -> Vec<SyntaxError<'a>> {
+  let mut errors: Vec<SyntaxError<'a>> = Vec::new();
+  let mut record = |err: SyntaxError<'a>| {
+    if errors
+      .last()
+      .is_none_or(|last| err.source.start() > last.source.start())
+    {
+      errors.push(err);
+    };
+  };
+  let scope_type = match top_level_mode {
+    TopLevelMode::Global => ScopeType::Global,
+    TopLevelMode::Module => ScopeType::Module,
+  };
+  let ctx = ParseCtx {
+    scope: Scope::new(session, SymbolGenerator::new(), None, scope_type),
+    session,
+    rules: ParsePatternRules {
+      await_allowed: true,
+      yield_allowed: true,
+    },
+  };
+  let mut early_errors = StatementEarlyErrors::default();
+  let mut source = normalize_source(session, source);
+  let mut parser = Parser::new(Lexer::new(source));
+  loop {
+    let start = parser.checkpoint();
+    let res = match parser.consume_if(TokenType::EOF) {
+      Ok(eof) if eof.is_match() => break,
+      Ok(_) => parser.parse_stmt(ctx).map(|stmt| {
+        // The statement parsed, so there's nothing to skip.
+        if let Err(err) = early_errors.check(stmt) {
+          record(err);
+        };
+      }),
+      Err(err) => Err(err),
+    };
+    let Err(err) = res else {
+      continue;
+    };
+    let error_pos = err.source.start();
+    record(err);
+    parser.restore_checkpoint(start);
+    let start = parser.checkpoint();
+    if let Err(err) = skip_statement(&mut parser, error_pos) {
+      let error_pos = err.source.start();
+      record(err);
+      let Some(blanked) = blank_line(session, source, error_pos) else {
+        break;
+      };
+      // Parse the statement again, as it may now be valid, or have an error after the blanked line.
+      source = blanked;
+      parser = Parser::new(Lexer::new(source));
+      parser.restore_checkpoint(start);
+    };
+  }
+  errors
+}
+
+#[cfg(test)]
+mod tests {
+  use super::syntax_errors;
+  use crate::Session;
+  use crate::TopLevelMode;
+  use parse_js::error::SyntaxErrorType;
+
+  fn check(source: &str, expected: &[(usize, SyntaxErrorType)]) {
+    let session = Session::new();
+    let errors = syntax_errors(&session, source.as_bytes(), TopLevelMode::Module)
+      .into_iter()
+      .map(|e| (e.source.start(), e.typ))
+      .collect::<Vec<_>>();
+    assert_eq!(errors, expected);
+  }
+
+  #[test]
+  fn test_syntax_errors() {
+    check("let a = 1;\nfunction f() { return a }", &[]);
+    check(
+      "let a = ;\nlet b = 1;\nif (a) { b( }\ntry {}\nlet c = 2",
+      &[
+        (8, SyntaxErrorType::ExpectedSyntax("expression operand")),
+        (33, SyntaxErrorType::ExpectedSyntax("expression operand")),
+        (35, SyntaxErrorType::TryStatementHasNoCatchOrFinally),
+      ],
+    );
+    // The lexer can't continue after an unterminated string.
+    check(
+      "let a = 'x\nlet b = 1;\nlet c = )",
+      &[
+        (10, SyntaxErrorType::LineTerminatorInString),
+        (30, SyntaxErrorType::ExpectedSyntax("expression operand")),
+      ],
+    );
+  }
+}
@@ -0,0 +1,60 @@
+use crate::json::write_json_string;
+use parse_js::symbol::ScopeType;
+use std::fmt::Write;
+
+/// A variable's name before and after minification.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Rename {
+  pub original: String,
+  /// Same as `original` if the variable wasn't renamed.
+  pub minified: String,
+  /// Byte offset of the variable's first declaration in the source.
+  pub position: usize,
+}
+
+/// Variables declared in a scope.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RenameScope {
+  /// Index of the parent scope in [RenameMap::scopes], or `None` for the top-level scope.
+  pub parent: Option<usize>,
+  pub typ: ScopeType,
+  pub renames: Vec<Rename>,
+}
+
+/// Mapping from original to minified names of all variables, grouped by scope. Undeclared and global variables aren't renamed and aren't included.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RenameMap {
+  /// All scopes in pre-order, so the top-level scope is first and a parent always comes before its children.
+  pub scopes: Vec<RenameScope>,
+}
+
+impl RenameMap {
+  /// Serialises the map as JSON, in the form `{"scopes":[{"parent":null,"type":"Global","renames":[{"original":"foo","minified":"a","position":4}]}]}`.
+  pub fn to_json(&self) -> String {
+    let mut out = String::from("{\"scopes\":[");
+    for (i, scope) in self.scopes.iter().enumerate() {
+      if i > 0 {
+        out.push(',');
+      };
+      out.push_str("{\"parent\":");
+      match scope.parent {
+        Some(p) => write!(out, "{}", p).unwrap(),
+        None => out.push_str("null"),
+      };
+      write!(out, ",\"type\":\"{:?}\",\"renames\":[", scope.typ).unwrap();
+      for (j, r) in scope.renames.iter().enumerate() {
+        if j > 0 {
+          out.push(',');
+        };
+        out.push_str("{\"original\":");
+        write_json_string(&mut out, &r.original);
+        out.push_str(",\"minified\":");
+        write_json_string(&mut out, &r.minified);
+        write!(out, ",\"position\":{}}}", r.position).unwrap();
+      }
+      out.push_str("]}");
+    }
+    out.push_str("]}");
+    out
+  }
+}
@@ -0,0 +1,80 @@
+use parse_js::session::Session;
+use std::time::Duration;
+use std::time::Instant;
+
+/// Time and memory used by each phase of a minification, if [MinifyOptions::report](crate::MinifyOptions::report) is enabled. This is useful for deciding which optional work (e.g. warnings, transforms, or position maps) to disable to meet a latency budget.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MinifyReport {
+  /// The phases in the order they ran. Phases for optional work, such as transforms, only appear if it was done.
+  pub phases: Vec<PhaseReport>,
+}
+
+/// Time and memory used by a phase in a [MinifyReport].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PhaseReport {
+  /// Name of the phase, such as `parse` or `pass1`. These are the same as the span names used by the `tracing` feature.
+  pub name: &'static str,
+  /// Wall time spent in the phase.
+  pub duration: Duration,
+  /// Bytes allocated in the [Session](crate::Session) arena during the phase. The arena only grows until the session is reset, so this is how much the phase added to peak memory usage. Temporary allocations outside the arena aren't counted.
+  pub allocated: usize,
+}
+
+impl MinifyReport {
+  /// The report for the phase with this name, if it ran.
+  pub fn phase(&self, name: &str) -> Option<&PhaseReport> {
+    self.phases.iter().find(|p| p.name == name)
+  }
+
+  /// Wall time spent in all phases.
+  pub fn total_duration(&self) -> Duration {
+    self.phases.iter().map(|p| p.duration).sum()
+  }
+
+  /// Bytes allocated in the session arena by all phases.
+  pub fn total_allocated(&self) -> usize {
+    self.phases.iter().map(|p| p.allocated).sum()
+  }
+}
+
+// Bytes used in the arena, including any unused space at the end of earlier chunks, as that memory can't be used either.
+fn arena_used(session: &Session) -> usize {
+  let arena = session.get_allocator();
+  arena.allocated_bytes() - arena.chunk_capacity()
+}
+
+// The start of a phase, if it's being measured.
+pub(crate) struct PhaseStart(Option<(Instant, usize)>);
+
+impl PhaseStart {
+  pub fn new(report: &Option<MinifyReport>, session: &Session) -> PhaseStart {
+    PhaseStart(
+      report
+        .as_ref()
+        .map(|_| (Instant::now(), arena_used(session))),
+    )
+  }
+
+  pub fn finish(self, report: &mut Option<MinifyReport>, session: &Session, name: &'static str) {
+    if let (Some(report), Some((start, allocated_start))) = (report.as_mut(), self.0) {
+      report.phases.push(PhaseReport {
+        name,
+        duration: start.elapsed(),
+        allocated: arena_used(session) - allocated_start,
+      });
+    };
+  }
+}
+
+// Runs a block as a phase: within a `tracing` span named `$name`, and measured in `$report` (an `Option<MinifyReport>`) if it's enabled. Evaluates to the block's value. If the block returns early (e.g. using `?`), the phase isn't recorded.
+macro_rules! phase {
+  ($report:expr, $session:expr, $name:literal, $body:block) => {{
+    $crate::trace::trace_span!($name);
+    let start = $crate::report::PhaseStart::new(&$report, $session);
+    let res = $body;
+    start.finish(&mut $report, $session, $name);
+    res
+  }};
+}
+
+pub(crate) use phase;
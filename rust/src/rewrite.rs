@@ -0,0 +1,82 @@
+use crate::Transform;
+use parse_js::ast::LiteralTemplatePart;
+use parse_js::ast::NodeData;
+use parse_js::ast::Syntax;
+use parse_js::session::Session;
+use parse_js::visit::JourneyControls;
+use parse_js::visit::Visitor;
+
+/// How a module specifier is used, as passed to the callback of [rewrite_imports].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImportKind {
+  /// An import statement, e.g. `import a from "x"`.
+  Static,
+  /// A dynamic import, e.g. `import("x")`. Only specifiers that are string literals, or templates without substitutions, can be rewritten.
+  Dynamic,
+  /// A re-export, e.g. `export * from "x"`.
+  Reexport,
+}
+
+struct RewriteImports<'a, 'f> {
+  session: &'a Session,
+  rewrite: &'f (dyn Fn(&str, ImportKind) -> Option<String> + Send + Sync),
+}
+
+impl<'a, 'f> RewriteImports<'a, 'f> {
+  fn rewrite(&self, specifier: &mut &'a str, kind: ImportKind) {
+    if let Some(new) = (self.rewrite)(specifier, kind) {
+      *specifier = self.session.get_allocator().alloc_str(&new);
+    };
+  }
+}
+
+impl<'a, 'f> Visitor<'a> for RewriteImports<'a, 'f> {
+  fn on_syntax_down(&mut self, node: &mut NodeData<'a>, _ctl: &mut JourneyControls) {
+    match &mut node.stx {
+      Syntax::ImportStmt { module, .. } => self.rewrite(module, ImportKind::Static),
+      Syntax::ExportListStmt {
+        from: Some(from), ..
+      } => self.rewrite(from, ImportKind::Reexport),
+      Syntax::ImportExpr { module } => match &mut module.stx {
+        Syntax::LiteralStringExpr { value } => self.rewrite(value, ImportKind::Dynamic),
+        Syntax::LiteralTemplateExpr { parts } => {
+          if let [LiteralTemplatePart::String(value)] = parts.as_mut_slice() {
+            self.rewrite(value, ImportKind::Dynamic);
+          };
+        }
+        _ => {}
+      },
+      _ => {}
+    };
+  }
+}
+
+/// Returns a transform that calls `rewrite` with every module specifier in import statements, re-exports, and dynamic imports, to add to [MinifyOptions::before_minify](crate::MinifyOptions::before_minify). If it returns a new specifier, such as a path with a content hash or a CDN URL, the specifier is replaced. Specifiers are passed and returned without escapes, and are quoted and escaped as needed in the output.
+///
+/// # Examples
+///
+/// ```
+/// use minify_js::{minify, rewrite_imports, ImportKind, MinifyOptions, Session, TopLevelMode};
+///
+/// let mut cfg = MinifyOptions::new().top_level_mode(TopLevelMode::Module);
+/// cfg.before_minify.push(rewrite_imports(|specifier, kind| match kind {
+///   ImportKind::Reexport => Some(format!("{}?v=2", specifier)),
+///   _ => specifier.strip_prefix("npm:").map(|name| format!("https://cdn.example/{}", name)),
+/// }));
+/// let code: &[u8] = b"import a from 'npm:a'; export * from './b.js'; a();";
+/// let session = Session::new();
+/// let mut out = Vec::new();
+/// minify(&session, code, &cfg, &mut out).unwrap();
+/// assert_eq!(out, b"import a from\"https://cdn.example/a\";export*from\"./b.js?v=2\";a()");
+/// ```
+pub fn rewrite_imports<F: Fn(&str, ImportKind) -> Option<String> + Send + Sync + 'static>(
+  rewrite: F,
+) -> Transform {
+  Transform::new(move |session, node| {
+    RewriteImports {
+      session,
+      rewrite: &rewrite,
+    }
+    .visit(node)
+  })
+}
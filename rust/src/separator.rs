@@ -0,0 +1,107 @@
+use crate::limits::scan_tokens;
+use memchr::memchr_iter;
+use parse_js::token::TokenType;
+
+// Whether `code` could have a numeric separator, without lexing. It's a `_` between two digits, so this looks for one between two hex digits; it also matches some identifiers (e.g. `a_b`), which are then lexed to rule out.
+pub(crate) fn may_have_numeric_separators(code: &[u8]) -> bool {
+  memchr_iter(b'_', code).any(|i| {
+    i > 0
+      && code[i - 1].is_ascii_hexdigit()
+      && code.get(i + 1).is_some_and(u8::is_ascii_hexdigit)
+  })
+}
+
+// The end of the run of digits in `radix` (or `_`) in `code` from `i`.
+fn skip_digits(code: &[u8], mut i: usize, radix: u32) -> usize {
+  while i < code.len() && (code[i] == b'_' || (code[i] as char).is_digit(radix)) {
+    i += 1;
+  }
+  i
+}
+
+// If the numeric literal at the start of `code` has numeric separators, all between two digits as required, returns the literal without them. Separators aren't allowed in legacy octal literals or those with a leading zero (e.g. `0_1`).
+fn without_separators(code: &[u8]) -> Option<(usize, Vec<u8>)> {
+  let radix = match code.get(..2) {
+    Some(b"0x" | b"0X") => 16,
+    Some(b"0o" | b"0O") => 8,
+    Some(b"0b" | b"0B") => 2,
+    _ => 10,
+  };
+  let mut end = if radix != 10 {
+    skip_digits(code, 2, radix)
+  } else {
+    let mut i = skip_digits(code, 0, 10);
+    if code.get(i) == Some(&b'.') {
+      i = skip_digits(code, i + 1, 10);
+    };
+    if matches!(code.get(i), Some(b'e' | b'E')) {
+      let j = i + 1 + usize::from(matches!(code.get(i + 1), Some(b'+' | b'-')));
+      if code.get(j).is_some_and(u8::is_ascii_digit) {
+        i = skip_digits(code, j, 10);
+      };
+    };
+    i
+  };
+  if code.get(end) == Some(&b'n') {
+    end += 1;
+  };
+  let literal = &code[..end];
+  if radix == 10 && literal[0] == b'0' && matches!(literal.get(1), Some(b'0'..=b'9' | b'_')) {
+    return None;
+  };
+  let is_digit = |c: Option<&u8>| c.is_some_and(|&c| (c as char).is_digit(radix));
+  let mut stripped = Vec::with_capacity(end);
+  for (i, &c) in literal.iter().enumerate() {
+    if c != b'_' {
+      stripped.push(c);
+    } else if i == 0 || !is_digit(literal.get(i - 1)) || !is_digit(literal.get(i + 1)) {
+      return None;
+    };
+  }
+  (stripped.len() < end).then_some((end, stripped))
+}
+
+// parse-js doesn't lex numeric separators (e.g. `1_000_000`), and lexes the number before the first as a literal and the rest as an identifier. This rewrites each literal with them to the literal without them, followed by spaces so it's the same length (e.g. `1000000  `). Numbers are emitted from their value, and BigInts from the rewritten literal, so separators are also dropped from the output. Invalid separators (e.g. `1__0` or `1_`) are left for the parser to report.
+pub(crate) fn strip_numeric_separators(code: &mut [u8]) {
+  let mut literals = Vec::new();
+  let _ = scan_tokens(code, |typ, range, _| {
+    if matches!(typ, TokenType::LiteralNumber | TokenType::LiteralBigInt)
+      && code.get(range.end) == Some(&b'_')
+    {
+      literals.push(range.start);
+    };
+    Ok::<_, ()>(())
+  });
+  for start in literals {
+    if let Some((end, stripped)) = without_separators(&code[start..]) {
+      code[start..start + stripped.len()].copy_from_slice(&stripped);
+      code[start + stripped.len()..start + end].fill(b' ');
+    };
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::strip_numeric_separators;
+
+  #[test]
+  fn test_strip_numeric_separators() {
+    let strip = |source: &str| {
+      let mut code = source.as_bytes().to_vec();
+      strip_numeric_separators(&mut code);
+      String::from_utf8(code).unwrap()
+    };
+    assert_eq!(strip("1_000_000"), "1000000  ");
+    assert_eq!(strip("1_0.5_0e1_0+a"), "10.50e10   +a");
+    assert_eq!(strip(".5_5"), ".55 ");
+    assert_eq!(strip("0xFF_FFn"), "0xFFFFn ");
+    assert_eq!(strip("0b1_0,0o7_7"), "0b10 ,0o77 ");
+    assert_eq!(strip("1_0..toString()"), "10. .toString()");
+    // They're not separators in identifiers and strings.
+    assert_eq!(strip("a1_0+'1_0'"), "a1_0+'1_0'");
+    // Invalid separators are left as is.
+    for invalid in ["1__0", "1_", "0_1", "1_.5", "1._5", "1e_1", "0x_1"] {
+      assert_eq!(strip(invalid), invalid);
+    }
+  }
+}
@@ -0,0 +1,35 @@
+use crate::diagnostic::Diagnostic;
+use crate::features::FeatureUse;
+use crate::position_map::PositionMap;
+use crate::rename_map::RenameMap;
+use crate::report::MinifyReport;
+
+/// Statistics about a minification, useful for reporting compression summaries.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MinifyStats {
+  /// Size of the source code in bytes.
+  pub input_len: usize,
+  /// Size of the output in bytes, including any banner, footer, and preserved comments.
+  pub output_len: usize,
+  /// Bytes saved by minifying identifier names. This can be negative if expanding shorthand properties (e.g. `{a}` to `{a:b}`) cost more than was saved.
+  pub renaming_savings: isize,
+  /// The original and minified names of all variables, if [MinifyOptions::rename_map](crate::MinifyOptions::rename_map) is enabled.
+  pub renames: Option<RenameMap>,
+  /// Map from positions in the output to the source code, if [MinifyOptions::position_map](crate::MinifyOptions::position_map) is enabled. Positions are offset by any `enclose` wrapper.
+  pub positions: Option<PositionMap>,
+  /// Suspicious but valid code found in the source, if [MinifyOptions::warnings](crate::MinifyOptions::warnings) is enabled, in source order. Also has a warning if [MinifyOptions::verify](crate::MinifyOptions::verify) couldn't check the output.
+  pub warnings: Vec<Diagnostic>,
+  /// The first use of each kind of syntax introduced after ES5, if [MinifyOptions::syntax_features](crate::MinifyOptions::syntax_features) is enabled, in source order.
+  pub syntax_features: Vec<FeatureUse>,
+  /// Code that prevents some minification, with the reason, if [MinifyOptions::bailouts](crate::MinifyOptions::bailouts) is enabled, in source order.
+  pub bailouts: Vec<Diagnostic>,
+  /// Time and memory used by each phase, if [MinifyOptions::report](crate::MinifyOptions::report) is enabled.
+  pub report: Option<MinifyReport>,
+}
+
+impl MinifyStats {
+  /// Bytes saved overall, which can be negative if the output is larger than the input (e.g. when beautifying).
+  pub fn savings(&self) -> isize {
+    self.input_len as isize - self.output_len as isize
+  }
+}
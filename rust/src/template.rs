@@ -0,0 +1,85 @@
+use crate::escape::decode_normalized;
+use memchr::memchr;
+use parse_js::ast::LiteralTemplatePart;
+use parse_js::ast::NodeData;
+use parse_js::ast::Syntax;
+use parse_js::session::Session;
+use parse_js::visit::JourneyControls;
+use parse_js::visit::Visitor;
+
+// The end of the `${` that starts a substitution in a template part from `i`, or the end of `raw` if there's none, which is the end of the template.
+fn part_end(raw: &[u8], mut i: usize) -> usize {
+  while i < raw.len() {
+    match raw[i] {
+      b'\\' => i += 2,
+      b'$' if raw.get(i + 1) == Some(&b'{') => return i,
+      _ => i += 1,
+    };
+  }
+  raw.len()
+}
+
+// The position after the `}` that ends a substitution, given the end of its expression at `i`. Only whitespace, comments, and the closing parentheses of a parenthesised expression (which aren't part of its `loc`) can be in between.
+fn substitution_end(raw: &[u8], mut i: usize) -> Option<usize> {
+  loop {
+    match raw.get(i..)? {
+      [b'}', ..] => return Some(i + 1),
+      [b'/', b'/', rest @ ..] => i += 2 + memchr(b'\n', rest)?,
+      [b'/', b'*', ..] => i += 2 + memchr::memmem::find(&raw[i + 2..], b"*/")? + 2,
+      [c, ..] if c.is_ascii_whitespace() || *c == b')' => i += 1,
+      _ => return None,
+    };
+  }
+}
+
+struct TemplateLineTerminators<'a> {
+  session: &'a Session,
+}
+
+impl<'a> Visitor<'a> for TemplateLineTerminators<'a> {
+  fn on_syntax_down(&mut self, node: &mut NodeData<'a>, _ctl: &mut JourneyControls) {
+    // The template's `loc` is from after the opening backtick to before the closing one.
+    let raw = node.loc.as_slice();
+    let start = node.loc.start();
+    let Syntax::LiteralTemplateExpr { parts } = &mut node.stx else {
+      return;
+    };
+    if memchr(b'\r', raw).is_none() {
+      return;
+    };
+    let mut i = 0;
+    for part in parts.iter_mut() {
+      match part {
+        LiteralTemplatePart::String(value) => {
+          let end = part_end(raw, i);
+          let part_raw = &raw[i..end];
+          if memchr(b'\r', part_raw).is_some() {
+            if let Some(decoded) = decode_normalized(part_raw) {
+              *value = self.session.get_allocator().alloc_str(&decoded);
+            };
+          };
+          i = end;
+        }
+        LiteralTemplatePart::Substitution(sub) => {
+          match sub
+            .loc
+            .end()
+            .checked_sub(start)
+            .and_then(|end| substitution_end(raw, end))
+          {
+            Some(end) => i = end,
+            // The rest of the parts can't be found, such as if a transform has replaced the substitution.
+            None => return,
+          };
+        }
+      };
+    }
+  }
+}
+
+// parse-js keeps `\r\n` line terminators in the values of template literals, but they're read as `\n`, and those after a line continuation aren't part of the value (see `normalize_whitespace`), so this fixes the values of templates with them in a parsed tree. Otherwise, they'd be emitted as a `\r` escape followed by a line break.
+pub fn fix_template_line_terminators<'a>(session: &'a Session, node: &mut NodeData<'a>) {
+  if memchr(b'\r', node.loc.as_slice()).is_some() {
+    TemplateLineTerminators { session }.visit(node);
+  };
+}
@@ -0,0 +1,2262 @@
+use crate::accessor::fix_accessors;
+use crate::diagnostics_to_json;
+use crate::minify;
+use crate::minify_bounded;
+use crate::minify_deep;
+use crate::minify_expression;
+use crate::minify_many;
+use crate::minify_str;
+use crate::minify_to_writer;
+use crate::property_order::preserving_property_order;
+use crate::regex::Regex;
+use crate::render_diagnostic;
+use crate::rewrite_imports;
+use crate::syntax_errors;
+use crate::verify::verify_output;
+use crate::CommentAction;
+use crate::CommentFilter;
+use crate::Diagnostic;
+use crate::Document;
+use crate::Environment;
+use crate::FsProvider;
+use crate::LegalComments;
+use crate::Limit;
+use crate::LimitExceeded;
+use crate::Limits;
+use crate::Minifier;
+use crate::MinifyCache;
+use crate::MinifyError;
+use crate::MinifyOptions;
+use crate::MinifyStats;
+use crate::NameAlphabet;
+use crate::Newline;
+use crate::Obfuscation;
+use crate::OutputCapacity;
+use crate::RenameDecision;
+use crate::Session;
+use crate::Severity;
+use crate::SourceFiles;
+use crate::SourceMap;
+use crate::SourceMapOptions;
+use crate::SourceMappingUrl;
+use crate::SyntaxFeature;
+use crate::Target;
+use crate::TopLevelMode;
+use crate::Verify;
+use crate::WriteError;
+use parse_js::ast::new_node;
+use parse_js::ast::ClassOrObjectMemberValue;
+use parse_js::ast::NodeData;
+use parse_js::ast::ObjectMemberType;
+use parse_js::ast::Syntax;
+use parse_js::visit::Visitor;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+fn check(cfg: &MinifyOptions, src: &str, expected: &str) {
+  let session = Session::new();
+  let mut out = Vec::new();
+  let stats = minify(&session, src.as_bytes(), cfg, &mut out).unwrap();
+  assert_eq!(std::str::from_utf8(&out).unwrap(), expected);
+  check_writer(cfg, src, &out, stats);
+}
+
+// Checks that minify_to_writer writes exactly the same output, and returns the same stats, as minify.
+fn check_writer(cfg: &MinifyOptions, src: &str, expected: &[u8], expected_stats: MinifyStats) {
+  let session = Session::new();
+  let mut out = Vec::new();
+  let stats = minify_to_writer(&session, src.as_bytes(), cfg, &mut out).unwrap();
+  assert_eq!(out, expected);
+  assert_eq!(stats, expected_stats);
+}
+
+#[test]
+fn test_source_mapping_url() {
+  let src = "let a = 1;\n//# sourceMappingURL=a.js.map\n";
+  check(&MinifyOptions::new(), src, "let a=1");
+  check(
+    &MinifyOptions {
+      source_mapping_url: SourceMappingUrl::Preserve,
+      ..MinifyOptions::new()
+    },
+    src,
+    "let a=1\n//# sourceMappingURL=a.js.map",
+  );
+  check(
+    &MinifyOptions {
+      source_mapping_url: SourceMappingUrl::Preserve,
+      ..MinifyOptions::new()
+    },
+    "let a = '//# sourceMappingURL=a.js.map';",
+    "let a=`//# sourceMappingURL=a.js.map`",
+  );
+  check(
+    &MinifyOptions {
+      source_mapping_url: SourceMappingUrl::Rewrite("b.min.js.map".to_string()),
+      ..MinifyOptions::new()
+    },
+    src,
+    "let a=1\n//# sourceMappingURL=b.min.js.map",
+  );
+}
+
+#[test]
+fn test_keep_source_url() {
+  let src = "let a = 1; // x\n//# sourceURL=a.js\n//# sourceMappingURL=a.js.map\n";
+  check(&MinifyOptions::new(), src, "let a=1");
+  check(
+    &MinifyOptions::new().keep_source_url(true),
+    src,
+    "let a=1\n//# sourceURL=a.js",
+  );
+  check(
+    &MinifyOptions::new()
+      .keep_source_url(true)
+      .source_mapping_url(SourceMappingUrl::Preserve)
+      .footer("f()"),
+    src,
+    "let a=1\nf()\n//# sourceURL=a.js\n//# sourceMappingURL=a.js.map",
+  );
+  check(
+    &MinifyOptions::new().keep_source_url(true),
+    "//# sourceURL=a.js\nlet a = 1;",
+    "let a=1",
+  );
+}
+
+#[test]
+fn test_legal_comments() {
+  let src = "/*! a */\nlet a = 1; // @license b\n/* c */ /*! a */\nlet b = 2;\n";
+  check(
+    &MinifyOptions::new(),
+    src,
+    "let a=1;let b=2\n/*! a */\n// @license b\n/*! a */",
+  );
+  check(
+    &MinifyOptions {
+      legal_comments: LegalComments::Top,
+      ..MinifyOptions::new()
+    },
+    src,
+    "/*! a */\n// @license b\nlet a=1;let b=2",
+  );
+  check(
+    &MinifyOptions {
+      legal_comments: LegalComments::None,
+      ..MinifyOptions::new()
+    },
+    src,
+    "let a=1;let b=2",
+  );
+  check(
+    &MinifyOptions {
+      source_mapping_url: SourceMappingUrl::Preserve,
+      ..MinifyOptions::new()
+    },
+    "let a = 1; /** @preserve */\n//# sourceMappingURL=a.js.map",
+    "let a=1\n/** @preserve */\n//# sourceMappingURL=a.js.map",
+  );
+}
+
+#[test]
+fn test_comment_filter() {
+  let cfg = MinifyOptions {
+    comment_filter: Some(CommentFilter::new(|c| {
+      if c.contains("@ts-check") {
+        CommentAction::Hoist
+      } else if c.contains("istanbul") {
+        CommentAction::Keep
+      } else {
+        CommentAction::Drop
+      }
+    })),
+    ..MinifyOptions::new()
+  };
+  check(
+    &cfg,
+    "/*! a */\nlet a = 1; /* istanbul ignore next */\n// @ts-check\n",
+    "// @ts-check\nlet a=1\n/* istanbul ignore next */",
+  );
+}
+
+#[test]
+fn test_max_line_len() {
+  let cfg = MinifyOptions {
+    max_line_len: Some(20),
+    ..MinifyOptions::new()
+  };
+  check(
+    &cfg,
+    "let a = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10]; f(a, `x\ny`, 'aaaaaaaaaaaaaaaaaaaaaaaaa'); let b = 1",
+    "let a=[1,2,3,4,5,6,\n7,8,9,10];f(a,`x\ny`,\n`aaaaaaaaaaaaaaaaaaaaaaaaa`);\nlet b=1",
+  );
+}
+
+#[test]
+fn test_target() {
+  let src = "function f(a) { return 'x' + a }; g(function (b) { return b })";
+  check(&MinifyOptions::new(), src, "var f=(a=>`x`+ a);g((a=>a))");
+  check(
+    &MinifyOptions {
+      target: Target::Es5,
+      ..MinifyOptions::new()
+    },
+    src,
+    "function f(a){return \"x\"+ a}g(function(a){return a})",
+  );
+}
+
+#[cfg(feature = "downlevel")]
+#[test]
+fn test_downlevel() {
+  let cfg = MinifyOptions {
+    target: Target::Es5,
+    ..MinifyOptions::new()
+  };
+  check(
+    &cfg,
+    "let x = 1; const o = {x, f() { return `${x}!` }}; g((a, b = 2) => a + b + this.y); { let z = `a${x}b${o}`; h(z) } function i(c) { { let d = c; h(d) } return c }",
+    "function i(a){{var b=a;h(b)}return a}var x=1;var o={x:x,f:(function(){return \"\".concat(x,\"!\")})};g((function(a,b){if(b===void 0)b=2;return a+ b+ this.y}).bind(this));{var z=\"a\".concat(x,\"b\",o);h(z)}",
+  );
+  check(
+    &cfg,
+    "function f() { 'use strict'; return (a) => arguments[a] } for (let i = 0; i < 3; i++) g(i)",
+    "function f(){\"use strict\";var _arguments=arguments;return (function(a){return _arguments[a]})}for(var i=0;i<3;i++)g(i)",
+  );
+  let unsupported = |src: &str| {
+    let session = Session::new();
+    match minify(&session, src.as_bytes(), &cfg, &mut Vec::new()) {
+      Err(MinifyError::Unsupported(err)) => (err.feature, err.position),
+      res => panic!("expected unsupported syntax, got {:?}", res.map(|_| ())),
+    }
+  };
+  // Each iteration has its own `i`, which `var` can't express.
+  assert_eq!(
+    unsupported("let fs = []; for (let i = 0; i < 3; i++) fs.push(() => i)"),
+    (SyntaxFeature::LetConst, 22)
+  );
+  assert_eq!(
+    unsupported("g(() => arguments)"),
+    (SyntaxFeature::ArrowFunction, 2)
+  );
+  assert_eq!(unsupported("class A {}"), (SyntaxFeature::Class, 0));
+}
+
+#[test]
+fn test_hashbang() {
+  let src = "#!/usr/bin/env node\r\n/*! a */\nlet x = 1; f(x)";
+  let cfg = MinifyOptions::new();
+  check(&cfg, src, "let x=1;f(x)\n/*! a */");
+  let cfg = cfg.keep_hashbang(true).banner("// banner");
+  check(
+    &cfg,
+    src,
+    "#!/usr/bin/env node\n// banner\nlet x=1;f(x)\n/*! a */",
+  );
+  check(&cfg, "#!node", "#!node\n// banner\n");
+  // It's only allowed at the very start.
+  let session = Session::new();
+  assert!(matches!(
+    minify(&session, b" #!node\nf()", &cfg, &mut Vec::new()),
+    Err(MinifyError::Syntax(_))
+  ));
+}
+
+#[test]
+fn test_banner_footer() {
+  let src = "/*! legal */ let a = `x`;\n//# sourceMappingURL=a.js.map\n";
+  check(
+    &MinifyOptions::new()
+      .banner("/* built by x */")
+      .footer("console.log(`${done}`)")
+      .source_mapping_url(SourceMappingUrl::Preserve),
+    src,
+    "/* built by x */\nlet a=`x`\n/*! legal */\nconsole.log(`${done}`)\n//# sourceMappingURL=a.js.map",
+  );
+}
+
+#[test]
+fn test_enclose() {
+  let src = "var counter = 0; function increment() { counter++; document.title = counter; } // end";
+  let cfg = MinifyOptions {
+    enclose: Some(vec!["window".to_string(), "document".to_string()]),
+    ..MinifyOptions::new()
+  };
+  check(
+    &cfg,
+    src,
+    "((a,b)=>{var d=(()=>{c++;b.title=c});var c=0})(window,document)",
+  );
+  check(
+    &MinifyOptions {
+      target: Target::Es5,
+      ..cfg.clone()
+    },
+    src,
+    "(function(a,b){function d(){c++;b.title=c}var c=0}).call(this,window,document)",
+  );
+  check(
+    &MinifyOptions {
+      enclose: Some(Vec::new()),
+      target: Target::Es5,
+      ..MinifyOptions::new()
+    },
+    "this.x = 1",
+    "(function(){this.x=1}).call(this)",
+  );
+  // The wrapper doesn't make a top-level `return` valid.
+  let session = Session::new();
+  for src in ["return 5", "if (a) { return }", "return () => 1"] {
+    assert!(matches!(
+      minify(&session, src.as_bytes(), &cfg, &mut Vec::new()),
+      Err(MinifyError::Syntax(_))
+    ));
+  }
+  check(&cfg, "f(() => { return 1 })", "((a,b)=>{f(()=>1)})(window,document)");
+  assert!(matches!(
+    minify(&session, b"return 5", &MinifyOptions::new(), &mut Vec::new()),
+    Err(MinifyError::Syntax(_))
+  ));
+}
+
+#[test]
+fn test_newline() {
+  let cfg = MinifyOptions {
+    newline: Newline::CrLf,
+    beautify: true,
+    banner: Some("// banner".to_string()),
+    source_mapping_url: SourceMappingUrl::Rewrite("a.js.map".to_string()),
+    ..MinifyOptions::new()
+  };
+  check(
+    &cfg,
+    "/*! a\nb */ for (;;) { f(`c\nd`); g() }",
+    "// banner\r\nfor (;;) {\r\n  f(`c\nd`);\r\n  g();\r\n}\r\n/*! a\nb */\r\n//# sourceMappingURL=a.js.map",
+  );
+  check(
+    &MinifyOptions {
+      beautify: false,
+      asi: true,
+      max_line_len: Some(10),
+      ..cfg
+    },
+    "f(1, 2, 3, 4, 5); g()",
+    "// banner\r\nf(1,2,3,4,\r\n5)\r\ng()\r\n//# sourceMappingURL=a.js.map",
+  );
+}
+
+// Path of a file used as input by the benchmarks.
+fn bench_input(name: &str) -> PathBuf {
+  Path::new(env!("CARGO_MANIFEST_DIR"))
+    .join("../bench/_input")
+    .join(name)
+}
+
+#[test]
+fn test_deterministic() {
+  // Hoisted function declarations used to be emitted in hash map iteration order.
+  let src = (0..20)
+    .map(|i| format!("function* f{i}() {{ yield {i} }}"))
+    .collect::<String>();
+  let src = format!("(function () {{ {src} }})()");
+  let expected = format!(
+    "(()=>{{{}}})()",
+    (0..20)
+      .map(|i| format!("function*{}(){{yield {i}}}", (b'a' + i) as char))
+      .collect::<String>()
+  );
+  for _ in 0..5 {
+    check(&MinifyOptions::new(), &src, &expected);
+  }
+  // Every hash map gets different hash keys, so real code minified more than once shows whether anything else depends on iteration order.
+  for name in ["jquery.js", "lodash.js", "react.js"] {
+    let src = std::fs::read(bench_input(name)).unwrap();
+    let minify_once = || {
+      let mut out = Vec::new();
+      minify(&Session::new(), &src, &MinifyOptions::new(), &mut out).unwrap();
+      out
+    };
+    let first = minify_once();
+    for _ in 0..2 {
+      assert!(minify_once() == first, "{name} minified differently");
+    }
+  }
+}
+
+#[test]
+fn test_stats() {
+  let src = "/* x */ function f(first, second) { let third = {first}; return [third, second] }";
+  let session = Session::new();
+  let mut out = Vec::new();
+  let stats = minify(&session, src.as_bytes(), &MinifyOptions::new(), &mut out).unwrap();
+  assert_eq!(out, b"var f=((a,b)=>{let c={first:a};return [c,b]})");
+  assert_eq!(stats, MinifyStats {
+    input_len: src.len(),
+    output_len: out.len(),
+    // Two references each of `first`, `second`, and `third`, minus the shorthand property's new key.
+    renaming_savings: 2 * 4 + 2 * 5 + 2 * 4 - 6,
+    renames: None,
+    positions: None,
+    warnings: Vec::new(),
+    syntax_features: Vec::new(),
+    bailouts: Vec::new(),
+    report: None,
+  });
+  assert_eq!(stats.savings(), 36);
+}
+
+#[test]
+fn test_rename_map() {
+  let src = "let x = 1; function f(first, second) { { let café = first; return [x, second] } }";
+  let session = Session::new();
+  let mut out = Vec::new();
+  let cfg = MinifyOptions::new()
+    .top_level_mode(TopLevelMode::Module)
+    .rename_map(true);
+  let stats = minify(&session, src.as_bytes(), &cfg, &mut out).unwrap();
+  assert_eq!(
+    stats.renames.unwrap().to_json(),
+    [
+      r#"{"scopes":["#,
+      r#"{"parent":null,"type":"Module","renames":[{"original":"x","minified":"a","position":4},{"original":"f","minified":"b","position":20}]},"#,
+      r#"{"parent":0,"type":"NonArrowFunction","renames":[{"original":"first","minified":"b","position":22},{"original":"second","minified":"c","position":29}]},"#,
+      r#"{"parent":1,"type":"Block","renames":[{"original":"café","minified":"d","position":45}]}"#,
+      r#"]}"#,
+    ]
+    .concat()
+  );
+}
+
+#[test]
+fn test_rename_filter() {
+  let cfg = MinifyOptions::new()
+    .top_level_mode(TopLevelMode::Module)
+    .rename_filter(|sym| match sym.name {
+      "keep" => RenameDecision::Keep,
+      "x" => RenameDecision::Rename("a".to_string()),
+      // This would shadow `keep`, so it's minified as usual.
+      "second" => RenameDecision::Rename("keep".to_string()),
+      _ => RenameDecision::Minify,
+    });
+  check(
+    &cfg,
+    "let keep = 1; let x = 2; function f(first, second) { return [keep, x, first, second] }",
+    "var b=((b,c)=>[keep,a,b,c]);let keep=1;let a=2",
+  );
+}
+
+#[test]
+fn test_keep_names() {
+  let cfg = MinifyOptions::new()
+    .keep_names([
+      Regex::new("^on[A-Z]").unwrap(),
+      Regex::new("Handler$").unwrap(),
+    ])
+    .rename_filter(|_| RenameDecision::Minify);
+  check(
+    &cfg,
+    "(() => { function onClick() {} let clickHandler = 1, once = 2; f(onClick, clickHandler, once) })()",
+    "(()=>{var onClick=(()=>{});let clickHandler=1,a=2;f(onClick,clickHandler,a)})()",
+  );
+}
+
+#[test]
+fn test_name_alphabet() {
+  let src = "(() => { let v1 = 1, v2 = 2, v3 = 3, v4 = 4, v5 = 5; f(v1, v2, v3, v4, v5) })()";
+  // `do` is a keyword, so it's skipped.
+  check(
+    &MinifyOptions::new().name_alphabet(NameAlphabet::new("do").unwrap()),
+    src,
+    "(()=>{let d=1,o=2,dd=3,od=4,oo=5;f(d,o,dd,od,oo)})()",
+  );
+  // `b` and `f` are the most frequent, but `f` is a global used in the same scope.
+  check(
+    &MinifyOptions::new().name_alphabet(NameAlphabet::new("abf").unwrap().by_frequency()),
+    "(() => { let first = 1, second = 2; f(first, second, 'bbb') })()",
+    "(()=>{let b=1,a=2;f(b,a,`bbb`)})()",
+  );
+  // JSX components can't start with a lowercase letter.
+  check(
+    &MinifyOptions::new().name_alphabet(NameAlphabet::new("ab").unwrap()),
+    "(() => { let Component = 1, value = 2; f(<Component />, value) })()",
+    "(()=>{let A=1,a=2;f(<A/>,a)})()",
+  );
+  assert!(NameAlphabet::new("").is_err());
+  assert!(NameAlphabet::new("aba").is_err());
+  assert!(NameAlphabet::new("é").is_err());
+  assert_eq!("a0".parse::<NameAlphabet>(), NameAlphabet::new("a0"));
+}
+
+#[test]
+fn test_position_map() {
+  let src = "function add(first, second) {\n  return first + second;\n}\nadd(1, 'x');";
+  for (cfg, expected) in [
+    (
+      MinifyOptions::new().position_map(true),
+      "var add=((a,b)=>a+ b);add(1,`x`)",
+    ),
+    (
+      MinifyOptions::new().position_map(true).max_line_len(16),
+      "var add=((a,\nb)=>a+ b);add(1,\n`x`)",
+    ),
+  ] {
+    let session = Session::new();
+    let mut out = Vec::new();
+    let stats = minify(&session, src.as_bytes(), &cfg, &mut out).unwrap();
+    assert_eq!(std::str::from_utf8(&out).unwrap(), expected);
+    let positions = stats.positions.as_ref().unwrap();
+    let source_of = |pos: usize| &src[positions.source_range(pos).unwrap()];
+    assert_eq!(source_of(expected.find('b').unwrap()), "second");
+    assert_eq!(source_of(expected.rfind('b').unwrap()), "second");
+    assert_eq!(source_of(expected.find('+').unwrap()), "first + second");
+    assert_eq!(source_of(expected.find('x').unwrap()), "'x'");
+    let x = expected.find('x').unwrap();
+    let line = expected[..x].matches('\n').count() + 1;
+    let column = x - expected[..x].rfind('\n').map_or(0, |i| i + 1) + 1;
+    assert_eq!(
+      &src[positions.source_range_at(&out, line, column).unwrap()],
+      "'x'"
+    );
+    check_writer(&cfg, src, &out, stats);
+  }
+}
+
+#[test]
+fn test_minify_to_writer_large() {
+  // Large enough that the output is written in several chunks.
+  let src = "/*! legal */\n".to_string()
+    + &"if (x) { g(x, [1, 2, 3], 'some string', 123456); }\n".repeat(10000);
+  for cfg in [
+    MinifyOptions::new(),
+    MinifyOptions {
+      beautify: true,
+      max_line_len: Some(40),
+      legal_comments: LegalComments::Top,
+      ..MinifyOptions::new()
+    },
+  ] {
+    let session = Session::new();
+    let mut out = Vec::new();
+    let stats = minify(&session, src.as_bytes(), &cfg, &mut out).unwrap();
+    assert!(out.len() > 300000);
+    check_writer(&cfg, &src, &out, stats);
+  }
+}
+
+#[test]
+fn test_diagnostics_json() {
+  let src = "let a = ;\nlet b = \"\n";
+  let session = Session::new();
+  let diagnostics = syntax_errors(&session, src.as_bytes(), TopLevelMode::Global)
+    .iter()
+    .map(|err| Diagnostic::from_syntax_error(src.as_bytes(), err))
+    .collect::<Vec<_>>();
+  assert_eq!(
+    diagnostics_to_json(&diagnostics),
+    [
+      r#"[{"code":"E_EXPECTED_SYNTAX","message":"expected expression operand but found Semicolon","severity":"error","span":{"start":8,"end":9,"line":1,"column":9}},"#,
+      r#"{"code":"E_LINE_TERMINATOR_IN_STRING","message":"line break in string","severity":"error","span":{"start":19,"end":20,"line":2,"column":10}}]"#,
+    ]
+    .concat()
+  );
+}
+
+#[test]
+fn test_warnings() {
+  let src = "function f(a) { return eval(a) }\nconst o = /*#__PURE__*/ g({ a: 1, get b() {}, set b(v) {}, a, 'c': 2, c: 3 });\n{ let eval = f; eval(o) }";
+  let session = Session::new();
+  let mut out = Vec::new();
+  let cfg = MinifyOptions::new().warnings(true);
+  let stats = minify(&session, src.as_bytes(), &cfg, &mut out).unwrap();
+  let warnings = stats
+    .warnings
+    .iter()
+    .map(|w| (w.code, w.line, w.column))
+    .collect::<Vec<_>>();
+  assert_eq!(warnings, [
+    ("W_DIRECT_EVAL", 1, 24),
+    ("W_IGNORED_ANNOTATION", 2, 11),
+    ("W_DUPLICATE_KEY", 2, 60),
+    ("W_DUPLICATE_KEY", 2, 71),
+  ]);
+  assert_eq!(
+    render_diagnostic(src.as_bytes(), "src.js", &stats.warnings[2]),
+    [
+      "warning[W_DUPLICATE_KEY]: duplicate key `a` in object literal; only the last value is kept\n",
+      " --> src.js:2:60\n",
+      "  |\n",
+      "2 | const o = /*#__PURE__*/ g({ a: 1, get b() {}, set b(v) {}, a, 'c': 2, c: 3 });\n",
+      "  |                                                            ^\n",
+    ]
+    .concat()
+  );
+}
+
+#[test]
+fn test_bailouts() {
+  let src = "var g = 1;\n(() => {\n  function C() {}\n  new C();\n  function d() {}\n  let o = { f: function () { return this }, h: function () { return arguments } };\n  o.f;\n  d(o);\n})();";
+  let session = Session::new();
+  let mut out = Vec::new();
+  let cfg = MinifyOptions::new().bailouts(true);
+  let stats = minify(&session, src.as_bytes(), &cfg, &mut out).unwrap();
+  let bailouts = stats
+    .bailouts
+    .iter()
+    .map(|b| (b.code, b.line, b.column))
+    .collect::<Vec<_>>();
+  assert_eq!(
+    std::str::from_utf8(&out).unwrap(),
+    "var g=1;(()=>{function a(){}var b=(()=>{});new a();let c={f:function(){return this},h:function(){return arguments}};c.f;b(c)})()"
+  );
+  assert_eq!(bailouts, [
+    ("B_GLOBAL_NAME", 1, 5),
+    ("B_CONSTRUCTOR", 3, 3),
+    ("B_USES_THIS", 6, 16),
+    ("B_USES_ARGUMENTS", 6, 48),
+    ("B_PROPERTY_READ", 7, 3),
+  ]);
+  // Nothing is reported for optimisations that are disabled.
+  let cfg = MinifyOptions::new()
+    .bailouts(true)
+    .top_level_mode(TopLevelMode::Module)
+    .target(Target::Es5)
+    .assume_plain_objects(true);
+  let stats = minify(&session, src.as_bytes(), &cfg, &mut Vec::new()).unwrap();
+  assert!(stats.bailouts.is_empty());
+}
+
+#[test]
+fn test_syntax_features() {
+  let src = "var f = function (a = 1, ...b) { return a ** b.length };\nlet { c, ...d } = f?.(`x`);\nclass E { #g = 1; static async *h() { try {} catch { this.#g ??= 2n } } }\nfor (const i of [...d]) i;";
+  let session = Session::new();
+  let mut out = Vec::new();
+  let cfg = MinifyOptions::new()
+    .top_level_mode(TopLevelMode::Module)
+    .syntax_features(true);
+  let stats = minify(&session, src.as_bytes(), &cfg, &mut out).unwrap();
+  let features = stats
+    .syntax_features
+    .iter()
+    .map(|f| (f.feature.as_str(), f.line, f.column))
+    .collect::<Vec<_>>();
+  assert_eq!(features, [
+    ("default-parameter", 1, 19),
+    ("rest-spread", 1, 29),
+    ("exponentiation", 1, 41),
+    ("let-const", 2, 1),
+    ("destructuring", 2, 5),
+    ("object-rest-spread", 2, 5),
+    ("optional-chaining", 2, 19),
+    ("template-literal", 2, 24),
+    ("class", 3, 1),
+    ("private-name", 3, 1),
+    ("class-field", 3, 1),
+    ("async-iteration", 3, 1),
+    ("optional-catch-binding", 3, 52),
+    ("logical-assignment", 3, 54),
+    ("bigint", 3, 66),
+    ("for-of", 4, 1),
+  ]);
+  assert_eq!(
+    stats.syntax_features.iter().map(|f| f.feature.target()).max(),
+    Some(Target::Es2022)
+  );
+  let stats = minify(&session, b"var a = { b: function () {} }", &cfg, &mut out).unwrap();
+  assert!(stats.syntax_features.is_empty());
+  let stats = minify(&session, b"var a = { b() {} }", &cfg, &mut out).unwrap();
+  assert_eq!(stats.syntax_features[0].feature, SyntaxFeature::ShorthandProperty);
+}
+
+#[test]
+fn test_minifier() {
+  let cfg = MinifyOptions::new().legal_comments(LegalComments::Top);
+  let mut minifier = Minifier::new(cfg.clone());
+  for src in [
+    "/*! a */ let first = 1; f(first)",
+    "function g(x) { return x * 2 }",
+    "let = ;",
+    "/*! b */ const o = { key: 1 }; h(o.key)",
+  ] {
+    let session = Session::new();
+    let mut out = Vec::new();
+    let expected = minify(&session, src.as_bytes(), &cfg, &mut out).map(|stats| (out, stats));
+    let mut out = Vec::new();
+    let res = minifier
+      .minify(src.as_bytes(), &mut out)
+      .map(|stats| (out, stats));
+    assert_eq!(res, expected);
+    let mut out = Vec::new();
+    let res = minifier
+      .minify_to_writer(src.as_bytes(), &mut out)
+      .map(|stats| (out, stats));
+    assert_eq!(res.ok(), expected.ok());
+  }
+}
+
+#[test]
+fn test_output_capacity() {
+  let src = "function f(long_name) { return long_name + 1 } f(1);\n".repeat(100);
+  let session = Session::new();
+  let run = |cfg: MinifyOptions| {
+    let mut out = Vec::new();
+    minify(&session, src.as_bytes(), &cfg, &mut out).unwrap();
+    out
+  };
+  let out = run(MinifyOptions::new());
+  assert!(out.capacity() >= out.len());
+  assert!(out.capacity() <= src.len());
+  let exact = run(MinifyOptions::new().output_capacity(OutputCapacity::Exact(100_000)));
+  assert_eq!(exact, out);
+  assert!(exact.capacity() >= 100_000);
+  let shrunk = run(
+    MinifyOptions::new()
+      .output_capacity(OutputCapacity::Exact(100_000))
+      .shrink_output(true),
+  );
+  assert_eq!(shrunk, out);
+  assert_eq!(shrunk.capacity(), shrunk.len());
+  assert_eq!(
+    run(MinifyOptions::new().output_capacity(OutputCapacity::Grow)),
+    out
+  );
+}
+
+#[test]
+fn test_minify_cache() {
+  let dir = std::env::temp_dir().join(format!("minify-js-cache-{}", std::process::id()));
+  let _ = std::fs::remove_dir_all(&dir);
+  let session = Session::new();
+  let run = |cache: &mut MinifyCache, src: &str, cfg: &MinifyOptions| {
+    let mut out = b"/* existing */".to_vec();
+    let stats = cache
+      .minify(&session, src.as_bytes(), cfg, &mut out)
+      .unwrap();
+    (String::from_utf8(out).unwrap(), stats.output_len)
+  };
+  let src = "function f(long) { return long + 1 } f(1)";
+  let cfg = MinifyOptions::new();
+  let expected = ("/* existing */var f=(a=>a+ 1);f(1)".to_string(), 20);
+
+  let mut cache = MinifyCache::with_dir(&dir);
+  assert_eq!(run(&mut cache, src, &cfg), expected);
+  assert_eq!(run(&mut cache, src, &cfg), expected);
+  assert_eq!((cache.hits(), cache.misses()), (1, 1));
+  // Different options or code are different entries.
+  let beautified = run(&mut cache, src, &MinifyOptions::new().beautify(true));
+  assert_ne!(beautified, expected);
+  assert_eq!(run(&mut cache, "f(2)", &cfg).0, "/* existing */f(2)");
+  assert_eq!((cache.hits(), cache.misses()), (1, 3));
+  // Options that add to the stats aren't cached.
+  let cfg_rename_map = MinifyOptions::new().rename_map(true);
+  run(&mut cache, src, &cfg_rename_map);
+  run(&mut cache, src, &cfg_rename_map);
+  assert_eq!((cache.hits(), cache.misses()), (1, 5));
+
+  // A new cache with the same directory reads the entries written by the first.
+  let mut cache = MinifyCache::with_dir(&dir);
+  assert_eq!(run(&mut cache, src, &cfg), expected);
+  assert_eq!(
+    run(&mut cache, src, &MinifyOptions::new().beautify(true)),
+    beautified
+  );
+  assert_eq!((cache.hits(), cache.misses()), (2, 0));
+  // Errors aren't cached.
+  let err = cache.minify(&session, b"let = ;", &cfg, &mut Vec::new());
+  assert!(err.is_err());
+  assert_eq!((cache.hits(), cache.misses()), (2, 1));
+  std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_minify_bounded() {
+  let run = |src: &str, cfg: &MinifyOptions, batch_len: usize| {
+    let mut out = Vec::new();
+    let stats = minify_bounded(src.as_bytes(), cfg, batch_len, &mut out).unwrap();
+    assert_eq!(stats.input_len, src.len());
+    assert_eq!(stats.output_len, out.len());
+    String::from_utf8(out).unwrap()
+  };
+  let src = "/*! a */ f(function (long_name) { return long_name });\n// @license b\nfunction f(x) { return x }\n(g)();\n";
+  let cfg = MinifyOptions::new()
+    .banner("/* banner */")
+    .footer("/* footer */");
+  // Functions stay hoisted above calls in earlier batches, and single-line comments don't swallow the next batch.
+  assert_eq!(
+    run(src, &cfg, 1),
+    "/* banner */\nf((a=>a));\n/*! a */\nfunction f(a){return a};\n// @license b\ng()\n/* footer */",
+  );
+  assert_eq!(
+    run(src, &cfg, src.len()),
+    "/* banner */\nfunction f(a){return a}f((a=>a));g()\n/*! a */\n// @license b\n/* footer */",
+  );
+
+  let cfg = MinifyOptions::new().top_level_mode(TopLevelMode::Module);
+  assert_eq!(
+    run(
+      "let long_name = 1;\nexport function f(other) { return long_name + other }",
+      &cfg,
+      1
+    ),
+    "let long_name=1;function f(a){return long_name+ a}export{f as f}",
+  );
+
+  // A batch's strings are only directives if everything before them is too.
+  assert_eq!(
+    run("'a';\n'use strict';\n1;\n'b';\nf()", &MinifyOptions::new(), 1),
+    "\"a\";\"use strict\";f()",
+  );
+  assert_eq!(run("1;\n'use strict';\nf()", &MinifyOptions::new(), 1), "f()");
+
+  let src = "let a = 1;\nlet b = 2;\nlet = 3;\n";
+  let err = minify_bounded(src.as_bytes(), &MinifyOptions::new(), 1, &mut Vec::new()).unwrap_err();
+  let WriteError::Syntax(err) = err else {
+    panic!("expected a syntax error");
+  };
+  assert_eq!(Diagnostic::from_syntax_error(src.as_bytes(), &err).line, 3);
+}
+
+#[test]
+fn test_send_sync() {
+  fn assert_send_sync<T: Send + Sync>() {}
+  assert_send_sync::<MinifyOptions>();
+  assert_send_sync::<MinifyStats>();
+  assert_send_sync::<Diagnostic>();
+  fn assert_send<T: Send>() {}
+  assert_send::<Minifier>();
+
+  // Errors borrow the source, so are converted to diagnostics to move them to another thread.
+  let src = "let a = ;";
+  let err = std::thread::spawn(move || {
+    let session = Session::new();
+    let mut out = Vec::new();
+    minify(&session, src.as_bytes(), &MinifyOptions::new(), &mut out)
+      .map_err(|err| Diagnostic::from_minify_error(src.as_bytes(), &err))
+  })
+  .join()
+  .unwrap()
+  .unwrap_err();
+  assert_eq!(
+    err.to_string(),
+    "error[E_EXPECTED_SYNTAX] at 1:9: expected expression operand but found Semicolon"
+  );
+}
+
+#[cfg(feature = "serialize")]
+#[test]
+fn test_options_serde() {
+  let cfg: MinifyOptions = serde_json::from_str(
+    r#"{"top_level_mode":"module","target":"es2015","newline":"crlf","source_mapping_url":{"rewrite":"a.js.map"},"enclose":["window"],"keep_names":["^on[A-Z]"],"defines":["a.b=1"],"mangle":false}"#,
+  )
+  .unwrap();
+  assert_eq!(cfg.top_level_mode, TopLevelMode::Module);
+  assert_eq!(cfg.target, Target::Es2015);
+  assert_eq!(cfg.newline, Newline::CrLf);
+  assert_eq!(
+    cfg.source_mapping_url,
+    SourceMappingUrl::Rewrite("a.js.map".to_string())
+  );
+  assert_eq!(cfg.enclose, Some(vec!["window".to_string()]));
+  assert_eq!(cfg.keep_names[0].as_str(), "^on[A-Z]");
+  assert_eq!(cfg.defines[0].to_string(), "a.b=1");
+  assert!(!cfg.mangle);
+  assert_eq!(cfg.legal_comments, LegalComments::Eof);
+  let json = serde_json::to_string(&cfg).unwrap();
+  let roundtrip: MinifyOptions = serde_json::from_str(&json).unwrap();
+  assert_eq!(serde_json::to_string(&roundtrip).unwrap(), json);
+}
+
+#[test]
+fn test_mangle_drop_console_defines() {
+  use crate::Define;
+
+  let src = "(() => { let value = g(); console.log(value); let x = console.info(value) ?? DEBUG; g(x, value) })()";
+  check(
+    &MinifyOptions::new(),
+    src,
+    "(()=>{let a=g();console.log(a);let b=console.info(a)??DEBUG;g(b,a)})()",
+  );
+  check(
+    &MinifyOptions::new().mangle(false),
+    src,
+    "(()=>{let value=g();console.log(value);let x=console.info(value)??DEBUG;g(x,value)})()",
+  );
+  check(
+    &MinifyOptions::new()
+      .drop_console(true)
+      .defines([Define::new("DEBUG", "false").unwrap()]),
+    src,
+    "(()=>{let a=g();let b=void 0??!1;g(b,a)})()",
+  );
+  // A variable named `console` isn't the global.
+  check(
+    &MinifyOptions::new().drop_console(true),
+    "(() => { let console = g(); console.log(1); window.console.log(2) })()",
+    "(()=>{let a=g();a.log(1);window.console.log(2)})()",
+  );
+}
+
+#[test]
+fn test_transforms() {
+  struct ReplaceGlobals;
+
+  impl<'a> Visitor<'a> for ReplaceGlobals {
+    fn on_syntax_up(&mut self, node: &mut NodeData<'a>) {
+      if let Syntax::IdentifierExpr { name } = node.stx {
+        match name.as_slice() {
+          b"__VERSION__" => node.stx = Syntax::LiteralStringExpr { value: "1.2.3" },
+          b"DEBUG" => node.stx = Syntax::LiteralBooleanExpr { value: false },
+          _ => {}
+        };
+      };
+    }
+  }
+
+  let cfg = MinifyOptions::new()
+    .before_minify(|_, node| ReplaceGlobals.visit(node))
+    .after_minify(|session, node| {
+      // Runs after renaming, so sees the minified names.
+      let Syntax::TopLevel { body } = &mut node.stx else {
+        unreachable!();
+      };
+      let loc = body[0].loc;
+      let scope = body[0].scope;
+      body.push(new_node(session, scope, loc, Syntax::DebuggerStmt {}));
+    });
+  check(
+    &cfg,
+    "(() => { let version = __VERSION__; if (DEBUG) log(version) })()",
+    "(()=>{let a=`1.2.3`;!1&&log(a)})();debugger",
+  );
+}
+
+#[test]
+fn test_rewrite_imports() {
+  let mut cfg = MinifyOptions::new().top_level_mode(TopLevelMode::Module);
+  cfg.before_minify.push(rewrite_imports(|specifier, kind| {
+    Some(format!("{:?}:{}\"\n", kind, specifier))
+  }));
+  check(
+    &cfg,
+    "import a from './a.js'; export { b } from './b.js'; f(a)",
+    "import a from\"Static:./a.js\\\"\\n\";export{b as b}from\"Reexport:./b.js\\\"\\n\";f(a)",
+  );
+}
+
+#[test]
+fn test_no_panic_on_unusual_input() {
+  let global = MinifyOptions::new();
+  let module = MinifyOptions::new().top_level_mode(TopLevelMode::Module);
+  check(&global, "if (y) {}", "y&&undefined");
+  check(&global, "if (y) {} else {}", "y?undefined:undefined");
+  // Exporting an undeclared variable, or a block-scoped declaration in a global script.
+  check(&module, "export { zz }", "export{zz as zz}");
+  check(&global, "let a = 1; export { a }", "let a=1;export{a as a}");
+  // An `if` whose branches aren't normalised, as it's not directly in a block.
+  check(
+    &global,
+    "function f() { switch (x) { case 1: if (a) { b(); return c } else d() } }",
+    "var f=(()=>{switch(x){case 1:if(a){b();return c}else d()}})",
+  );
+  // A block wrapping an `else if` has the same scope as the function.
+  check(
+    &global,
+    "function f() { if (a) b(); else if (c) return; else { d() } function g() {} }",
+    "var f=(()=>{var e=(()=>{});if(a)b();else{if(c)return undefined;d()}})",
+  );
+}
+
+#[test]
+fn test_limits() {
+  let cfg = MinifyOptions::new().limits(Limits {
+    max_nesting_depth: Some(100),
+    ..Limits::default()
+  });
+  // This would overflow the stack if parsed.
+  let src = format!("{}1{}", "(".repeat(100_000), ")".repeat(100_000));
+  let session = Session::new();
+  let expected = LimitExceeded {
+    limit: Limit::NestingDepth,
+    max: 100,
+    position: 100,
+  };
+  assert_eq!(
+    minify(&session, src.as_bytes(), &cfg, &mut Vec::new()),
+    Err(MinifyError::LimitExceeded(expected))
+  );
+  assert!(matches!(
+    minify_to_writer(&session, src.as_bytes(), &cfg, &mut Vec::new()),
+    Err(WriteError::LimitExceeded(err)) if err == expected
+  ));
+  check(&cfg, "f(((1)))", "f(1)");
+}
+
+#[test]
+fn test_minify_deep() {
+  let cfg = MinifyOptions::new();
+  // Each of these would overflow the stack of a test thread if minified with `minify`.
+  let n = 5_000;
+  let cases = [
+    (
+      format!("x({}1{})", "(".repeat(n), ")".repeat(n)),
+      "x(1)".to_string(),
+    ),
+    (
+      format!("x={}0", "a?b:".repeat(n)),
+      format!("x={}0", "a?b:".repeat(n)),
+    ),
+    (format!("x={}a", "!".repeat(n)), format!("x={}a", "!".repeat(n))),
+    (
+      format!("{}{}", "(function(){".repeat(n), "})();".repeat(n)),
+      format!("{}{}", "(()=>{".repeat(n), "})()".repeat(n)),
+    ),
+  ];
+  for (src, expected) in cases {
+    let mut out = Vec::new();
+    let stats = minify_deep(src.as_bytes(), &cfg, &mut out).unwrap();
+    assert_eq!(std::str::from_utf8(&out).unwrap(), expected);
+    assert_eq!(stats.output_len, out.len());
+  }
+  // Shallow code is minified on the calling thread, and errors are diagnostics.
+  let mut out = Vec::new();
+  minify_deep(b"let a = 1", &cfg, &mut out).unwrap();
+  assert_eq!(out, b"let a=1");
+  let err = minify_deep(b"let a = ", &cfg, &mut Vec::new()).unwrap_err();
+  assert_eq!(err.severity, Severity::Error);
+  let cfg = cfg.limits(Limits {
+    max_nesting_depth: Some(100),
+    ..Limits::default()
+  });
+  let src = "(".repeat(1_000);
+  let err = minify_deep(src.as_bytes(), &cfg, &mut Vec::new()).unwrap_err();
+  assert_eq!(err.code, "E_NESTED_TOO_DEEPLY");
+}
+
+#[test]
+fn test_line_terminators() {
+  let cfg = MinifyOptions::new();
+  check(&cfg, "let a = 1\rf(a) // b\rg(a)", "let a=1;f(a);g(a)");
+  check(&cfg, "let a = 1\u{2028}f(a) // b\u{2029}g(a)", "let a=1;f(a);g(a)");
+  check(&cfg, "f('\u{2028}', `\r`)", "f(`\\u2028`,`\n`)");
+  // Positions are the same as in the source.
+  let session = Session::new();
+  let err = minify(&session, "a\u{2028}b\r)".as_bytes(), &cfg, &mut Vec::new()).unwrap_err();
+  let MinifyError::Syntax(err) = err else {
+    panic!("expected a syntax error");
+  };
+  assert_eq!(err.source.start(), 6);
+  // A `\` before `\r\n` continues the line in strings and templates.
+  check(&cfg, "f('a\\\r\nb', `c\\\r\nd`)", "f(`ab`,`cd`)");
+  // Regexes can't have line terminators, even after a `\`.
+  for source in ["/a\\\r/", "/a\\\r\n/", "/a\\\n/", "/a\u{2028}/"] {
+    let err = minify(&session, source.as_bytes(), &cfg, &mut Vec::new()).unwrap_err();
+    assert!(matches!(err, MinifyError::Syntax(_)), "{source:?}");
+  }
+}
+
+#[test]
+fn test_template_line_terminators() {
+  let cfg = MinifyOptions::new();
+  // CRLF in a template is read as LF, but a `\r` escape is kept.
+  check(
+    &cfg,
+    "f(`a\r\nb`, `c\\r\r\n${d}\r\ne\\\r\nf`, `${(d) /* } */ }\r\n`)",
+    "f(`a\nb`,`c\\r\n${d}\nef`,`${d}\n`)",
+  );
+}
+
+#[test]
+fn test_unicode_identifiers() {
+  let cfg = MinifyOptions::new();
+  check(
+    &cfg,
+    "let 变量 = o.ñ; const é = { ü: 1, ℮() {} }; (() => { let café = 1; f(café, 变量, é) })()",
+    "let 变量=o.ñ;const é={ü:1,℮(){}};(()=>{let a=1;f(a,变量,é)})()",
+  );
+  // Unicode whitespace isn't part of identifiers.
+  check(&cfg, "let a\u{a0}=\u{3000}b", "let a=b");
+  let session = Session::new();
+  for src in [
+    "let a😀 = 1",
+    "o.a€",
+    "o = { ☃: 1 }",
+    "o = { ☃ }",
+    "({ ☃: o } = p)",
+    "class A { ☃ = 1 }",
+    "class A { ☃() {} }",
+    "class A { #☃ = 1 }",
+    "a: for (;;) break a☃",
+    "a: for (;;) continue a☃",
+  ] {
+    assert!(
+      matches!(
+        minify(&session, src.as_bytes(), &cfg, &mut Vec::new()),
+        Err(MinifyError::Syntax(_))
+      ),
+      "{src}"
+    );
+  }
+  // Unicode escapes are decoded.
+  check(
+    &cfg,
+    r"let \u53d8\u91cf = 1; const a = { \u53d8\u91cf, \u0062: b }; f(a.\u0062, caf\u{e9})",
+    "let 变量=1;const a={变量,b:b};f(a.b,café)",
+  );
+}
+
+#[test]
+fn test_legacy_octal() {
+  let cfg = MinifyOptions::new();
+  check(
+    &cfg,
+    r#"f(0755, 089, 00, 0.5, "\07a", "\101\8", "\0")"#,
+    "f(493,89,0,0.5,`\u{7}a`,`A8`,`\0`)",
+  );
+  let session = Session::new();
+  let module = cfg.clone().top_level_mode(TopLevelMode::Module);
+  for (cfg, src) in [
+    (&module, "f(0755)"),
+    (&module, r#"f("\07")"#),
+    (&module, r#"f("\8")"#),
+    (&cfg, "'use strict'; f(089)"),
+    (&cfg, r#"function g() { "use strict"; f("\1") }"#),
+    (&cfg, "class A { m() { f(01) } }"),
+  ] {
+    assert!(
+      matches!(
+        minify(&session, src.as_bytes(), cfg, &mut Vec::new()),
+        Err(MinifyError::Syntax(_))
+      ),
+      "{}",
+      src
+    );
+  }
+  check(&module, r#"f(0, "\0", 0.5)"#, "f(0,`\0`,0.5)");
+}
+
+#[test]
+fn test_bigint() {
+  let cfg = MinifyOptions::new();
+  // Non-decimal literals are written in decimal where it's shorter.
+  check(
+    &cfg,
+    "f(123n, 0xFFn, 0b101n, 0o17n, 0x1n, 0xFFFFFFFFFFFFFFFFFFFFn)",
+    "f(123n,255n,5n,15n,1n,0xFFFFFFFFFFFFFFFFFFFFn)",
+  );
+  // BigInts are never treated as numbers.
+  check(
+    &cfg,
+    "if (0n) f(-1n, !0n, 1n === 1, typeof 1n, 2n ** 64n, `${1n}`)",
+    "0n&&f(-1n,!0n,1n===1,typeof 1n,2n**64n,`${1n}`)",
+  );
+  let session = Session::new();
+  assert!(matches!(
+    minify(&session, b"f(01n)", &cfg, &mut Vec::new()),
+    Err(MinifyError::Syntax(_))
+  ));
+}
+
+#[test]
+fn test_numeric_separators() {
+  let cfg = MinifyOptions::new();
+  check(
+    &cfg,
+    "f(1_000_000, 0xFF_FF, 1_0.5_0, 1e1_0, 0xFF_FFn, 1_000n, 'a_1')",
+    "f(1000000,65535,10.5,10000000000,65535n,1000n,`a_1`)",
+  );
+  let session = Session::new();
+  for src in ["f(1__0)", "f(1_)", "f(0_1)"] {
+    assert!(matches!(
+      minify(&session, src.as_bytes(), &cfg, &mut Vec::new()),
+      Err(MinifyError::Syntax(_))
+    ));
+  }
+}
+
+#[test]
+fn test_environment() {
+  let src = "function f() { window; documnt; process; Math; helper(); helper(); if (typeof fetch2 < 'u') g() }\nnavigator = 1; helper = g;";
+  let warnings = |environment| {
+    let session = Session::new();
+    let cfg = MinifyOptions::new().warnings(true).environment(environment);
+    let stats = minify(&session, src.as_bytes(), &cfg, &mut Vec::new()).unwrap();
+    stats
+      .warnings
+      .iter()
+      .map(|w| (w.code, w.line, w.column))
+      .collect::<Vec<_>>()
+  };
+  // Globals assigned to by the code aren't unknown, and `typeof` checks are allowed.
+  assert_eq!(warnings(Environment::Browser), [
+    ("W_UNKNOWN_GLOBAL", 1, 24),
+    ("W_UNKNOWN_GLOBAL", 1, 33),
+    ("W_UNKNOWN_GLOBAL", 1, 93),
+    ("W_GLOBAL_REASSIGNED", 2, 1),
+  ]);
+  assert_eq!(warnings(Environment::Node), [
+    ("W_UNKNOWN_GLOBAL", 1, 16),
+    ("W_UNKNOWN_GLOBAL", 1, 24),
+    ("W_UNKNOWN_GLOBAL", 1, 93),
+  ]);
+  assert_eq!(warnings(Environment::None), []);
+
+  // Reading known globals has no side effects.
+  check(
+    &MinifyOptions::new().environment(Environment::Worker),
+    "function f() { self; Math; document; return 1 }",
+    "var f=(()=>{document;return 1})",
+  );
+  check(
+    &MinifyOptions::new(),
+    "function f() { self; return 1 }",
+    "var f=(()=>{self;return 1})",
+  );
+  assert_eq!("node".parse(), Ok(Environment::Node));
+}
+
+#[test]
+fn test_verify() {
+  let verify_err = |cfg: &MinifyOptions, src: &str| {
+    let session = Session::new();
+    match minify(&session, src.as_bytes(), cfg, &mut Vec::new()) {
+      Err(MinifyError::Internal(diagnostic)) => diagnostic.code,
+      res => panic!("expected an internal error, got {:?}", res.map(|_| ())),
+    }
+  };
+
+  let cfg = MinifyOptions::new().verify(Verify::Idempotent);
+  check(
+    &cfg,
+    "function f(first, second) { return first ? \"it's\" : `${second}!` } const value = true; f(value, -1)",
+    "var f=((a,b)=>a?`it's`:`${b}!`);const value=!0;f(value,-1)",
+  );
+  check(&cfg, "export const a = 1; export { a as b }", "const a=1;export{a as a,a as b}");
+  // parse-js parses getters and setters as methods, which turning them back into getters and setters fixes.
+  check(
+    &cfg,
+    "o = { get x() { return 1 }, set x(v) { g(v) }, get() {}, set: 1 }; class A extends get { static get [k]() {} a = get\n m() {} }",
+    "o={get x(){return 1},set x(a){g(a)},get(){},set:1};class A extends get{static get[k](){}a=get;m(){}}",
+  );
+
+  // `&&`, `||`, and comma chains built by minifying are nested differently from the same chains parsed.
+  let cfg = MinifyOptions::new().verify(Verify::Reparse);
+  check(&cfg, "function f() { if (a) { if (b) c() } }", "var f=(()=>{a&&b&&c()})");
+  // Output that reparsing found was wrong.
+  check(
+    &cfg,
+    "f(a, (b, c)); [(a, b)]; ({ [(a, b)]: (c, d) }).e",
+    "f(a,(b,c));[(a,b)];({[(a,b)]:(c,d)}.e)",
+  );
+  check(&cfg, "if (c ? x : y) a(); else b()", "(c?x:y)?a():b()");
+  check(
+    &cfg,
+    "if (a) { if (b) { for (;;) f() } } else { g(); h() }",
+    "if(a){if(b)for(;;)f()}else{g();h()}",
+  );
+  check(&cfg, "(() => { f(); var a; })()", "(()=>{f();var a;})()");
+
+  // A transform that writes a tree the emitter can't represent, like a negative number literal, which is parsed as a negation.
+  let cfg = MinifyOptions::new()
+    .verify(Verify::Reparse)
+    .after_minify(|_, node| {
+      struct Negate;
+      impl<'a> Visitor<'a> for Negate {
+        fn on_syntax_up(&mut self, node: &mut NodeData<'a>) {
+          if let Syntax::LiteralNumberExpr { value } = &mut node.stx {
+            value.0 = -value.0;
+          };
+        }
+      }
+      Negate.visit(node);
+    });
+  assert_eq!(verify_err(&cfg, "f(1)"), "E_INTERNAL_MISMATCH");
+
+  // Member kinds, keys, and flags are compared too, such as if the emitter dropped `get` or `static`.
+  let cfg = MinifyOptions::new().verify(Verify::Reparse);
+  for (src, output) in [
+    ("o={get x(){}}", "o={x(){}}"),
+    ("o={async*x(){}}", "o={async x(){}}"),
+    ("o={x:1}", "o={y:1}"),
+    ("class A{static x(){}}", "class A{x(){}}"),
+    ("for(a of b);", "for(a in b);"),
+  ] {
+    let session = Session::new();
+    let parsed = parse_js::parse(&session, src.as_bytes(), TopLevelMode::Global).unwrap();
+    fix_accessors(&session, parsed);
+    let err = verify_output(src.as_bytes(), parsed, output.as_bytes(), &cfg).unwrap_err();
+    assert_eq!(err.code, "E_INTERNAL_MISMATCH");
+    assert_eq!(
+      verify_output(src.as_bytes(), parsed, src.as_bytes(), &cfg),
+      Ok(None)
+    );
+  }
+
+  // Output that parse-js can't parse isn't checked, which is reported as a warning.
+  let cfg = MinifyOptions::new()
+    .verify(Verify::Reparse)
+    .after_minify(|session, node| {
+      let Syntax::TopLevel { body } = &mut node.stx else {
+        unreachable!();
+      };
+      let (loc, scope) = (body[0].loc, body[0].scope);
+      let module = new_node(session, scope, loc, Syntax::LiteralStringExpr { value: "a" });
+      let expression = new_node(session, scope, loc, Syntax::ImportExpr { module });
+      body.push(new_node(session, scope, loc, Syntax::ExpressionStmt { expression }));
+    });
+  let session = Session::new();
+  let mut out = Vec::new();
+  let stats = minify(&session, b"f()", &cfg, &mut out).unwrap();
+  assert_eq!(out, b"f();import(`a`)");
+  assert_eq!(
+    stats.warnings.iter().map(|w| w.code).collect::<Vec<_>>(),
+    ["W_VERIFY_SKIPPED"]
+  );
+
+  // Transforms must also be idempotent.
+  let cfg = MinifyOptions::new().after_minify(|session, node| {
+    let Syntax::TopLevel { body } = &mut node.stx else {
+      unreachable!();
+    };
+    let (loc, scope) = (body[0].loc, body[0].scope);
+    body.push(new_node(session, scope, loc, Syntax::DebuggerStmt {}));
+  });
+  check(&cfg.clone().verify(Verify::Reparse), "f()", "f();debugger");
+  assert_eq!(
+    verify_err(&cfg.verify(Verify::Idempotent), "f()"),
+    "E_INTERNAL_NOT_IDEMPOTENT"
+  );
+}
+
+#[test]
+fn test_verify_bench_inputs() {
+  // They're large, so minify them on separate threads.
+  let cfg = MinifyOptions::new().verify(Verify::Reparse);
+  std::thread::scope(|s| {
+    for entry in std::fs::read_dir(bench_input("")).unwrap() {
+      let path = entry.unwrap().path();
+      let cfg = &cfg;
+      s.spawn(move || {
+        let src = std::fs::read(&path).unwrap();
+        if let Err(err) = minify(&Session::new(), &src, cfg, &mut Vec::new()) {
+          panic!("{}: {err}", path.display());
+        };
+      });
+    }
+  });
+}
+
+#[test]
+fn test_cancel() {
+  let cancel = Arc::new(AtomicBool::new(false));
+  let cfg = MinifyOptions::new().cancel(cancel.clone());
+  check(&cfg, "let a = 1", "let a=1");
+  cancel.store(true, Ordering::Relaxed);
+  let session = Session::new();
+  assert_eq!(
+    minify(&session, b"let a = 1", &cfg, &mut Vec::new()),
+    Err(MinifyError::Cancelled)
+  );
+
+  // Cancel partway through minifying, as another thread would.
+  let cancel = Arc::new(AtomicBool::new(false));
+  let cancel_in_transform = cancel.clone();
+  let cfg = MinifyOptions::new()
+    .cancel(cancel.clone())
+    .before_minify(move |_, _| cancel_in_transform.store(true, Ordering::Relaxed));
+  assert!(matches!(
+    minify_to_writer(&session, b"let a = 1", &cfg, &mut Vec::new()),
+    Err(WriteError::Cancelled)
+  ));
+}
+
+#[test]
+fn test_report() {
+  let session = Session::new();
+  let source = b"let a = 1; a";
+  let stats = minify(&session, source, &MinifyOptions::new(), &mut Vec::new()).unwrap();
+  assert_eq!(stats.report, None);
+
+  let cfg = MinifyOptions::new()
+    .report(true)
+    .warnings(true)
+    .before_minify(|_, _| {});
+  let stats = minify(&session, source, &cfg, &mut Vec::new()).unwrap();
+  let report = stats.report.unwrap();
+  assert_eq!(
+    report.phases.iter().map(|p| p.name).collect::<Vec<_>>(),
+    [
+      "check_limits",
+      "parse",
+      "collect_warnings",
+      "before_minify",
+      "pass1",
+      "pass2",
+      "minify_names",
+      "pass3",
+      "emit",
+    ]
+  );
+  assert!(report.phase("parse").unwrap().allocated > 0);
+  assert_eq!(report.phase("check_limits").unwrap().allocated, 0);
+  assert_eq!(report.phase("after_minify"), None);
+  assert!(report.total_allocated() >= report.phase("parse").unwrap().allocated);
+
+  let mut minifier = Minifier::new(cfg);
+  let stats = minifier.minify_to_writer(source, &mut Vec::new()).unwrap();
+  assert_eq!(stats.report.unwrap().phases.last().unwrap().name, "emit");
+}
+
+#[test]
+fn test_minifier_capacity() {
+  let src = b"function f(first, second) { return first.map(x => x + second) } f([1, 2], 3);";
+  let mut minifier = Minifier::new(MinifyOptions::new());
+  assert_eq!(minifier.capacity(), 0);
+  let mut expected = Vec::new();
+  minifier.minify(src, &mut expected).unwrap();
+  // The memory kept can settle into fewer, larger chunks over the first calls.
+  for _ in 0..2 {
+    minifier.minify(src, &mut Vec::new()).unwrap();
+  }
+  let warm = minifier.capacity();
+  assert!(warm > 0);
+  for _ in 0..3 {
+    let mut out = Vec::new();
+    minifier.minify(src, &mut out).unwrap();
+    assert_eq!(out, expected);
+    assert_eq!(minifier.capacity(), warm);
+  }
+
+  minifier.shrink_to(0);
+  assert_eq!(minifier.capacity(), 0);
+
+  let mut minifier = Minifier::with_capacity(MinifyOptions::new(), 1 << 16);
+  let capacity = minifier.capacity();
+  assert!(capacity >= 1 << 16);
+  let mut out = Vec::new();
+  minifier.minify_to_writer(src, &mut out).unwrap();
+  assert_eq!(out, expected);
+  assert_eq!(minifier.capacity(), capacity);
+  minifier.shrink_to(1 << 20);
+  assert_eq!(minifier.capacity(), capacity);
+}
+
+#[test]
+fn test_minify_str() {
+  let session = Session::new();
+  let cfg = MinifyOptions::new().max_line_len(8);
+  assert_eq!(
+    minify_str(&session, "{ let s = 'é' + `\u{2028}`; f(s, /ü/u) }", &cfg),
+    Ok("{\nlet a=`é`+ `\\u2028`;\nf(a,\n/ü/u)}".to_string())
+  );
+  assert!(minify_str(&session, "let = ;", &cfg).is_err());
+  let mut minifier = Minifier::new(MinifyOptions::new().beautify(true));
+  assert_eq!(minifier.minify_str("/* ü */ f('é')").unwrap(), "f(`é`);");
+}
+
+#[test]
+fn test_minify_expression() {
+  let check_expression = |cfg: &MinifyOptions, src: &str, expected: &str| {
+    let session = Session::new();
+    let mut out = Vec::new();
+    let stats = minify_expression(&session, src.as_bytes(), cfg, &mut out).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), expected);
+    assert_eq!(stats.input_len, src.len());
+    assert_eq!(stats.output_len, expected.len());
+  };
+  let cfg = MinifyOptions::new();
+  check_expression(&cfg, "  a + 'b' // c\n", "a+ `b`");
+  check_expression(
+    &cfg,
+    "{ a: 1, 'b': function () { return 2; } }",
+    "{a:1,'b':(()=>2)}",
+  );
+  check_expression(
+    &cfg,
+    "function (x) { let y = x; return y; }",
+    "(a=>{let b=a;return b})",
+  );
+  check_expression(&cfg, "(a, b)", "a,b");
+  check_expression(&cfg.clone().safe_concat(true), "(() => 1)()", "(()=>1)()");
+  check_expression(
+    &cfg.clone().obfuscation(Obfuscation::all(1)),
+    "a.b",
+    "a[`b`]",
+  );
+  let session = Session::new();
+  for src in ["", "a;", "a; b", "let a = 1", "a)"] {
+    assert!(minify_expression(&session, src.as_bytes(), &cfg, &mut Vec::new()).is_err());
+  }
+}
+
+#[test]
+fn test_source_map() {
+  // Columns are in UTF-16 code units.
+  let src = "let 𝑥 = 1;\nf(𝑥,\n  'x');";
+  let session = Session::new();
+  let mut out = Vec::new();
+  let cfg = MinifyOptions::new().position_map(true).max_line_len(12);
+  let stats = minify(&session, src.as_bytes(), &cfg, &mut out).unwrap();
+  assert_eq!(std::str::from_utf8(&out).unwrap(), "let 𝑥=1;\nf(𝑥,`x`)");
+  assert_eq!(
+    stats
+      .positions
+      .unwrap()
+      .to_source_map(&out, src.as_bytes(), "a\"b.js"),
+    r#"{"version":3,"sources":["a\"b.js"],"names":[],"mappings":"AAAA,IAAI,GAAK;AACT,EAAE,GACA"}"#
+  );
+}
+
+#[test]
+fn test_minify_many() {
+  let sources = (0..50)
+    .map(|i| {
+      if i % 7 == 3 {
+        "let = ;".to_string()
+      } else {
+        format!("{{ let value{} = {}; f(value{}) }}", i, i, i)
+      }
+    })
+    .collect::<Vec<_>>();
+  let cfg = MinifyOptions::new();
+  for threads in [0, 1, 4, 100] {
+    let results = minify_many(&sources, &cfg, threads);
+    assert_eq!(results.len(), sources.len());
+    for (i, result) in results.into_iter().enumerate() {
+      if i % 7 == 3 {
+        assert_eq!(result.unwrap_err().code, "E_EXPECTED_SYNTAX");
+      } else {
+        let (output, stats) = result.unwrap();
+        assert_eq!(output, format!("{{let a={};f(a)}}", i).into_bytes());
+        assert_eq!(stats.output_len, output.len());
+      };
+    }
+  }
+  assert!(minify_many::<&str>(&[], &cfg, 0).is_empty());
+}
+
+#[test]
+fn test_source_map_options() {
+  let src = "let x = 1;\nf(x);";
+  let session = Session::new();
+  let mut out = Vec::new();
+  let cfg = MinifyOptions::new().position_map(true);
+  let positions = minify(&session, src.as_bytes(), &cfg, &mut out)
+    .unwrap()
+    .positions
+    .unwrap();
+  assert_eq!(std::str::from_utf8(&out).unwrap(), "let x=1;f(x)");
+  let opts = SourceMapOptions {
+    source_root: Some("/src".to_string()),
+    sources_content: true,
+    ..SourceMapOptions::default()
+  };
+  assert_eq!(
+    positions.to_source_map_with(&out, src.as_bytes(), "a.js", &opts),
+    r#"{"version":3,"sourceRoot":"/src","sources":["a.js"],"sourcesContent":["let x = 1;\u000af(x);"],"names":[],"mappings":"AAAA,IAAI,EAAI,EACR,EAAE"}"#
+  );
+
+  // Positions are mapped through the input map, and those it doesn't map are dropped.
+  let opts = SourceMapOptions {
+    sources_content: true,
+    input_map: Some(SourceMap {
+      source_root: Some("lib/".to_string()),
+      sources: vec!["orig.ts".to_string()],
+      names: vec!["value".to_string()],
+      mappings: "AAUA,IAAMA,I;AACJ,EAAEA".to_string(),
+      ..SourceMap::default()
+    }),
+    ..SourceMapOptions::default()
+  };
+  assert_eq!(
+    positions.to_source_map_with(&out, src.as_bytes(), "a.js", &opts),
+    r#"{"version":3,"sources":["lib/orig.ts"],"sourcesContent":[null],"names":["value"],"mappings":"AAUA,IAAMA,IACJ,EAAEA"}"#
+  );
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn test_minify_batch() {
+  use crate::minify_batch;
+  use crate::Define;
+  use crate::NameCache;
+
+  let cfg = MinifyOptions::new()
+    .top_level_mode(TopLevelMode::Module)
+    .warnings(true);
+  let defines = [Define::new("process.env.NODE_ENV", "\"production\"").unwrap()];
+  let mut cache = NameCache::new();
+  cache.names.insert("shared".to_string(), "s".to_string());
+  let modules = [
+    "let shared = process.env.NODE_ENV; let other = 1; f(shared, other); export {shared, other}",
+    "let = ;",
+    "let other = eval('x'); export {other}",
+  ];
+  let out = minify_batch(&modules, &cfg, &defines, &mut cache);
+  assert_eq!(
+    out.outputs[0].as_ref().unwrap().0,
+    b"let s=`production`;let a=1;f(s,a);export{s as shared,a as other}"
+  );
+  assert!(out.outputs[1].is_none());
+  assert!(out.has_errors());
+  let codes = out
+    .diagnostics
+    .iter()
+    .map(|d| (d.module, d.diagnostic.code))
+    .collect::<Vec<_>>();
+  assert_eq!(codes, [(1, "E_EXPECTED_SYNTAX"), (2, "W_DIRECT_EVAL")]);
+  // Names from the first module are added to the cache, and names already in it are kept.
+  assert_eq!(cache.names["shared"], "s");
+  assert_eq!(cache.names["other"], "a");
+  assert!(out.outputs[0].as_ref().unwrap().1.renames.is_none());
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn test_minify_concat() {
+  use crate::minify_concat;
+  use crate::NameCache;
+
+  let session = Session::new();
+  let mut files = SourceFiles::new(&session);
+  files.add("a.js", b"f(1); g(2)");
+  files.add("b.js", b"x = 'caf\xc3\xa9'");
+  files.add("c.js", b"");
+  files.add("d.js", b"[3].map(h)");
+  let cfg = MinifyOptions::new().asi(true);
+  let opts = SourceMapOptions::default();
+  let out = minify_concat(&files, &cfg, &[], &mut NameCache::new(), &opts).unwrap();
+  assert_eq!(
+    std::str::from_utf8(&out.code).unwrap(),
+    "f(1)\ng(2);x=`caf\u{e9}`;[3].map(h);"
+  );
+  assert_eq!(out.stats.len(), 4);
+  assert!(out.stats[0].positions.is_none());
+  let offsets = out
+    .source_map
+    .match_indices(r#""offset":"#)
+    .map(|(i, _)| &out.source_map[i + 9..][..out.source_map[i + 9..].find('}').unwrap() + 1])
+    .collect::<Vec<_>>();
+  assert_eq!(offsets, [
+    r#"{"line":0,"column":0}"#,
+    r#"{"line":1,"column":5}"#,
+    r#"{"line":1,"column":14}"#,
+    // The leading `;` of the last file is dropped, as the file before it already ends with one.
+    r#"{"line":1,"column":13}"#,
+  ]);
+
+  // A file that can't be minified fails the whole concatenation.
+  files.add("e.js", b"let = ;");
+  let err = minify_concat(&files, &cfg, &[], &mut NameCache::new(), &opts).unwrap_err();
+  assert_eq!(err.len(), 1);
+  assert_eq!((err[0].module, err[0].diagnostic.code), (4, "E_EXPECTED_SYNTAX"));
+}
+
+#[test]
+fn test_inline_script() {
+  let cfg = MinifyOptions::new().inline_script(true);
+  check(
+    &cfg,
+    r#"x = "</SCRIPT><!--", y = `</script ${z}<!--`, w = {"</script>": 1}"#,
+    r#"x=`\x3C/SCRIPT>\x3C!--`,y=`\x3C/script ${z}\x3C!--`,w={"\x3C/script>":1}"#,
+  );
+  // Already escaped `<` only needs the `x3C`.
+  check(
+    &cfg,
+    r"r = /<!--[</script]\<!--/",
+    r"r=/\x3C!--[\x3C/script]\x3C!--/",
+  );
+  // Operators are kept apart. `<!--` would start a comment in a global script even outside HTML.
+  check(&cfg, "x = a < /script/.b", "x=a< /script/.b");
+  check(&MinifyOptions::new(), "x = a < !--b", "x=a<! --b");
+  check(
+    &cfg.clone().legal_comments(LegalComments::Eof),
+    "/*! <!-- </script> */ x()",
+    "x()\n/*! <\\!-- <\\/script> */",
+  );
+
+  let session = Session::new();
+  let mut out = Vec::new();
+  crate::minify_inline_script(
+    &session,
+    b"let x = '</script>'; export {x}",
+    crate::ScriptKind::Module,
+    &MinifyOptions::new(),
+    &mut out,
+  )
+  .unwrap();
+  assert_eq!(out, br"let a=`\x3C/script>`;export{a as x}");
+}
+
+#[test]
+fn test_minify_json() {
+  use crate::minify_json;
+  use crate::minify_jsonc;
+
+  assert_eq!(
+    minify_json(b" {\"a\" : {}, \"b\": [ [], \"\\u00e9\\n\", 0, -0.5, 1E+2 ] } \r\n").unwrap(),
+    br#"{"a":{},"b":[[],"\u00e9\n",0,-0.5,1E+2]}"#
+  );
+  assert_eq!(minify_json(b"\"x\"").unwrap(), b"\"x\"");
+  let code = |source: &[u8]| minify_json(source).unwrap_err().code;
+  assert_eq!(code(b""), "E_UNEXPECTED_END");
+  assert_eq!(code(b"[1"), "E_UNEXPECTED_END");
+  assert_eq!(code(b"{\"a\":1,}"), "E_JSON_UNEXPECTED_TOKEN");
+  assert_eq!(code(b"{a:1}"), "E_JSON_UNEXPECTED_TOKEN");
+  assert_eq!(code(b"[1}"), "E_JSON_UNEXPECTED_TOKEN");
+  assert_eq!(code(b"1 2"), "E_JSON_UNEXPECTED_TOKEN");
+  assert_eq!(code(b"['a']"), "E_JSON_INVALID_STRING");
+  assert_eq!(code(b"\"\\x41\""), "E_JSON_INVALID_STRING");
+  assert_eq!(code(b"\"a\tb\""), "E_JSON_INVALID_STRING");
+  for n in ["01", ".5", "5.", "0x10", "1_000", "- 1", "+1", "1n"] {
+    assert!(minify_json(n.as_bytes()).is_err(), "{}", n);
+  }
+  assert_eq!(code(b"[1,\x0b2]"), "E_JSON_UNEXPECTED_CHARACTER");
+  let err = minify_json(b"{\n  // c\n}").unwrap_err();
+  assert_eq!(
+    (err.code, err.line, err.column),
+    ("E_JSON_UNEXPECTED_CHARACTER", 2, 3)
+  );
+
+  assert_eq!(
+    minify_jsonc(b"// c\n{\"a\": /* b */ 1} // d").unwrap(),
+    br#"{"a":1}"#
+  );
+}
+
+#[test]
+fn test_document() {
+  let mut doc = Document::new(
+    b"let a = 1;\nlet b = 2;\n(c)\n/* d */ f(a, b)\nexport const g = `${a}`\n",
+    TopLevelMode::Module,
+  );
+  // Each edit must give the same result as parsing the new code from scratch.
+  let mut edit = |range: std::ops::Range<usize>, text: &str| {
+    let reparsed = doc.edit(range, text.as_bytes());
+    let fresh = Document::new(doc.source(), TopLevelMode::Module);
+    assert_eq!(doc.statements(), fresh.statements());
+    assert_eq!(doc.error(), fresh.error());
+    reparsed
+  };
+  assert_eq!(edit(39..40, "x"), 21..66);
+  // Joins `let b = 2` and `(c)` into a call.
+  assert_eq!(edit(20..21, ""), 0..40);
+  assert_eq!(edit(20..20, ";"), 0..41);
+  // Comments out everything after it, which is an error.
+  assert_eq!(edit(25..25, "/*"), 0..68);
+  assert_eq!(edit(25..27, ""), 0..66);
+  assert_eq!(edit(0..0, "\n"), 0..22);
+  assert_eq!(edit(67..67, "let h = 1"), 26..76);
+  assert_eq!(edit(0..76, ""), 0..0);
+}
+
+#[test]
+fn test_source_files() {
+  let provider = FsProvider::new(env!("CARGO_MANIFEST_DIR"));
+  let session = Session::new();
+  let mut files = SourceFiles::new(&session);
+  let id = files.load(&provider, "Cargo.toml").unwrap();
+  assert!(files.code(id).starts_with(b"[package]"));
+  assert!(files.load(&provider, "missing.js").is_err());
+  assert_eq!(files.len(), 1);
+
+  let id = files.add("a.js", b"let a = 1;");
+  let mut out = Vec::new();
+  let stats = minify(
+    &session,
+    files.code(id),
+    &MinifyOptions::new().position_map(true),
+    &mut out,
+  )
+  .unwrap();
+  assert_eq!(
+    files.source_map(
+      id,
+      stats.positions.as_ref().unwrap(),
+      &out,
+      &SourceMapOptions::default()
+    ),
+    stats
+      .positions
+      .unwrap()
+      .to_source_map(&out, b"let a = 1;", "a.js"),
+  );
+}
+
+#[cfg(feature = "bundle")]
+#[test]
+fn test_bundle() {
+  use crate::bundle;
+  use crate::BundleError;
+  use crate::FileProvider;
+  use crate::ModuleResolver;
+  use std::collections::HashMap;
+
+  struct Files(HashMap<&'static str, &'static str>);
+
+  impl FileProvider for Files {
+    fn read(&self, name: &str) -> Result<Vec<u8>, String> {
+      self
+        .0
+        .get(name)
+        .map(|s| s.as_bytes().to_vec())
+        .ok_or_else(|| "not found".to_string())
+    }
+  }
+
+  impl ModuleResolver for Files {
+    fn resolve(&self, specifier: &str, _importer: &str) -> Result<String, String> {
+      Ok(specifier.trim_start_matches("./").to_string())
+    }
+  }
+
+  let check = |files: &[(&'static str, &'static str)], expected: &str| {
+    let files = Files(files.iter().copied().collect());
+    let session = Session::new();
+    let mut out = Vec::new();
+    // Don't minify, so the result is easier to check.
+    let cfg = MinifyOptions::new().rename_filter(|_| RenameDecision::Keep);
+    bundle(&session, "main.js", &files, &cfg, &mut out).unwrap();
+    assert_eq!(std::str::from_utf8(&out).unwrap(), expected);
+  };
+
+  // Clashing top-level names are renamed apart, including in shorthand properties and patterns.
+  check(
+    &[
+      (
+        "main.js",
+        "import v, { x as y } from './a.js'; let x = 2; export const o = { x, y, v };",
+      ),
+      ("a.js", "let x = 1; const { q: { x: z } = {} } = {}; export { x }; export default function () { return x; }"),
+    ],
+    "var _default$1=(()=>x$1);let x$1=1;const {q:{x:z}={}}={};let x$2=2;const o={x:x$2,y:x$1,v:_default$1};export{o as o}",
+  );
+  // Namespace imports, re-exports, and external imports.
+  check(
+    &[
+      (
+        "main.js",
+        "import * as ns from './a.js'; import React from 'react'; export * from './a.js'; export { b as c } from './b.js'; f(ns, React);",
+      ),
+      ("a.js", "export { b as a } from './b.js'; export default 1 + 1;"),
+      ("b.js", "import { useState } from 'react'; export let b = useState;"),
+    ],
+    "import{useState as useState$1}from\"react\";import{default as React$1}from\"react\";const ns$1=Object.freeze(Object.defineProperties(Object.create(null),{\"default\":{get:()=>_default$1,enumerable:!0},\"a\":{get:()=>b,enumerable:!0}}));let b=useState$1;const _default$1=1+ 1;f(ns$1,React$1);export{b as c,b as a}",
+  );
+
+  let files = Files(
+    [
+      ("main.js", "import { x } from './a.js'; f(x);"),
+      ("a.js", "export const y = 1;"),
+    ]
+    .into_iter()
+    .collect(),
+  );
+  let err = bundle(
+    &Session::new(),
+    "main.js",
+    &files,
+    &MinifyOptions::new(),
+    &mut Vec::new(),
+  )
+  .unwrap_err();
+  assert_eq!(err, BundleError::MissingExport {
+    importer: "main.js".to_string(),
+    module: "a.js".to_string(),
+    name: "x".to_string(),
+  });
+  // A name from more than one `export *` can't be imported, unless they're the same variable, but the others can be.
+  let stars = [
+    ("a.js", "export * from './b.js'; export * from './c.js'; export * from './d.js';"),
+    ("b.js", "export const x = 1; export const y = 2;"),
+    ("c.js", "export const x = 3;"),
+    ("d.js", "export * from './b.js';"),
+  ];
+  let files = Files(
+    [("main.js", "import { x } from './a.js'; f(x);")]
+      .into_iter()
+      .chain(stars)
+      .collect(),
+  );
+  let err = bundle(
+    &Session::new(),
+    "main.js",
+    &files,
+    &MinifyOptions::new(),
+    &mut Vec::new(),
+  )
+  .unwrap_err();
+  assert_eq!(
+    err.to_string(),
+    "`main.js` imports `x` from `a.js`, which exports it from more than one `export *`"
+  );
+  let mut files = Vec::from(stars);
+  files.push(("main.js", "import * as ns from './a.js'; import { y } from './a.js'; export * from './a.js'; f(ns, y);"));
+  check(
+    &files,
+    "const ns$1=Object.freeze(Object.defineProperties(Object.create(null),{\"y\":{get:()=>y$1,enumerable:!0}}));const x$1=1;const y$1=2;const x$2=3;f(ns$1,y$1);export{y$1 as y}",
+  );
+  let files = Files(
+    [("main.js", "import * as m from './missing.js'; f(m);")]
+      .into_iter()
+      .collect(),
+  );
+  let err = bundle(
+    &Session::new(),
+    "main.js",
+    &files,
+    &MinifyOptions::new(),
+    &mut Vec::new(),
+  )
+  .unwrap_err();
+  assert_eq!(err.to_string(), "can't load `missing.js`: not found");
+  let files = Files(
+    [
+      ("main.js", "import * as m from './a.js'; f(m);"),
+      ("a.js", "exports.x = require('x');"),
+    ]
+    .into_iter()
+    .collect(),
+  );
+  let err = bundle(
+    &Session::new(),
+    "main.js",
+    &files,
+    &MinifyOptions::new(),
+    &mut Vec::new(),
+  )
+  .unwrap_err();
+  assert_eq!(
+    err.to_string(),
+    "`a.js` is a CommonJS module, which can't be bundled"
+  );
+}
+
+#[test]
+fn test_purity() {
+  let cfg = MinifyOptions::new();
+  check(
+    &cfg,
+    "function f(a) { 'use strict'; 1; a; [1, a, { k: 2, [3]: a, m() {} }]; `x${1}`; -1; 1 + 2; void 0; typeof b; !a; a === 1; Math.max(1, 2); String(1); new Map; new Set(); (() => a); undefined; if (a) { 1 } else { 2 } return a }",
+    "var f=(a=>{\"use strict\";return a})",
+  );
+  // Top-level statements are dropped too, but directives are kept as string literals.
+  check(
+    &cfg,
+    "'use strict'; 1; `x`; (() => 1); if (1) { 2 } f()",
+    "\"use strict\";f()",
+  );
+  // Strings after other statements aren't directives, so are dropped rather than becoming directives once those are.
+  check(
+    &cfg,
+    "function f() { 1; 'use strict'; return this } function g() { 'a'; 'use strict'; 1; 'b'; { 'c' } return this } 1; 'use strict'; h(f, g)",
+    "function f(){return this}function g(){\"a\";\"use strict\";return this}h(f,g)",
+  );
+  // Globals could be undeclared, property reads could run getters, conversions could run `valueOf`, and mixing BigInts throws.
+  check(
+    &cfg,
+    "function f(a) { b; a.b; -a; a + 1; 1n + 1; Math.max(a); new Map(a); [...a]; g() }",
+    "var f=(a=>{b;a.b;-a;a+ 1;1n+ 1;Math.max(a);new Map(a);[...a];g()})",
+  );
+  // Shadowed globals aren't known to be pure.
+  check(
+    &cfg,
+    "function f() { let Math = g(); Math.max(1) }",
+    "var f=(()=>{let a=g();a.max(1)})",
+  );
+  check(
+    &cfg,
+    "function f(a) { /*#__PURE__*/ g(1); /* @__PURE__ */ new G(a); /*#__PURE__*/ g(h()); if (a) {} }",
+    "var f=(a=>{g(h())})",
+  );
+}
+
+#[test]
+fn test_purity_temporal_dead_zone() {
+  // Reading `let`, `const`, and `class` bindings before they're initialised throws.
+  check(
+    &MinifyOptions::new(),
+    "function f(o) { x; { x } let x = x; x; const { a, b = a } = o; a; b; class C {} C; return () => { x; return x } }",
+    "var f=(a=>{b;b;let b=b;const {a:c,b:d=c}=a;class e{}e;return ()=>{b;return b}})",
+  );
+  check(
+    &MinifyOptions::new(),
+    "function f(a) { switch (a) { case 0: let y = 1; y; case 1: y } for (const z of a) { z } }",
+    "var f=(a=>{switch(a){case 0:let b=1;b;case 1:b}for(const b of a){}})",
+  );
+  // `typeof` doesn't throw for undeclared variables, but does in a temporal dead zone.
+  check(
+    &MinifyOptions::new(),
+    "function f() { typeof undeclared; { typeof x; let x = 1; typeof x; g(x) } }",
+    "var f=(()=>{{typeof a;let a=1;g(a)}})",
+  );
+}
+
+#[test]
+fn test_labels() {
+  // Labels that are targeted must be kept with their whole statement; others are dropped.
+  check(
+    &MinifyOptions::new(),
+    "function f(a) { unused: { g() } loop: for (;;) { if (a) break; g() } used: for (;;) { inner: for (;;) { if (a) continue used; h() } } outer: { if (a) { g(); break outer } h() } x: { y: { break y } } }",
+    "var f=(a=>{g();for(;;){if(a)break;g()};used:for(;;)for(;;){if(a)continue used;h()};outer:{if(a){g();break outer};h()};y:{break y}})",
+  );
+}
+
+#[test]
+fn test_assume_plain_objects() {
+  let src =
+    "function f(a) { a.b; a?.b.c; a[0]; a[g()]; a[b]; void { ...a }; [...a]; g().b; return a }";
+  check(
+    &MinifyOptions::new(),
+    src,
+    "var f=(a=>{a.b;a?.b.c;a[0];a[g()];a[b];void {...a};[...a];g().b;return a})",
+  );
+  check(
+    &MinifyOptions::new().assume_plain_objects(true),
+    src,
+    "var f=(a=>{a[g()];a[b];[...a];g().b;return a})",
+  );
+}
+
+#[test]
+fn test_safe_concat() {
+  let cfg = MinifyOptions::new().safe_concat(true);
+  check(&MinifyOptions::new(), "(g)(); let a = 1", "g();let a=1");
+  check(&cfg, "(g)(); let a = 1", "g();let a=1;");
+  check(&cfg, "[1, 2].map(g); a = `b`", ";[1,2].map(g);a=`b`;");
+  check(&cfg, "try { g() } catch { h() }", "try{g()}catch{h()}");
+  check(&cfg, "while (a);", "while(a);");
+  check(&cfg, "", "");
+  check(&cfg.clone().asi(true), "(a || b)()\nh()", ";(a||b)()\nh();");
+  check(&cfg.clone().beautify(true), "a = 1; (g)()", "a = 1;\ng();");
+  // Output ending with a single-line comment ends with a line terminator, so it can't comment out the next script.
+  check(&cfg, "x = 1 //! legal", "x=1;\n//! legal\n");
+  check(&cfg, "x = 1 /*! legal */", "x=1;\n/*! legal */");
+  check(
+    &cfg.clone().source_mapping_url(SourceMappingUrl::Rewrite("a.map".to_string())),
+    "x = 1",
+    "x=1;\n//# sourceMappingURL=a.map\n",
+  );
+
+  let mut out = Vec::new();
+  minify_bounded(b"(g)();\n[a] = b;\nh()", &cfg, 1, &mut out).unwrap();
+  assert_eq!(out, b"g();[a]=b;h();");
+}
+
+#[test]
+fn test_keep_fn_names() {
+  let src = "(() => { function named() {} let anon = () => {}; let value = 1; g(named, anon, value, named.name) })()";
+  check(
+    &MinifyOptions::new(),
+    src,
+    "(()=>{var a=(()=>{});let b=()=>{};let c=1;g(a,b,c,a.name)})()",
+  );
+  check(
+    &MinifyOptions::new().keep_fn_names(true),
+    src,
+    "(()=>{var named=(()=>{});let anon=()=>{};let a=1;g(named,anon,a,named.name)})()",
+  );
+  // Names are kept even if this code doesn't read them, as code they're passed to could.
+  check(
+    &MinifyOptions::new().keep_fn_names(true),
+    "(() => { let cls = class {}; g(cls, cls.length) })()",
+    "(()=>{let cls=class{};g(cls,cls.length)})()",
+  );
+  check(
+    &MinifyOptions::new().keep_fn_names(true),
+    "(() => { class Foo {} function bar() {} g(Foo, bar) })()",
+    "(()=>{var bar=(()=>{});class Foo{}g(Foo,bar)})()",
+  );
+  check(
+    &MinifyOptions::new().keep_fn_names(true),
+    "(() => { let cls = class {}; g(cls[k]) })()",
+    "(()=>{let cls=class{};g(cls[k])})()",
+  );
+}
+
+#[test]
+fn test_obfuscation() {
+  let properties = MinifyOptions::new().obfuscation(Obfuscation {
+    indirect_properties: true,
+    ..Obfuscation::default()
+  });
+  check(
+    &properties,
+    "a.b.c = d?.e; (f?.g).h; class K { #p; m() { return super.m(this.#p) } }",
+    "a[`b`][`c`]=d?.[`e`];(f?.g)[`h`];class K{#p;m(){return super[`m`](this.#p)}}",
+  );
+
+  let session = Session::new();
+  let src = "function g(first, second) { \"use strict\"; return first.name + second + 'secret' + 'secret' } g(a, import('./c.js')));";
+  let obfuscate = |obfuscation| {
+    let cfg = MinifyOptions::new().obfuscation(obfuscation);
+    minify_str(&session, src, &cfg).unwrap()
+  };
+  let out = obfuscate(Obfuscation::all(1));
+  assert!(out.starts_with("function _0x"));
+  assert!(!out.contains("secret") && !out.contains("name"));
+  // Strings are decoded by index, and each is only stored once.
+  let decoder = &out["function ".len()..out.find('(').unwrap()];
+  assert_eq!(out.matches(&format!("{}(1)", decoder)).count(), 2);
+  assert_eq!(out.matches(&format!("{}(2)", decoder)).count(), 0);
+  // Directives and dynamic import specifiers still work.
+  assert!(out.contains("\"use strict\"") && out.contains("./c.js"));
+  assert_eq!(out, obfuscate(Obfuscation::all(1)));
+  assert_ne!(out, obfuscate(Obfuscation::all(2)));
+
+  let plain = obfuscate(Obfuscation::default());
+  assert!(plain.contains("((a,b)=>"));
+  let shuffled = obfuscate(Obfuscation {
+    shuffle_names: true,
+    seed: 1,
+    ..Obfuscation::default()
+  });
+  assert!(!shuffled.contains("((a,b)=>"));
+}
+
+#[test]
+fn test_property_order() {
+  check(
+    &MinifyOptions::new(),
+    "(() => { let a = 1; g({ a, 1: a, __proto__: a, ['__proto__']: a, ...a, a }) })()",
+    "(()=>{let a=1;g({a,1:a,__proto__:a,[`__proto__`]:a,...a,a})})()",
+  );
+  // Expanding the shorthand would set the prototype instead.
+  check(
+    &MinifyOptions::new(),
+    "(() => { let __proto__ = 1, long_name = 2; g({ __proto__, long_name }) })()",
+    "(()=>{let __proto__=1,a=2;g({__proto__,long_name:a})})()",
+  );
+
+  struct ReverseMembers;
+
+  impl<'a> Visitor<'a> for ReverseMembers {
+    fn on_syntax_up(&mut self, node: &mut NodeData<'a>) {
+      if let Syntax::LiteralObjectExpr { members } = &mut node.stx {
+        members.reverse();
+      };
+    }
+  }
+
+  let reverse = |src: &'static str| {
+    let session = Session::new();
+    let node = parse_js::parse(&session, src.as_bytes(), TopLevelMode::Global).unwrap();
+    preserving_property_order(node, |node| ReverseMembers.visit(node));
+  };
+  // Integer keys are always enumerated in ascending order, and later duplicates don't move a key.
+  reverse("x = { 1: a, '0': b, 2.0: c }");
+  reverse("x = { a, b: 1, a: 2 }");
+  // The order is only checked in debug builds.
+  if cfg!(debug_assertions) {
+    assert!(std::panic::catch_unwind(|| reverse("x = { a, b }")).is_err());
+    assert!(
+      std::panic::catch_unwind(|| reverse("x = { __proto__: a, '__proto__'() {} }")).is_err()
+    );
+  };
+}
+
+#[test]
+fn test_escaped_identifiers() {
+  let message = |src: &str| {
+    let session = Session::new();
+    let errors = syntax_errors(&session, src.as_bytes(), TopLevelMode::Global);
+    Diagnostic::from_syntax_error(src.as_bytes(), &errors[0]).message
+  };
+  assert_eq!(
+    message(r"\u0069f (x) g()"),
+    "escaped `if` is a reserved word, so can't be used as a keyword or identifier",
+  );
+  assert_eq!(
+    message(r"var \u0076ar = 1"),
+    "escaped `var` is a reserved word, so can't be used as a keyword or identifier",
+  );
+  assert_eq!(
+    message(r"let a\u{20} = 1"),
+    "identifier with a Unicode escape for a character that can't be in one (`a `)",
+  );
+  // They can be property names, and escaped words that are only reserved in strict mode code can be identifiers in other code.
+  let cfg = MinifyOptions::new();
+  check(&cfg, r"var l\u0065t = o.\u0069f", "var let=o.if");
+  assert_eq!(message("let a = @"), "unexpected character");
+}
+
+#[test]
+fn test_canonical_escaped_identifiers() {
+  let cfg = MinifyOptions::new();
+  // An escaped name is the same variable as the name unescaped, and is written unescaped.
+  check(
+    &cfg,
+    r"(() => { let \u0061bc = 1; f(abc, a\u{62}c) })(); g(\u0067)",
+    "(()=>{let a=1;f(a,a)})();g(g)",
+  );
+}
+
+#[test]
+fn test_duplicate_proto() {
+  let session = Session::new();
+  let src = "x = { __proto__: a, '__proto__': b };\ny = { __proto__: a, __proto__, ['__proto__']: b, __proto__() {} };\n({ __proto__: a, __proto__: b } = c);";
+  let errors = syntax_errors(&session, src.as_bytes(), TopLevelMode::Global);
+  assert_eq!(errors.len(), 1);
+  let diagnostic = Diagnostic::from_syntax_error(src.as_bytes(), &errors[0]);
+  assert_eq!(
+    (diagnostic.start, diagnostic.message.as_str()),
+    (20, "expected at most one `__proto__` property"),
+  );
+  let mut out = Vec::new();
+  let res = minify(&session, src.as_bytes(), &MinifyOptions::new(), &mut out);
+  assert!(matches!(res, Err(MinifyError::Syntax(_))));
+}
+
+#[test]
+fn test_contextual_keywords() {
+  let session = Session::new();
+  let first_error = |src: &str, mode| {
+    syntax_errors(&session, src.as_bytes(), mode)
+      .first()
+      .map(|err| Diagnostic::from_syntax_error(src.as_bytes(), err))
+      .map(|d| (d.start, d.message))
+  };
+  // `await` is only reserved in modules, and `yield` in strict mode code.
+  let src = "var await = 1; function f(yield) { await: yield }";
+  assert_eq!(first_error(src, TopLevelMode::Global), None);
+  assert_eq!(
+    first_error(src, TopLevelMode::Module),
+    Some((
+      4,
+      "expected identifier other than `await` in a module".to_string()
+    )),
+  );
+  for (src, start) in [
+    ("\"use strict\";\nvar yield;", 18),
+    ("function f() { \"use strict\"; yield: 1 }", 29),
+    ("function yield() { \"use strict\" }", 9),
+    ("x = class { m(y = yield) {} }", 18),
+  ] {
+    assert_eq!(
+      first_error(src, TopLevelMode::Global),
+      Some((
+        start,
+        "expected identifier other than `yield` in strict mode code".to_string()
+      )),
+      "{}",
+      src
+    );
+  }
+  // A string that isn't at the start isn't a directive.
+  assert_eq!(
+    first_error("x;\n\"use strict\";\nvar yield;", TopLevelMode::Global),
+    None
+  );
+  // Property names can be any name.
+  let src = "let x = { await: 1, yield: 2 }; x.await = x.yield;";
+  assert_eq!(first_error(src, TopLevelMode::Module), None);
+  let mut out = Vec::new();
+  let res = minify(
+    &session,
+    b"var await = 1;",
+    &MinifyOptions::new().top_level_mode(TopLevelMode::Module),
+    &mut out,
+  );
+  assert!(matches!(res, Err(MinifyError::Syntax(_))));
+}
@@ -0,0 +1,90 @@
+// Helpers for `tracing` instrumentation that compile to nothing without the `tracing` feature, so call sites don't need their own `cfg` attributes.
+
+// Enters a debug-level span named after the phase until the end of the enclosing block. Fields are given like `tracing::debug_span!`.
+macro_rules! trace_span {
+  ($name:literal $(, $($fields:tt)*)?) => {
+    #[cfg(feature = "tracing")]
+    let _span = tracing::debug_span!($name $(, $($fields)*)?).entered();
+  };
+}
+
+// Records a debug-level event with counters, given like `tracing::debug!`.
+macro_rules! trace_counters {
+  ($($args:tt)*) => {
+    #[cfg(feature = "tracing")]
+    tracing::debug!($($args)*);
+  };
+}
+
+pub(crate) use trace_counters;
+pub(crate) use trace_span;
+
+#[cfg(all(test, feature = "tracing"))]
+mod tests {
+  use crate::minify;
+  use crate::MinifyOptions;
+  use crate::Session;
+  use std::sync::atomic::AtomicU64;
+  use std::sync::atomic::Ordering;
+  use std::sync::Mutex;
+  use tracing::span;
+  use tracing::subscriber::with_default;
+  use tracing::Event;
+  use tracing::Metadata;
+  use tracing::Subscriber;
+
+  // Records the names of spans and the number of events.
+  #[derive(Default)]
+  struct Recorder {
+    spans: Mutex<Vec<&'static str>>,
+    events: AtomicU64,
+  }
+
+  impl Subscriber for &'static Recorder {
+    fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+      true
+    }
+
+    fn new_span(&self, span: &span::Attributes<'_>) -> span::Id {
+      let mut spans = self.spans.lock().unwrap();
+      spans.push(span.metadata().name());
+      span::Id::from_u64(spans.len() as u64)
+    }
+
+    fn record(&self, _span: &span::Id, _values: &span::Record<'_>) {}
+
+    fn record_follows_from(&self, _span: &span::Id, _follows: &span::Id) {}
+
+    fn event(&self, _event: &Event<'_>) {
+      self.events.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn enter(&self, _span: &span::Id) {}
+
+    fn exit(&self, _span: &span::Id) {}
+  }
+
+  #[test]
+  fn test_tracing() {
+    let recorder: &'static Recorder = Box::leak(Box::default());
+    with_default(recorder, || {
+      let session = Session::new();
+      let mut out = Vec::new();
+      minify(&session, b"let a = 1; a", &MinifyOptions::new(), &mut out).unwrap();
+    });
+    assert_eq!(
+      recorder.spans.lock().unwrap().as_slice(),
+      &[
+        "minify",
+        "check_limits",
+        "parse",
+        "pass1",
+        "pass2",
+        "minify_names",
+        "pass3",
+        "emit",
+      ]
+    );
+    assert_eq!(recorder.events.load(Ordering::Relaxed), 3);
+  }
+}
@@ -0,0 +1,530 @@
+use crate::accessor::fix_accessors;
+use crate::cfg::MinifyOptions;
+use crate::cfg::SourceMappingUrl;
+use crate::cfg::Verify;
+use crate::cst::syntax_kind;
+use crate::diagnostic::syntax_error_message;
+use crate::diagnostic::Diagnostic;
+use crate::diagnostic::Severity;
+use crate::emit::new_call;
+use crate::error::line_column;
+use crate::lossless::is_from_source;
+use crate::minify;
+use crate::Obfuscation;
+use parse_js::ast::ArrayElement;
+use parse_js::ast::ClassOrObjectMemberKey;
+use parse_js::ast::ClassOrObjectMemberValue;
+use parse_js::ast::ForStmtHeader;
+use parse_js::ast::LiteralTemplatePart;
+use parse_js::ast::NodeData;
+use parse_js::ast::ObjectMemberType;
+use parse_js::ast::Syntax;
+use parse_js::operator::OperatorName;
+use parse_js::parse;
+use parse_js::session::Session;
+use parse_js::source::SourceRange;
+use parse_js::visit::JourneyControls;
+use parse_js::visit::Visitor;
+
+// A step of a walk over a tree: entering a node (with a description of it), or leaving the most recently entered node.
+enum Step {
+  Enter(String),
+  // Entering a node whose details aren't known, which matches any node with a description starting with this. parse-js decodes some escapes in strings incorrectly (e.g. `\x3C` has trailing NUL characters), so the values of strings with escapes in the output aren't compared.
+  EnterSimilar(&'static str),
+  Leave,
+}
+
+impl Step {
+  fn matches(&self, other: &Step) -> bool {
+    match (self, other) {
+      (Step::Enter(a), Step::Enter(b)) => a == b,
+      (Step::Enter(desc), Step::EnterSimilar(prefix))
+      | (Step::EnterSimilar(prefix), Step::Enter(desc)) => desc.starts_with(prefix),
+      (Step::EnterSimilar(a), Step::EnterSimilar(b)) => a == b,
+      (Step::Leave, Step::Leave) => true,
+      _ => false,
+    }
+  }
+}
+
+// Describes a tree as the steps of a walk over it, with the position of each node, so trees can be compared without comparing positions. The emitter writes some nodes as different but equivalent syntax (e.g. `true` as `!0`, and strings as templates), and drops syntax that makes no difference (e.g. `export` on declarations that are also in an export list, and empty statements), so those are described the same way. Chains of `&&`, `||`, `??`, and `,` are described as one node with all their operands, as the minifier builds them nested to the right, but they're emitted without parentheses and parsed nested to the left, which means the same. Similarly, parse-js parses `new a(b).c` as a `new` of `a(b).c` (see `new_call`), so the `new` is described around the call it takes the arguments of.
+struct Walk<'s> {
+  // The code the tree was parsed from. Nodes created while minifying aren't from it, so they don't have a position.
+  source: &'s [u8],
+  // Whether to describe the names of variables, which differ between outputs that only differ in minified names.
+  names: bool,
+  // The position of the first syntax that parse-js can't parse as emitted, such as a dynamic import, if the tree has any.
+  unparseable: Option<usize>,
+  // For each node being walked, the operator if it's in a chain, and how many nodes to leave after it, which is none if it was described as part of its parent's chain.
+  entered: Vec<(Option<OperatorName>, usize)>,
+  // Calls that a `new` takes the arguments of, by address, with the description and position of the `new`.
+  new_calls: Vec<(usize, String, Option<usize>)>,
+  steps: Vec<(Step, Option<usize>)>,
+}
+
+impl<'s> Walk<'s> {
+  fn position(&self, loc: SourceRange<'_>) -> Option<usize> {
+    is_from_source(self.source, loc).then(|| loc.start())
+  }
+
+  fn enter(&mut self, desc: String, pos: Option<usize>) {
+    self.steps.push((Step::Enter(desc), pos));
+  }
+
+  fn unparseable(&mut self, pos: Option<usize>) {
+    self.unparseable.get_or_insert(pos.unwrap_or(0));
+  }
+}
+
+// Whether the statement is emitted ending with a labelled `break` or `continue`, or a `debugger`. parse-js doesn't end those at a `;`, so it fails on an `else` after them.
+fn ends_before_semicolon(node: &NodeData<'_>) -> bool {
+  match &node.stx {
+    Syntax::BreakStmt { label: Some(_) }
+    | Syntax::ContinueStmt { label: Some(_) }
+    | Syntax::DebuggerStmt {} => true,
+    Syntax::WhileStmt { body, .. } | Syntax::ForStmt { body, .. } => ends_before_semicolon(body),
+    Syntax::LabelStmt { statement, .. } => ends_before_semicolon(statement),
+    Syntax::IfStmt {
+      consequent,
+      alternate,
+      ..
+    } => ends_before_semicolon(alternate.as_ref().unwrap_or(consequent)),
+    _ => false,
+  }
+}
+
+// Whether the expression is emitted ending with an arrow function without braces around its body. parse-js parses the body of one before a `,` as continuing past it (e.g. `a=b=>c,d` as `a=b=>(c,d)`).
+fn ends_with_arrow_expression(node: &NodeData<'_>) -> bool {
+  match &node.stx {
+    Syntax::ArrowFunctionExpr {
+      parenthesised: false,
+      body,
+      ..
+    } => !matches!(body.stx, Syntax::BlockStmt { .. }),
+    Syntax::BinaryExpr { right, .. } => ends_with_arrow_expression(right),
+    Syntax::ConditionalExpr { alternate, .. } => ends_with_arrow_expression(alternate),
+    _ => false,
+  }
+}
+
+fn describe_string(value: &str) -> String {
+  format!("string {:?}", value)
+}
+
+fn describe_function(is_async: bool, generator: bool) -> &'static str {
+  match (is_async, generator) {
+    (false, false) => "",
+    (true, false) => " async",
+    (false, true) => " generator",
+    (true, true) => " async generator",
+  }
+}
+
+// Keys are emitted as they were written, except that `<` is escaped when inlining into HTML, so keys with escapes are described the same way.
+fn describe_key(key: &ClassOrObjectMemberKey<'_>) -> String {
+  match key {
+    ClassOrObjectMemberKey::Direct(name) if name.as_slice().iter().any(|&c| c == b'\\' || c == b'<') => {
+      "key with escapes".to_string()
+    }
+    ClassOrObjectMemberKey::Direct(name) => {
+      format!("key `{}`", String::from_utf8_lossy(name.as_slice()))
+    }
+    ClassOrObjectMemberKey::Computed(_) => "computed key".to_string(),
+  }
+}
+
+// Class members and object properties aren't all nodes, so their kind and key are described with the class or object member.
+fn describe_member(key: &ClassOrObjectMemberKey<'_>, value: &ClassOrObjectMemberValue<'_>) -> String {
+  let kind = match value {
+    ClassOrObjectMemberValue::Getter { .. } => "getter".to_string(),
+    ClassOrObjectMemberValue::Setter { .. } => "setter".to_string(),
+    ClassOrObjectMemberValue::Method {
+      is_async,
+      generator,
+      ..
+    } => format!("method{}", describe_function(*is_async, *generator)),
+    ClassOrObjectMemberValue::Property { .. } => "property".to_string(),
+  };
+  format!("{} {}", kind, describe_key(key))
+}
+
+impl<'a, 's> Visitor<'a> for Walk<'s> {
+  fn on_syntax_down(&mut self, node: &mut NodeData<'a>, ctl: &mut JourneyControls) {
+    let pos = self.position(node.loc);
+    let kind = syntax_kind(&node.stx);
+    let chain = match &node.stx {
+      Syntax::BinaryExpr { operator, .. }
+        if matches!(
+          operator,
+          OperatorName::LogicalAnd
+            | OperatorName::LogicalOr
+            | OperatorName::NullishCoalescing
+            | OperatorName::Comma
+        ) =>
+      {
+        Some(*operator)
+      }
+      _ => None,
+    };
+    if chain.is_some() && self.entered.last().is_some_and(|(c, _)| *c == chain) {
+      self.entered.push((chain, 0));
+      return;
+    };
+    let address = node as *const NodeData as usize;
+    let mut leaves = 1;
+    if let Some(i) = self.new_calls.iter().position(|(a, ..)| *a == address) {
+      let (_, desc, new_pos) = self.new_calls.swap_remove(i);
+      self.enter(desc, new_pos);
+      leaves = 2;
+    };
+    self.entered.push((chain, leaves));
+    let desc = match &node.stx {
+      Syntax::EmptyStmt {} => {
+        self.entered.pop();
+        ctl.skip();
+        return;
+      }
+      Syntax::LiteralBooleanExpr { value } => format!("boolean {}", value),
+      Syntax::UnaryExpr {
+        operator: OperatorName::LogicalNot,
+        argument,
+        ..
+      } if matches!(argument.stx, Syntax::LiteralNumberExpr { value } if value.0 == 0.0 || value.0 == 1.0) =>
+      {
+        let Syntax::LiteralNumberExpr { value } = argument.stx else {
+          unreachable!();
+        };
+        self.enter(format!("boolean {}", value.0 == 0.0), pos);
+        self.steps.push((Step::Leave, pos));
+        self.entered.pop();
+        ctl.skip();
+        return;
+      }
+      Syntax::LiteralNumberExpr { value } => format!("number {}", value),
+      Syntax::LiteralStringExpr { .. } | Syntax::LiteralTemplateExpr { .. }
+        if pos.is_some() && node.loc.as_slice().contains(&b'\\') =>
+      {
+        let similar = match &node.stx {
+          Syntax::LiteralTemplateExpr { parts } if parts.len() > 1 => "template",
+          _ => "string",
+        };
+        self.steps.push((Step::EnterSimilar(similar), pos));
+        return;
+      }
+      Syntax::LiteralStringExpr { value } => describe_string(value),
+      Syntax::LiteralTemplateExpr { parts } => match parts.as_slice() {
+        [] => describe_string(""),
+        [LiteralTemplatePart::String(s)] => describe_string(s),
+        parts => {
+          let strings = parts
+            .iter()
+            .map(|p| match p {
+              LiteralTemplatePart::String(s) => format!("{:?}", s),
+              LiteralTemplatePart::Substitution(_) => "${}".to_string(),
+            })
+            .collect::<String>();
+          format!("template {}", strings)
+        }
+      },
+      Syntax::IdentifierExpr { name }
+      | Syntax::IdentifierPattern { name }
+      | Syntax::ClassOrFunctionName { name }
+        if self.names =>
+      {
+        format!("{} `{}`", kind, String::from_utf8_lossy(name.as_slice()))
+      }
+      Syntax::ImportExpr { .. } => {
+        self.unparseable(pos);
+        kind.to_string()
+      }
+      Syntax::IfStmt {
+        consequent,
+        alternate: Some(_),
+        ..
+      } if ends_before_semicolon(consequent) => {
+        self.unparseable(pos);
+        kind.to_string()
+      }
+      Syntax::BinaryExpr {
+        operator: OperatorName::Comma,
+        left,
+        ..
+      } if ends_with_arrow_expression(left) => {
+        self.unparseable(pos);
+        format!("{} {:?}", kind, OperatorName::Comma)
+      }
+      Syntax::UnaryExpr {
+        operator: OperatorName::New,
+        argument,
+        ..
+      } if new_call(argument).is_some_and(|call| !std::ptr::eq(call, *argument)) => {
+        let call = new_call(argument).unwrap();
+        self.new_calls.push((
+          call as *const NodeData as usize,
+          format!("{} {:?}", kind, OperatorName::New),
+          pos,
+        ));
+        self.entered.last_mut().unwrap().1 = 0;
+        return;
+      }
+      Syntax::BinaryExpr { operator, .. }
+      | Syntax::UnaryExpr { operator, .. }
+      | Syntax::UnaryPostfixExpr { operator, .. } => format!("{} {:?}", kind, operator),
+      Syntax::MemberExpr {
+        right,
+        optional_chaining,
+        ..
+      } => format!(
+        "{} `{}{}`",
+        kind,
+        if *optional_chaining { "?." } else { "." },
+        String::from_utf8_lossy(right.as_slice())
+      ),
+      Syntax::VarDecl { mode, .. } => format!("{} {:?}", kind, mode),
+      Syntax::ClassDecl { members, .. } | Syntax::ClassExpr { members, .. } => {
+        let members = members
+          .iter()
+          .map(|m| {
+            let member = describe_member(&m.key, &m.value);
+            if m.statik {
+              format!("static {}", member)
+            } else {
+              member
+            }
+          })
+          .collect::<Vec<_>>();
+        format!("{} [{}]", kind, members.join(", "))
+      }
+      Syntax::ObjectMember { typ } => match typ {
+        ObjectMemberType::Valued { key, value } => {
+          format!("{} {}", kind, describe_member(key, value))
+        }
+        ObjectMemberType::Shorthand { .. } => format!("{} shorthand", kind),
+        ObjectMemberType::Rest { .. } => format!("{} rest", kind),
+      },
+      Syntax::ObjectPatternProperty { key, shorthand, .. } => format!(
+        "{} {}{}",
+        kind,
+        describe_key(key),
+        if *shorthand { " shorthand" } else { "" }
+      ),
+      Syntax::FunctionDecl {
+        is_async,
+        generator,
+        ..
+      }
+      | Syntax::FunctionExpr {
+        is_async,
+        generator,
+        ..
+      } => format!("{}{}", kind, describe_function(*is_async, *generator)),
+      Syntax::ArrowFunctionExpr { is_async, .. } => {
+        format!("{}{}", kind, describe_function(*is_async, false))
+      }
+      Syntax::ParamDecl { rest: true, .. } => format!("{} rest", kind),
+      Syntax::CallArg { spread: true, .. } => format!("{} spread", kind),
+      Syntax::CallExpr {
+        optional_chaining: true,
+        ..
+      } => format!("{} `?.()`", kind),
+      Syntax::ComputedMemberExpr {
+        optional_chaining: true,
+        ..
+      } => format!("{} `?.[]`", kind),
+      Syntax::LiteralArrayExpr { elements } => {
+        let elements = elements
+          .iter()
+          .map(|e| match e {
+            ArrayElement::Single(_) => "_",
+            ArrayElement::Rest(_) => "...",
+            ArrayElement::Empty => "hole",
+          })
+          .collect::<Vec<_>>();
+        format!("{} [{}]", kind, elements.join(", "))
+      }
+      Syntax::ArrayPattern { elements, rest } => {
+        let elements = elements
+          .iter()
+          .map(|e| if e.is_some() { "_" } else { "hole" })
+          .chain(rest.as_ref().map(|_| "..."))
+          .collect::<Vec<_>>();
+        format!("{} [{}]", kind, elements.join(", "))
+      }
+      Syntax::ForStmt { header, .. } => match header {
+        ForStmtHeader::Three { .. } => format!("{} `;;`", kind),
+        ForStmtHeader::InOf { of: false, .. } => format!("{} `in`", kind),
+        ForStmtHeader::InOf {
+          of: true, await_, ..
+        } => format!("{} `{}of`", kind, if *await_ { "await " } else { "" }),
+      },
+      _ => kind.to_string(),
+    };
+    self.enter(desc, pos);
+  }
+
+  fn on_syntax_up(&mut self, node: &mut NodeData<'a>) {
+    let (_, leaves) = self.entered.pop().unwrap();
+    let pos = self.position(node.loc);
+    for _ in 0..leaves {
+      self.steps.push((Step::Leave, pos));
+    }
+  }
+}
+
+fn walk<'a, 's>(source: &'s [u8], node: &mut NodeData<'a>, names: bool) -> Walk<'s> {
+  let mut walk = Walk {
+    source,
+    names,
+    unparseable: None,
+    entered: Vec::new(),
+    new_calls: Vec::new(),
+    steps: Vec::new(),
+  };
+  walk.visit(node);
+  walk
+}
+
+// Returns the index of the first step where the walks differ.
+fn first_mismatch(expected: &[(Step, Option<usize>)], actual: &[(Step, Option<usize>)]) -> Option<usize> {
+  expected
+    .iter()
+    .zip(actual.iter())
+    .position(|((a, _), (b, _))| !a.matches(b))
+    .or_else(|| (expected.len() != actual.len()).then(|| expected.len().min(actual.len())))
+}
+
+fn describe(step: Option<&(Step, Option<usize>)>) -> String {
+  match step {
+    Some((Step::Enter(desc), _)) => desc.clone(),
+    Some((Step::EnterSimilar(desc), _)) => desc.to_string(),
+    Some((Step::Leave, _)) => "the end of a node".to_string(),
+    None => "nothing".to_string(),
+  }
+}
+
+fn internal_error(code: &'static str, message: String, source: &[u8], pos: usize) -> Diagnostic {
+  let (line, column, _) = line_column(source, pos);
+  Diagnostic {
+    code,
+    message: format!("{}; this is a bug in the minifier", message),
+    severity: Severity::Error,
+    start: pos,
+    end: pos,
+    line,
+    column,
+  }
+}
+
+fn at_output(output: &[u8], pos: usize) -> String {
+  let (line, column, _) = line_column(output, pos);
+  format!("{}:{} of the output", line, column)
+}
+
+// Checks the emitted code (without any banner, footer, or comments) of a minified tree, as configured by [MinifyOptions::verify]. `source` is the code the tree was parsed from, which mismatches are reported in. If the code can't be checked, returns a warning saying so.
+pub(crate) fn verify_output(
+  source: &[u8],
+  minified: &mut NodeData<'_>,
+  code: &[u8],
+  cfg: &MinifyOptions,
+) -> Result<Option<Diagnostic>, Diagnostic> {
+  if cfg.verify == Verify::None {
+    return Ok(None);
+  };
+  let expected = walk(source, minified, true);
+  if let Some(pos) = expected.unparseable {
+    let (line, column, _) = line_column(source, pos);
+    return Ok(Some(Diagnostic {
+      code: "W_VERIFY_SKIPPED",
+      message: "the output wasn't verified, as it has syntax that parse-js can't parse as emitted (e.g. a dynamic import)".to_string(),
+      severity: Severity::Warning,
+      start: pos,
+      end: pos,
+      line,
+      column,
+    }));
+  };
+  let session = Session::new();
+  let reparsed = parse(&session, code, cfg.top_level_mode).map_err(|err| {
+    internal_error(
+      "E_INTERNAL_INVALID_OUTPUT",
+      format!(
+        "the output isn't valid JavaScript: {} at {}",
+        syntax_error_message(&err),
+        at_output(code, err.source.start())
+      ),
+      source,
+      0,
+    )
+  })?;
+  fix_accessors(&session, reparsed);
+  let (expected, actual) = (expected.steps, walk(code, reparsed, true).steps);
+  if let Some(i) = first_mismatch(&expected, &actual) {
+    // Nodes created while minifying don't have a position in the source, so they're reported at the start.
+    let pos = expected.get(i).and_then(|(_, pos)| *pos).unwrap_or(0);
+    let output_pos = actual
+      .get(i)
+      .or(actual.last())
+      .and_then(|(_, pos)| *pos)
+      .unwrap_or(code.len());
+    return Err(internal_error(
+      "E_INTERNAL_MISMATCH",
+      format!(
+        "the output doesn't have the same syntax as the minified code: expected {}, but found {} at {}",
+        describe(expected.get(i)),
+        describe(actual.get(i)),
+        at_output(code, output_pos)
+      ),
+      source,
+      pos,
+    ));
+  };
+  if cfg.verify == Verify::Idempotent {
+    // Only the code is minified again, so options that add to it or that use randomness don't apply.
+    let cfg = MinifyOptions {
+      verify: Verify::None,
+      banner: None,
+      footer: None,
+      enclose: None,
+      source_mapping_url: SourceMappingUrl::Strip,
+      obfuscation: Obfuscation::default(),
+      ..cfg.clone()
+    };
+    let mut again = Vec::new();
+    minify(&session, code, &cfg, &mut again).map_err(|err| {
+      internal_error(
+        "E_INTERNAL_NOT_IDEMPOTENT",
+        format!("the output can't be minified again: {}", err),
+        source,
+        0,
+      )
+    })?;
+    // Minified names can be assigned in a different order the second time, so they aren't compared.
+    let walk_again = |code: &[u8]| {
+      let n = parse(&session, code, cfg.top_level_mode).ok()?;
+      fix_accessors(&session, n);
+      Some(walk(code, n, false).steps)
+    };
+    let (a, b) = (walk_again(code), walk_again(&again));
+    let changed = match (a, b) {
+      (Some(a), Some(b)) => first_mismatch(&a, &b).is_some(),
+      _ => true,
+    };
+    if changed {
+      let pos = code
+        .iter()
+        .zip(again.iter())
+        .position(|(a, b)| a != b)
+        .unwrap_or(code.len().min(again.len()));
+      return Err(internal_error(
+        "E_INTERNAL_NOT_IDEMPOTENT",
+        format!(
+          "minifying the output again changes it, starting at {}",
+          at_output(code, pos)
+        ),
+        source,
+        0,
+      ));
+    };
+  };
+  Ok(None)
+}
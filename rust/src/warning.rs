@@ -0,0 +1,220 @@
+use crate::comment::collect_comments;
+use crate::diagnostic::Diagnostic;
+use crate::diagnostic::Severity;
+use crate::environment::Environment;
+use crate::error::line_column;
+use crate::pattern::Patterns;
+use parse_js::ast::ClassOrObjectMemberKey;
+use parse_js::ast::ClassOrObjectMemberValue;
+use parse_js::ast::NodeData;
+use parse_js::ast::ObjectMemberType;
+use parse_js::ast::Syntax;
+use parse_js::operator::OperatorName;
+use parse_js::source::SourceRange;
+use parse_js::visit::JourneyControls;
+use parse_js::visit::Visitor;
+use std::collections::HashSet;
+
+// Annotations understood by other minifiers and bundlers, which are dropped from the output. Only `__PURE__` is used, to drop unused calls.
+static ANNOTATION_PATTERNS: Patterns = Patterns::new(&[
+  b"@__PURE__",
+  b"#__PURE__",
+  b"@__NO_SIDE_EFFECTS__",
+  b"#__NO_SIDE_EFFECTS__",
+  b"@__INLINE__",
+  b"#__INLINE__",
+  b"@__NOINLINE__",
+  b"#__NOINLINE__",
+  b"@__KEY__",
+  b"#__KEY__",
+]);
+
+struct Warnings<'a> {
+  source: &'a [u8],
+  out: Vec<Diagnostic>,
+  environment: Environment,
+  // Undeclared variables that are read, and that are assigned to. Reads are only reported once the whole tree is visited, as assigning to an undeclared variable in a script creates it as a global.
+  global_reads: Vec<SourceRange<'a>>,
+  global_writes: HashSet<Vec<u8>>,
+  // Positions of variables that are the argument of `typeof`, which is how code checks whether a global exists.
+  typeof_args: HashSet<usize>,
+}
+
+impl<'a> Warnings<'a> {
+  fn warn(&mut self, code: &'static str, message: String, start: usize, end: usize) {
+    let (line, column, _) = line_column(self.source, start);
+    self.out.push(Diagnostic {
+      code,
+      message,
+      severity: Severity::Warning,
+      start,
+      end,
+      line,
+      column,
+    });
+  }
+
+  fn offset_of(&self, slice: &[u8]) -> Option<usize> {
+    let offset = (slice.as_ptr() as usize).checked_sub(self.source.as_ptr() as usize)?;
+    (offset + slice.len() <= self.source.len()).then_some(offset)
+  }
+
+  fn check_duplicate_keys(&mut self, members: &[&mut NodeData<'a>]) {
+    let mut seen = HashSet::new();
+    for m in members {
+      let Syntax::ObjectMember { typ } = &m.stx else {
+        continue;
+      };
+      // The parser reads getters and setters as methods, so we can't tell a getter and setter pair from duplicate methods, and only check properties.
+      let key = match typ {
+        ObjectMemberType::Valued {
+          key: ClassOrObjectMemberKey::Direct(key),
+          value: ClassOrObjectMemberValue::Property { .. },
+        } => *key,
+        ObjectMemberType::Shorthand { identifier } => match identifier.stx {
+          Syntax::IdentifierExpr { name } => name,
+          _ => continue,
+        },
+        _ => continue,
+      };
+      // String keys include their quotes. Compare them without quotes unless they contain escapes.
+      let raw = key.as_slice();
+      let name = match raw.first() {
+        Some(b'"' | b'\'') if !raw.contains(&b'\\') => &raw[1..raw.len() - 1],
+        _ => raw,
+      };
+      if !seen.insert(name.to_vec()) {
+        // Members' ranges aren't always accurate, so point to the key itself.
+        let (start, end) = match self.offset_of(key.as_slice()) {
+          Some(start) => (start, start + key.len()),
+          None => (m.loc.start(), m.loc.end()),
+        };
+        self.warn(
+          "W_DUPLICATE_KEY",
+          format!(
+            "duplicate key `{}` in object literal; only the last value is kept",
+            String::from_utf8_lossy(name)
+          ),
+          start,
+          end,
+        );
+      };
+    }
+  }
+}
+
+impl<'a> Visitor<'a> for Warnings<'a> {
+  fn on_syntax_down(&mut self, node: &mut NodeData<'a>, _ctl: &mut JourneyControls) {
+    match &node.stx {
+      Syntax::CallExpr {
+        optional_chaining: false,
+        callee,
+        ..
+      } => {
+        if let Syntax::IdentifierExpr { name } = callee.stx {
+          if name.as_slice() == b"eval" && node.scope.find_symbol(name).is_none() {
+            self.warn(
+              "W_DIRECT_EVAL",
+              "direct eval can access local variables, but they're still renamed, so the evaluated code may not find them".to_string(),
+              node.loc.start(),
+              node.loc.end(),
+            );
+          };
+        };
+      }
+      Syntax::LiteralObjectExpr { members } => {
+        self.check_duplicate_keys(members);
+      }
+      Syntax::UnaryExpr {
+        operator: OperatorName::Typeof,
+        argument,
+        ..
+      } => {
+        self.typeof_args.insert(argument.loc.start());
+      }
+      Syntax::IdentifierExpr { name }
+        if self.environment != Environment::None
+          && node.scope.find_symbol(*name).is_none()
+          && !self.typeof_args.contains(&node.loc.start()) =>
+      {
+        self.global_reads.push(*name);
+      }
+      Syntax::BinaryExpr {
+        operator, left, ..
+      } if operator.is_assignment() && self.environment != Environment::None => {
+        if let Syntax::IdentifierPattern { name } = left.stx {
+          if left.scope.find_symbol(name).is_none() {
+            self.global_writes.insert(name.as_slice().to_vec());
+            if self.environment.has_global(name.as_slice()) {
+              self.warn(
+                "W_GLOBAL_REASSIGNED",
+                format!(
+                  "`{}` is assumed not to be reassigned in the {:?} environment, so code that reads it may be minified as if it wasn't",
+                  name.as_str(),
+                  self.environment
+                ),
+                name.start(),
+                name.end(),
+              );
+            };
+          };
+        };
+      }
+      _ => {}
+    };
+  }
+}
+
+// Finds suspicious but valid code and annotations we can't honour. `source` must be the source that `node` was parsed from.
+pub fn collect_warnings<'a>(
+  source: &'a [u8],
+  node: &mut NodeData<'a>,
+  environment: Environment,
+) -> Vec<Diagnostic> {
+  let mut warnings = Warnings {
+    source,
+    out: Vec::new(),
+    environment,
+    global_reads: Vec::new(),
+    global_writes: HashSet::new(),
+    typeof_args: HashSet::new(),
+  };
+  for c in collect_comments(source) {
+    if let Some((m_start, m_end)) = ANNOTATION_PATTERNS.find(c.raw) {
+      let start = warnings.offset_of(c.raw).unwrap();
+      warnings.warn(
+        "W_IGNORED_ANNOTATION",
+        format!(
+          "`{}` annotations are dropped from the output, so later tools won't see them",
+          String::from_utf8_lossy(&c.raw[m_start..m_end])
+        ),
+        start,
+        start + c.raw.len(),
+      );
+    };
+  }
+  warnings.visit(node);
+  // Only the first read of each unknown global is reported.
+  let mut reported = HashSet::new();
+  for name in std::mem::take(&mut warnings.global_reads) {
+    let raw = name.as_slice();
+    if environment.has_global(raw)
+      || warnings.global_writes.contains(raw)
+      || !reported.insert(raw.to_vec())
+    {
+      continue;
+    };
+    warnings.warn(
+      "W_UNKNOWN_GLOBAL",
+      format!(
+        "`{}` isn't declared, and isn't a known global in the {:?} environment, so it may not exist when the code runs",
+        name.as_str(),
+        environment
+      ),
+      name.start(),
+      name.end(),
+    );
+  }
+  warnings.out.sort_by_key(|d| d.start);
+  warnings.out
+}
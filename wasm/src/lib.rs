@@ -0,0 +1,228 @@
+use js_sys::Error;
+use js_sys::Reflect;
+use js_sys::Uint8Array;
+use minify_js::Diagnostic;
+use minify_js::MinifyOptions;
+use minify_js::Session;
+use serde::Deserialize;
+use std::cell::RefCell;
+use std::str::FromStr;
+use wasm_bindgen::prelude::*;
+
+#[wasm_bindgen(typescript_custom_section)]
+const TS_TYPES: &str = r#"
+export type MinifyOptions = {
+  /** Whether the code is a script or an ES module. Defaults to "global". */
+  mode?: "global" | "module";
+  /** The oldest ECMAScript version the output must run on. Defaults to "esnext". */
+  target?: "es5" | "es2015" | "es2016" | "es2017" | "es2018" | "es2019" | "es2020" | "es2021" | "es2022" | "esnext";
+  /** Where to keep license comments. */
+  legalComments?: "none" | "eof" | "top";
+  /** Line ending to use in the output. */
+  newline?: "lf" | "crlf";
+  /** Break lines once they reach this length. */
+  maxLineLen?: number;
+  /** Text to put before the output. */
+  banner?: string;
+  /** Text to put after the output. */
+  footer?: string;
+  /** Indent and put statements on their own lines. */
+  beautify?: boolean;
+  /** Separate statements with line breaks instead of semicolons where automatic semicolon insertion allows. */
+  asi?: boolean;
+};
+
+/**
+ * Error thrown for code that can't be minified.
+ */
+export interface MinifyError extends Error {
+  /** Stable identifier of the problem, such as "E_EXPECTED_SYNTAX". */
+  code: string;
+  /** 1-based line of the problem. */
+  line: number;
+  /** 1-based column of the problem. */
+  column: number;
+}
+
+/**
+ * Minifies JavaScript code, given as a string or a Uint8Array containing UTF-8.
+ *
+ * @param code - Source JS code
+ * @param options - Minification options
+ * @returns Minified JS code, of the same type as `code`
+ */
+export function minify(code: string, options?: MinifyOptions): string;
+export function minify(code: Uint8Array, options?: MinifyOptions): Uint8Array;
+"#;
+
+// The options object given to `minify` in JS.
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "camelCase")]
+struct Options {
+  mode: Option<String>,
+  target: Option<String>,
+  legal_comments: Option<String>,
+  newline: Option<String>,
+  max_line_len: Option<usize>,
+  banner: Option<String>,
+  footer: Option<String>,
+  beautify: bool,
+  asi: bool,
+}
+
+fn parse_option<T: FromStr>(key: &str, raw: &Option<String>) -> Result<Option<T>, String> {
+  raw
+    .as_deref()
+    .map(|raw| T::from_str(raw).map_err(|_| format!("invalid {} `{}`", key, raw)))
+    .transpose()
+}
+
+impl Options {
+  fn to_minify_options(&self) -> Result<MinifyOptions, String> {
+    let mut cfg = MinifyOptions::new().beautify(self.beautify).asi(self.asi);
+    if let Some(mode) = parse_option("mode", &self.mode)? {
+      cfg = cfg.top_level_mode(mode);
+    };
+    if let Some(target) = parse_option("target", &self.target)? {
+      cfg = cfg.target(target);
+    };
+    if let Some(legal_comments) = parse_option("legalComments", &self.legal_comments)? {
+      cfg = cfg.legal_comments(legal_comments);
+    };
+    if let Some(newline) = parse_option("newline", &self.newline)? {
+      cfg = cfg.newline(newline);
+    };
+    if let Some(max_line_len) = self.max_line_len {
+      cfg = cfg.max_line_len(max_line_len);
+    };
+    if let Some(banner) = &self.banner {
+      cfg = cfg.banner(banner.clone());
+    };
+    if let Some(footer) = &self.footer {
+      cfg = cfg.footer(footer.clone());
+    };
+    Ok(cfg)
+  }
+}
+
+thread_local! {
+  // WASM instances are single threaded, so the session is reused across calls.
+  static SESSION: RefCell<Session> = RefCell::new(Session::new());
+}
+
+// Creates a JS error for a diagnostic, with its code and position as properties, like Node.js's own errors.
+fn diagnostic_error(d: Diagnostic) -> JsValue {
+  let err = Error::new(&format!("{} ({}:{})", d.message, d.line, d.column));
+  for (key, value) in [
+    ("code", JsValue::from_str(d.code)),
+    ("line", JsValue::from(d.line as u32)),
+    ("column", JsValue::from(d.column as u32)),
+  ] {
+    Reflect::set(&err, &JsValue::from_str(key), &value).unwrap();
+  }
+  err.into()
+}
+
+fn options_from_js(options: JsValue) -> Result<MinifyOptions, JsValue> {
+  let options: Options = if options.is_undefined() || options.is_null() {
+    Options::default()
+  } else {
+    serde_wasm_bindgen::from_value(options)
+      .map_err(|err| js_sys::TypeError::new(&format!("invalid options: {}", err)))?
+  };
+  Ok(
+    options
+      .to_minify_options()
+      .map_err(|msg| js_sys::TypeError::new(&msg))?,
+  )
+}
+
+#[wasm_bindgen(skip_typescript)]
+pub fn minify(code: JsValue, options: JsValue) -> Result<JsValue, JsValue> {
+  let cfg = options_from_js(options)?;
+  let (src, is_string) = match code.as_string() {
+    Some(s) => (s.into_bytes(), true),
+    None => match code.dyn_into::<Uint8Array>() {
+      Ok(a) => (a.to_vec(), false),
+      Err(_) => return Err(js_sys::TypeError::new("code must be a string or Uint8Array").into()),
+    },
+  };
+  SESSION.with(|session| {
+    let session = &mut *session.borrow_mut();
+    session.reset();
+    let mut out = Vec::new();
+    match minify_js::minify(session, &src, &cfg, &mut out) {
+      Ok(_) if is_string => Ok(JsValue::from_str(
+        std::str::from_utf8(&out).expect("minified code is UTF-8"),
+      )),
+      Ok(_) => Ok(Uint8Array::from(out.as_slice()).into()),
+      Err(err) => Err(diagnostic_error(Diagnostic::from_minify_error(&src, &err))),
+    }
+  })
+}
+
+/// Minifies code given in chunks, such as from a `ReadableStream`, and returns the output in chunks.
+///
+/// Minification needs all the code, so nothing is output until `finish` is called, but chunks are copied straight into WASM memory instead of being joined in JS, and memory is freed as soon as it's no longer needed: the code and syntax tree once minified, and the output once it's all been read. This keeps peak memory low enough for runtimes with tight memory limits, such as Cloudflare Workers.
+#[wasm_bindgen]
+pub struct StreamingMinifier {
+  cfg: MinifyOptions,
+  code: Vec<u8>,
+  output: Vec<u8>,
+  // How much of `output` has been read.
+  read: usize,
+  finished: bool,
+}
+
+#[wasm_bindgen]
+impl StreamingMinifier {
+  #[wasm_bindgen(constructor)]
+  pub fn new(
+    #[wasm_bindgen(unchecked_param_type = "MinifyOptions | undefined")] options: JsValue,
+  ) -> Result<StreamingMinifier, JsValue> {
+    Ok(StreamingMinifier {
+      cfg: options_from_js(options)?,
+      code: Vec::new(),
+      output: Vec::new(),
+      read: 0,
+      finished: false,
+    })
+  }
+
+  /// Adds a chunk of UTF-8 code. Chunks may split characters.
+  pub fn write(&mut self, chunk: &[u8]) -> Result<(), JsValue> {
+    if self.finished {
+      return Err(Error::new("can't write after finish()").into());
+    };
+    self.code.extend_from_slice(chunk);
+    Ok(())
+  }
+
+  /// Minifies the code written so far. Throws a `MinifyError` if it can't be minified.
+  pub fn finish(&mut self) -> Result<(), JsValue> {
+    if self.finished {
+      return Err(Error::new("finish() was already called").into());
+    };
+    self.finished = true;
+    let code = std::mem::take(&mut self.code);
+    // A session just for this, so that its memory is freed as soon as minification is done, instead of being kept for reuse.
+    let session = Session::new();
+    let res = minify_js::minify(&session, &code, &self.cfg, &mut self.output)
+      .map(|_| ())
+      .map_err(|err| diagnostic_error(Diagnostic::from_minify_error(&code, &err)));
+    self.output.shrink_to_fit();
+    res
+  }
+
+  /// Returns the next chunk of output, of at most `max_len` bytes, or an empty array once it's all been read. Chunks may split characters, so use a streaming `TextDecoder` to decode them.
+  pub fn read(&mut self, max_len: usize) -> Uint8Array {
+    let end = self.output.len().min(self.read + max_len.max(1));
+    let chunk = Uint8Array::from(&self.output[self.read..end]);
+    self.read = end;
+    if self.read == self.output.len() {
+      self.output = Vec::new();
+      self.read = 0;
+    };
+    chunk
+  }
+}